@@ -0,0 +1,28 @@
+//! Cost of building and making requests with the blocking client, against the in-process
+//! server of the integration tests.
+
+#[path = "../tests/support/mod.rs"]
+mod support;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use esplora::Builder;
+use support::{FakeChain, Server};
+
+fn urls(c: &mut Criterion) {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let client = Builder::new(&server.url()).build_blocking().unwrap();
+
+    // recording a request builds its path without sending it
+    let dry_run = client.dry_run();
+    c.bench_function("dry_run_tx_status", |b| {
+        b.iter(|| {
+            let _ = dry_run.tx_status(&txid);
+            dry_run.clear();
+        })
+    });
+    c.bench_function("tx_status", |b| b.iter(|| client.tx_status(&txid).unwrap()));
+}
+
+criterion_group!(benches, urls);
+criterion_main!(benches);
@@ -2,78 +2,437 @@
 //!
 //! see: <https://github.com/Blockstream/esplora/blob/master/API.md>
 
+use std::cell::Cell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::time::Duration;
+
 use amplify::confinement::Confined;
-use amplify::hex::FromHex;
-use amplify::{confinement, Bytes32};
+use amplify::hex::{FromHex, ToHex};
+use amplify::{confinement, ByteArray, Bytes32};
 use bpstd::{
-    BlockHash, LockTime, Outpoint, ScriptPubkey, SeqNo, SigScript, Tx as Transaction, TxIn, TxOut,
-    TxVer, Txid, Witness,
+    Address, BlockHash, LockTime, Outpoint, Sats, ScriptPubkey, SeqNo, SigScript,
+    Tx as Transaction, TxIn, TxOut, TxVer, Txid, Weight, Witness,
 };
-use serde::Deserialize;
-use serde_with::hex::Hex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::Error;
+
+mod cursor;
+pub mod esplora_error;
+mod stream;
+
+pub use cursor::{Cursor, CursorFamily, CursorKey};
+#[allow(deprecated)]
+pub use esplora_error::ErrorBody;
+pub use esplora_error::{classify_error, ErrorDetails, EsploraErrorKind};
+#[cfg(feature = "async")]
+pub(crate) use stream::TxArrayParser;
+pub use stream::{stream_parse_txs, TxArrayReader};
 
-#[serde_as]
-#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct PrevOut {
+    #[serde(deserialize_with = "deserialize_sats")]
     pub value: u64,
-    #[serde_as(as = "Hex")]
+    #[serde(
+        serialize_with = "serialize_script",
+        deserialize_with = "deserialize_scriptpubkey"
+    )]
     pub scriptpubkey: ScriptPubkey,
 }
 
-#[serde_as]
-#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Vin {
     pub txid: Txid,
     pub vout: u32,
     // None if coinbase
     pub prevout: Option<PrevOut>,
-    #[serde_as(as = "Hex")]
+    #[serde(
+        serialize_with = "serialize_script",
+        deserialize_with = "deserialize_scriptsig"
+    )]
     pub scriptsig: SigScript,
-    #[serde(deserialize_with = "deserialize_witness", default)]
+    #[serde(
+        serialize_with = "serialize_witness",
+        deserialize_with = "deserialize_witness",
+        default
+    )]
     pub witness: Vec<Vec<u8>>,
     pub sequence: u32,
+    /// Whether the input is the one of a coinbase transaction; assumed not when the server
+    /// omits it, see [`Tx::is_coinbase`].
+    #[serde(default)]
     pub is_coinbase: bool,
 }
 
-#[serde_as]
-#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+impl Vin {
+    /// Items of the witness stack of the input, from the bottom of the stack; none for inputs
+    /// without witness.
+    pub fn witness_items(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.witness.iter().map(Vec::as_slice)
+    }
+
+    /// Whether the input spends a segwit output, carrying a non-empty witness.
+    pub fn has_witness(&self) -> bool {
+        !self.witness.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(remote = "Self")]
 pub struct Vout {
+    #[serde(deserialize_with = "deserialize_sats")]
     pub value: u64,
-    #[serde_as(as = "Hex")]
+    #[serde(
+        serialize_with = "serialize_script",
+        deserialize_with = "deserialize_scriptpubkey"
+    )]
     pub scriptpubkey: ScriptPubkey,
+    /// Type of the output script, as classified by the server.
+    #[serde(
+        rename = "scriptpubkey_type",
+        default,
+        skip_serializing_if = "is_none_and_readable"
+    )]
+    pub script_type: Option<ScriptType>,
+    /// Address of the output, when the script can be represented as one. The network is the
+    /// one of the server, as encoded in the address.
+    #[serde(
+        rename = "scriptpubkey_address",
+        default,
+        serialize_with = "serialize_address",
+        deserialize_with = "deserialize_address",
+        skip_serializing_if = "is_none_and_readable"
+    )]
+    pub address: Option<Address>,
+}
+
+impl Vout {
+    /// Whether the output pays to `address`, so that it can be spent by its owner.
+    pub fn is_spendable_by_address(&self, address: &Address) -> bool {
+        self.scriptpubkey == address.script_pubkey()
+    }
+}
+
+/// Type of an output script, as reported by Esplora in `scriptpubkey_type`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum ScriptType {
+    P2pk,
+    P2pkh,
+    P2sh,
+    V0P2wpkh,
+    V0P2wsh,
+    V1P2tr,
+    OpReturn,
+    Multisig,
+    Nonstandard,
+    /// Type not known to this library, with the string reported by the server.
+    Other(String),
+}
+
+impl ScriptType {
+    /// Classify an output script like Esplora does, for the standard types.
+    pub fn from_script(script: &ScriptPubkey) -> Self {
+        if script.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if script.is_p2sh() {
+            ScriptType::P2sh
+        } else if script.is_p2wpkh() {
+            ScriptType::V0P2wpkh
+        } else if script.is_p2wsh() {
+            ScriptType::V0P2wsh
+        } else if script.is_p2tr() {
+            ScriptType::V1P2tr
+        } else if script.is_op_return() {
+            ScriptType::OpReturn
+        } else {
+            ScriptType::Nonstandard
+        }
+    }
+
+    /// Weight (in weight units) of an input spending an output of this type, assuming
+    /// compressed keys, 72-byte ECDSA signatures and key-path spends of taproot outputs.
+    ///
+    /// `None` for types whose spending cost depends on the script, like P2SH, P2WSH and bare
+    /// multisig outputs.
+    pub fn input_weight(&self) -> Option<u64> {
+        // outpoint, script length and sequence: 41 bytes, and the signature push: 73 bytes
+        match self {
+            ScriptType::P2pk => Some((41 + 73) * 4),
+            // plus the push of a 33-byte public key
+            ScriptType::P2pkh => Some((41 + 73 + 34) * 4),
+            // witness item count, signature and public key
+            ScriptType::V0P2wpkh => Some(41 * 4 + 1 + 73 + 34),
+            // witness item count and 64-byte Schnorr signature
+            ScriptType::V1P2tr => Some(41 * 4 + 1 + 65),
+            _ => None,
+        }
+    }
+
+    /// String used by Esplora for the script type.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ScriptType::P2pk => "p2pk",
+            ScriptType::P2pkh => "p2pkh",
+            ScriptType::P2sh => "p2sh",
+            ScriptType::V0P2wpkh => "v0_p2wpkh",
+            ScriptType::V0P2wsh => "v0_p2wsh",
+            ScriptType::V1P2tr => "v1_p2tr",
+            ScriptType::OpReturn => "op_return",
+            ScriptType::Multisig => "multisig",
+            ScriptType::Nonstandard => "nonstandard",
+            ScriptType::Other(other) => other,
+        }
+    }
+}
+
+impl Display for ScriptType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for ScriptType {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "p2pk" => ScriptType::P2pk,
+            "p2pkh" => ScriptType::P2pkh,
+            "p2sh" => ScriptType::P2sh,
+            "v0_p2wpkh" => ScriptType::V0P2wpkh,
+            "v0_p2wsh" => ScriptType::V0P2wsh,
+            "v1_p2tr" => ScriptType::V1P2tr,
+            "op_return" => ScriptType::OpReturn,
+            "multisig" => ScriptType::Multisig,
+            "nonstandard" => ScriptType::Nonstandard,
+            _ => ScriptType::Other(s),
+        }
+    }
+}
+
+impl From<ScriptType> for String {
+    fn from(script_type: ScriptType) -> Self {
+        script_type.as_str().to_owned()
+    }
 }
 
-#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(remote = "Self")]
 pub struct TxStatus {
     pub confirmed: bool,
+    #[serde(skip_serializing_if = "is_none_and_readable")]
     pub block_height: Option<u32>,
+    #[serde(skip_serializing_if = "is_none_and_readable")]
     pub block_hash: Option<BlockHash>,
+    #[serde(skip_serializing_if = "is_none_and_readable")]
     pub block_time: Option<u64>,
 }
 
-#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+impl TxStatus {
+    /// Get the number of confirmations of the transaction when the chain tip is at height
+    /// `tip`, or `None` while it is unconfirmed. A confirmed transaction whose block height
+    /// wasn't reported counts as confirmed once.
+    pub fn confirmations(&self, tip: u32) -> Option<u32> {
+        if !self.confirmed {
+            return None;
+        }
+        Some(
+            self.block_height
+                .map_or(1, |height| tip.saturating_sub(height) + 1),
+        )
+    }
+}
+
+/// Statistics of the outputs funded and spent by an address or a script, as returned by
+/// `/address/:address` and `/scripthash/:hash`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(remote = "Self")]
+pub struct AddressStats {
+    /// Address the statistics are about, when requested by address.
+    #[serde(default, skip_serializing_if = "is_none_and_readable")]
+    pub address: Option<String>,
+    /// Hash of the script the statistics are about, when requested by script.
+    #[serde(default, skip_serializing_if = "is_none_and_readable")]
+    pub scripthash: Option<String>,
+    /// Statistics of the confirmed transactions.
+    pub chain_stats: AddressTxsSummary,
+    /// Statistics of the unconfirmed transactions.
+    pub mempool_stats: AddressTxsSummary,
+}
+
+impl AddressStats {
+    /// Number of confirmed and unconfirmed transactions involving the address, failing with
+    /// [`Error::InvalidServerData`] if the counts sent by the server overflow.
+    pub fn tx_count(&self) -> Result<u64, Error> {
+        self.chain_stats
+            .tx_count
+            .checked_add(self.mempool_stats.tx_count)
+            .ok_or(Error::InvalidServerData)
+    }
+
+    /// Whether the address appears in any confirmed or unconfirmed transaction.
+    pub fn has_history(&self) -> bool {
+        self.chain_stats.tx_count > 0 || self.mempool_stats.tx_count > 0
+    }
+
+    /// Balance of the address in satoshis, from its confirmed transactions only.
+    pub fn confirmed_balance(&self) -> u64 {
+        self.chain_stats
+            .funded_txo_sum
+            .saturating_sub(self.chain_stats.spent_txo_sum)
+    }
+
+    /// Balance of the address in satoshis, including its unconfirmed transactions, failing with
+    /// [`Error::InvalidServerData`] if the sums sent by the server overflow.
+    pub fn balance(&self) -> Result<u64, Error> {
+        let funded = self
+            .chain_stats
+            .funded_txo_sum
+            .checked_add(self.mempool_stats.funded_txo_sum);
+        let spent = self
+            .chain_stats
+            .spent_txo_sum
+            .checked_add(self.mempool_stats.spent_txo_sum);
+        match (funded, spent) {
+            (Some(funded), Some(spent)) => Ok(funded.saturating_sub(spent)),
+            _ => Err(Error::InvalidServerData),
+        }
+    }
+}
+
+/// Sum the balances of addresses, including their unconfirmed transactions, in satoshis,
+/// failing with [`Error::InvalidServerData`] if the figures sent by the server overflow.
+pub fn total_balance(stats: &[AddressStats]) -> Result<u64, Error> {
+    stats.iter().try_fold(0u64, |total, stats| {
+        total
+            .checked_add(stats.balance()?)
+            .ok_or(Error::InvalidServerData)
+    })
+}
+
+/// Balance of an address cross-checked against the sum of its unspent outputs.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedBalance {
+    /// Balance of the address in satoshis, including its unconfirmed transactions, as derived
+    /// from its statistics.
+    pub balance: u64,
+    /// Whether the sum of the unspent outputs of the address matches its balance.
+    pub verified: bool,
+    /// Difference between the sum of the unspent outputs and the balance, when they don't
+    /// match.
+    pub discrepancy: Option<i64>,
+    /// Hash of the chain tip the balance was checked at.
+    pub tip: BlockHash,
+}
+
+impl VerifiedBalance {
+    /// Check the balance derived from the statistics of an address against the sum of its
+    /// unspent outputs, as known by the server at `tip`.
+    ///
+    /// Both include unconfirmed transactions: the unspent outputs listed by Esplora include
+    /// unconfirmed ones and exclude those spent by unconfirmed transactions, so comparing
+    /// confirmed figures only would flag every address with a pending spend.
+    ///
+    /// Fails with [`Error::InvalidServerData`] if the statistics or the sum of the unspent
+    /// outputs overflow.
+    pub fn check(stats: &AddressStats, utxos: &[Utxo], tip: BlockHash) -> Result<Self, Error> {
+        let balance = stats.balance()?;
+        let utxo_sum = utxos.iter().try_fold(0u64, |sum, utxo| {
+            sum.checked_add(utxo.value).ok_or(Error::InvalidServerData)
+        })?;
+        let discrepancy = utxo_sum as i128 - balance as i128;
+        Ok(VerifiedBalance {
+            balance,
+            verified: discrepancy == 0,
+            discrepancy: match discrepancy {
+                0 => None,
+                discrepancy => Some(discrepancy.clamp(i64::MIN as i128, i64::MAX as i128) as i64),
+            },
+            tip,
+        })
+    }
+}
+
+/// Counts and sums of the outputs of an address, in satoshis.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AddressTxsSummary {
+    pub funded_txo_count: u64,
+    #[serde(deserialize_with = "deserialize_sats")]
+    pub funded_txo_sum: u64,
+    pub spent_txo_count: u64,
+    #[serde(deserialize_with = "deserialize_sats")]
+    pub spent_txo_sum: u64,
+    pub tx_count: u64,
+}
+
+/// Confirmation details of a transaction, as shown on a receipt.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConfirmationDetails {
+    /// Number of confirmations, zero while the transaction is in the mempool.
+    pub confirmations: u32,
+    pub block_hash: Option<BlockHash>,
+    pub block_height: Option<u32>,
+    pub block_time: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct MerkleProof {
     pub block_height: u32,
     pub merkle: Vec<Txid>,
     pub pos: usize,
 }
 
-#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+impl MerkleProof {
+    /// Compute the merkle root of the block this proof proves the inclusion of `txid` in, to be
+    /// compared with the merkle root of its header.
+    pub fn merkle_root(&self, txid: &Txid) -> [u8; 32] {
+        let mut hash = txid.to_byte_array();
+        let mut pos = self.pos;
+        for sibling in &self.merkle {
+            let mut engine = Sha256::new();
+            if pos & 1 == 0 {
+                engine.update(hash);
+                engine.update(sibling.to_byte_array());
+            } else {
+                engine.update(sibling.to_byte_array());
+                engine.update(hash);
+            }
+            hash = Sha256::digest(engine.finalize()).into();
+            pos >>= 1;
+        }
+        hash
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(remote = "Self")]
 pub struct OutputStatus {
     pub spent: bool,
+    #[serde(skip_serializing_if = "is_none_and_readable")]
     pub txid: Option<Txid>,
-    pub vin: Option<u64>,
+    #[serde(skip_serializing_if = "is_none_and_readable")]
+    pub vin: Option<u32>,
+    #[serde(skip_serializing_if = "is_none_and_readable")]
     pub status: Option<TxStatus>,
 }
 
-#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(remote = "Self")]
 pub struct BlockStatus {
     pub in_best_chain: bool,
+    #[serde(skip_serializing_if = "is_none_and_readable")]
     pub height: Option<u32>,
+    #[serde(skip_serializing_if = "is_none_and_readable")]
     pub next_best: Option<BlockHash>,
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(remote = "Self")]
 pub struct Tx {
     pub txid: Txid,
     pub version: i32,
@@ -81,33 +440,524 @@ pub struct Tx {
     pub vin: Vec<Vin>,
     pub vout: Vec<Vout>,
     pub status: TxStatus,
-    pub fee: u64,
+    /// Fee paid by the transaction; not reported by all backends.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_opt_sats",
+        skip_serializing_if = "is_none_and_readable"
+    )]
+    pub fee: Option<u64>,
     pub size: u32,
     pub weight: u32,
 }
 
-#[derive(Deserialize, Clone, Debug)]
+/// Lightweight summary of a transaction, as needed to list a wallet history, without its inputs
+/// and outputs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(remote = "Self")]
+pub struct TxSummary {
+    pub txid: Txid,
+    pub status: TxStatus,
+    /// Fee paid by the transaction; not reported by all backends.
+    #[serde(default, skip_serializing_if = "is_none_and_readable")]
+    pub fee: Option<u64>,
+    pub size: u32,
+    pub weight: u32,
+    /// Effect of the transaction on the balance of the scripts it was summarized for.
+    pub net_value: NetValue,
+}
+
+/// Effect of a transaction on the balance of a set of scripts: the value of the outputs paying
+/// to them minus the value of the outputs of theirs it spends, in satoshis.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NetValue {
+    /// Value computed from all the inputs of the transaction.
+    Complete(i64),
+    /// Value computed without the inputs whose previous output wasn't reported by the server,
+    /// which may spend from the scripts.
+    Incomplete(i64),
+}
+
+impl NetValue {
+    /// Get the value, which is only an upper bound when it is incomplete.
+    pub fn value(self) -> i64 {
+        match self {
+            NetValue::Complete(value) | NetValue::Incomplete(value) => value,
+        }
+    }
+
+    /// Whether the value was computed from all the inputs of the transaction.
+    pub fn is_complete(self) -> bool {
+        matches!(self, NetValue::Complete(_))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(remote = "Self")]
 pub struct Utxo {
     pub txid: Txid,
-    pub vout: Vec<Vout>,
+    /// Index of the output in the transaction; `/utxo` returns it as a plain number.
+    pub vout: u32,
+    #[serde(deserialize_with = "deserialize_sats")]
     pub value: u64,
     pub status: TxStatus,
+    /// Number of confirmations, as computed by the `*_utxo_filtered` methods of the clients;
+    /// `None` for unconfirmed outputs and when it wasn't computed.
+    #[serde(default, skip_serializing_if = "is_none_and_readable")]
+    pub confirmations: Option<u32>,
+}
+
+impl Utxo {
+    /// Annotates unspent outputs with their confirmations when the chain tip is at height
+    /// `tip`, keeping the ones with at least `min_confirmations`, and the unconfirmed ones if
+    /// `include_unconfirmed`.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn filter_by_confirmations(
+        utxos: Vec<Utxo>,
+        tip: u32,
+        min_confirmations: u32,
+        include_unconfirmed: bool,
+    ) -> Vec<Utxo> {
+        utxos
+            .into_iter()
+            .filter_map(|mut utxo| {
+                utxo.confirmations = utxo.status.confirmations(tip);
+                let keep = match utxo.confirmations {
+                    Some(confirmations) => confirmations >= min_confirmations,
+                    None => include_unconfirmed,
+                };
+                if keep {
+                    Some(utxo)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Summary of a transaction recently entered into the mempool.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MempoolRecent {
+    pub txid: Txid,
+    #[serde(deserialize_with = "deserialize_sats")]
+    pub fee: u64,
+    pub vsize: u64,
+    #[serde(deserialize_with = "deserialize_sats")]
+    pub value: u64,
 }
 
-#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+/// Backlog of the mempool.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MempoolStats {
+    pub count: u64,
+    pub vsize: u64,
+    #[serde(deserialize_with = "deserialize_sats")]
+    pub total_fee: u64,
+    /// Feerates (in sat/vB) in decreasing order, each with the total virtual size of the
+    /// transactions paying it or more, up to the previous feerate.
+    pub fee_histogram: Vec<(f32, u64)>,
+}
+
+impl MempoolStats {
+    /// Minimum feerate (in sat/vB) relayed by nodes with the default policy.
+    pub const MIN_RELAY_FEERATE: f32 = 1.0;
+
+    /// Get the feerate (in sat/vB) needed to be part of the next block, assuming it is made
+    /// of the highest-paying transactions up to `block_vsize` virtual bytes.
+    ///
+    /// When the mempool doesn't fill such a block, any transaction paying the minimum relay
+    /// feerate would be included, so [`MempoolStats::MIN_RELAY_FEERATE`] is returned.
+    pub fn next_block_feerate(&self, block_vsize: u64) -> f32 {
+        let mut vsize = 0u64;
+        for (feerate, bucket_vsize) in &self.fee_histogram {
+            vsize = vsize.saturating_add(*bucket_vsize);
+            if vsize >= block_vsize {
+                return feerate.max(Self::MIN_RELAY_FEERATE);
+            }
+        }
+        Self::MIN_RELAY_FEERATE
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct BlockTime {
     pub timestamp: u64,
     pub height: u32,
 }
 
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(from = "BlockSummaryFields", into = "BlockSummaryFields")]
 pub struct BlockSummary {
     pub id: BlockHash,
-    #[serde(flatten)]
     pub time: BlockTime,
     /// Hash of the previous block, will be `None` for the genesis block.
     pub previousblockhash: Option<BlockHash>,
     pub merkle_root: Bytes32,
+    /// Number of transactions of the block, including its coinbase; zero when read from a
+    /// summary serialized before it was recorded.
+    pub tx_count: u32,
+    /// Size of the block, in bytes; zero when read from a summary serialized before it was
+    /// recorded.
+    pub size: u32,
+}
+
+/// Fields of [`BlockSummary`] as laid out by the server, with the block time inlined. Used in
+/// place of `#[serde(flatten)]`, which only works with self-describing formats.
+#[derive(Serialize, Deserialize)]
+struct BlockSummaryFields {
+    id: BlockHash,
+    timestamp: u64,
+    height: u32,
+    previousblockhash: Option<BlockHash>,
+    merkle_root: Bytes32,
+    #[serde(default)]
+    tx_count: u32,
+    #[serde(default)]
+    size: u32,
+}
+
+impl From<BlockSummaryFields> for BlockSummary {
+    fn from(fields: BlockSummaryFields) -> Self {
+        BlockSummary {
+            id: fields.id,
+            time: BlockTime {
+                timestamp: fields.timestamp,
+                height: fields.height,
+            },
+            previousblockhash: fields.previousblockhash,
+            merkle_root: fields.merkle_root,
+            tx_count: fields.tx_count,
+            size: fields.size,
+        }
+    }
+}
+
+impl From<BlockSummary> for BlockSummaryFields {
+    fn from(summary: BlockSummary) -> Self {
+        BlockSummaryFields {
+            id: summary.id,
+            timestamp: summary.time.timestamp,
+            height: summary.time.height,
+            previousblockhash: summary.previousblockhash,
+            merkle_root: summary.merkle_root,
+            tx_count: summary.tx_count,
+            size: summary.size,
+        }
+    }
+}
+
+impl BlockSummary {
+    /// Average time between consecutive blocks of a list ordered from the newest to the oldest,
+    /// according to their timestamps; zero if there are less than two blocks.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn average_interval(summaries: &[BlockSummary]) -> Duration {
+        let (newest, oldest) = match (summaries.first(), summaries.last()) {
+            (Some(newest), Some(oldest)) if summaries.len() > 1 => (newest, oldest),
+            _ => return Duration::from_secs(0),
+        };
+        let span = newest.time.timestamp.saturating_sub(oldest.time.timestamp);
+        Duration::from_secs(span) / (summaries.len() - 1) as u32
+    }
+}
+
+/// Feerate statistics of the transactions of a block, but its coinbase.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct BlockFeeStats {
+    /// Lowest feerate (in sat/vB) paid in the block.
+    pub min_feerate: f32,
+    /// Median of the feerates (in sat/vB) of the transactions of the block.
+    pub median_feerate: f32,
+    /// Highest feerate (in sat/vB) paid in the block.
+    pub max_feerate: f32,
+    /// Sum of the fees (in sats) paid in the block.
+    pub total_fees: u64,
+}
+
+impl BlockFeeStats {
+    /// Computes the statistics of the transactions of a block, skipping the coinbase and the
+    /// transactions whose fee wasn't reported by the server; all zeros when none is left.
+    ///
+    /// Fails with [`Error::InvalidServerData`] if the fees add up to more than a `u64`.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn from_txs(txs: &[Tx]) -> Result<Self, Error> {
+        let mut total_fees = 0u64;
+        let mut feerates = vec![];
+        for tx in txs.iter().filter(|tx| !tx.is_coinbase()) {
+            if let Some(fee) = tx.fee {
+                total_fees = total_fees
+                    .checked_add(fee)
+                    .ok_or(Error::InvalidServerData)?;
+                feerates.push(fee as f32 / tx.vsize().max(1) as f32);
+            }
+        }
+        feerates.sort_by(|a, b| a.partial_cmp(b).expect("feerates are never NaN"));
+
+        let median_feerate = match feerates.len() {
+            0 => 0.0,
+            len if len % 2 == 1 => feerates[len / 2],
+            len => (feerates[len / 2 - 1] + feerates[len / 2]) / 2.0,
+        };
+        Ok(BlockFeeStats {
+            min_feerate: feerates.first().copied().unwrap_or_default(),
+            median_feerate,
+            max_feerate: feerates.last().copied().unwrap_or_default(),
+            total_fees,
+        })
+    }
+}
+
+/// Block fetched ahead of time by [`crate::AsyncClient::block_prefetcher`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrefetchedBlock {
+    pub summary: BlockSummary,
+    /// Ids of the block transactions, in block order.
+    pub txids: Vec<Txid>,
+}
+
+/// Output of a transaction paying to a given script.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PaymentInfo {
+    pub vout: u32,
+    #[serde(deserialize_with = "deserialize_sats")]
+    pub value: u64,
+    pub status: TxStatus,
+    #[serde(
+        serialize_with = "serialize_script",
+        deserialize_with = "deserialize_payment_script"
+    )]
+    pub script_pubkey: ScriptPubkey,
+}
+
+/// Software implementing the Esplora API.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendFlavor {
+    /// Blockstream esplora.
+    Esplora,
+    /// mempool.space backend.
+    MempoolSpace,
+    /// Plain electrs.
+    Electrs,
+    /// Backend which couldn't be identified.
+    Unknown,
+}
+
+/// Best-effort identification of an Esplora server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BackendInfo {
+    pub flavor: BackendFlavor,
+    pub version: Option<String>,
+    pub base_url: String,
+}
+
+impl BackendInfo {
+    /// Identifies a backend from the results of probing it: whether it serves mempool.space's
+    /// `/v1/difficulty-adjustment` and esplora's `/mempool/recent` endpoints, and the software
+    /// reported in its `X-Powered-By` or `Server` response headers.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn identify(
+        base_url: &str,
+        difficulty_adjustment: bool,
+        recent_mempool: bool,
+        software: Option<&str>,
+    ) -> Self {
+        let (name, version) = match software.map(|software| software.trim().split_once('/')) {
+            Some(Some((name, version))) => (name.to_lowercase(), Some(version.to_owned())),
+            Some(None) => (software.unwrap_or_default().trim().to_lowercase(), None),
+            None => (String::new(), None),
+        };
+        let flavor = if difficulty_adjustment || name.contains("mempool") {
+            BackendFlavor::MempoolSpace
+        } else if name.contains("electrs") {
+            BackendFlavor::Electrs
+        } else if name.contains("esplora") || recent_mempool {
+            BackendFlavor::Esplora
+        } else {
+            BackendFlavor::Unknown
+        };
+        let known_software = ["mempool", "electrs", "esplora"]
+            .iter()
+            .any(|known| name.contains(known));
+        BackendInfo {
+            flavor,
+            version: version.filter(|_| known_software),
+            base_url: base_url.to_owned(),
+        }
+    }
+
+    /// Whether the backend paginates address histories with an `after_txid` query parameter
+    /// rather than with a path segment.
+    pub fn supports_query_pagination(&self) -> bool {
+        self.flavor == BackendFlavor::MempoolSpace
+    }
+}
+
+/// Origin of fee estimates.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeEstimatesSource {
+    /// Estimates were provided by the server.
+    Primary,
+    /// Estimates were all synthesized from a fallback.
+    Fallback,
+    /// Estimates provided by the server were complemented from a fallback.
+    Merged,
+}
+
+/// Fee estimates in either of the shapes returned by the servers: Esplora keys them by
+/// confirmation target, mempool.space-compatible ones return named tiers.
+#[cfg(any(feature = "blocking", feature = "async"))]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FeeEstimatesBody {
+    // tried first, as tiers would also deserialize as a map
+    Tiers(RecommendedFees),
+    Targets(HashMap<String, f64>),
+}
+
+/// Feerates (in sat/vB) of the named tiers of mempool.space.
+#[cfg(any(feature = "blocking", feature = "async"))]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecommendedFees {
+    fastest_fee: f64,
+    half_hour_fee: f64,
+    hour_fee: f64,
+    economy_fee: f64,
+    minimum_fee: f64,
+}
+
+/// Parses fee estimates in either shape into a map from confirmation targets (in number of
+/// blocks) to feerates (in sat/vB), named tiers being mapped to the targets they stand for.
+#[cfg(any(feature = "blocking", feature = "async"))]
+pub(crate) fn parse_fee_estimates(body: &[u8]) -> Result<HashMap<String, f64>, serde_json::Error> {
+    Ok(match serde_json::from_slice(body)? {
+        FeeEstimatesBody::Targets(estimates) => estimates,
+        FeeEstimatesBody::Tiers(fees) => [
+            (1, fees.fastest_fee),
+            (3, fees.half_hour_fee),
+            (6, fees.hour_fee),
+            (144, fees.economy_fee),
+            (1008, fees.minimum_fee),
+        ]
+        .iter()
+        .map(|(target, rate)| (target.to_string(), *rate))
+        .collect(),
+    })
+}
+
+/// Map from confirmation targets (in number of blocks) to estimated feerates (in sat/vB), tagged
+/// with the origin of the estimates.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FeeEstimates {
+    pub estimates: HashMap<String, f64>,
+    pub source: FeeEstimatesSource,
+}
+
+impl FeeEstimates {
+    /// Estimates provided by the server.
+    pub fn primary(estimates: HashMap<String, f64>) -> Self {
+        FeeEstimates {
+            estimates,
+            source: FeeEstimatesSource::Primary,
+        }
+    }
+
+    /// Merges estimates provided by the server with the ones from a fallback, with the values
+    /// provided by the server taking precedence.
+    pub fn merge(primary: HashMap<String, f64>, fallback: HashMap<String, f64>) -> Self {
+        let source = if primary.is_empty() && !fallback.is_empty() {
+            FeeEstimatesSource::Fallback
+        } else if fallback.keys().any(|target| !primary.contains_key(target)) {
+            FeeEstimatesSource::Merged
+        } else {
+            FeeEstimatesSource::Primary
+        };
+        let mut estimates = fallback;
+        estimates.extend(primary);
+        FeeEstimates { estimates, source }
+    }
+}
+
+/// Confirmation target of a fee estimate, in number of blocks.
+///
+/// Esplora provides estimates for targets between 1 and 1008 blocks; [`ConfTarget::new`],
+/// conversions from integers and parsing reject values outside of that range.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConfTarget(u16);
+
+impl ConfTarget {
+    /// Smallest supported confirmation target.
+    pub const MIN: ConfTarget = ConfTarget(1);
+    /// Largest supported confirmation target.
+    pub const MAX: ConfTarget = ConfTarget(1008);
+
+    /// Constructs a confirmation target, checking it is within the supported range.
+    pub fn new(blocks: u16) -> Result<Self, ConfTargetError> {
+        if !(Self::MIN.0..=Self::MAX.0).contains(&blocks) {
+            return Err(ConfTargetError::OutOfRange(blocks as i128));
+        }
+        Ok(ConfTarget(blocks))
+    }
+
+    /// Number of blocks of the target.
+    pub fn blocks(self) -> u16 {
+        self.0
+    }
+}
+
+impl Display for ConfTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for ConfTarget {
+    type Err = ConfTargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let blocks = s
+            .parse::<i128>()
+            .map_err(|_| ConfTargetError::NotANumber(s.to_owned()))?;
+        ConfTarget::try_from(blocks)
+    }
+}
+
+impl From<ConfTarget> for u16 {
+    fn from(target: ConfTarget) -> Self {
+        target.0
+    }
+}
+
+macro_rules! impl_conf_target_try_from {
+    ($($ty:ty),+) => {
+        $(
+            impl TryFrom<$ty> for ConfTarget {
+                type Error = ConfTargetError;
+
+                fn try_from(blocks: $ty) -> Result<Self, Self::Error> {
+                    let blocks = blocks as i128;
+                    if !(Self::MIN.0 as i128..=Self::MAX.0 as i128).contains(&blocks) {
+                        return Err(ConfTargetError::OutOfRange(blocks));
+                    }
+                    Ok(ConfTarget(blocks as u16))
+                }
+            }
+        )+
+    };
+}
+impl_conf_target_try_from!(u8, u16, u32, u64, usize, i32, i64, i128);
+
+/// Errors constructing a [`ConfTarget`].
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+#[display(doc_comments)]
+pub enum ConfTargetError {
+    /// confirmation target '{0}' is not a number of blocks.
+    NotANumber(String),
+
+    /// confirmation target of {0} blocks is outside of the supported 1..=1008 range.
+    OutOfRange(i128),
 }
 
 impl Tx {
@@ -130,6 +980,65 @@ impl Tx {
         })
     }
 
+    /// Virtual size of the transaction in vbytes.
+    pub fn vsize(&self) -> u32 {
+        (self.weight + 3) / 4
+    }
+
+    /// Whether the transaction is a coinbase, as flagged by the server on its input, or else
+    /// recognized by its single input spending the null outpoint.
+    pub fn is_coinbase(&self) -> bool {
+        match self.vin.as_slice() {
+            [vin] => vin.is_coinbase || vin.txid.is_coinbase(),
+            _ => false,
+        }
+    }
+
+    /// Summarize the transaction, computing its effect on the balance of `my_scripts`.
+    ///
+    /// Coinbase inputs spend nothing. Inputs lacking their previous output make the net value
+    /// [`NetValue::Incomplete`].
+    ///
+    /// Fails with [`Error::InvalidServerData`] if the values overflow.
+    pub fn summarize(&self, my_scripts: &HashSet<ScriptPubkey>) -> Result<TxSummary, Error> {
+        let received = self
+            .vout
+            .iter()
+            .filter(|vout| my_scripts.contains(&vout.scriptpubkey))
+            .try_fold(0u64, |sum, vout| sum.checked_add(vout.value))
+            .ok_or(Error::InvalidServerData)?;
+        let mut spent = 0u64;
+        let mut complete = true;
+        // the input of a coinbase spends nothing
+        let inputs = self.vin.iter().filter(|_| !self.is_coinbase());
+        for vin in inputs {
+            match &vin.prevout {
+                Some(prevout) if my_scripts.contains(&prevout.scriptpubkey) => {
+                    spent = spent
+                        .checked_add(prevout.value)
+                        .ok_or(Error::InvalidServerData)?;
+                }
+                Some(_) => {}
+                None => complete = false,
+            }
+        }
+        // valid amounts are below the 21M BTC supply, which fits an i64 in satoshis
+        let value = i64::try_from(i128::from(received) - i128::from(spent))
+            .map_err(|_| Error::InvalidServerData)?;
+        Ok(TxSummary {
+            txid: self.txid,
+            status: self.status.clone(),
+            fee: self.fee,
+            size: self.size,
+            weight: self.weight,
+            net_value: if complete {
+                NetValue::Complete(value)
+            } else {
+                NetValue::Incomplete(value)
+            },
+        })
+    }
+
     pub fn confirmation_time(&self) -> Option<BlockTime> {
         match self.status {
             TxStatus {
@@ -142,6 +1051,33 @@ impl Tx {
         }
     }
 
+    /// Lists all outputs of the transaction paying to `script`, in output order.
+    pub fn payments_to(&self, script: &ScriptPubkey) -> Vec<PaymentInfo> {
+        self.vout
+            .iter()
+            .enumerate()
+            .filter(|(_, vout)| &vout.scriptpubkey == script)
+            .map(|(index, vout)| PaymentInfo {
+                vout: index as u32,
+                value: vout.value,
+                status: self.status.clone(),
+                script_pubkey: vout.scriptpubkey.clone(),
+            })
+            .collect()
+    }
+
+    /// Lists the index and value of all outputs of the transaction paying to `address`, in
+    /// output order.
+    pub fn outputs_to(&self, address: &Address) -> Vec<(u32, Sats)> {
+        let script = address.script_pubkey();
+        self.vout
+            .iter()
+            .enumerate()
+            .filter(|(_, vout)| vout.scriptpubkey == script)
+            .map(|(index, vout)| (index as u32, Sats::from(vout.value)))
+            .collect()
+    }
+
     pub fn previous_outputs(&self) -> Vec<Option<TxOut>> {
         self.vin
             .iter()
@@ -154,15 +1090,1161 @@ impl Tx {
             })
             .collect()
     }
+
+    /// Lists the outputs spent by the inputs of the transaction, in input order.
+    pub fn input_outpoints(&self) -> Vec<Outpoint> {
+        self.vin
+            .iter()
+            .map(|vin| Outpoint::new(vin.txid, vin.vout))
+            .collect()
+    }
+
+    /// Lists the outpoints and values (in satoshis) of the outputs of the transaction, in output
+    /// order.
+    pub fn output_outpoints(&self) -> Vec<(Outpoint, u64)> {
+        self.vout
+            .iter()
+            .enumerate()
+            .map(|(index, vout)| (Outpoint::new(self.txid, index as u32), vout.value))
+            .collect()
+    }
+}
+
+impl TryFrom<&Tx> for Transaction {
+    type Error = Error;
+
+    /// Reconstructs the consensus transaction, as [`Tx::to_tx`] does, saving a request for the
+    /// raw transaction when its API representation is at hand.
+    ///
+    /// Fails with [`Error::BitcoinEncoding`] if the transaction can't be encoded, or if its
+    /// txid or weight differ from the ones reported by the server, as when the server omitted
+    /// witnesses.
+    fn try_from(tx: &Tx) -> Result<Self, Self::Error> {
+        let encoding_error = |reason: String| Error::BitcoinEncoding {
+            txid: tx.txid,
+            reason,
+        };
+        let transaction = tx.to_tx().map_err(|err| encoding_error(err.to_string()))?;
+        let txid = transaction.txid();
+        if txid != tx.txid {
+            return Err(encoding_error(format!("it was rebuilt with txid {}", txid)));
+        }
+        let weight = transaction.weight_units().to_u32();
+        if weight != tx.weight {
+            return Err(encoding_error(format!(
+                "it was rebuilt with a weight of {} WU instead of {} WU",
+                weight, tx.weight
+            )));
+        }
+        Ok(transaction)
+    }
+}
+
+/// Order transactions for display: unconfirmed ones first, then confirmed ones by decreasing
+/// block height, transactions of the same block being ordered by txid so that the order doesn't
+/// depend on the server.
+pub fn cmp_tx_for_display(a: &Tx, b: &Tx) -> Ordering {
+    let rank = |tx: &Tx| match (tx.status.confirmed, tx.status.block_height) {
+        (false, _) => (0, Reverse(0)),
+        (true, Some(height)) => (1, Reverse(height)),
+        (true, None) => (2, Reverse(0)),
+    };
+    rank(a).cmp(&rank(b)).then_with(|| a.txid.cmp(&b.txid))
+}
+
+/// Merge transaction histories of the same scripts from two sources, deduplicating them by
+/// txid and ordering them with [`cmp_tx_for_display`].
+///
+/// When both sources have a transaction with different statuses, a confirmed one wins over an
+/// unconfirmed one, and the one with more confirmations (at a lower height) wins between two
+/// confirmed ones. Otherwise the transaction from `a` is kept, so the merge is commutative
+/// except for transactions with the same status but different details.
+pub fn merge_histories(a: Vec<Tx>, b: Vec<Tx>) -> Vec<Tx> {
+    let mut merged = dedup_history(a.into_iter().chain(b));
+    merged.sort_by(cmp_tx_for_display);
+    merged
+}
+
+/// Deduplicate the transactions of a history by txid with the precedence of
+/// [`merge_histories`], keeping them in the order given, e.g. the order of the server across
+/// the pages of a history.
+pub(crate) fn dedup_history(txs: impl IntoIterator<Item = Tx>) -> Vec<Tx> {
+    let mut deduped: Vec<Tx> = vec![];
+    let mut positions = HashMap::<Txid, usize>::new();
+    for tx in txs {
+        match positions.get(&tx.txid) {
+            Some(&pos) if supersedes(&tx.status, &deduped[pos].status) => deduped[pos] = tx,
+            Some(_) => {}
+            None => {
+                positions.insert(tx.txid, deduped.len());
+                deduped.push(tx);
+            }
+        }
+    }
+    deduped
+}
+
+/// Whether `status` takes precedence over the `other` status of the same transaction.
+fn supersedes(status: &TxStatus, other: &TxStatus) -> bool {
+    match (status.confirmed, other.confirmed) {
+        (true, false) => true,
+        (true, true) => {
+            status.block_height.unwrap_or(u32::MAX) < other.block_height.unwrap_or(u32::MAX)
+        }
+        _ => false,
+    }
+}
+
+thread_local! {
+    /// Whether the value being serialized goes to a human-readable format, see
+    /// [`is_none_and_readable`].
+    static HUMAN_READABLE: Cell<bool> = Cell::new(true);
+}
+
+/// Whether to leave out an optional field: `None` is omitted from human-readable formats, as
+/// the server does, but kept in binary formats, which rely on the position of the fields.
+fn is_none_and_readable<T>(value: &Option<T>) -> bool {
+    value.is_none() && HUMAN_READABLE.with(Cell::get)
+}
+
+/// Implements `Serialize` and `Deserialize` for types deriving them with
+/// `#[serde(remote = "Self")]`, recording whether the serializer is human-readable for
+/// [`is_none_and_readable`], since `skip_serializing_if` functions don't get the serializer.
+macro_rules! skip_none_if_readable {
+    ($($ty:ident),+ $(,)?) => {$(
+        impl Serialize for $ty {
+            fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::ser::Serializer,
+            {
+                let outer = HUMAN_READABLE.with(|cell| cell.replace(s.is_human_readable()));
+                let res = $ty::serialize(self, s);
+                HUMAN_READABLE.with(|cell| cell.set(outer));
+                res
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D>(d: D) -> Result<Self, D::Error>
+            where
+                D: serde::de::Deserializer<'de>,
+            {
+                $ty::deserialize(d)
+            }
+        }
+    )+};
+}
+
+skip_none_if_readable!(
+    Vout,
+    TxStatus,
+    AddressStats,
+    OutputStatus,
+    BlockStatus,
+    Tx,
+    TxSummary,
+    Utxo
+);
+
+/// Byte string serialized as hex in human-readable formats, as the server sends them, and as
+/// raw bytes in binary formats.
+struct HexBytes<'a>(&'a [u8]);
+
+impl Serialize for HexBytes<'_> {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        if s.is_human_readable() {
+            s.serialize_str(&self.0.to_hex())
+        } else {
+            s.serialize_bytes(self.0)
+        }
+    }
+}
+
+/// Counterpart of [`HexBytes`], failing on malformed hex with the hex error alone, for the
+/// callers to name the field.
+struct HexByteBuf(Result<Vec<u8>, amplify::hex::Error>);
+
+impl<'de> Deserialize<'de> for HexByteBuf {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        if d.is_human_readable() {
+            let hex_str = String::deserialize(d)?;
+            return Ok(HexByteBuf(Vec::<u8>::from_hex(&hex_str)));
+        }
+        d.deserialize_byte_buf(ByteBufVisitor)
+            .map(|bytes| HexByteBuf(Ok(bytes)))
+    }
+}
+
+/// Visitor of byte strings, also accepting sequences of bytes from the formats without a
+/// distinct byte string type.
+struct ByteBufVisitor;
+
+impl<'de> serde::de::Visitor<'de> for ByteBufVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("a byte string")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(bytes)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default().min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+fn serialize_witness<S>(witness: &[Vec<u8>], s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::ser::Serializer,
+{
+    s.collect_seq(witness.iter().map(|item| HexBytes(item)))
+}
+
+/// Serializes a script as the hex string the server sent it as, or as raw bytes in binary
+/// formats.
+fn serialize_script<S, T>(script: &T, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::ser::Serializer,
+    T: AsRef<[u8]>,
+{
+    HexBytes(script.as_ref()).serialize(s)
+}
+
+fn deserialize_scriptpubkey<'de, D>(d: D) -> Result<ScriptPubkey, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    deserialize_script(d, "scriptpubkey")
+}
+
+fn deserialize_scriptsig<'de, D>(d: D) -> Result<SigScript, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    deserialize_script(d, "scriptsig")
+}
+
+fn deserialize_payment_script<'de, D>(d: D) -> Result<ScriptPubkey, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    deserialize_script(d, "script_pubkey")
+}
+
+/// Deserializes a script from its hex encoding, or its raw bytes in binary formats, naming
+/// `field` in the error when the hex is malformed, since the position reported by the JSON
+/// parser doesn't tell which script of a transaction is.
+fn deserialize_script<'de, D, T>(d: D, field: &str) -> Result<T, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+    T: TryFrom<Vec<u8>>,
+    T::Error: Display,
+{
+    let bytes = HexByteBuf::deserialize(d)?.0.map_err(|err| {
+        serde::de::Error::custom(format_args!("invalid hex in `{}`: {}", field, err))
+    })?;
+    T::try_from(bytes).map_err(|err| {
+        serde::de::Error::custom(format_args!("invalid script in `{}`: {}", field, err))
+    })
+}
+
+/// Smallest integer an `f64` can't be trusted to hold exactly, 2^53: it is also what the larger
+/// integers up to 2^53 + 2 round to.
+const MAX_SAFE_FLOAT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// Amount in satoshis read from either a JSON number or a string of decimal digits, as some
+/// backends quote the values too large to be safely read as JSON numbers by JavaScript clients.
+/// Binary formats only take the integer form.
+struct LenientSats(u64);
+
+impl<'de> Deserialize<'de> for LenientSats {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        if d.is_human_readable() {
+            d.deserialize_any(SatsVisitor).map(LenientSats)
+        } else {
+            u64::deserialize(d).map(LenientSats)
+        }
+    }
+}
+
+struct SatsVisitor;
+
+impl<'de> serde::de::Visitor<'de> for SatsVisitor {
+    type Value = u64;
+
+    fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("an amount in satoshis, as an integer or a string of decimal digits")
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(value)
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        u64::try_from(value)
+            .map_err(|_| E::invalid_value(serde::de::Unexpected::Signed(value), &self))
+    }
+
+    /// Accepts whole numbers written with a fraction or an exponent only as long as they are
+    /// exact, rather than silently rounding the amount. This relies on the `float_roundtrip`
+    /// feature of serde_json, without which floats may be parsed one unit off.
+    fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        if value >= 0.0 && value.fract() == 0.0 && value < MAX_SAFE_FLOAT_INTEGER {
+            Ok(value as u64)
+        } else {
+            Err(E::invalid_value(serde::de::Unexpected::Float(value), &self))
+        }
+    }
+
+    fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        value
+            .parse()
+            .map_err(|_| E::invalid_value(serde::de::Unexpected::Str(value), &self))
+    }
+}
+
+fn deserialize_sats<'de, D>(d: D) -> Result<u64, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    LenientSats::deserialize(d).map(|sats| sats.0)
+}
+
+fn deserialize_opt_sats<'de, D>(d: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    Option::<LenientSats>::deserialize(d).map(|sats| sats.map(|sats| sats.0))
+}
+
+/// Serializes an optional address as its string form, whatever the format, for
+/// [`deserialize_address`] to read it back.
+fn serialize_address<S>(address: &Option<Address>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::ser::Serializer,
+{
+    address.as_ref().map(Address::to_string).serialize(s)
+}
+
+/// Deserializes an optional address, ignoring addresses which can't be parsed rather than
+/// failing the whole transaction.
+fn deserialize_address<'de, D>(d: D) -> Result<Option<Address>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    let address = Option::<String>::deserialize(d)?;
+    Ok(address.and_then(|address| Address::from_str(&address).ok()))
 }
 
 fn deserialize_witness<'de, D>(d: D) -> Result<Vec<Vec<u8>>, D::Error>
 where
     D: serde::de::Deserializer<'de>,
 {
-    let list = Vec::<String>::deserialize(d)?;
+    let list = Vec::<HexByteBuf>::deserialize(d)?;
     list.into_iter()
-        .map(|hex_str| Vec::<u8>::from_hex(&hex_str))
+        .map(|item| item.0)
         .collect::<Result<Vec<Vec<u8>>, _>>()
         .map_err(serde::de::Error::custom)
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+
+    /// Confirmed transaction as returned by `/tx/:txid`, with the fields this library doesn't
+    /// model, like the script assembly.
+    const TX_JSON: &str = r#"{
+        "txid": "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050",
+        "version": 2,
+        "locktime": 0,
+        "vin": [{
+            "txid": "1f2e3d4c5b6a79880f1e2d3c4b5a69780f1e2d3c4b5a69788796a5b4c3d2e1f0",
+            "vout": 1,
+            "prevout": {
+                "scriptpubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+                "scriptpubkey_asm": "OP_0 OP_PUSHBYTES_20 751e76e8199196d454941c45d1b3a323f1433bd6",
+                "scriptpubkey_type": "v0_p2wpkh",
+                "scriptpubkey_address": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                "value": 100000
+            },
+            "scriptsig": "",
+            "scriptsig_asm": "",
+            "witness": ["3044022000010203040506070809000102030405060708090001020304050607080902200001020304050607080900010203040506070809000102030405060708090001", "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"],
+            "is_coinbase": false,
+            "sequence": 4294967293
+        }],
+        "vout": [{
+            "scriptpubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            "scriptpubkey_asm": "OP_0 OP_PUSHBYTES_20 751e76e8199196d454941c45d1b3a323f1433bd6",
+            "scriptpubkey_type": "v0_p2wpkh",
+            "scriptpubkey_address": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "value": 90000
+        }, {
+            "scriptpubkey": "6a0568656c6c6f",
+            "scriptpubkey_asm": "OP_RETURN OP_PUSHBYTES_5 68656c6c6f",
+            "scriptpubkey_type": "op_return",
+            "value": 0
+        }],
+        "size": 222,
+        "weight": 561,
+        "fee": 10000,
+        "status": {
+            "confirmed": true,
+            "block_height": 800000,
+            "block_hash": "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054",
+            "block_time": 1690168629
+        }
+    }"#;
+
+    /// Parse a fixture, check that serializing the value to JSON, CBOR and bincode gives the
+    /// same value back and return it.
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + fmt::Debug>(json: &str) -> T {
+        let value = serde_json::from_str::<T>(json).unwrap();
+        let sizes = encoded_sizes(&value);
+        assert!(sizes.bincode > 0 && sizes.cbor > 0);
+        value
+    }
+
+    /// Sizes of a value encoded in each of the tested formats.
+    struct EncodedSizes {
+        json: usize,
+        cbor: usize,
+        bincode: usize,
+    }
+
+    /// Serialize `value` to JSON, CBOR and bincode, checking that each gives the same value back.
+    fn encoded_sizes<T>(value: &T) -> EncodedSizes
+    where
+        T: Serialize + DeserializeOwned + PartialEq + fmt::Debug,
+    {
+        let json = serde_json::to_string(value).unwrap();
+        assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), value);
+
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(value, &mut cbor).unwrap();
+        assert_eq!(
+            &ciborium::de::from_reader::<T, _>(&cbor[..]).unwrap(),
+            value
+        );
+
+        let bincode = bincode::serialize(value).unwrap();
+        assert_eq!(&bincode::deserialize::<T>(&bincode).unwrap(), value);
+
+        EncodedSizes {
+            json: json.len(),
+            cbor: cbor.len(),
+            bincode: bincode.len(),
+        }
+    }
+
+    #[test]
+    fn lenient_sats() {
+        let sats = |json: &str| {
+            serde_json::from_str::<MempoolRecent>(&format!(
+                r#"{{
+                    "txid": "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050",
+                    "fee": 1000,
+                    "vsize": 141,
+                    "value": {}
+                }}"#,
+                json
+            ))
+            .map(|recent| recent.value)
+        };
+        assert_eq!(sats("9007199254740993").unwrap(), 9_007_199_254_740_993);
+        assert_eq!(sats("\"9007199254740993\"").unwrap(), 9_007_199_254_740_993);
+        assert_eq!(sats("\"18446744073709551615\"").unwrap(), u64::MAX);
+        assert_eq!(sats("9007199254740991.0").unwrap(), 9_007_199_254_740_991);
+        assert_eq!(sats("1e3").unwrap(), 1000);
+        // parsed as 2^53, which might have been any of the integers around it
+        assert!(sats("9007199254740993.0").is_err());
+        assert!(sats("9007199254740992.0").is_err());
+        assert!(sats("1.5").is_err());
+        assert!(sats("-1").is_err());
+        assert!(sats("\"0x10\"").is_err());
+        assert!(sats("\"18446744073709551616\"").is_err());
+    }
+
+    #[test]
+    fn other_types_round_trip() {
+        let summary = round_trip::<BlockSummary>(
+            r#"{
+                "id": "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054",
+                "height": 800000,
+                "timestamp": 1690168629,
+                "previousblockhash": null,
+                "merkle_root": "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050",
+                "tx_count": 3721,
+                "size": 1634715
+            }"#,
+        );
+        assert_eq!(summary.time.height, 800_000);
+        let legacy = serde_json::from_str::<BlockSummary>(
+            r#"{
+                "id": "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054",
+                "height": 800000,
+                "timestamp": 1690168629,
+                "previousblockhash": null,
+                "merkle_root": "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!((legacy.tx_count, legacy.size), (0, 0));
+        assert_eq!(legacy.id, summary.id);
+        let mempool = round_trip::<MempoolStats>(
+            r#"{
+                "count": 2,
+                "vsize": 300,
+                "total_fee": "1500",
+                "fee_histogram": [[5.5, 100], [2.0, 200]]
+            }"#,
+        );
+        assert_eq!(mempool.total_fee, 1500);
+        round_trip::<Vec<MempoolRecent>>(
+            r#"[{
+                "txid": "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050",
+                "fee": 1000,
+                "vsize": 141,
+                "value": 90000
+            }]"#,
+        );
+        let payment = round_trip::<PaymentInfo>(
+            r#"{
+                "vout": 0,
+                "value": 90000,
+                "status": { "confirmed": false },
+                "script_pubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6"
+            }"#,
+        );
+        assert_eq!(payment.script_pubkey.len(), 22);
+        round_trip::<TxSummary>(
+            r#"{
+                "txid": "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050",
+                "status": { "confirmed": false },
+                "size": 222,
+                "weight": 561,
+                "net_value": { "Incomplete": -10000 }
+            }"#,
+        );
+        round_trip::<VerifiedBalance>(
+            r#"{
+                "balance": 90000,
+                "verified": false,
+                "discrepancy": -10000,
+                "tip": "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054"
+            }"#,
+        );
+        round_trip::<ConfirmationDetails>(
+            r#"{
+                "confirmations": 0,
+                "block_hash": null,
+                "block_height": null,
+                "block_time": null
+            }"#,
+        );
+        round_trip::<BlockFeeStats>(
+            r#"{ "min_feerate": 1.0, "median_feerate": 2.5, "max_feerate": 40.0, "total_fees": 5000 }"#,
+        );
+        round_trip::<BackendInfo>(
+            r#"{ "flavor": "electrs", "version": "0.10.1", "base_url": "http://localhost:3000" }"#,
+        );
+        let estimates = round_trip::<FeeEstimates>(
+            r#"{ "estimates": { "1": 20.5, "144": 1.0 }, "source": "merged" }"#,
+        );
+        assert_eq!(estimates.source, FeeEstimatesSource::Merged);
+    }
+
+    #[test]
+    fn binary_encodings() {
+        let tx = serde_json::from_str::<Tx>(TX_JSON).unwrap();
+        let sizes = encoded_sizes(&tx);
+        // Scripts, witnesses and hashes are raw bytes rather than twice as many hex digits.
+        assert!(
+            sizes.bincode * 2 < sizes.json,
+            "{} vs {}",
+            sizes.bincode,
+            sizes.json
+        );
+        assert!(sizes.cbor < sizes.json, "{} vs {}", sizes.cbor, sizes.json);
+
+        // Missing fields are left out of JSON, as the server does, but kept in the positional
+        // layout of bincode.
+        let mut unconfirmed = tx;
+        unconfirmed.status = TxStatus {
+            confirmed: false,
+            block_height: None,
+            block_hash: None,
+            block_time: None,
+        };
+        unconfirmed.fee = None;
+        unconfirmed.vout[0].address = None;
+        let json = serde_json::to_string(&unconfirmed).unwrap();
+        for field in &[
+            "block_height",
+            "block_hash",
+            "block_time",
+            "fee",
+            "scriptpubkey_address",
+        ] {
+            assert!(!json.contains(field), "{} in {}", field, json);
+        }
+        encoded_sizes(&unconfirmed);
+
+        let utxo = Utxo {
+            txid: unconfirmed.txid,
+            vout: 1,
+            value: 90_000,
+            status: unconfirmed.status.clone(),
+            confirmations: None,
+        };
+        let sizes = encoded_sizes(&utxo);
+        assert!(
+            sizes.bincode < sizes.json,
+            "{} vs {}",
+            sizes.bincode,
+            sizes.json
+        );
+        assert!(!serde_json::to_string(&utxo)
+            .unwrap()
+            .contains("confirmations"));
+    }
+
+    #[test]
+    fn consensus_txs() {
+        let mut tx = round_trip::<Tx>(TX_JSON);
+        let transaction = tx.to_tx().unwrap();
+        tx.txid = transaction.txid();
+        tx.weight = transaction.weight_units().to_u32();
+        assert_eq!(Transaction::try_from(&tx).unwrap(), transaction);
+
+        let mut unsigned = tx.clone();
+        unsigned.vin[0].witness.clear();
+        let err = Transaction::try_from(&unsigned).unwrap_err();
+        assert!(matches!(err, Error::BitcoinEncoding { txid, .. } if txid == tx.txid));
+
+        let mut altered = tx;
+        altered.locktime = 1;
+        let err = Transaction::try_from(&altered).unwrap_err();
+        assert!(err.to_string().contains("rebuilt with txid"), "{}", err);
+    }
+
+    #[test]
+    fn tx_round_trip() {
+        let tx = round_trip::<Tx>(TX_JSON);
+        assert_eq!(tx.vin[0].witness.len(), 2);
+        assert_eq!(tx.vin[0].prevout.as_ref().unwrap().value, 100_000);
+        assert_eq!(tx.vout[0].script_type, Some(ScriptType::V0P2wpkh));
+        assert_eq!(
+            tx.vout[0].address.unwrap().to_string(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+        assert_eq!(tx.vout[1].address, None);
+        assert_eq!(tx.fee, Some(10_000));
+        assert_eq!(tx.status.block_height, Some(800_000));
+    }
+
+    #[test]
+    fn unconfirmed_tx_round_trip() {
+        let json = TX_JSON.replace(
+            r#""confirmed": true,
+            "block_height": 800000,
+            "block_hash": "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054",
+            "block_time": 1690168629"#,
+            r#""confirmed": false"#,
+        );
+        let tx = round_trip::<Tx>(&json);
+        assert_eq!(tx.status.block_hash, None);
+    }
+
+    #[test]
+    fn utxo_round_trip() {
+        let utxo = round_trip::<Utxo>(
+            r#"{
+                "txid": "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050",
+                "vout": 0,
+                "status": { "confirmed": false },
+                "value": 90000
+            }"#,
+        );
+        assert_eq!(utxo.vout, 0);
+        assert_eq!(utxo.confirmations, None);
+    }
+
+    #[test]
+    fn status_round_trips() {
+        let outspend = round_trip::<OutputStatus>(
+            r#"{
+                "spent": true,
+                "txid": "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050",
+                "vin": 0,
+                "status": { "confirmed": false }
+            }"#,
+        );
+        assert_eq!(outspend.vin, Some(0));
+        assert!(!round_trip::<OutputStatus>(r#"{ "spent": false }"#).spent);
+
+        let block_status = round_trip::<BlockStatus>(
+            r#"{
+                "in_best_chain": true,
+                "height": 800000,
+                "next_best": "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054"
+            }"#,
+        );
+        assert_eq!(block_status.height, Some(800_000));
+        assert!(!round_trip::<BlockStatus>(r#"{ "in_best_chain": false }"#).in_best_chain);
+
+        let proof = round_trip::<MerkleProof>(
+            r#"{
+                "block_height": 800000,
+                "merkle": ["4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050"],
+                "pos": 1
+            }"#,
+        );
+        assert_eq!(proof.merkle.len(), 1);
+    }
+
+    #[test]
+    fn stats_round_trip() {
+        let stats = round_trip::<AddressStats>(
+            r#"{
+                "address": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                "chain_stats": {
+                    "funded_txo_count": 2,
+                    "funded_txo_sum": 150000,
+                    "spent_txo_count": 1,
+                    "spent_txo_sum": 100000,
+                    "tx_count": 3
+                },
+                "mempool_stats": {
+                    "funded_txo_count": 0,
+                    "funded_txo_sum": 0,
+                    "spent_txo_count": 0,
+                    "spent_txo_sum": 0,
+                    "tx_count": 0
+                }
+            }"#,
+        );
+        assert_eq!(stats.chain_stats.tx_count, 3);
+        assert_eq!(stats.scripthash, None);
+        assert_eq!(stats.tx_count().unwrap(), 3);
+        assert!(stats.has_history());
+
+        let mut overflowing = stats;
+        overflowing.mempool_stats.tx_count = u64::MAX;
+        assert!(matches!(
+            overflowing.tx_count(),
+            Err(Error::InvalidServerData)
+        ));
+        assert!(overflowing.has_history());
+    }
+
+    #[test]
+    fn balances() {
+        let stats =
+            |funded: u64, spent: u64, mempool_funded: u64, mempool_spent: u64| AddressStats {
+                address: None,
+                scripthash: None,
+                chain_stats: AddressTxsSummary {
+                    funded_txo_sum: funded,
+                    spent_txo_sum: spent,
+                    ..AddressTxsSummary::default()
+                },
+                mempool_stats: AddressTxsSummary {
+                    funded_txo_sum: mempool_funded,
+                    spent_txo_sum: mempool_spent,
+                    ..AddressTxsSummary::default()
+                },
+            };
+        assert_eq!(
+            stats(150_000, 100_000, 20_000, 0).balance().unwrap(),
+            70_000
+        );
+        assert_eq!(stats(150_000, 100_000, 0, 50_000).balance().unwrap(), 0);
+        assert_eq!(
+            stats(150_000, 100_000, 0, 50_000).confirmed_balance(),
+            50_000
+        );
+        assert!(matches!(
+            stats(u64::MAX, 0, 1, 0).balance(),
+            Err(Error::InvalidServerData)
+        ));
+        assert!(matches!(
+            stats(0, u64::MAX, 0, 1).balance(),
+            Err(Error::InvalidServerData)
+        ));
+
+        let all = [stats(10, 0, 0, 0), stats(0, 0, 5, 0)];
+        assert_eq!(total_balance(&all).unwrap(), 15);
+        assert_eq!(total_balance(&[]).unwrap(), 0);
+        let overflowing = [stats(u64::MAX, 0, 0, 0), stats(1, 0, 0, 0)];
+        assert!(matches!(
+            total_balance(&overflowing),
+            Err(Error::InvalidServerData)
+        ));
+    }
+
+    #[test]
+    fn verified_balances() {
+        let stats = |funded: u64, mempool_spent: u64| AddressStats {
+            address: None,
+            scripthash: None,
+            chain_stats: AddressTxsSummary {
+                funded_txo_sum: funded,
+                ..AddressTxsSummary::default()
+            },
+            mempool_stats: AddressTxsSummary {
+                spent_txo_sum: mempool_spent,
+                ..AddressTxsSummary::default()
+            },
+        };
+        let utxo = |value: u64| Utxo {
+            txid: Txid::from_byte_array([1; 32]),
+            vout: 0,
+            value,
+            status: TxStatus {
+                confirmed: false,
+                block_height: None,
+                block_hash: None,
+                block_time: None,
+            },
+            confirmations: None,
+        };
+        let tip = BlockHash::from_byte_array([2; 32]);
+
+        let balance = VerifiedBalance::check(&stats(30, 10), &[utxo(5), utxo(15)], tip).unwrap();
+        assert_eq!(
+            balance,
+            VerifiedBalance {
+                balance: 20,
+                verified: true,
+                discrepancy: None,
+                tip,
+            }
+        );
+        let balance = VerifiedBalance::check(&stats(30, 0), &[utxo(20)], tip).unwrap();
+        assert!(!balance.verified);
+        assert_eq!(balance.discrepancy, Some(-10));
+        let balance = VerifiedBalance::check(&stats(0, 0), &[utxo(u64::MAX)], tip).unwrap();
+        assert_eq!(balance.discrepancy, Some(i64::MAX));
+
+        assert!(matches!(
+            VerifiedBalance::check(&stats(10, 0), &[utxo(u64::MAX), utxo(1)], tip),
+            Err(Error::InvalidServerData)
+        ));
+    }
+
+    #[test]
+    fn conf_targets() {
+        assert_eq!(ConfTarget::try_from(1u8), Ok(ConfTarget::MIN));
+        assert_eq!(ConfTarget::try_from(1008usize), Ok(ConfTarget::MAX));
+        assert_eq!(ConfTarget::try_from(6i32).map(ConfTarget::blocks), Ok(6));
+        assert_eq!(
+            ConfTarget::try_from(0u32),
+            Err(ConfTargetError::OutOfRange(0))
+        );
+        assert_eq!(
+            ConfTarget::try_from(1009u16),
+            Err(ConfTargetError::OutOfRange(1009))
+        );
+        assert_eq!(
+            ConfTarget::try_from(-1i32),
+            Err(ConfTargetError::OutOfRange(-1))
+        );
+        assert_eq!(
+            ConfTarget::try_from(u64::MAX),
+            Err(ConfTargetError::OutOfRange(u64::MAX as i128))
+        );
+        assert_eq!(ConfTarget::new(0), Err(ConfTargetError::OutOfRange(0)));
+
+        assert_eq!("144".parse::<ConfTarget>().map(u16::from), Ok(144));
+        assert_eq!(
+            "-3".parse::<ConfTarget>(),
+            Err(ConfTargetError::OutOfRange(-3))
+        );
+        assert_eq!(
+            "70000".parse::<ConfTarget>(),
+            Err(ConfTargetError::OutOfRange(70000))
+        );
+        assert!(matches!(
+            "six".parse::<ConfTarget>(),
+            Err(ConfTargetError::NotANumber(_))
+        ));
+    }
+
+    #[test]
+    fn fee_rates() {
+        let estimates = [("1", 20.0), ("6", 10.0), ("144", 2.0)]
+            .iter()
+            .map(|(target, rate)| (target.to_string(), *rate))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            crate::convert_fee_rate(6u16, estimates.clone()).unwrap(),
+            10.0
+        );
+        assert_eq!(
+            crate::convert_fee_rate(100u32, estimates.clone()).unwrap(),
+            10.0
+        );
+        assert!(matches!(
+            crate::convert_fee_rate(0usize, estimates.clone()),
+            Err(crate::Error::InvalidInput {
+                field: "target",
+                ..
+            })
+        ));
+        assert!(crate::convert_fee_rate(5000u64, estimates).is_err());
+    }
+
+    /// Transaction of a history, with an id made of `id` bytes, confirmed at `height` if any.
+    fn history_tx(id: u8, height: Option<u32>) -> Tx {
+        let mut tx = serde_json::from_str::<Tx>(TX_JSON).unwrap();
+        tx.txid = Txid::from_byte_array([id; 32]);
+        tx.status = TxStatus {
+            confirmed: height.is_some(),
+            block_hash: None,
+            block_height: height,
+            block_time: None,
+        };
+        tx
+    }
+
+    fn ids(txs: &[Tx]) -> Vec<u8> {
+        txs.iter().map(|tx| tx.txid.to_byte_array()[0]).collect()
+    }
+
+    fn histories() -> impl Strategy<Value = Vec<Tx>> {
+        prop::collection::vec((0u8..12, prop::option::of(1u32..6)), 0..24).prop_map(|txs| {
+            txs.into_iter()
+                .map(|(id, height)| history_tx(id, height))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn coinbases() {
+        let tx = serde_json::from_str::<Tx>(TX_JSON).unwrap();
+        assert!(!tx.is_coinbase());
+
+        let mut flagged = tx.clone();
+        flagged.vin[0].is_coinbase = true;
+        assert!(flagged.is_coinbase());
+
+        // older backends don't flag the input, which spends the null outpoint
+        let mut unflagged = tx.clone();
+        unflagged.vin[0].txid = Txid::from_byte_array([0; 32]);
+        unflagged.vin[0].vout = u32::MAX;
+        unflagged.vin[0].prevout = None;
+        assert!(unflagged.is_coinbase());
+
+        let mut two_inputs = flagged;
+        two_inputs.vin.push(tx.vin[0].clone());
+        assert!(!two_inputs.is_coinbase());
+
+        let mut no_input = tx;
+        no_input.vin.clear();
+        assert!(!no_input.is_coinbase());
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    #[test]
+    fn fee_estimate_shapes() {
+        let targets = parse_fee_estimates(br#"{"1": 20.5, "6": 5.0, "144": 1.0}"#).unwrap();
+        assert_eq!(targets.len(), 3);
+        assert_eq!(targets["1"], 20.5);
+        assert_eq!(targets["144"], 1.0);
+        assert!(parse_fee_estimates(b"{}").unwrap().is_empty());
+
+        let tiers = parse_fee_estimates(
+            br#"{"fastestFee": 30, "halfHourFee": 20, "hourFee": 10, "economyFee": 5,
+                "minimumFee": 1}"#,
+        )
+        .unwrap();
+        let mut tiers = tiers.into_iter().collect::<Vec<_>>();
+        tiers.sort_by_key(|(target, _)| target.parse::<u32>().unwrap());
+        assert_eq!(
+            tiers,
+            [
+                ("1".to_owned(), 30.0),
+                ("3".to_owned(), 20.0),
+                ("6".to_owned(), 10.0),
+                ("144".to_owned(), 5.0),
+                ("1008".to_owned(), 1.0)
+            ]
+        );
+
+        assert!(parse_fee_estimates(br#"{"1": "fast"}"#).is_err());
+        assert!(parse_fee_estimates(b"[]").is_err());
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    #[test]
+    fn fee_stats() {
+        let tx = serde_json::from_str::<Tx>(TX_JSON).unwrap();
+        let with_fee = |fee: Option<u64>, weight: u32| {
+            let mut tx = tx.clone();
+            tx.fee = fee;
+            tx.weight = weight;
+            tx
+        };
+        let mut coinbase = with_fee(Some(1_000_000), 400);
+        coinbase.vin[0].is_coinbase = true;
+
+        let stats = BlockFeeStats::from_txs(&[coinbase.clone()]).unwrap();
+        assert_eq!(stats.total_fees, 0);
+        assert_eq!(stats.median_feerate, 0.0);
+
+        let txs = [
+            coinbase,
+            with_fee(Some(1_000), 400),
+            with_fee(Some(3_000), 400),
+            with_fee(None, 400),
+            with_fee(Some(2_000), 400),
+        ];
+        let stats = BlockFeeStats::from_txs(&txs).unwrap();
+        assert_eq!(stats.total_fees, 6_000);
+        assert_eq!(
+            (stats.min_feerate, stats.median_feerate, stats.max_feerate),
+            (10.0, 20.0, 30.0)
+        );
+        let stats = BlockFeeStats::from_txs(&txs[..3]).unwrap();
+        assert_eq!(stats.median_feerate, 20.0);
+
+        let overflowing = [with_fee(Some(u64::MAX), 400), with_fee(Some(1), 400)];
+        assert!(matches!(
+            BlockFeeStats::from_txs(&overflowing),
+            Err(Error::InvalidServerData)
+        ));
+    }
+
+    #[test]
+    fn summaries() {
+        let tx = serde_json::from_str::<Tx>(TX_JSON).unwrap();
+        let mine = tx.vout[0].scriptpubkey.clone();
+        let other = tx.vout[1].scriptpubkey.clone();
+        let scripts =
+            |script: &ScriptPubkey| std::iter::once(script.clone()).collect::<HashSet<_>>();
+        let net_value =
+            |tx: &Tx, script: &ScriptPubkey| tx.summarize(&scripts(script)).unwrap().net_value;
+
+        // the input and the first output are both ours, the difference being the fee
+        let summary = tx.summarize(&scripts(&mine)).unwrap();
+        assert_eq!(summary.net_value, NetValue::Complete(-10_000));
+        assert_eq!(summary.txid, tx.txid);
+        assert_eq!(summary.fee, Some(10_000));
+
+        let mut send = tx.clone();
+        send.vout[0].scriptpubkey = other.clone();
+        assert_eq!(net_value(&send, &mine), NetValue::Complete(-100_000));
+
+        let mut receive = tx.clone();
+        receive.vout[1].value = 5_000;
+        assert_eq!(net_value(&receive, &other), NetValue::Complete(5_000));
+        assert_eq!(
+            net_value(&receive, &ScriptPubkey::new()),
+            NetValue::Complete(0)
+        );
+
+        let mut incomplete = tx.clone();
+        incomplete.vin[0].prevout = None;
+        assert_eq!(net_value(&incomplete, &mine), NetValue::Incomplete(90_000));
+
+        let mut coinbase = incomplete;
+        coinbase.vin[0].is_coinbase = true;
+        assert_eq!(net_value(&coinbase, &mine), NetValue::Complete(90_000));
+
+        let mut overflowing = tx.clone();
+        overflowing.vout[0].value = u64::MAX;
+        overflowing.vout[1].value = 1;
+        overflowing.vout[1].scriptpubkey = mine.clone();
+        assert!(matches!(
+            overflowing.summarize(&scripts(&mine)),
+            Err(Error::InvalidServerData)
+        ));
+        let mut huge = tx;
+        huge.vout[0].value = u64::MAX;
+        assert!(matches!(
+            huge.summarize(&scripts(&mine)),
+            Err(Error::InvalidServerData)
+        ));
+    }
+
+    #[test]
+    fn history_dedup() {
+        let server = vec![
+            history_tx(5, None),
+            history_tx(4, None),
+            history_tx(6, Some(9)),
+            history_tx(2, Some(9)),
+            history_tx(4, Some(8)),
+        ];
+        let deduped = dedup_history(server);
+        assert_eq!(ids(&deduped), [5, 4, 6, 2]);
+        assert_eq!(deduped[1].status.block_height, Some(8));
+
+        assert_eq!(ids(&merge_histories(deduped, vec![])), [5, 2, 6, 4]);
+    }
+
+    proptest! {
+        #[test]
+        fn merge_is_idempotent(a in histories(), b in histories()) {
+            let merged = merge_histories(a, b);
+            prop_assert_eq!(&merge_histories(merged.clone(), merged.clone()), &merged);
+            prop_assert_eq!(&merge_histories(merged.clone(), vec![]), &merged);
+        }
+
+        #[test]
+        fn merge_is_commutative(a in histories(), b in histories()) {
+            // transactions with the same txid and status are the same here, so the precedence
+            // between statuses decides of all the conflicts
+            prop_assert_eq!(merge_histories(a.clone(), b.clone()), merge_histories(b, a));
+        }
+
+        #[test]
+        fn merge_is_ordered(a in histories(), b in histories()) {
+            let merged = merge_histories(a, b);
+            for pair in merged.windows(2) {
+                prop_assert_eq!(cmp_tx_for_display(&pair[0], &pair[1]), Ordering::Less);
+            }
+        }
+
+        #[test]
+        fn dedup_keeps_the_order(txs in histories()) {
+            let mut first_seen = vec![];
+            for tx in &txs {
+                if !first_seen.contains(&tx.txid) {
+                    first_seen.push(tx.txid);
+                }
+            }
+            let mut deduped = dedup_history(txs.clone());
+            prop_assert_eq!(deduped.iter().map(|tx| tx.txid).collect::<Vec<_>>(), first_seen);
+
+            deduped.sort_by(cmp_tx_for_display);
+            prop_assert_eq!(deduped, merge_histories(txs, vec![]));
+        }
+    }
+}
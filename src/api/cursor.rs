@@ -0,0 +1,237 @@
+//! Opaque positions in paginated transaction histories, which services built on top of the
+//! client can hand out to their own clients.
+
+use std::borrow::Borrow;
+use std::fmt::{self, Debug, Formatter};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bpstd::{BlockHash, Txid};
+
+use crate::hmac::hmac_sha256;
+use crate::Error;
+
+/// Version of the encoding of cursors.
+const CURSOR_VERSION: u8 = 1;
+
+/// Length of the truncated HMAC-SHA256 tag ending an encoded cursor.
+const TAG_LEN: usize = 16;
+
+/// Length of an encoded cursor: version, family, last seen txid, tip hash and tag.
+const CURSOR_LEN: usize = 2 + 32 + 32 + TAG_LEN;
+
+/// Paginated histories a [`Cursor`] can continue.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum CursorFamily {
+    /// transaction history of a script
+    ScriptTxs,
+
+    /// transaction history of an address
+    AddressTxs,
+}
+
+impl CursorFamily {
+    fn to_byte(self) -> u8 {
+        match self {
+            CursorFamily::ScriptTxs => 1,
+            CursorFamily::AddressTxs => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(CursorFamily::ScriptTxs),
+            2 => Some(CursorFamily::AddressTxs),
+            _ => None,
+        }
+    }
+}
+
+/// Secret key authenticating the [`Cursor`]s handed out by a service, so that its clients
+/// can't forge or alter them.
+#[derive(Clone, Eq, PartialEq)]
+pub struct CursorKey([u8; 32]);
+
+impl CursorKey {
+    /// Use `secret`, which should be drawn at random and kept by the service, as the key.
+    pub fn new(secret: [u8; 32]) -> Self {
+        CursorKey(secret)
+    }
+
+    fn tag(&self, payload: &[u8]) -> [u8; TAG_LEN] {
+        let mut tag = [0u8; TAG_LEN];
+        tag.copy_from_slice(&hmac_sha256(&self.0, payload)[..TAG_LEN]);
+        tag
+    }
+}
+
+impl From<[u8; 32]> for CursorKey {
+    fn from(secret: [u8; 32]) -> Self {
+        CursorKey::new(secret)
+    }
+}
+
+impl Debug for CursorKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("CursorKey(..)")
+    }
+}
+
+/// Position in a paginated transaction history, encoded as a compact URL-safe string.
+///
+/// The string carries a version and an HMAC-SHA256 tag made with a [`CursorKey`], so that
+/// corrupted cursors, and cursors not encoded with the same key, fail to decode with
+/// [`Error::InvalidCursor`].
+///
+/// Both directions need the key, so the type deliberately implements neither `Display` nor
+/// `FromStr`: [`Cursor::encode`] is the way to format a cursor and [`Cursor::decode`] the way
+/// to parse one.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Cursor {
+    /// History the cursor continues.
+    pub family: CursorFamily,
+    /// Last confirmed transaction of the previous page.
+    pub last_seen: Txid,
+    /// Chain tip when the first page was requested, letting callers notice a reorg since.
+    pub tip: BlockHash,
+}
+
+impl Cursor {
+    /// Check that the cursor continues a history of `family`.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn check_family(&self, family: CursorFamily) -> Result<(), Error> {
+        if self.family != family {
+            return Err(Error::InvalidCursor("cursor of another history"));
+        }
+        Ok(())
+    }
+
+    /// Encode the cursor, authenticated with `key`.
+    pub fn encode(&self, key: &CursorKey) -> String {
+        let last_seen: &[u8] = self.last_seen.borrow();
+        let tip: &[u8] = self.tip.borrow();
+        let mut data = Vec::with_capacity(CURSOR_LEN);
+        data.push(CURSOR_VERSION);
+        data.push(self.family.to_byte());
+        data.extend_from_slice(last_seen);
+        data.extend_from_slice(tip);
+        let tag = key.tag(&data);
+        data.extend_from_slice(&tag);
+        URL_SAFE_NO_PAD.encode(data)
+    }
+
+    /// Decode a cursor encoded with [`Cursor::encode`] and the same `key`.
+    pub fn decode(s: &str, key: &CursorKey) -> Result<Self, Error> {
+        let data = URL_SAFE_NO_PAD
+            .decode(s)
+            .map_err(|_| Error::InvalidCursor("malformed encoding"))?;
+        if data.len() != CURSOR_LEN {
+            return Err(Error::InvalidCursor("malformed encoding"));
+        }
+        let (payload, tag) = data.split_at(CURSOR_LEN - TAG_LEN);
+        if !constant_time_eq(&key.tag(payload), tag) {
+            return Err(Error::InvalidCursor("authentication failed"));
+        }
+        if payload[0] != CURSOR_VERSION {
+            return Err(Error::InvalidCursor("unsupported version"));
+        }
+        let family =
+            CursorFamily::from_byte(payload[1]).ok_or(Error::InvalidCursor("unknown history"))?;
+        let mut last_seen = [0u8; 32];
+        last_seen.copy_from_slice(&payload[2..34]);
+        let mut tip = [0u8; 32];
+        tip.copy_from_slice(&payload[34..66]);
+        Ok(Cursor {
+            family,
+            last_seen: Txid::from(last_seen),
+            tip: BlockHash::from(tip),
+        })
+    }
+}
+
+/// Compares `a` and `b` in a time independent of the position of their first difference.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use amplify::ByteArray;
+
+    use super::*;
+
+    fn cursor() -> Cursor {
+        Cursor {
+            family: CursorFamily::AddressTxs,
+            last_seen: Txid::from_byte_array([1u8; 32]),
+            tip: BlockHash::from_byte_array([2u8; 32]),
+        }
+    }
+
+    fn forged(key: &CursorKey, version: u8, family: u8) -> String {
+        let mut data = vec![version, family];
+        data.extend_from_slice(&[0u8; 64]);
+        let tag = key.tag(&data);
+        data.extend_from_slice(&tag);
+        URL_SAFE_NO_PAD.encode(data)
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = CursorKey::new([7u8; 32]);
+        for family in [CursorFamily::ScriptTxs, CursorFamily::AddressTxs] {
+            let cursor = Cursor { family, ..cursor() };
+            let encoded = cursor.encode(&key);
+            assert!(encoded
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+            assert_eq!(Cursor::decode(&encoded, &key).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn tampered_cursors() {
+        let key = CursorKey::new([7u8; 32]);
+        let data = URL_SAFE_NO_PAD.decode(cursor().encode(&key)).unwrap();
+        for bit in 0..data.len() * 8 {
+            let mut tampered = data.clone();
+            tampered[bit / 8] ^= 1 << (bit % 8);
+            let err = Cursor::decode(&URL_SAFE_NO_PAD.encode(tampered), &key).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidCursor("authentication failed")),
+                "{:?}",
+                err
+            );
+        }
+
+        let other = CursorKey::new([8u8; 32]);
+        let err = Cursor::decode(&cursor().encode(&other), &key).unwrap_err();
+        assert!(matches!(err, Error::InvalidCursor("authentication failed")));
+    }
+
+    #[test]
+    fn malformed_cursors() {
+        let key = CursorKey::new([7u8; 32]);
+        let encoded = cursor().encode(&key);
+        for malformed in ["", "not base64!", &encoded[..encoded.len() - 2]] {
+            let err = Cursor::decode(malformed, &key).unwrap_err();
+            assert!(matches!(err, Error::InvalidCursor("malformed encoding")));
+        }
+        let err = Cursor::decode(&forged(&key, 2, 1), &key).unwrap_err();
+        assert!(matches!(err, Error::InvalidCursor("unsupported version")));
+        let err = Cursor::decode(&forged(&key, CURSOR_VERSION, 3), &key).unwrap_err();
+        assert!(matches!(err, Error::InvalidCursor("unknown history")));
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    #[test]
+    fn families() {
+        cursor().check_family(CursorFamily::AddressTxs).unwrap();
+        let err = cursor().check_family(CursorFamily::ScriptTxs).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidCursor("cursor of another history")
+        ));
+    }
+}
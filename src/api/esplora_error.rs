@@ -0,0 +1,205 @@
+//! Classification of the error messages Esplora servers put in the body of error responses.
+
+use serde::Deserialize;
+
+/// Well-known errors reported by Esplora servers.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum EsploraErrorKind {
+    /// transaction not found
+    TxNotFound,
+
+    /// block not found
+    BlockNotFound,
+
+    /// resource not found
+    NotFound,
+
+    /// invalid hex string
+    InvalidHex,
+
+    /// invalid address
+    InvalidAddress,
+
+    /// too many unconfirmed ancestors or descendants in the mempool
+    MempoolChainLimit,
+
+    /// transaction conflicts with a mempool transaction
+    MempoolConflict,
+
+    /// transaction inputs are missing or already spent
+    MissingInputs,
+
+    /// transaction fee is too low
+    InsufficientFee,
+
+    /// transaction is already known
+    AlreadyKnown,
+
+    /// too many requests
+    RateLimited,
+
+    /// unrecognized error
+    Other,
+}
+
+impl Default for EsploraErrorKind {
+    fn default() -> Self {
+        EsploraErrorKind::Other
+    }
+}
+
+impl EsploraErrorKind {
+    /// Whether the error is about a resource the server doesn't know.
+    pub fn is_not_found(self) -> bool {
+        matches!(
+            self,
+            EsploraErrorKind::TxNotFound
+                | EsploraErrorKind::BlockNotFound
+                | EsploraErrorKind::NotFound
+        )
+    }
+
+    /// Whether the server rejected a malformed request parameter.
+    pub fn is_invalid_input(self) -> bool {
+        matches!(
+            self,
+            EsploraErrorKind::InvalidHex | EsploraErrorKind::InvalidAddress
+        )
+    }
+}
+
+/// Fragments of the error messages of electrs and of the bitcoind errors it relays on
+/// broadcast, lowercased, with the kind they identify.
+const PATTERNS: &[(&str, EsploraErrorKind)] = &[
+    ("transaction not found", EsploraErrorKind::TxNotFound),
+    ("block not found", EsploraErrorKind::BlockNotFound),
+    ("invalid hex string", EsploraErrorKind::InvalidHex),
+    ("invalid bitcoin address", EsploraErrorKind::InvalidAddress),
+    (
+        "address on invalid network",
+        EsploraErrorKind::InvalidAddress,
+    ),
+    (
+        "too-long-mempool-chain",
+        EsploraErrorKind::MempoolChainLimit,
+    ),
+    ("txn-mempool-conflict", EsploraErrorKind::MempoolConflict),
+    ("missingorspent", EsploraErrorKind::MissingInputs),
+    ("missing-inputs", EsploraErrorKind::MissingInputs),
+    ("missing inputs", EsploraErrorKind::MissingInputs),
+    ("min relay fee not met", EsploraErrorKind::InsufficientFee),
+    ("mempool min fee not met", EsploraErrorKind::InsufficientFee),
+    ("insufficient fee", EsploraErrorKind::InsufficientFee),
+    ("txn-already-known", EsploraErrorKind::AlreadyKnown),
+    ("txn-already-in-mempool", EsploraErrorKind::AlreadyKnown),
+    ("already in block chain", EsploraErrorKind::AlreadyKnown),
+];
+
+/// Structured error body `{ "error": "...", "code": N, "retry_after": S }` returned by some
+/// Esplora deployments and gateways instead of a plain-text message.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ErrorDetails {
+    /// Error message.
+    pub error: String,
+    /// Server-specific error code, if any.
+    #[serde(default)]
+    pub code: Option<i32>,
+    /// Number of seconds the server asks to wait before retrying, if any.
+    #[serde(default)]
+    pub retry_after: Option<u64>,
+}
+
+/// Former name of [`ErrorDetails`].
+#[deprecated(since = "0.11.0", note = "renamed to `ErrorDetails`")]
+pub type ErrorBody = ErrorDetails;
+
+impl ErrorDetails {
+    /// Parse a structured error body, returning `None` for plain-text bodies.
+    pub fn parse(body: &str) -> Option<Self> {
+        serde_json::from_str(body.trim()).ok()
+    }
+}
+
+/// Classify an error response from its status and body.
+///
+/// Known messages take precedence over the status, which is only used when the body isn't
+/// recognized.
+pub fn classify_error(status: u16, body: &str) -> EsploraErrorKind {
+    let body = body.to_lowercase();
+    if let Some((_, kind)) = PATTERNS.iter().find(|(pattern, _)| body.contains(pattern)) {
+        return *kind;
+    }
+    match status {
+        404 => EsploraErrorKind::NotFound,
+        429 => EsploraErrorKind::RateLimited,
+        _ => EsploraErrorKind::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    use serde::Deserialize;
+
+    use super::*;
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    use crate::Error;
+
+    /// Error response of a real backend, with the kind it must be classified as.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    #[derive(Deserialize)]
+    struct Fixture {
+        backend: String,
+        status: u16,
+        kind: String,
+        body: String,
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    #[test]
+    fn body_corpus() {
+        let fixtures: Vec<Fixture> =
+            serde_json::from_str(include_str!("../../tests/fixtures/error_bodies.json")).unwrap();
+        for fixture in fixtures {
+            let kind = match Error::http_response(fixture.status, &fixture.body) {
+                Error::HttpResponse { kind, .. } => kind,
+                err => panic!("{:?}", err),
+            };
+            assert_eq!(
+                format!("{:?}", kind),
+                fixture.kind,
+                "{} {}: {}",
+                fixture.backend,
+                fixture.status,
+                fixture.body
+            );
+        }
+    }
+
+    #[test]
+    fn messages_take_precedence_over_status() {
+        assert_eq!(
+            classify_error(404, "Block not found"),
+            EsploraErrorKind::BlockNotFound
+        );
+        assert_eq!(classify_error(404, "whatever"), EsploraErrorKind::NotFound);
+        assert_eq!(
+            classify_error(500, "TXN-MEMPOOL-CONFLICT"),
+            EsploraErrorKind::MempoolConflict
+        );
+        assert!(EsploraErrorKind::TxNotFound.is_not_found());
+        assert!(EsploraErrorKind::InvalidHex.is_invalid_input());
+        assert!(!EsploraErrorKind::RateLimited.is_not_found());
+    }
+
+    #[test]
+    fn error_details() {
+        let details =
+            ErrorDetails::parse(" {\"error\":\"slow down\",\"retry_after\":5}\n").unwrap();
+        assert_eq!(details.error, "slow down");
+        assert_eq!(details.code, None);
+        assert_eq!(details.retry_after, Some(5));
+        assert_eq!(ErrorDetails::parse("Transaction not found"), None);
+    }
+}
@@ -0,0 +1,179 @@
+//! Incremental parsing of JSON arrays of transactions, holding a single transaction in memory
+//! at a time.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+use super::Tx;
+use crate::Error;
+
+/// Size of the chunks read from the underlying reader.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    /// Before the opening bracket.
+    Start,
+    /// After the opening bracket, where the array may end right away.
+    First,
+    /// After a separator, where an element must follow.
+    Element,
+    /// Inside an element.
+    Inside,
+    /// After an element, where a separator or the closing bracket must follow.
+    Separator,
+    /// After the closing bracket.
+    Done,
+    /// After an error.
+    Failed,
+}
+
+/// Push parser splitting a JSON array of transaction objects into its elements, which are
+/// deserialized one by one as soon as they are complete.
+#[derive(Debug)]
+pub(crate) struct TxArrayParser {
+    state: State,
+    element: Vec<u8>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Default for TxArrayParser {
+    fn default() -> Self {
+        TxArrayParser {
+            state: State::Start,
+            element: vec![],
+            depth: 0,
+            in_string: false,
+            escaped: false,
+        }
+    }
+}
+
+impl TxArrayParser {
+    /// Parses the next chunk of the array, adding the completed transactions to `parsed`.
+    ///
+    /// Returns `false` once an error was added, after which the parser ignores further input.
+    pub fn feed(&mut self, chunk: &[u8], parsed: &mut VecDeque<Result<Tx, Error>>) -> bool {
+        for byte in chunk {
+            match self.push(*byte) {
+                Ok(Some(tx)) => parsed.push_back(Ok(tx)),
+                Ok(None) => {}
+                Err(err) => {
+                    self.state = State::Failed;
+                    parsed.push_back(Err(err));
+                    return false;
+                }
+            }
+        }
+        self.state != State::Failed
+    }
+
+    /// Ends the input, adding an error to `parsed` if the array is incomplete.
+    pub fn finish(&mut self, parsed: &mut VecDeque<Result<Tx, Error>>) {
+        match self.state {
+            State::Done | State::Failed => {}
+            _ => {
+                self.state = State::Failed;
+                parsed.push_back(Err(Error::InvalidServerData));
+            }
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> Result<Option<Tx>, Error> {
+        match (self.state, byte) {
+            (State::Inside, _) => return self.push_inside(byte),
+            (State::Failed, _) => {}
+            (_, byte) if byte.is_ascii_whitespace() => {}
+            (State::Start, b'[') => self.state = State::First,
+            (State::First, b']') | (State::Separator, b']') => self.state = State::Done,
+            (State::First, b'{') | (State::Element, b'{') => {
+                self.element.clear();
+                self.element.push(byte);
+                self.depth = 1;
+                self.state = State::Inside;
+            }
+            (State::Separator, b',') => self.state = State::Element,
+            _ => return Err(Error::InvalidServerData),
+        }
+        Ok(None)
+    }
+
+    fn push_inside(&mut self, byte: u8) -> Result<Option<Tx>, Error> {
+        self.element.push(byte);
+        if self.in_string {
+            match byte {
+                _ if self.escaped => self.escaped = false,
+                b'\\' => self.escaped = true,
+                b'"' => self.in_string = false,
+                _ => {}
+            }
+            return Ok(None);
+        }
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' => self.depth -= 1,
+            _ => {}
+        }
+        if self.depth > 0 {
+            return Ok(None);
+        }
+        self.state = State::Separator;
+        let tx = serde_json::from_slice(&self.element)?;
+        self.element.clear();
+        Ok(Some(tx))
+    }
+}
+
+/// Iterator over the transactions of a JSON array read from `R`.
+#[derive(Debug)]
+pub struct TxArrayReader<R> {
+    reader: Option<R>,
+    parser: TxArrayParser,
+    parsed: VecDeque<Result<Tx, Error>>,
+}
+
+impl<R: Read> Iterator for TxArrayReader<R> {
+    type Item = Result<Tx, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.parsed.pop_front() {
+                return Some(item);
+            }
+            let reader = self.reader.as_mut()?;
+            let mut chunk = [0u8; CHUNK_SIZE];
+            match reader.read(&mut chunk) {
+                Ok(0) => {
+                    self.reader = None;
+                    self.parser.finish(&mut self.parsed);
+                }
+                Ok(len) => {
+                    if !self.parser.feed(&chunk[..len], &mut self.parsed) {
+                        self.reader = None;
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.reader = None;
+                    self.parsed.push_back(Err(err.into()));
+                }
+            }
+        }
+    }
+}
+
+/// Parse a JSON array of transactions read from `reader`, yielding them one at a time.
+///
+/// Unlike deserializing the whole array, memory usage is bounded by the size of the largest
+/// transaction, so that huge unpaginated histories can be processed. Iteration ends after the
+/// first error.
+pub fn stream_parse_txs<R: Read>(reader: R) -> TxArrayReader<R> {
+    TxArrayReader {
+        reader: Some(reader),
+        parser: TxArrayParser::default(),
+        parsed: VecDeque::new(),
+    }
+}
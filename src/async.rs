@@ -10,30 +10,147 @@
 // licenses.
 
 //! Esplora by way of `reqwest` HTTP client.
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
 use std::io;
 use std::io::Cursor;
+use std::mem;
 use std::str::FromStr;
-use std::{collections::HashMap, io::Read};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+#[cfg(feature = "aws-sigv4")]
+use std::time::SystemTime;
 
-use bpstd::{BlockHash, ConsensusDecode, ScriptPubkey, Tx, Txid};
+#[cfg(feature = "addresses")]
+use bpstd::{Address, AddressNetwork};
+use bpstd::{BlockHash, BlockHeader, ConsensusDecode, ScriptPubkey, Tx, Txid};
 
+use amplify::hex::{FromHex, ToHex};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace};
 
-use reqwest::{Client, Response, StatusCode};
+use futures::future::{self, BoxFuture, FutureExt, Shared};
+use futures::stream::{self, BoxStream, FuturesUnordered, Stream, StreamExt};
+use reqwest::{header, Client, RequestBuilder, Response, StatusCode, Url};
 use sha2::{Digest, Sha256};
 
-use crate::{BlockStatus, BlockSummary, Builder, Config, Error, OutputStatus, TxStatus};
+use crate::api::{parse_fee_estimates, TxArrayParser};
+use crate::bandwidth::BandwidthMeter;
+use crate::cache::{is_cacheable, Cache, HttpCache, MemoryCache};
+use crate::headers::{check_header_chain, cumulative_work, Work};
+use crate::lifecycle::{Lifecycle, SHUTDOWN_POLL_INTERVAL};
+use crate::pagination::{next_page_cursor, PageGuard, BLOCK_TXS_PAGE_SIZE};
+#[cfg(feature = "addresses")]
+use crate::parse_address;
+#[cfg(feature = "addresses")]
+use crate::query::QueryParams;
+use crate::request_id::RequestIds;
+use crate::rng::SplitMix64;
+use crate::sleeper::with_timeout;
+#[cfg(feature = "async-std-sleeper")]
+pub use crate::sleeper::AsyncStdSleeper;
+#[cfg(feature = "smol-sleeper")]
+pub use crate::sleeper::SmolSleeper;
+pub use crate::sleeper::{Sleeper, TokioSleeper};
+use crate::validation;
+use crate::wallet_history::HistoryMerge;
+use crate::warm_up::WarmCache;
+use crate::watch::{ChainWatch, WatchStep};
+#[cfg(feature = "aws-sigv4")]
+use crate::SigV4;
+use crate::{
+    dedup_history, diff_utxos, extract_witness_commitment, has_all_fee_targets, normalize_base_url,
+    output_index, parse_input, BackendInfo, BandwidthUsage, BlockCommitments, BlockFeeStats,
+    BlockHashCache, BlockStatus, BlockSummary, BlockedRedirect, Builder, CheckpointUpdate,
+    Checkpoints, ClientConfig, Config, ConfirmationDetails, CursorFamily, DryRunClient,
+    EndpointCategory, Error, ErrorKind, FeeEstimates, FeeFallback, MempoolRecent, MempoolStats,
+    MerkleProof, OutputStatus, PaymentInfo, PrefetchItem, PrefetchSet, PrefetchedBlock,
+    RedirectPolicy, RequestIdPolicy, RequestMeta, RequestTrace, Retry, RetryContext, ScriptType,
+    SpendableOpts, SpendableSet, SyncCache, TipCache, TxStatus, Utxo, UtxoDiff, UtxoSnapshot,
+    WarmUpReport, WatchFilter, WatchMatch, WatchedBlock, ANCHOR_CACHE_DEPTH,
+    BROADCAST_POLL_INTERVAL, DEFAULT_BLOCK_VSIZE, DEFAULT_MAX_CONCURRENCY, DEFAULT_MAX_PAGES,
+    DEFAULT_SHUTDOWN_GRACE, DEFAULT_WARM_UP_BUDGET, ERROR_BODY_LIMIT, MAX_ANCESTOR_DEPTH,
+    MAX_FEE_STATS_TXS, MAX_PREALLOCATED_BLOCKS, REQUEST_ID_HEADER,
+};
+
+const BYTES_LIMIT: usize = 10 * 1_024 * 1_024;
+
+/// Number of times in a row batch requests back off after being rate limited while down to a
+/// single request at a time, before giving up.
+const MAX_BATCH_BACKOFFS: usize = 6;
+
+/// Stream of blocks returned by [`AsyncClient::block_prefetcher`].
+pub type BlockStream = BoxStream<'static, Result<PrefetchedBlock, Error>>;
+
+type SharedGet = Shared<BoxFuture<'static, Result<Fetched, FetchError>>>;
+
+/// Registry of in-flight GET requests shared between concurrent callers.
+#[derive(Clone, Default)]
+struct Inflight(Arc<Mutex<HashMap<String, SharedGet>>>);
+
+impl Debug for Inflight {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Inflight(..)")
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct AsyncClient {
-    url: String,
+    /// Base URL the client was built with.
+    base_url: String,
+    /// Base URL requests are made to, shared by all the clones.
+    url: Arc<RwLock<String>>,
+    proxy: Option<String>,
+    timeout: Option<u64>,
+    max_pages: usize,
+    max_concurrency: usize,
+    fee_fallback: Option<FeeFallback>,
+    /// Last fee estimates, requested again conditionally.
+    fee_cache: Option<Arc<HttpCache>>,
+    backend: Arc<Mutex<Option<BackendInfo>>>,
+    inflight: Option<Inflight>,
+    block_hashes: Option<BlockHashCache>,
+    require_anchors: bool,
+    anchors: BlockHashCache,
+    http_cache: Option<Arc<HttpCache>>,
+    cache: Option<Arc<dyn Cache>>,
+    sync_cache: Option<Arc<SyncCache>>,
+    retry: Retry,
+    redirect_policy: RedirectPolicy,
+    verify_txid: bool,
+    tip_height: TipCache,
+    prefetch: PrefetchSet,
+    warm_up_budget: Duration,
+    warm: WarmCache,
+    lifecycle: Lifecycle,
+    shutdown_grace: Duration,
+    block_vsize: u64,
+    bandwidth: BandwidthMeter,
+    request_ids: RequestIds,
+    trace_retries: bool,
+    #[cfg(feature = "addresses")]
+    network: Option<AddressNetwork>,
+    #[cfg(feature = "aws-sigv4")]
+    sigv4: Option<SigV4>,
+    sleeper: Arc<dyn Sleeper>,
     client: Client,
 }
 
 impl AsyncClient {
     /// build an async client from a [`Builder`]
     pub fn from_builder(builder: Builder) -> Result<Self, Error> {
+        Self::from_builder_with_sleeper(builder, Arc::new(TokioSleeper))
+    }
+
+    /// build an async client from a [`Builder`], waiting between retries with the timers of
+    /// `sleeper` instead of the Tokio ones
+    pub fn from_builder_with_sleeper(
+        builder: Builder,
+        sleeper: Arc<dyn Sleeper>,
+    ) -> Result<Self, Error> {
         let mut client_builder = Client::builder();
 
         #[cfg(not(target_arch = "wasm32"))]
@@ -46,7 +163,88 @@ impl AsyncClient {
             client_builder = client_builder.timeout(core::time::Duration::from_secs(timeout));
         }
 
-        Ok(Self::from_client(builder.base_url, client_builder.build()?))
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            client_builder = client_builder.redirect(redirect_policy(builder.redirect_policy));
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        for (host, addr) in &builder.resolve {
+            client_builder = client_builder.resolve(host, *addr);
+        }
+
+        if let Some(languages) = builder.accept_language_header()? {
+            let mut headers = header::HeaderMap::new();
+            headers.insert(
+                header::ACCEPT_LANGUAGE,
+                header::HeaderValue::from_str(languages)
+                    .map_err(|_| Error::InvalidHttpHeaderValue(languages.to_owned()))?,
+            );
+            client_builder = client_builder.default_headers(headers);
+        }
+
+        let base_url = normalize_base_url(&builder.base_url)?;
+        Ok(AsyncClient {
+            base_url: base_url.clone(),
+            url: Arc::new(RwLock::new(base_url)),
+            proxy: builder.proxy,
+            timeout: builder.timeout,
+            max_pages: builder.max_pages,
+            max_concurrency: builder.max_concurrency,
+            fee_fallback: builder
+                .fee_fallback
+                .map(FeeFallback::normalized)
+                .transpose()?,
+            fee_cache: if builder.conditional_fee_cache {
+                Some(Arc::new(HttpCache::new(Arc::new(MemoryCache::default()))))
+            } else {
+                None
+            },
+            backend: Arc::new(Mutex::new(None)),
+            inflight: if builder.coalesce_requests {
+                Some(Inflight::default())
+            } else {
+                None
+            },
+            block_hashes: if builder.validate_responses {
+                Some(BlockHashCache::default())
+            } else {
+                None
+            },
+            http_cache: builder
+                .http_cache
+                .map(|cache| Arc::new(HttpCache::new(cache))),
+            cache: builder.cache,
+            sync_cache: builder.sync_cache,
+            retry: Retry::new(
+                builder.max_retries,
+                builder.base_backoff,
+                builder.retry_if,
+                builder.retry_budget,
+                builder.backoff_jitter,
+                builder.jitter_seed,
+            ),
+            redirect_policy: builder.redirect_policy,
+            verify_txid: builder.verify_txid,
+            tip_height: TipCache::new(builder.tip_cache_ttl),
+            prefetch: builder.prefetch,
+            warm_up_budget: builder.warm_up_budget,
+            warm: WarmCache::new(builder.tip_cache_ttl),
+            lifecycle: Lifecycle::default(),
+            shutdown_grace: builder.shutdown_grace,
+            block_vsize: builder.block_vsize,
+            require_anchors: builder.require_anchors,
+            anchors: BlockHashCache::default(),
+            bandwidth: BandwidthMeter::new(builder.bandwidth_budget),
+            request_ids: RequestIds::new(builder.request_id),
+            trace_retries: builder.trace_retries,
+            #[cfg(feature = "addresses")]
+            network: builder.network,
+            #[cfg(feature = "aws-sigv4")]
+            sigv4: builder.sigv4,
+            sleeper,
+            client: client_builder.build()?,
+        })
     }
 
     /// build an async client from a [`Config`]
@@ -56,34 +254,489 @@ impl AsyncClient {
 
     /// build an async client from the base url and [`Client`]
     pub fn from_client(url: String, client: Client) -> Self {
-        AsyncClient { url, client }
+        AsyncClient {
+            base_url: url.clone(),
+            url: Arc::new(RwLock::new(url)),
+            proxy: None,
+            timeout: None,
+            max_pages: DEFAULT_MAX_PAGES,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            fee_fallback: None,
+            fee_cache: None,
+            backend: Arc::new(Mutex::new(None)),
+            inflight: None,
+            block_hashes: None,
+            http_cache: None,
+            cache: None,
+            sync_cache: None,
+            retry: Retry::default(),
+            redirect_policy: RedirectPolicy::default(),
+            verify_txid: true,
+            tip_height: TipCache::default(),
+            prefetch: PrefetchSet::default(),
+            warm_up_budget: DEFAULT_WARM_UP_BUDGET,
+            warm: WarmCache::default(),
+            lifecycle: Lifecycle::default(),
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            block_vsize: DEFAULT_BLOCK_VSIZE,
+            require_anchors: false,
+            anchors: BlockHashCache::default(),
+            bandwidth: BandwidthMeter::default(),
+            request_ids: RequestIds::new(RequestIdPolicy::default()),
+            trace_retries: false,
+            #[cfg(feature = "addresses")]
+            network: None,
+            #[cfg(feature = "aws-sigv4")]
+            sigv4: None,
+            sleeper: Arc::new(TokioSleeper),
+            client,
+        }
+    }
+
+    /// Make a GET request to `url`, returning the response status and body.
+    ///
+    /// Responses of immutable resources found in the read-through cache are returned without
+    /// making any request. When request coalescing is enabled, concurrent calls for the same
+    /// URL share a single in-flight request.
+    async fn get_bytes(&self, url: String) -> Result<Fetched, Error> {
+        let _request = self.lifecycle.enter()?;
+        let path = self.relative_path(&url);
+        let store = self.cache.as_ref().filter(|_| is_cacheable(path));
+        if let Some(body) = store.and_then(|store| store.get(&url)) {
+            return Ok(Fetched {
+                status: StatusCode::OK,
+                body,
+                request_id: None,
+            });
+        }
+        let fetched = self.get_bytes_uncached(path, url.clone()).await?;
+        if let Some(store) = store.filter(|_| fetched.status.is_success()) {
+            store.put(&url, fetched.body.clone());
+        }
+        Ok(fetched)
+    }
+
+    /// Make a GET request to `url`, for the resource at `path`, bypassing the read-through cache.
+    ///
+    /// Retries are sent with the id of the first attempt, so that they can be told apart from
+    /// other requests in the logs of the server.
+    async fn get_bytes_uncached(&self, path: &str, url: String) -> Result<Fetched, Error> {
+        if let Some(cache) = self.http_cache.as_ref().filter(|_| is_cacheable(path)) {
+            return self.get_conditional(cache, &url).await;
+        }
+        self.bandwidth.check()?;
+        let inflight = match &self.inflight {
+            Some(inflight) => inflight,
+            None => {
+                let request_id = self.request_ids.next();
+                let (status, body, trace) = fetch_with_retry(&self.retry, &*self.sleeper, || {
+                    let request =
+                        self.signed(identify(self.client.get(&url), request_id.as_deref()));
+                    fetch_bytes(request, self.redirect_policy, &self.bandwidth)
+                })
+                .await
+                .map_err(FetchError::into_error)?;
+                self.log_retries(&url, status, trace);
+                return checked_body_size(Fetched {
+                    status,
+                    body,
+                    request_id,
+                });
+            }
+        };
+
+        let request = inflight
+            .0
+            .lock()
+            .expect("poisoned in-flight request registry")
+            .entry(url.clone())
+            .or_insert_with(|| {
+                let this = self.clone();
+                let registry = inflight.0.clone();
+                async move {
+                    // accounted once for all the callers sharing the response
+                    let request_id = this.request_ids.next();
+                    let result = fetch_with_retry(&this.retry, &*this.sleeper, || {
+                        let request =
+                            this.signed(identify(this.client.get(&url), request_id.as_deref()));
+                        fetch_bytes(request, this.redirect_policy, &this.bandwidth)
+                    })
+                    .await;
+                    registry
+                        .lock()
+                        .expect("poisoned in-flight request registry")
+                        .remove(&url);
+                    result.map(|(status, body, trace)| {
+                        this.log_retries(&url, status, trace);
+                        Fetched {
+                            status,
+                            body,
+                            request_id,
+                        }
+                    })
+                }
+                .boxed()
+                .shared()
+            })
+            .clone();
+        checked_body_size(request.await.map_err(FetchError::into_error)?)
+    }
+
+    /// Tag `request` with the id of the next request, unless ids are disabled, returning the
+    /// id along with it.
+    fn identified(&self, request: RequestBuilder) -> (RequestBuilder, Option<String>) {
+        let request_id = self.request_ids.next();
+        (identify(request, request_id.as_deref()), request_id)
+    }
+
+    /// Sign `request` with the SigV4 signer set on the builder, if any, right before sending it.
+    #[cfg(feature = "aws-sigv4")]
+    fn signed(&self, request: RequestBuilder) -> RequestBuilder {
+        let signer = match &self.sigv4 {
+            Some(signer) => signer,
+            None => return request,
+        };
+        let built = match request.try_clone().map(RequestBuilder::build) {
+            Some(Ok(built)) => built,
+            // left to fail when sent
+            _ => return request,
+        };
+        let body = built
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .unwrap_or_default();
+        signer
+            .sign(
+                built.method().as_str(),
+                built.url(),
+                body,
+                SystemTime::now(),
+            )
+            .into_iter()
+            .fold(request, |request, (name, value)| {
+                request.header(name, value)
+            })
+    }
+
+    #[cfg(not(feature = "aws-sigv4"))]
+    fn signed(&self, request: RequestBuilder) -> RequestBuilder {
+        request
+    }
+
+    /// Make a GET request to `url`, retrying it as configured.
+    async fn get_with_retry(&self, url: &str) -> Result<Response, Error> {
+        self.send_with_retry(self.client.get(url)).await
+    }
+
+    /// Make a GET request to `path`, returning the response body along with the
+    /// [`RequestTrace`] of the retries it took.
+    pub async fn get_traced(&self, path: &str) -> Result<(Vec<u8>, RequestTrace), Error> {
+        let url = self.endpoint(format_args!("{}", path));
+        let (resp, trace) = self.send_traced(self.client.get(&url)).await?;
+        let resp = error_for_status(resp).await?;
+        Ok((into_bytes(resp).await?, trace))
+    }
+
+    /// Send `request` as [`AsyncClient::send_traced`] does, logging the retries it took when
+    /// enabled with [`Builder::trace_retries`].
+    async fn send_with_retry(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let (resp, trace) = self.send_traced(request).await?;
+        self.log_retries(resp.url().as_str(), resp.status(), trace);
+        Ok(resp)
+    }
+
+    /// Log the retries a request to `url` completed with `status` took, when enabled with
+    /// [`Builder::trace_retries`].
+    fn log_retries(&self, url: &str, status: StatusCode, trace: RequestTrace) {
+        if self.trace_retries && trace.retries > 0 {
+            info!(
+                "request to {} completed with status {} after {} retries, waiting {:?}",
+                url, status, trace.retries, trace.delay
+            );
+        }
+    }
+
+    /// Send `request`, retrying it with exponential backoff while the server responds with a
+    /// retryable status or the retry predicate asks for it, up to the maximum number of retries.
+    async fn send_traced(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<(Response, RequestTrace), Error> {
+        let _request = self.lifecycle.enter()?;
+        let (request, request_id) = self.identified(request);
+        let cloned = || {
+            request
+                .try_clone()
+                .expect("GET requests have no streaming body")
+        };
+        let requested = cloned().build().map_err(into_error)?.url().clone();
+        let mut trace = RequestTrace::default();
+        loop {
+            self.bandwidth.check()?;
+            let mut resp = self.signed(cloned()).send().await.map_err(into_error)?;
+            check_redirect(self.redirect_policy, &requested, &resp).map_err(redirect_error)?;
+            if let Some(length) = resp.content_length() {
+                self.bandwidth
+                    .debit(EndpointCategory::of_url(resp.url().as_str()), length);
+            }
+            let body = peek_body(&self.retry, &mut resp).await?;
+            let context = RetryContext {
+                status: resp.status().as_u16(),
+                attempt: trace.retries,
+                body: body.as_deref(),
+            };
+            if !self.retry.should_retry(&context) {
+                if let Some(body) = body {
+                    resp = rebuild_response(&resp, body);
+                }
+                tag_response(&mut resp, request_id);
+                return Ok((resp, trace));
+            }
+            debug!(
+                "retrying request to {} after status {}",
+                resp.url(),
+                resp.status()
+            );
+            let body = match body {
+                Some(body) => Some(body),
+                None => into_bytes(resp).await.ok(),
+            };
+            let delay = self.retry.delay(trace.retries, body.as_deref());
+            self.sleeper.sleep(delay).await;
+            trace.retries += 1;
+            trace.delay += delay;
+        }
+    }
+
+    /// Make a GET request to `url` as [`AsyncClient::get_with_retry`] does, failing with
+    /// [`Error::HttpResponse`] when the server responds with an error status.
+    async fn get_checked(&self, url: &str) -> Result<Response, Error> {
+        error_for_status(self.get_with_retry(url).await?).await
+    }
+
+    /// Make a GET request to `url` conditional on the validators of the response cached for it,
+    /// reusing the cached response if the server confirms it didn't change.
+    async fn get_conditional(&self, cache: &HttpCache, url: &str) -> Result<Fetched, Error> {
+        let cached = cache.load(url);
+
+        let mut request = self.client.get(url);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let resp = self.send_with_retry(request).await?;
+
+        let status = resp.status();
+        let request_id = request_id_of(&resp);
+        if let (StatusCode::NOT_MODIFIED, Some(cached)) = (status, cached) {
+            cache.hit();
+            return Ok(Fetched {
+                status: StatusCode::OK,
+                body: cached.body,
+                request_id,
+            });
+        }
+        let validator = |name: header::HeaderName| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let etag = validator(header::ETAG);
+        let last_modified = validator(header::LAST_MODIFIED).or_else(|| validator(header::DATE));
+        let length = resp.content_length();
+        let body = into_bytes(resp).await?;
+        if length.is_none() {
+            self.bandwidth
+                .debit(EndpointCategory::of_url(url), body.len() as u64);
+        }
+        if status == StatusCode::OK {
+            cache.store(url, etag, last_modified, &body);
+        }
+        Ok(Fetched {
+            status,
+            body,
+            request_id,
+        })
+    }
+
+    /// Make a GET request to `path`, returning the response body as text if the request
+    /// succeeded.
+    async fn get_text(&self, path: &str) -> Result<String, Error> {
+        let url = self.endpoint(format_args!("{}", path));
+        self.get_bytes(url).await?.into_text(path)
     }
 
     /// Get a [`Transaction`] option given its [`Txid`]
     pub async fn tx(&self, txid: &Txid) -> Result<Option<Tx>, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/tx/{}/raw", self.url, txid))
-            .send()
-            .await;
-
-        match resp {
-            Ok(resp) => match resp.status() {
-                StatusCode::OK => {
-                    let bytes = into_bytes(resp).await?;
-                    let tx = Tx::consensus_decode(&mut Cursor::new(bytes))
-                        .map_err(|_| Error::InvalidServerData)?;
-                    Ok(Some(tx))
+        match self.tx_raw_bytes(txid).await? {
+            Some(bytes) => {
+                let tx = Tx::consensus_decode(&mut Cursor::new(bytes))
+                    .map_err(|_| Error::InvalidServerData)?;
+                if self.verify_txid && tx.txid() != *txid {
+                    return Err(Error::TxidMismatch {
+                        requested: *txid,
+                        got: tx.txid(),
+                    });
                 }
-                code => {
-                    if is_status_not_found(code) {
-                        return Ok(None);
+                Ok(Some(tx))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Get a [`Transaction`] option given its [`Txid`] as a hex string.
+    ///
+    /// Fails with [`Error::InvalidInput`] before making any request if `txid` isn't a valid
+    /// transaction id.
+    pub async fn tx_by_str(&self, txid: &str) -> Result<Option<Tx>, Error> {
+        self.tx(&parse_input("txid", txid)?).await
+    }
+
+    /// Get the consensus-encoded bytes of a transaction given its [`Txid`], without decoding
+    /// them.
+    pub async fn tx_raw_bytes(&self, txid: &Txid) -> Result<Option<Vec<u8>>, Error> {
+        let url = self.endpoint(format_args!("/tx/{}/raw", txid));
+        let fetched = self.get_bytes(url).await?;
+
+        match fetched.status {
+            StatusCode::OK => Ok(Some(fetched.body)),
+            code if is_status_not_found(code) => Ok(None),
+            _ => Err(fetched.error()),
+        }
+    }
+
+    /// Get the consensus encoding of a transaction as a hex string given its [`Txid`].
+    ///
+    /// The binary encoding, half the size of the hex one, is fetched and encoded locally.
+    pub async fn tx_hex(&self, txid: &Txid) -> Result<Option<String>, Error> {
+        Ok(self
+            .tx_raw_bytes(txid)
+            .await?
+            .map(|bytes| bytes[..].to_hex()))
+    }
+
+    /// Get transactions given their [`Txid`]s, in the same order.
+    ///
+    /// Requests are made concurrently, adapting the concurrency to the server's rate limits
+    /// up to the maximum set with [`Builder::adaptive_concurrency`].
+    pub async fn txs(&self, txids: &[Txid]) -> Result<Vec<Option<Tx>>, Error> {
+        self.batch(txids, |txid| self.tx(txid)).await
+    }
+
+    /// Get transactions given their [`Txid`]s, returning the result of each request in the
+    /// same order.
+    ///
+    /// Unlike [`AsyncClient::txs`], a failed request doesn't discard the others, so that only
+    /// the failed ones need to be retried.
+    pub async fn txs_partial(&self, txids: &[Txid]) -> Vec<Result<Option<Tx>, Error>> {
+        self.batch_results(txids, |txid| self.tx(txid), false)
+            .await
+            .unwrap_or_else(|_| unreachable!("errors are returned per request"))
+    }
+
+    /// Get the statuses of transactions given their [`Txid`]s, in the same order.
+    ///
+    /// Requests are made concurrently, adapting the concurrency to the server's rate limits
+    /// up to the maximum set with [`Builder::adaptive_concurrency`].
+    pub async fn tx_statuses(&self, txids: &[Txid]) -> Result<Vec<TxStatus>, Error> {
+        self.batch(txids, |txid| self.tx_status(txid)).await
+    }
+
+    /// Get the unspent outputs of [`Address`]es, in the same order.
+    ///
+    /// Requests are made concurrently, as [`AsyncClient::tx_statuses`] does.
+    #[cfg(feature = "addresses")]
+    pub async fn addresses_utxo(&self, addresses: &[Address]) -> Result<Vec<Vec<Utxo>>, Error> {
+        self.batch(addresses, |address| async move {
+            self.scripthash_utxo(&address.script_pubkey()).await
+        })
+        .await
+    }
+
+    /// Runs a request for each of the items concurrently, returning the results in the same
+    /// order.
+    ///
+    /// Concurrency is controlled with an additive-increase/multiplicative-decrease scheme:
+    /// it grows by one after as many successful requests as the current limit, and halves on
+    /// each rate-limited request, which gets rescheduled. Once down to a single request at a
+    /// time, rate-limited requests are rescheduled after the retry backoff, up to
+    /// [`MAX_BATCH_BACKOFFS`] times in a row.
+    async fn batch<'a, I, T, F, Fut>(&self, items: &'a [I], request: F) -> Result<Vec<T>, Error>
+    where
+        F: Fn(&'a I) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        self.batch_results(items, request, true)
+            .await?
+            .into_iter()
+            .collect()
+    }
+
+    /// Runs a request for each of the items concurrently, as [`AsyncClient::batch`] does,
+    /// returning the result of each request.
+    ///
+    /// With `fail_fast`, the first error which isn't caused by rate limiting is returned
+    /// immediately, dropping the requests still in flight.
+    async fn batch_results<'a, I, T, F, Fut>(
+        &self,
+        items: &'a [I],
+        request: F,
+        fail_fast: bool,
+    ) -> Result<Vec<Result<T, Error>>, Error>
+    where
+        F: Fn(&'a I) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let request = &request;
+        let mut aimd = Aimd::new(self.max_concurrency);
+        let mut results = (0..items.len()).map(|_| None).collect::<Vec<_>>();
+        let mut pending = (0..items.len()).collect::<VecDeque<_>>();
+        let mut in_flight = FuturesUnordered::new();
+        let mut throttled_in_a_row = 0;
+        loop {
+            while in_flight.len() < aimd.limit() {
+                match pending.pop_front() {
+                    Some(index) => {
+                        in_flight.push(async move { (index, request(&items[index]).await) })
                     }
-                    Err(Error::HttpResponse(code.into()))
+                    None => break,
                 }
-            },
-            Err(e) => Err(Error::Reqwest(e)),
+            }
+            let (index, result) = match in_flight.next().await {
+                Some(completed) => completed,
+                None => break,
+            };
+            match result {
+                Ok(value) => {
+                    aimd.on_success();
+                    throttled_in_a_row = 0;
+                    results[index] = Some(Ok(value));
+                }
+                Err(e) if is_throttled(&e) && aimd.limit() > 1 => {
+                    aimd.on_throttled();
+                    pending.push_front(index);
+                }
+                Err(e) if is_throttled(&e) && throttled_in_a_row < MAX_BATCH_BACKOFFS => {
+                    debug!("backing off batch requests after status 429");
+                    self.sleeper
+                        .sleep(self.retry.backoff(throttled_in_a_row))
+                        .await;
+                    throttled_in_a_row += 1;
+                    pending.push_front(index);
+                }
+                Err(e) if fail_fast => return Err(e),
+                Err(e) => results[index] = Some(Err(e)),
+            }
         }
+        Ok(results
+            .into_iter()
+            .map(|result| result.expect("all requests are completed"))
+            .collect())
     }
 
     /// Get a [`Transaction`] given its [`Txid`].
@@ -95,181 +748,975 @@ impl AsyncClient {
         }
     }
 
+    /// Get transaction info given its [`Txid`], including its status and fee.
+    ///
+    /// A malformed response fails with [`Error::Json`] in both clients.
+    pub async fn tx_info(&self, txid: &Txid) -> Result<Option<crate::Tx>, Error> {
+        let url = self.endpoint(format_args!("/tx/{}", txid));
+        let resp = self.get_with_retry(&url).await?;
+
+        if let StatusCode::NOT_FOUND = resp.status() {
+            return Ok(None);
+        }
+
+        let body = error_for_status(resp).await?.bytes().await?;
+        let mut tx: crate::Tx = serde_json::from_slice(&body)?;
+        self.fill_anchor(tx.txid, &mut tx.status).await?;
+        Ok(Some(tx))
+    }
+
+    /// Get all outputs of the transaction with the given [`Txid`] paying to an [`Address`].
+    ///
+    /// Returns an empty list if the transaction exists but doesn't pay to the address, and
+    /// [`Error::TransactionNotFound`] if the transaction is unknown.
+    #[cfg(feature = "addresses")]
+    pub async fn find_payment(
+        &self,
+        txid: &Txid,
+        address: &Address,
+    ) -> Result<Vec<PaymentInfo>, Error> {
+        self.find_script_payment(txid, &address.script_pubkey())
+            .await
+    }
+
+    /// Get all outputs of the transaction with the given [`Txid`] paying to a [`ScriptPubkey`].
+    ///
+    /// Returns an empty list if the transaction exists but doesn't pay to the script, and
+    /// [`Error::TransactionNotFound`] if the transaction is unknown.
+    pub async fn find_script_payment(
+        &self,
+        txid: &Txid,
+        script: &ScriptPubkey,
+    ) -> Result<Vec<PaymentInfo>, Error> {
+        match self.tx_info(txid).await? {
+            Some(tx) => Ok(tx.payments_to(script)),
+            None => Err(Error::TransactionNotFound(*txid)),
+        }
+    }
+
+    /// Get the fee paid by a transaction given its [`Txid`].
+    ///
+    /// Uses the fee reported by the server when available. Otherwise the fee is computed from the
+    /// values of the spent outputs, fetching the previous transactions whenever their outputs are
+    /// not embedded into the response.
+    ///
+    /// Fails with [`Error::InvalidServerData`] if the values reported by the server overflow or
+    /// the outputs are worth more than the inputs.
+    pub async fn tx_fee(&self, txid: &Txid) -> Result<u64, Error> {
+        match self.tx_info(txid).await? {
+            Some(tx) => self.fee_of(&tx).await,
+            None => Err(Error::TransactionNotFound(*txid)),
+        }
+    }
+
+    /// Get the fee paid by a transaction, computing it from the spent outputs when the server
+    /// doesn't report it.
+    async fn fee_of(&self, tx: &crate::Tx) -> Result<u64, Error> {
+        if tx.is_coinbase() {
+            return Err(Error::CoinbaseFee(tx.txid));
+        }
+        if let Some(fee) = tx.fee {
+            return Ok(fee);
+        }
+
+        let mut input_value = 0u64;
+        for vin in &tx.vin {
+            let value = match &vin.prevout {
+                Some(prevout) => prevout.value,
+                None => {
+                    let prev_tx = match self.tx_info(&vin.txid).await? {
+                        Some(prev_tx) => prev_tx,
+                        None => return Err(Error::TransactionNotFound(vin.txid)),
+                    };
+                    match prev_tx.vout.get(vin.vout as usize) {
+                        Some(vout) => vout.value,
+                        None => return Err(Error::InvalidServerData),
+                    }
+                }
+            };
+            input_value = input_value
+                .checked_add(value)
+                .ok_or(Error::InvalidServerData)?;
+        }
+        let output_value = tx
+            .vout
+            .iter()
+            .try_fold(0u64, |sum, vout| sum.checked_add(vout.value))
+            .ok_or(Error::InvalidServerData)?;
+        input_value
+            .checked_sub(output_value)
+            .ok_or(Error::InvalidServerData)
+    }
+
+    /// Get the feerate (in sat/vB) of a transaction together with its unconfirmed ancestors.
+    ///
+    /// For an unconfirmed transaction, the unconfirmed ancestors are found by walking the
+    /// transactions spent by its inputs, stopping at confirmed ones. Ancestors deeper than
+    /// [`MAX_ANCESTOR_DEPTH`] are not taken into account. For a confirmed transaction this is
+    /// just its own feerate.
+    pub async fn effective_feerate(&self, txid: &Txid) -> Result<f32, Error> {
+        let tx = match self.tx_info(txid).await? {
+            Some(tx) => tx,
+            None => return Err(Error::TransactionNotFound(*txid)),
+        };
+        let mut total_fee = self.fee_of(&tx).await?;
+        let mut total_vsize = tx.vsize();
+
+        if !tx.status.confirmed {
+            let mut visited = HashSet::new();
+            let mut level = tx.vin.iter().map(|vin| vin.txid).collect::<Vec<_>>();
+            for _ in 0..MAX_ANCESTOR_DEPTH {
+                let mut next = vec![];
+                for parent_txid in level {
+                    if !visited.insert(parent_txid) {
+                        continue;
+                    }
+                    let parent = match self.tx_info(&parent_txid).await? {
+                        Some(parent) => parent,
+                        None => return Err(Error::TransactionNotFound(parent_txid)),
+                    };
+                    if parent.status.confirmed {
+                        continue;
+                    }
+                    total_fee += self.fee_of(&parent).await?;
+                    total_vsize += parent.vsize();
+                    next.extend(parent.vin.iter().map(|vin| vin.txid));
+                }
+                if next.is_empty() {
+                    break;
+                }
+                level = next;
+            }
+        }
+
+        Ok(total_fee as f32 / total_vsize as f32)
+    }
+
     /// Get a [`Txid`] of a transaction given its index in a block with a given hash.
+    ///
+    /// Returns `None` if the block is unknown or the index is out of range. Backends disagree
+    /// on how to report the latter (404, 400 or an empty body), so all of them are mapped to
+    /// `None`.
     pub async fn txid_at_block_index(
         &self,
         block_hash: &BlockHash,
         index: usize,
     ) -> Result<Option<Txid>, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/block/{}/txid/{}", self.url, block_hash, index))
-            .send()
-            .await?;
+        let path = format!("/block/{}/txid/{}", block_hash, index);
+        let url = self.endpoint(format_args!("{}", path));
+        let resp = self.get_with_retry(&url).await?;
 
-        if let StatusCode::NOT_FOUND = resp.status() {
+        if let StatusCode::NOT_FOUND | StatusCode::BAD_REQUEST = resp.status() {
             return Ok(None);
         }
 
-        Ok(Some(Txid::from_str(&resp.text().await?)?))
+        let resp = error_for_status(resp).await?;
+        let text = text_body(&path, into_bytes(resp).await?)?;
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Txid::from_str(text.trim())?))
     }
 
     /// Get the status of a [`Transaction`] given its [`Txid`].
     pub async fn tx_status(&self, txid: &Txid) -> Result<TxStatus, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/tx/{}/status", self.url, txid))
-            .send()
-            .await?;
+        let url = self.endpoint(format_args!("/tx/{}/status", txid));
+        let resp = self.get_with_retry(&url).await?;
+
+        let mut status = error_for_status(resp).await?.json().await?;
+        self.fill_anchor(*txid, &mut status).await?;
+        self.check_tx_status(&status).await?;
+        Ok(status)
+    }
+
+    /// Fills in the block hash of the confirmed `status` of `txid` lacking it, if anchors are
+    /// required.
+    async fn fill_anchor(&self, txid: Txid, status: &mut TxStatus) -> Result<(), Error> {
+        self.fill_anchors(vec![(txid, status)]).await
+    }
+
+    /// Fills in the block hash of the confirmed statuses lacking it, given with the txid they
+    /// are the status of, if anchors are required.
+    ///
+    /// The block at each height is looked up once.
+    async fn fill_anchors(&self, statuses: Vec<(Txid, &mut TxStatus)>) -> Result<(), Error> {
+        if !self.require_anchors {
+            return Ok(());
+        }
+        let mut blocks = HashMap::<u32, (BlockHash, Option<Vec<Txid>>)>::new();
+        for (txid, status) in statuses {
+            let height = match status.block_height {
+                Some(height) if status.confirmed && status.block_hash.is_none() => height,
+                _ => continue,
+            };
+            let (hash, txids) = match blocks.entry(height) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.anchor_block(height).await?),
+            };
+            if let Some(txids) = txids {
+                validation::check_anchor(&txid, height, *hash, txids)?;
+            }
+            status.block_hash = Some(*hash);
+        }
+        Ok(())
+    }
+
+    /// Get the hash of the block at `height` to anchor confirmed statuses or proofs, caching it
+    /// once the block is deep enough not to be reorganized.
+    ///
+    /// The txids of a block too recent to be cached are returned along with its hash, so that
+    /// the anchored transactions can be checked to be in it: after a reorg, the block now at
+    /// `height` may not be the one the server reported them confirmed in.
+    async fn anchor_block(&self, height: u32) -> Result<(BlockHash, Option<Vec<Txid>>), Error> {
+        let cached = self
+            .anchors
+            .lock()
+            .expect("poisoned block hash cache")
+            .get(&height)
+            .copied();
+        if let Some(hash) = cached {
+            return Ok((hash, None));
+        }
+        let hash = self.block_hash(height).await?;
+        if height.saturating_add(ANCHOR_CACHE_DEPTH) <= self.height().await? {
+            self.anchors
+                .lock()
+                .expect("poisoned block hash cache")
+                .insert(height, hash);
+            return Ok((hash, None));
+        }
+        Ok((hash, Some(self.block_txids(&hash).await?)))
+    }
+
+    /// Cross-checks the block hash of a confirmed transaction status against the hash of the
+    /// block at the reported height, if response validation is enabled.
+    async fn check_tx_status(&self, status: &TxStatus) -> Result<(), Error> {
+        let cache = match &self.block_hashes {
+            Some(cache) => cache,
+            None => return Ok(()),
+        };
+        let (height, hash) = match validation::unverified_block(cache, status) {
+            Some(claimed) => claimed,
+            None => return Ok(()),
+        };
+        let actual = self.block_hash(height).await?;
+        validation::check_block_hash(cache, height, hash, actual)
+    }
+
+    /// Checks that a merkle `proof` of `txid` leads to the merkle root of the header of the
+    /// block at its height, if response validation is enabled.
+    async fn check_merkle_proof(&self, txid: &Txid, proof: &MerkleProof) -> Result<(), Error> {
+        if self.block_hashes.is_none() {
+            return Ok(());
+        }
+        let block_hash = self.block_hash(proof.block_height).await?;
+        let header = self.header_by_hash(&block_hash).await?;
+        validation::check_merkle_proof(txid, proof, &header)
+    }
 
-        Ok(resp.error_for_status()?.json().await?)
+    /// Spot-checks a sample of `utxos` against the number of outputs of their funding
+    /// transactions, if response validation is enabled.
+    async fn check_utxos(&self, utxos: &[Utxo]) -> Result<(), Error> {
+        if self.block_hashes.is_none() {
+            return Ok(());
+        }
+        let mut rng = SplitMix64::from_entropy();
+        for utxo in validation::sample_utxos(utxos, &mut rng) {
+            let outputs = self.tx(&utxo.txid).await?.map(|tx| tx.outputs.len());
+            validation::check_utxo(utxo, outputs)?;
+        }
+        Ok(())
     }
 
-    /* Uncomment once `bp-primitives` will support consensus serialziation
     /// Get a [`BlockHeader`] given a particular block hash.
     pub async fn header_by_hash(&self, block_hash: &BlockHash) -> Result<BlockHeader, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/block/{}/header", self.url, block_hash))
-            .send()
-            .await?;
+        BlockHeader::from_str(&self.header_hex(block_hash).await?)
+            .map_err(|_| Error::InvalidServerData)
+    }
+
+    /// Get the consensus encoding of the header of a block as a hex string, given its
+    /// [`BlockHash`].
+    ///
+    /// Esplora serves headers only in hex: the raw block would be the only binary alternative,
+    /// which is far larger than the 160 hex characters of a header.
+    pub async fn header_hex(&self, block_hash: &BlockHash) -> Result<String, Error> {
+        let path = format!("/block/{}/header", block_hash);
+        let url = self.endpoint(format_args!("{}", path));
+        let fetched = self.get_bytes(url).await?;
 
-        let header = deserialize(&Vec::from_hex(&resp.text().await?)?)?;
+        if let StatusCode::NOT_FOUND = fetched.status {
+            return Err(Error::HeaderHashNotFound(*block_hash));
+        }
 
-        Ok(header)
+        Ok(fetched.into_text(&path)?.trim().to_owned())
+    }
+
+    /// Get the consensus-encoded bytes of the header of a block given its [`BlockHash`],
+    /// decoded locally from [`AsyncClient::header_hex`].
+    pub async fn header_raw_bytes(&self, block_hash: &BlockHash) -> Result<Vec<u8>, Error> {
+        Vec::<u8>::from_hex(&self.header_hex(block_hash).await?)
+            .map_err(|_| Error::InvalidServerData)
+    }
+
+    /// Get a [`BlockHeader`] given a block hash as a hex string.
+    ///
+    /// Fails with [`Error::InvalidInput`] before making any request if `block_hash` isn't a
+    /// valid block hash.
+    pub async fn header_by_hash_str(&self, block_hash: &str) -> Result<BlockHeader, Error> {
+        self.header_by_hash(&parse_input("block hash", block_hash)?)
+            .await
+    }
+
+    /// Get the merkle root and the witness commitment of a block given its [`BlockHash`].
+    ///
+    /// The coinbase is fetched with [`AsyncClient::txid_at_block_index`] and
+    /// [`AsyncClient::tx_info`]. When the server doesn't return it, the block is reported
+    /// without coinbase txid nor witness commitment.
+    pub async fn block_commitments(
+        &self,
+        block_hash: &BlockHash,
+    ) -> Result<BlockCommitments, Error> {
+        let header = self.header_by_hash(block_hash).await?;
+        let coinbase = match self.txid_at_block_index(block_hash, 0).await? {
+            Some(txid) => self.tx_info(&txid).await?,
+            None => None,
+        };
+        Ok(BlockCommitments {
+            merkle_root: header.merkle_root,
+            coinbase_txid: coinbase.as_ref().map(|tx| tx.txid),
+            witness_commitment: coinbase.as_ref().and_then(extract_witness_commitment),
+        })
+    }
+
+    /// Get the total work proven by the headers of the blocks from `from_height` to
+    /// `to_height`, both included.
+    ///
+    /// Headers are fetched concurrently, as for [`AsyncClient::txs`]. Fails with
+    /// [`Error::InconsistentBackend`] unless each header is the one of the block at its height
+    /// and builds on the previous one.
+    pub async fn chain_work_between(
+        &self,
+        from_height: u32,
+        to_height: u32,
+    ) -> Result<Work, Error> {
+        let heights = (from_height..=to_height).collect::<Vec<_>>();
+        let (hashes, headers): (Vec<_>, Vec<_>) = self
+            .batch(&heights, |height| async move {
+                let block_hash = self.block_hash(*height).await?;
+                let header = self.header_by_hash(&block_hash).await?;
+                Ok((block_hash, header))
+            })
+            .await?
+            .into_iter()
+            .unzip();
+        check_header_chain(&hashes, &headers)?;
+        Ok(cumulative_work(&headers))
     }
-     */
 
     /// Get the [`BlockStatus`] given a particular [`BlockHash`].
     pub async fn block_status(&self, block_hash: &BlockHash) -> Result<BlockStatus, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/block/{}/status", self.url, block_hash))
-            .send()
-            .await?;
+        let url = self.endpoint(format_args!("/block/{}/status", block_hash));
+        let resp = self.get_with_retry(&url).await?;
 
-        Ok(resp.error_for_status()?.json().await?)
+        Ok(error_for_status(resp).await?.json().await?)
     }
 
     /* TODO: Uncomment once `bp-primitives` will support blocks
     /// Get a [`Block`] given a particular [`BlockHash`].
     pub async fn block_by_hash(&self, block_hash: &BlockHash) -> Result<Option<Block>, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/block/{}/raw", self.url, block_hash))
-            .send()
+        let resp = self.get_with_retry(&self.endpoint(format_args!("/block/{}/raw", block_hash)))
             .await?;
 
         if let StatusCode::NOT_FOUND = resp.status() {
             return Ok(None);
         }
-        Ok(Some(deserialize(&resp.error_for_status()?.bytes().await?)?))
+        Ok(Some(deserialize(&error_for_status(resp).await?.bytes().await?)?))
     }
 
-    /// Get a merkle inclusion proof for a [`Transaction`] with the given [`Txid`].
-    pub async fn merkle_proof(&self, tx_hash: &Txid) -> Result<Option<MerkleProof>, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/tx/{}/merkle-proof", self.url, tx_hash))
-            .send()
+    /// Get a [`MerkleBlock`] inclusion proof for a [`Transaction`] with the given [`Txid`].
+    pub async fn merkle_block(&self, tx_hash: &Txid) -> Result<Option<MerkleBlock>, Error> {
+        let resp = self.get_with_retry(&self.endpoint(format_args!("/tx/{}/merkleblock-proof", tx_hash)))
             .await?;
 
         if let StatusCode::NOT_FOUND = resp.status() {
             return Ok(None);
         }
 
-        Ok(Some(resp.error_for_status()?.json().await?))
+        let merkle_block = deserialize(&Vec::from_hex(&resp.text().await?)?)?;
+
+        Ok(Some(merkle_block))
     }
+     */
 
-    /// Get a [`MerkleBlock`] inclusion proof for a [`Transaction`] with the given [`Txid`].
-    pub async fn merkle_block(&self, tx_hash: &Txid) -> Result<Option<MerkleBlock>, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/tx/{}/merkleblock-proof", self.url, tx_hash))
-            .send()
-            .await?;
+    /// Get a merkle inclusion proof for a [`Transaction`] with the given [`Txid`].
+    pub async fn merkle_proof(&self, tx_hash: &Txid) -> Result<Option<MerkleProof>, Error> {
+        let url = self.endpoint(format_args!("/tx/{}/merkle-proof", tx_hash));
+        let resp = self.get_with_retry(&url).await?;
 
         if let StatusCode::NOT_FOUND = resp.status() {
             return Ok(None);
         }
 
-        let merkle_block = deserialize(&Vec::from_hex(&resp.text().await?)?)?;
+        let proof: MerkleProof = error_for_status(resp).await?.json().await?;
+        self.check_merkle_proof(tx_hash, &proof).await?;
+        Ok(Some(proof))
+    }
 
-        Ok(Some(merkle_block))
+    /// Get a merkle inclusion proof for a [`Transaction`] with the given [`Txid`], along with
+    /// the hash of the block it proves inclusion in, so that it can be verified against the
+    /// header of that block.
+    ///
+    /// Esplora doesn't return the hash with the proof, so it is the hash of the block at the
+    /// height of the proof, which is cached once the block is deep enough. Until then, the
+    /// txids of the block are also requested to check that the transaction is still in it.
+    pub async fn merkle_proof_with_block(
+        &self,
+        tx_hash: &Txid,
+    ) -> Result<Option<(MerkleProof, BlockHash)>, Error> {
+        let proof = match self.merkle_proof(tx_hash).await? {
+            Some(proof) => proof,
+            None => return Ok(None),
+        };
+        let (block_hash, txids) = self.anchor_block(proof.block_height).await?;
+        if let Some(txids) = txids {
+            validation::check_anchor(tx_hash, proof.block_height, block_hash, &txids)?;
+        }
+        Ok(Some((proof, block_hash)))
     }
-     */
 
     /// Get the spending status of an output given a [`Txid`] and the output index.
-    pub async fn output_status(
-        &self,
-        txid: &Txid,
-        index: u64,
-    ) -> Result<Option<OutputStatus>, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/tx/{}/outspend/{}", self.url, txid, index))
-            .send()
-            .await?;
+    pub async fn outspend(&self, txid: &Txid, vout: u32) -> Result<Option<OutputStatus>, Error> {
+        let url = self.endpoint(format_args!("/tx/{}/outspend/{}", txid, vout));
+        let resp = self.get_with_retry(&url).await?;
 
         if let StatusCode::NOT_FOUND = resp.status() {
             return Ok(None);
         }
 
-        Ok(Some(resp.error_for_status()?.json().await?))
+        let mut output: OutputStatus = error_for_status(resp).await?.json().await?;
+        if let (Some(txid), Some(status)) = (output.txid, &mut output.status) {
+            self.fill_anchor(txid, status).await?;
+        }
+        Ok(Some(output))
     }
 
-    pub async fn broadcast(&self, tx: &Tx) -> Result<(), Error> {
-        self
-            .client
-            .post(&format!("{}/tx", self.url))
-            .body(format!("{tx:x}").to_string())
-            .send()
-            .await?
-            ;
+    /// Get the spending status of all the outputs of a transaction given its [`Txid`], in
+    /// output order.
+    pub async fn tx_outspends(&self, txid: &Txid) -> Result<Vec<OutputStatus>, Error> {
+        let url = self.endpoint(format_args!("/tx/{}/outspends", txid));
+        let mut outputs: Vec<OutputStatus> = self.get_checked(&url).await?.json().await?;
+        self.fill_anchors(
+            outputs
+                .iter_mut()
+                .filter_map(|output| Some((output.txid?, output.status.as_mut()?)))
+                .collect(),
+        )
+        .await?;
+        Ok(outputs)
+    }
 
-        Ok(())
+    /// Get the spending status of the outputs of each transaction of a block, in block order,
+    /// which tells the outputs the block created and which of them were spent since.
+    ///
+    /// This is a heavy operation: it makes one request for the txids of the block, then one
+    /// request per transaction, concurrently, up to the limit set with
+    /// [`Builder::adaptive_concurrency`].
+    pub async fn block_outspends(
+        &self,
+        block_hash: &BlockHash,
+    ) -> Result<Vec<(Txid, Vec<OutputStatus>)>, Error> {
+        let txids = self.block_txids(block_hash).await?;
+        let outspends = self.batch(&txids, |txid| self.tx_outspends(txid)).await?;
+        Ok(txids.into_iter().zip(outspends).collect())
+    }
+
+    /// Get the spending status of an output given a [`Txid`] and an output index provided as
+    /// `u64`, which is rejected if it doesn't fit a `u32`.
+    #[deprecated(since = "0.11.0", note = "output indexes are u32, use `outspend`")]
+    pub async fn output_status(
+        &self,
+        txid: &Txid,
+        index: u64,
+    ) -> Result<Option<OutputStatus>, Error> {
+        self.outspend(txid, output_index(index)?).await
     }
 
+    pub async fn broadcast(&self, tx: &Tx) -> Result<(), Error> {
+        self.broadcast_with_meta(tx).await.map(|_| ())
+    }
 
-    /// Get the current height of the blockchain tip
-    pub async fn height(&self) -> Result<u32, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/blocks/tip/height", self.url))
-            .send()
-            .await?;
+    /// Broadcast a [`Transaction`] to Esplora as [`AsyncClient::broadcast`] does, returning the
+    /// id sent with the request so that it can be logged.
+    pub async fn broadcast_with_meta(&self, tx: &Tx) -> Result<RequestMeta, Error> {
+        let _request = self.lifecycle.enter()?;
+        let body = format!("{tx:x}");
+        self.bandwidth.upload(body.len() as u64);
+        let url = self.endpoint(format_args!("/tx"));
+        let (request, request_id) = self.identified(self.client.post(&url).body(body));
+        let resp = self.signed(request).send().await.map_err(into_error)?;
+        if let Ok(requested) = Url::parse(&url) {
+            check_redirect(self.redirect_policy, &requested, &resp).map_err(redirect_error)?;
+        }
+        error_for_status(resp)
+            .await
+            .map_err(|err| err.with_request_id(request_id.clone()))?;
 
-        Ok(resp.error_for_status()?.text().await?.parse()?)
+        Ok(RequestMeta { request_id })
     }
 
-    /// Get the [`BlockHash`] of the current blockchain tip.
+    /// Broadcast transactions concurrently, returning the result of each broadcast in the same
+    /// order, so that a rejected transaction doesn't prevent the others from being broadcast.
+    ///
+    /// Rejections carry the reason given by the node, as for [`AsyncClient::broadcast`]. The
+    /// order in which the server receives the transactions isn't guaranteed, so transactions
+    /// spending each other must be broadcast one after the other instead.
+    pub async fn broadcast_all(&self, txs: &[Tx]) -> Vec<Result<Txid, Error>> {
+        self.batch_results(
+            txs,
+            |tx| async move { self.broadcast(tx).await.map(|()| tx.txid()) },
+            false,
+        )
+        .await
+        .unwrap_or_else(|_| unreachable!("errors are returned per request"))
+    }
+
+    /// Broadcast a [`Tx`] to Esplora, then wait until the server returns it, which means it
+    /// was accepted into its mempool, for at most `timeout` after the broadcast.
+    ///
+    /// The broadcast itself is never cut short by `timeout`. Fails with
+    /// [`Error::DeadlineExceeded`] if the transaction isn't returned in time.
+    pub async fn broadcast_and_wait(&self, tx: &Tx, timeout: Duration) -> Result<Txid, Error> {
+        let txid = tx.txid();
+        self.broadcast(tx).await?;
+        let wait = async {
+            while self.tx_info(&txid).await?.is_none() {
+                self.sleeper.sleep(BROADCAST_POLL_INTERVAL).await;
+            }
+            Ok::<_, Error>(txid)
+        };
+        with_timeout(&*self.sleeper, timeout, wait)
+            .await
+            .unwrap_or(Err(Error::DeadlineExceeded))
+    }
+
+    /// Get the current height of the blockchain tip
+    ///
+    /// The height is reused for the TTL set with [`Builder::tip_cache_ttl`], so that polling
+    /// methods don't request it again and again.
+    pub async fn height(&self) -> Result<u32, Error> {
+        if let Some(height) = self.tip_height.get() {
+            return Ok(height);
+        }
+        self.height_uncached().await
+    }
+
+    /// Get the height of the current blockchain tip, bypassing the cached height, which is
+    /// then updated.
+    pub async fn height_uncached(&self) -> Result<u32, Error> {
+        let height = self.get_text("/blocks/tip/height").await?.parse()?;
+        self.tip_height.set(height);
+        Ok(height)
+    }
+
+    /// Get the number of confirmations of a transaction given its [`Txid`], zero if it is
+    /// unconfirmed.
+    pub async fn tx_confirmations(&self, txid: &Txid) -> Result<u32, Error> {
+        let status = self.tx_status(txid).await?;
+        let block_height = match status.block_height {
+            Some(block_height) if status.confirmed => block_height,
+            _ => return Ok(0),
+        };
+        let tip = self.height().await?;
+        Ok(tip.saturating_sub(block_height) + 1)
+    }
+
+    /// Get the confirmations, block hash, height and time of a transaction given its
+    /// [`Txid`], or `None` if the server doesn't know it.
+    ///
+    /// Confirmations are computed from the cached tip height, and are zero while the
+    /// transaction is in the mempool.
+    pub async fn tx_confirmation_details(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<ConfirmationDetails>, Error> {
+        let status = match self.tx_status(txid).await {
+            Ok(status) => status,
+            Err(err) if err.is_not_found() => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let confirmations = match status.block_height {
+            Some(block_height) if status.confirmed => {
+                self.height().await?.saturating_sub(block_height) + 1
+            }
+            _ => 0,
+        };
+        Ok(Some(ConfirmationDetails {
+            confirmations,
+            block_hash: status.block_hash.filter(|_| status.confirmed),
+            block_height: status.block_height.filter(|_| status.confirmed),
+            block_time: status.block_time.filter(|_| status.confirmed),
+        }))
+    }
+
+    /// Check `checkpoints` against the chain of the server, adding one at the tip when it is far
+    /// enough above the highest one.
+    ///
+    /// When the highest checkpoint is no longer in the chain, the highest one still in it is
+    /// looked up with a binary search, and the checkpoints above it are dropped and reported in
+    /// [`CheckpointUpdate::Reorg`]. The fork point is then found between the two checkpoints
+    /// from the ancestors of the lowest dropped one.
+    pub async fn update_checkpoints(
+        &self,
+        checkpoints: &mut Checkpoints,
+    ) -> Result<CheckpointUpdate, Error> {
+        let tip = self.height_uncached().await?;
+        let on_chain = match checkpoints.latest() {
+            Some(latest) => self.is_on_chain(latest, tip).await?,
+            None => true,
+        };
+
+        if !on_chain {
+            // the checkpoint at `high` is known not to be in the chain
+            let (mut low, mut high) = (0, checkpoints.len() - 1);
+            while low < high {
+                let mid = (low + high) / 2;
+                if self.is_on_chain(checkpoints.get(mid), tip).await? {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            let surviving = high.checked_sub(1).map(|index| checkpoints.get(index).0);
+            let dropped = checkpoints.drop_from(high);
+            let fork_height = self.fork_height(surviving, dropped[0], tip).await?;
+            return Ok(CheckpointUpdate::Reorg {
+                fork_height,
+                dropped,
+            });
+        }
+
+        if !checkpoints.is_due(tip) {
+            return Ok(CheckpointUpdate::Unchanged);
+        }
+        let hash = self.block_hash(tip).await?;
+        checkpoints.push(tip, hash);
+        Ok(CheckpointUpdate::Added { height: tip, hash })
+    }
+
+    /// Finds the height of the highest block of the chain whose tip is at height `tip` which is
+    /// an ancestor of the `dropped` checkpoint, at or above the `surviving` checkpoint.
+    ///
+    /// The ancestors are walked down from the dropped checkpoint, then the highest one still in
+    /// the chain is looked up with a binary search. If the server no longer serves them, the
+    /// surviving checkpoint is taken as the fork. Without one, the fork is somewhere below all
+    /// the checkpoints, and the height right below the dropped one is reported.
+    async fn fork_height(
+        &self,
+        surviving: Option<u32>,
+        (height, hash): (u32, BlockHash),
+        tip: u32,
+    ) -> Result<u32, Error> {
+        let floor = match surviving {
+            Some(floor) => floor,
+            None => return Ok(height.saturating_sub(1)),
+        };
+        // hashes of the stale branch, from `height` down to `floor + 1`
+        let mut branch = vec![hash];
+        for _ in floor + 1..height {
+            let last = branch[branch.len() - 1];
+            match self.header_by_hash(&last).await {
+                Ok(header) => branch.push(header.prev_block_hash),
+                Err(err) if err.kind() == ErrorKind::NotFound => return Ok(floor),
+                Err(err) => return Err(err),
+            }
+        }
+        // the blocks of the branch still in the chain are the ones up to the fork
+        let (mut low, mut high) = (floor, height);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            let hash = branch[(height - mid) as usize];
+            if self.is_on_chain((mid, hash), tip).await? {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
+    }
+
+    /// Whether the block `hash` at `height` is in the chain whose tip is at height `tip`.
+    async fn is_on_chain(&self, (height, hash): (u32, BlockHash), tip: u32) -> Result<bool, Error> {
+        if height > tip {
+            return Ok(false);
+        }
+        Ok(self.block_hash(height).await? == hash)
+    }
+
+    /// Get the [`BlockHash`] of the current blockchain tip.
+    ///
+    /// After an [`AsyncClient::warm_up`], the prefetched hash is returned while it is fresh.
     pub async fn tip_hash(&self) -> Result<BlockHash, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/blocks/tip/hash", self.url))
-            .send()
-            .await?;
+        if let Some(hash) = self.warm.tip_hash.get() {
+            return Ok(hash);
+        }
+        self.fetch_tip_hash().await
+    }
 
+    async fn fetch_tip_hash(&self) -> Result<BlockHash, Error> {
         Ok(BlockHash::from_str(
-            &resp.error_for_status()?.text().await?,
+            &self.get_text("/blocks/tip/hash").await?,
         )?)
     }
 
     /// Get the [`BlockHash`] of a specific block height
     pub async fn block_hash(&self, block_height: u32) -> Result<BlockHash, Error> {
-        let resp = self
-            .client
-            .get(&format!("{}/block-height/{}", self.url, block_height))
-            .send()
-            .await?;
+        let path = format!("/block-height/{}", block_height);
+        let url = self.endpoint(format_args!("{}", path));
+        let fetched = self.get_bytes(url).await?;
 
-        if let StatusCode::NOT_FOUND = resp.status() {
+        if let StatusCode::NOT_FOUND = fetched.status {
             return Err(Error::HeaderHeightNotFound(block_height));
         }
 
-        Ok(BlockHash::from_str(
-            &resp.error_for_status()?.text().await?,
-        )?)
+        Ok(BlockHash::from_str(&fetched.into_text(&path)?)?)
+    }
+
+    /// Get the [`BlockSummary`] of a block given its [`BlockHash`].
+    pub async fn block_summary(
+        &self,
+        block_hash: &BlockHash,
+    ) -> Result<Option<BlockSummary>, Error> {
+        let url = self.endpoint(format_args!("/block/{}", block_hash));
+        let resp = self.get_with_retry(&url).await?;
+
+        if let StatusCode::NOT_FOUND = resp.status() {
+            return Ok(None);
+        }
+
+        Ok(Some(error_for_status(resp).await?.json().await?))
+    }
+
+    /// Get the [`Txid`]s of the transactions of a block given its [`BlockHash`], in block order.
+    pub async fn block_txids(&self, block_hash: &BlockHash) -> Result<Vec<Txid>, Error> {
+        let url = self.endpoint(format_args!("/block/{}/txids", block_hash));
+        Ok(self.get_checked(&url).await?.json().await?)
+    }
+
+    /// Stream the blocks of the chain starting at `start_height`, in height order, together
+    /// with their txids.
+    ///
+    /// Up to `lookahead` blocks are fetched concurrently ahead of the one being consumed and
+    /// buffered until they are pulled. Dropping the stream cancels the requests in flight. The
+    /// stream ends at the chain tip, or after yielding the first error.
+    pub fn block_prefetcher(&self, start_height: u32, lookahead: usize) -> BlockStream {
+        let client = self.clone();
+        stream::iter(start_height..)
+            .map(move |height| {
+                let client = client.clone();
+                async move { client.prefetch_block(height).await }
+            })
+            .buffered(lookahead.max(1))
+            .scan(false, |failed, block| {
+                let item = match block {
+                    _ if *failed => None,
+                    Ok(block) => block.map(Ok),
+                    Err(err) => {
+                        *failed = true;
+                        Some(Err(err))
+                    }
+                };
+                future::ready(item)
+            })
+            .boxed()
+    }
+
+    /// Fetches the block at `height` for [`AsyncClient::block_prefetcher`], or `None` if the
+    /// chain doesn't reach that height yet.
+    async fn prefetch_block(&self, height: u32) -> Result<Option<PrefetchedBlock>, Error> {
+        let block_hash = match self.block_hash(height).await {
+            Ok(block_hash) => block_hash,
+            Err(Error::HeaderHeightNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let (summary, txids) = future::try_join(
+            self.block_summary(&block_hash),
+            self.block_txids(&block_hash),
+        )
+        .await?;
+        let summary = summary.ok_or(Error::HeaderHashNotFound(block_hash))?;
+        Ok(Some(PrefetchedBlock { summary, txids }))
+    }
+
+    /// Get the [`BlockFeeStats`] of a block given its [`BlockHash`].
+    ///
+    /// This is costly, as all the transactions of the block are fetched, in concurrent pages of
+    /// 25, after the summary of the block giving their count. Unless `force` is set, fails with
+    /// [`Error::BlockTooLarge`] before fetching them when the block has more than
+    /// [`MAX_FEE_STATS_TXS`] transactions.
+    pub async fn block_fee_stats(
+        &self,
+        block_hash: &BlockHash,
+        force: bool,
+    ) -> Result<BlockFeeStats, Error> {
+        let tx_count = self
+            .block_summary(block_hash)
+            .await?
+            .ok_or(Error::HeaderHashNotFound(*block_hash))?
+            .tx_count as usize;
+        if !force && tx_count > MAX_FEE_STATS_TXS {
+            return Err(Error::BlockTooLarge {
+                tx_count,
+                limit: MAX_FEE_STATS_TXS,
+            });
+        }
+        let txs = self.block_txs_paged(block_hash, tx_count).await?;
+        BlockFeeStats::from_txs(&txs)
+    }
+
+    /// Fetches the `tx_count` transactions of a block, in block order, in concurrent pages of
+    /// 25.
+    async fn block_txs_paged(
+        &self,
+        block_hash: &BlockHash,
+        tx_count: usize,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let starts = (0..tx_count)
+            .step_by(BLOCK_TXS_PAGE_SIZE)
+            .collect::<Vec<_>>();
+        let pages = self
+            .batch(&starts, |start| async move {
+                let url = self.endpoint(format_args!("/block/{}/txs/{}", block_hash, start));
+                Ok::<_, Error>(
+                    self.get_checked(&url)
+                        .await?
+                        .json::<Vec<crate::Tx>>()
+                        .await?,
+                )
+            })
+            .await?;
+        let mut txs = pages.into_iter().flatten().collect::<Vec<_>>();
+        if self.require_anchors {
+            anchor_to_block(&mut txs, block_hash);
+        }
+        Ok(txs)
+    }
+
+    /// Get the transactions of a block matching `filter`, in block order, with the reason why
+    /// they match.
+    ///
+    /// The transactions are fetched in concurrent pages of 25, unless the filter is empty, and
+    /// matched locally.
+    pub async fn scan_block(
+        &self,
+        block_hash: &BlockHash,
+        filter: &WatchFilter,
+    ) -> Result<Vec<WatchMatch>, Error> {
+        if filter.is_empty() {
+            return Ok(Vec::new());
+        }
+        let tx_count = self
+            .block_summary(block_hash)
+            .await?
+            .ok_or(Error::HeaderHashNotFound(*block_hash))?
+            .tx_count as usize;
+        let txs = self.block_txs_paged(block_hash, tx_count).await?;
+        Ok(filter.scan(&txs))
+    }
+
+    /// Stream the blocks mined after the current chain tip, each with its transactions matching
+    /// `filter`, as [`AsyncClient::scan_block`] finds them.
+    ///
+    /// The tip is polled every `poll`. On a reorg, the blocks of the new best chain are scanned
+    /// from the fork, but the ones scanned before are not yielded again. Errors are yielded
+    /// without ending the stream, the failed block being retried after `poll`, except
+    /// [`Error::ClientClosed`]: the stream ends once the client is shut down.
+    pub fn watch_chain(
+        &self,
+        filter: WatchFilter,
+        poll: Duration,
+    ) -> impl Stream<Item = Result<WatchedBlock, Error>> + '_ {
+        stream::unfold(
+            (None, filter, false),
+            move |(mut watch, filter, failed)| async move {
+                if failed {
+                    self.sleeper.sleep(poll).await;
+                }
+                loop {
+                    let watched = match watch.take() {
+                        Some(mut state) => {
+                            let watched = self.next_watched_block(&mut state, &filter).await;
+                            watch = Some(state);
+                            watched
+                        }
+                        None => self.height_uncached().await.map(|tip| {
+                            watch = Some(ChainWatch::new(tip));
+                            None
+                        }),
+                    };
+                    match watched {
+                        Ok(Some(block)) => return Some((Ok(block), (watch, filter, false))),
+                        Ok(None) => self.sleeper.sleep(poll).await,
+                        Err(Error::ClientClosed) => return None,
+                        Err(err) => return Some((Err(err), (watch, filter, true))),
+                    }
+                }
+            },
+        )
+    }
+
+    /// Scans the next block of the chain for [`AsyncClient::watch_chain`], or returns `None`
+    /// if the chain doesn't reach it yet.
+    async fn next_watched_block(
+        &self,
+        watch: &mut ChainWatch,
+        filter: &WatchFilter,
+    ) -> Result<Option<WatchedBlock>, Error> {
+        loop {
+            let height = watch.next_height();
+            let hash = match self.block_hash(height).await {
+                Ok(hash) => hash,
+                Err(Error::HeaderHeightNotFound(_)) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            let summary = self
+                .block_summary(&hash)
+                .await?
+                .ok_or(Error::HeaderHashNotFound(hash))?;
+            match watch.step(&summary) {
+                WatchStep::Skip | WatchStep::Rewind => continue,
+                WatchStep::Scan => {
+                    let matches = self.scan_block(&hash, filter).await?;
+                    watch.scanned(height, hash);
+                    return Ok(Some(WatchedBlock {
+                        height,
+                        hash,
+                        matches,
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Get the [`BlockSummary`]s of the blocks at the given heights, in the same order.
+    ///
+    /// Heights above the current tip are skipped, so fewer summaries than requested heights
+    /// may be returned. Requests are made concurrently, as for [`AsyncClient::txs`].
+    pub async fn block_summaries_at(&self, heights: &[u32]) -> Result<Vec<BlockSummary>, Error> {
+        let summaries = self
+            .batch(heights, |height| self.block_at_height(*height))
+            .await?;
+        Ok(summaries.into_iter().flatten().collect())
+    }
+
+    /// Get the [`BlockSummary`] of the block at `height` in the best chain, or `None` if the
+    /// chain doesn't reach that height.
+    pub async fn block_at_height(&self, height: u32) -> Result<Option<BlockSummary>, Error> {
+        let block_hash = match self.block_hash(height).await {
+            Ok(block_hash) => block_hash,
+            Err(Error::HeaderHeightNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        self.block_summary(&block_hash).await
     }
 
     /// Get confirmed transaction history for the specified address/scripthash,
@@ -284,85 +1731,1641 @@ impl AsyncClient {
         hasher.update(script);
         let script_hash = hasher.finalize();
         let url = match last_seen {
-            Some(last_seen) => format!(
-                "{}/scripthash/{:x}/txs/chain/{}",
-                self.url, script_hash, last_seen
-            ),
-            None => format!("{}/scripthash/{:x}/txs", self.url, script_hash),
+            Some(last_seen) => self.endpoint(format_args!(
+                "/scripthash/{:x}/txs/chain/{}",
+                script_hash, last_seen
+            )),
+            None => self.endpoint(format_args!("/scripthash/{:x}/txs", script_hash)),
         };
-        Ok(self
-            .client
-            .get(url)
-            .send()
+        let mut txs = self
+            .get_checked(&url)
             .await?
-            .error_for_status()?
             .json::<Vec<crate::Tx>>()
-            .await?)
+            .await?;
+        self.fill_anchors(tx_statuses(&mut txs)).await?;
+        Ok(txs)
     }
 
-    /// Get an map where the key is the confirmation target (in number of blocks)
-    /// and the value is the estimated feerate (in sat/vB).
-    pub async fn fee_estimates(&self) -> Result<HashMap<String, f64>, Error> {
+    /// Get the unconfirmed transactions of the specified address/scripthash.
+    pub async fn scripthash_mempool_txs(
+        &self,
+        script: &ScriptPubkey,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let mut hasher = Sha256::default();
+        hasher.update(script);
+        let script_hash = hasher.finalize();
+        let url = self.endpoint(format_args!("/scripthash/{:x}/txs/mempool", script_hash));
         Ok(self
-            .client
-            .get(&format!("{}/fee-estimates", self.url,))
-            .send()
+            .get_checked(&url)
             .await?
-            .error_for_status()?
-            .json::<HashMap<String, f64>>()
+            .json::<Vec<crate::Tx>>()
             .await?)
     }
 
-    /// Gets some recent block summaries starting at the tip or at `height` if provided.
+    /// Get the statistics of the outputs funded and spent by a script.
+    pub async fn scripthash_stats(
+        &self,
+        script: &ScriptPubkey,
+    ) -> Result<crate::AddressStats, Error> {
+        let mut hasher = Sha256::default();
+        hasher.update(script);
+        let script_hash = hasher.finalize();
+        let url = self.endpoint(format_args!("/scripthash/{:x}", script_hash));
+        Ok(self.get_checked(&url).await?.json().await?)
+    }
+
+    /// Get the backlog of the mempool, including its fee histogram.
+    pub async fn mempool(&self) -> Result<MempoolStats, Error> {
+        let url = self.endpoint(format_args!("/mempool"));
+        Ok(self.get_checked(&url).await?.json().await?)
+    }
+
+    /// Get the feerate (in sat/vB) needed for a transaction to be part of the next block,
+    /// from the fee histogram of the mempool.
     ///
-    /// The maximum number of summaries returned depends on the backend itself: esplora returns `10`
-    /// while [mempool.space](https://mempool.space/docs/api) returns `15`.
-    pub async fn blocks(&self, height: Option<u32>) -> Result<Vec<BlockSummary>, Error> {
-        let url = match height {
-            Some(height) => format!("{}/blocks/{}", self.url, height),
-            None => format!("{}/blocks", self.url),
+    /// Unlike the estimates by confirmation target of [`AsyncClient::fee_estimates`], this
+    /// reflects the current content of the mempool. The block is assumed to be as large as set
+    /// with [`Builder::block_vsize`], see [`MempoolStats::next_block_feerate`].
+    pub async fn suggested_feerate_for_next_block(&self) -> Result<f32, Error> {
+        Ok(self.mempool().await?.next_block_feerate(self.block_vsize))
+    }
+
+    /// Get the [`Txid`]s of all the transactions in the mempool.
+    pub async fn mempool_txids(&self) -> Result<Vec<Txid>, Error> {
+        let url = self.endpoint(format_args!("/mempool/txids"));
+        Ok(self.get_checked(&url).await?.json().await?)
+    }
+
+    /// Get confirmed transaction history for the specified address/scripthash, sorted with
+    /// newest first, excluding mempool transactions. Returns 25 transactions per page.
+    /// More can be requested by specifying the last txid seen by the previous query.
+    pub async fn scripthash_txs_confirmed(
+        &self,
+        script: &ScriptPubkey,
+        last_seen: Option<Txid>,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let mut hasher = Sha256::default();
+        hasher.update(script);
+        let script_hash = hasher.finalize();
+        let url = match last_seen {
+            Some(last_seen) => self.endpoint(format_args!(
+                "/scripthash/{:x}/txs/chain/{}",
+                script_hash, last_seen
+            )),
+            None => self.endpoint(format_args!("/scripthash/{:x}/txs/chain", script_hash)),
         };
+        let mut txs = self
+            .get_checked(&url)
+            .await?
+            .json::<Vec<crate::Tx>>()
+            .await?;
+        self.fill_anchors(tx_statuses(&mut txs)).await?;
+        Ok(txs)
+    }
 
-        Ok(self
-            .client
-            .get(&url)
-            .send()
+    /// Get confirmed transaction history for the specified address, sorted with newest first.
+    /// Returns 25 transactions per page. More can be requested by specifying the last txid seen
+    /// by the previous query.
+    ///
+    /// Unlike the `/address/:address/txs` endpoint, whose first page also lists up to 50
+    /// mempool transactions, this always queries `/address/:address/txs/chain`, so no
+    /// unconfirmed transaction is ever returned.
+    #[cfg(feature = "addresses")]
+    pub async fn address_txs_confirmed(
+        &self,
+        address: &Address,
+        last_seen: Option<Txid>,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let url = match last_seen {
+            Some(last_seen) => {
+                self.endpoint(format_args!("/address/{}/txs/chain/{}", address, last_seen))
+            }
+            None => self.endpoint(format_args!("/address/{}/txs/chain", address)),
+        };
+        let mut txs = self
+            .get_checked(&url)
             .await?
-            .error_for_status()?
-            .json()
-            .await?)
+            .json::<Vec<crate::Tx>>()
+            .await?;
+        self.fill_anchors(tx_statuses(&mut txs)).await?;
+        Ok(txs)
     }
 
-    /// Get the underlying base URL.
-    pub fn url(&self) -> &str {
-        &self.url
+    /// Get a page of the transaction history of an [`Address`] as
+    /// [`TxSummary`](crate::TxSummary) values, with their effect on the balance of
+    /// `my_scripts`, as listed by `/address/:address/txs` or after `last_seen` by
+    /// `/address/:address/txs/chain/:last_seen`.
+    ///
+    /// Transactions are summarized as soon as they are parsed from the response, so that
+    /// their inputs and outputs are never held in memory all together.
+    #[cfg(feature = "addresses")]
+    pub async fn address_tx_summaries(
+        &self,
+        address: &Address,
+        last_seen: Option<Txid>,
+        my_scripts: &HashSet<ScriptPubkey>,
+    ) -> Result<Vec<crate::TxSummary>, Error> {
+        let url = match last_seen {
+            Some(last_seen) => {
+                self.endpoint(format_args!("/address/{}/txs/chain/{}", address, last_seen))
+            }
+            None => self.endpoint(format_args!("/address/{}/txs", address)),
+        };
+        stream_txs(self.get_checked(&url).await?)
+            .map(|tx| tx.and_then(|tx| tx.summarize(my_scripts)))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
     }
 
-    /// Get the underlying [`Client`].
-    pub fn client(&self) -> &Client {
-        &self.client
+    /// Get a page of the confirmed transaction history of an [`Address`], newest first,
+    /// continuing after `after_txid` and keeping at most `limit` transactions.
+    ///
+    /// Backends supporting it, as detected by [`AsyncClient::backend_info`], are queried with
+    /// the `after_txid` query parameter, and the others with the path form used by
+    /// [`AsyncClient::address_txs_confirmed`].
+    #[cfg(feature = "addresses")]
+    pub async fn address_txs_after(
+        &self,
+        address: &Address,
+        after_txid: Option<Txid>,
+        limit: Option<usize>,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let mut txs: Vec<crate::Tx> = match after_txid {
+            Some(after_txid) if self.backend_info().await?.supports_query_pagination() => {
+                let query = QueryParams::new().param("after_txid", after_txid);
+                let url = self.endpoint(format_args!("/address/{}/txs{}", address, query));
+                let mut txs: Vec<crate::Tx> = self.get_checked(&url).await?.json().await?;
+                self.fill_anchors(tx_statuses(&mut txs)).await?;
+                txs
+            }
+            _ => self.address_txs_confirmed(address, after_txid).await?,
+        };
+        if let Some(limit) = limit {
+            txs.truncate(limit);
+        }
+        Ok(txs)
     }
-}
 
-fn is_status_not_found(status: StatusCode) -> bool {
-    status == 404
-}
+    /// Get the `n` most recent transactions of an [`Address`], unconfirmed ones first, then
+    /// confirmed ones newest first.
+    ///
+    /// Pages of the history are only fetched until `n` transactions are found, so that a
+    /// single request is made when the first page, with up to 50 unconfirmed and 25 confirmed
+    /// transactions, has enough of them. Fails with [`Error::PaginationAnomaly`] as
+    /// [`AsyncClient::scripthash_txs_all`] does.
+    #[cfg(feature = "addresses")]
+    pub async fn address_txs_latest(
+        &self,
+        address: &Address,
+        n: usize,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let script = address.script_pubkey();
+        let mut guard = PageGuard::new(self.max_pages);
+        let mut txs = vec![];
+        let mut last_seen = None;
+        while txs.len() < n {
+            let page = self.scripthash_txs(&script, last_seen).await?;
+            let next = guard.next_cursor(&page);
+            txs.extend(page);
+            last_seen =
+                next.map_err(|err| err.with_partial_txs(dedup_history(mem::take(&mut txs))))?;
+            if last_seen.is_none() {
+                break;
+            }
+        }
+        let mut txs = dedup_history(txs);
+        txs.truncate(n);
+        Ok(txs)
+    }
 
-async fn into_bytes(resp: Response) -> Result<Vec<u8>, std::io::Error> {
-    const BYTES_LIMIT: usize = 10 * 1_024 * 1_024;
-    let mut buf: Vec<u8> = vec![];
+    /// Get the statistics of the outputs funded and spent by an [`Address`].
+    #[cfg(feature = "addresses")]
+    pub async fn address_stats(&self, address: &Address) -> Result<crate::AddressStats, Error> {
+        let url = self.endpoint(format_args!("/address/{}", address));
+        Ok(self.get_checked(&url).await?.json().await?)
+    }
 
-    resp.bytes()
-        .await
-        .expect("invalid bytes data")
-        .take((BYTES_LIMIT + 1) as u64)
-        .read_to_end(&mut buf)?;
-    if buf.len() > BYTES_LIMIT {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "response too big for into_bytes",
-        ));
+    /// Get the statistics of several [`Address`]es, in the same order, e.g. to sum their
+    /// balances with [`total_balance`](crate::total_balance).
+    ///
+    /// Requests are made concurrently, as for [`AsyncClient::txs`].
+    #[cfg(feature = "addresses")]
+    pub async fn addresses_stats(
+        &self,
+        addresses: &[Address],
+    ) -> Result<Vec<crate::AddressStats>, Error> {
+        self.batch(addresses, |address| self.address_stats(address))
+            .await
     }
 
-    Ok(buf)
+    /// Get the balance of an [`Address`] cross-checked against the sum of its unspent
+    /// outputs, which can disagree when they are requested across a new block or from a
+    /// lagging backend.
+    ///
+    /// The check is made once more if the chain tip changed while requesting them. A
+    /// discrepancy remaining after that isn't an error, but is reported in the returned
+    /// [`VerifiedBalance`](crate::VerifiedBalance) so that it can be monitored.
+    #[cfg(feature = "addresses")]
+    pub async fn verified_balance(
+        &self,
+        address: &Address,
+    ) -> Result<crate::VerifiedBalance, Error> {
+        let script = address.script_pubkey();
+        let mut retried = false;
+        loop {
+            let tip = self.fetch_tip_hash().await?;
+            let stats = self.address_stats(address).await?;
+            let utxos = self.scripthash_utxo(&script).await?;
+            let tip_after = self.fetch_tip_hash().await?;
+            let balance = crate::VerifiedBalance::check(&stats, &utxos, tip_after)?;
+            if balance.verified || retried || tip_after == tip {
+                return Ok(balance);
+            }
+            debug!(
+                "balance of {} doesn't match its unspent outputs across a new block, checking again",
+                address
+            );
+            retried = true;
+        }
+    }
+
+    /// Get the unspent outputs of an [`Address`] along with the status of their spending
+    /// transaction, if they were already spent by an unconfirmed one, e.g. to detect double
+    /// spends or outputs not worth sweeping anymore.
+    ///
+    /// Outputs are checked concurrently, as for [`AsyncClient::txs`].
+    #[cfg(feature = "addresses")]
+    pub async fn address_utxo_spend_status(
+        &self,
+        address: &Address,
+    ) -> Result<Vec<(Utxo, Option<OutputStatus>)>, Error> {
+        let utxos = self.scripthash_utxo(&address.script_pubkey()).await?;
+        let spends = self
+            .batch(&utxos, |utxo| async move {
+                Ok(self
+                    .outspend(&utxo.txid, utxo.vout)
+                    .await?
+                    .filter(|status| status.spent))
+            })
+            .await?;
+        Ok(utxos.into_iter().zip(spends).collect())
+    }
+
+    /// Check whether an [`Address`] appears in any confirmed or unconfirmed transaction.
+    ///
+    /// This only requests the address statistics, without fetching any page of transactions,
+    /// which makes it much cheaper than [`AsyncClient::address_txs_confirmed`] to decide
+    /// whether a wallet scan reached its gap limit. The [`SyncCache`] is used as in
+    /// [`AsyncClient::script_has_history`].
+    #[cfg(feature = "addresses")]
+    pub async fn address_has_history(&self, address: &Address) -> Result<bool, Error> {
+        self.script_has_history(&address.script_pubkey()).await
+    }
+
+    /// Check whether a script appears in any confirmed or unconfirmed transaction, from its
+    /// statistics.
+    ///
+    /// When the client has a [`SyncCache`], a state still valid at the current tip, identified
+    /// by its height and hash, is reused without requesting the statistics, and the checked
+    /// state is recorded otherwise.
+    pub async fn script_has_history(&self, script: &ScriptPubkey) -> Result<bool, Error> {
+        let cache = match &self.sync_cache {
+            Some(cache) => cache,
+            None => return Ok(self.scripthash_stats(script).await?.has_history()),
+        };
+        let tip = self.height().await?;
+        let tip_hash = self.tip_hash().await?;
+        cache.observe_tip(tip, tip_hash);
+        if let Some(state) = cache.lookup(script, tip, tip_hash) {
+            return Ok(state.has_history);
+        }
+        let stats = self.scripthash_stats(script).await?;
+        cache.record_stats(script, &stats, tip, tip_hash);
+        Ok(stats.has_history())
+    }
+
+    /// Stream the confirmed transaction history for the specified address, one page of 25
+    /// transactions per item, newest first.
+    ///
+    /// See [`AsyncClient::scripthash_txs_stream`] for details.
+    #[cfg(feature = "addresses")]
+    pub fn address_txs_stream(
+        &self,
+        address: &Address,
+    ) -> impl Stream<Item = Result<Vec<crate::Tx>, Error>> + Send + 'static {
+        self.scripthash_txs_stream(&address.script_pubkey())
+    }
+
+    /// Stream the confirmed transaction history for the specified script, one page of 25
+    /// transactions per item, newest first.
+    ///
+    /// Pages are requested lazily as the stream gets polled, and no more requests are made once
+    /// the stream is dropped. The stream ends after the last page, or after yielding the first
+    /// error.
+    pub fn scripthash_txs_stream(
+        &self,
+        script: &ScriptPubkey,
+    ) -> impl Stream<Item = Result<Vec<crate::Tx>, Error>> + Send + 'static {
+        let guard = PageGuard::new(self.max_pages);
+        let state = (self.clone(), script.clone(), guard, Some(None));
+        stream::unfold(state, |(client, script, mut guard, cursor)| async move {
+            let last_seen = cursor?;
+            let result = client.scripthash_txs_confirmed(&script, last_seen).await;
+            let page = match result {
+                Ok(page) if page.is_empty() => return None,
+                Ok(page) => page,
+                Err(err) => return Some((Err(err), (client, script, guard, None))),
+            };
+            let next = match guard.next_cursor(&page) {
+                Ok(next) => next.map(Some),
+                Err(err) => {
+                    let err = err.with_partial_txs(page);
+                    return Some((Err(err), (client, script, guard, None)));
+                }
+            };
+            Some((Ok(page), (client, script, guard, next)))
+        })
+    }
+
+    /// Get statistics about an address given as a string.
+    ///
+    /// Fails with [`Error::InvalidInput`] before making any request if the address can't be
+    /// parsed or is for another network than the one set with [`Builder::network`].
+    #[cfg(feature = "addresses")]
+    pub async fn address_stats_str(&self, address: &str) -> Result<crate::AddressStats, Error> {
+        let address = self.parse_address(address)?;
+        self.address_stats(&address).await
+    }
+
+    /// Get confirmed transaction history for an address given as a string, and optionally
+    /// the last txid seen as a hex string.
+    ///
+    /// Fails with [`Error::InvalidInput`] before making any request if the address or the txid
+    /// can't be parsed, or if the address is for another network than the one set with
+    /// [`Builder::network`].
+    #[cfg(feature = "addresses")]
+    pub async fn address_txs_confirmed_str(
+        &self,
+        address: &str,
+        last_seen: Option<&str>,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let address = self.parse_address(address)?;
+        let last_seen = match last_seen {
+            Some(txid) => Some(parse_input("last seen txid", txid)?),
+            None => None,
+        };
+        self.address_txs_confirmed(&address, last_seen).await
+    }
+
+    /// Parse an address given as a string, checking it is for the network of the client.
+    #[cfg(feature = "addresses")]
+    pub(crate) fn parse_address(&self, address: &str) -> Result<Address, Error> {
+        parse_address(self.network, address)
+    }
+
+    /// Get the transactions of a block given its [`BlockHash`], parsing the response
+    /// incrementally as the stream gets polled.
+    ///
+    /// Meant for servers returning all the transactions in a single response, which is then
+    /// never held in memory at once.
+    pub async fn block_txs_streamed(
+        &self,
+        block_hash: &BlockHash,
+    ) -> Result<impl Stream<Item = Result<crate::Tx, Error>> + Send + 'static, Error> {
+        let url = self.endpoint(format_args!("/block/{}/txs", block_hash));
+        let resp = self.get_checked(&url).await?;
+        Ok(stream_txs(resp))
+    }
+
+    /// Get the transaction history for the specified address/scripthash, as returned by
+    /// `/scripthash/:hash/txs`, parsing the response incrementally as the stream gets polled.
+    ///
+    /// Meant for servers returning the whole history in a single response, which is then
+    /// never held in memory at once.
+    pub async fn scripthash_txs_streamed(
+        &self,
+        script: &ScriptPubkey,
+    ) -> Result<impl Stream<Item = Result<crate::Tx, Error>> + Send + 'static, Error> {
+        let mut hasher = Sha256::default();
+        hasher.update(script);
+        let script_hash = hasher.finalize();
+        let url = self.endpoint(format_args!("/scripthash/{:x}/txs", script_hash));
+        let resp = self.get_checked(&url).await?;
+        Ok(stream_txs(resp))
+    }
+
+    /// Get the full transaction history for the specified address/scripthash, sorted with
+    /// newest first, following the pagination of the confirmed history until its end.
+    ///
+    /// Transactions repeated across pages are deduplicated with the precedence of
+    /// [`merge_histories`](crate::merge_histories), keeping the order of the server.
+    ///
+    /// Fails with [`Error::PaginationAnomaly`] when the server keeps paginating past the
+    /// configured page limit or returns pages which would never end.
+    /// The transactions fetched until then can be recovered with [`Error::partial_txs`].
+    ///
+    /// When the client has a [`SyncCache`], the history is recorded in it.
+    pub async fn scripthash_txs_all(&self, script: &ScriptPubkey) -> Result<Vec<crate::Tx>, Error> {
+        let mut guard = PageGuard::new(self.max_pages);
+        let mut txs = vec![];
+        let mut last_seen = None;
+        loop {
+            let page = self.scripthash_txs(script, last_seen).await?;
+            let next = guard.next_cursor(&page);
+            txs.extend(page);
+            last_seen =
+                next.map_err(|err| err.with_partial_txs(dedup_history(mem::take(&mut txs))))?;
+            if last_seen.is_none() {
+                break;
+            }
+        }
+        let txs = dedup_history(txs);
+        if let Some(cache) = &self.sync_cache {
+            cache.record_history(script, &txs);
+        }
+        Ok(txs)
+    }
+
+    /// Get the transactions of several scripts, newest first across all of them, by merging
+    /// their paginated histories.
+    ///
+    /// The pages of each script are fetched lazily, as the stream gets polled, only as far as
+    /// needed to tell which transaction comes next, and a transaction touching several of the
+    /// scripts is only yielded once. Transactions are ordered with
+    /// [`cmp_tx_for_display`](crate::cmp_tx_for_display), the stream ending after the first
+    /// error.
+    pub fn wallet_history_stream(
+        &self,
+        scripts: Vec<ScriptPubkey>,
+    ) -> impl Stream<Item = Result<crate::Tx, Error>> + '_ {
+        let merge = HistoryMerge::new(scripts, self.max_pages);
+        stream::unfold(Some(merge), move |merge| async move {
+            let mut merge = merge?;
+            while let Some((index, script, cursor)) = merge.pending() {
+                let pushed = match self.scripthash_txs(&script, cursor).await {
+                    Ok(page) => merge.push_page(index, page),
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = pushed {
+                    return Some((Err(err), None));
+                }
+            }
+            let tx = merge.pop()?;
+            Some((Ok(tx), Some(merge)))
+        })
+    }
+
+    /// Get a page of the transaction history of a script as [`AsyncClient::scripthash_txs`]
+    /// does, continuing after `cursor`, along with the cursor of the next page, or `None` if
+    /// this is the last page.
+    ///
+    /// Fails with [`Error::InvalidCursor`] if `cursor` continues another kind of history.
+    pub async fn scripthash_txs_cursor(
+        &self,
+        script: &ScriptPubkey,
+        cursor: Option<&crate::Cursor>,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        self.txs_page_cursor(CursorFamily::ScriptTxs, script, cursor, false)
+            .await
+    }
+
+    /// Get a page of the transaction history of an [`Address`] as
+    /// [`AsyncClient::scripthash_txs_cursor`] does for its script.
+    #[cfg(feature = "addresses")]
+    pub async fn address_txs_cursor(
+        &self,
+        address: &Address,
+        cursor: Option<&crate::Cursor>,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        self.txs_page_cursor(
+            CursorFamily::AddressTxs,
+            &address.script_pubkey(),
+            cursor,
+            false,
+        )
+        .await
+    }
+
+    /// Get a page of the confirmed transaction history of a script as
+    /// [`AsyncClient::scripthash_txs_confirmed`] does, continuing after `cursor`, along with the
+    /// cursor of the next page, or `None` if this is the last page.
+    ///
+    /// Fails with [`Error::InvalidCursor`] if `cursor` continues another kind of history.
+    pub async fn scripthash_txs_confirmed_cursor(
+        &self,
+        script: &ScriptPubkey,
+        cursor: Option<&crate::Cursor>,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        self.txs_page_cursor(CursorFamily::ScriptTxs, script, cursor, true)
+            .await
+    }
+
+    /// Get a page of the confirmed transaction history of an [`Address`] as
+    /// [`AsyncClient::scripthash_txs_confirmed_cursor`] does for its script.
+    #[cfg(feature = "addresses")]
+    pub async fn address_txs_confirmed_cursor(
+        &self,
+        address: &Address,
+        cursor: Option<&crate::Cursor>,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        self.txs_page_cursor(
+            CursorFamily::AddressTxs,
+            &address.script_pubkey(),
+            cursor,
+            true,
+        )
+        .await
+    }
+
+    /// Stream the confirmed transaction history of a script as
+    /// [`AsyncClient::scripthash_txs_stream`] does, starting after `cursor`, each page along
+    /// with the cursor resuming the history after it, or `None` for the last page.
+    ///
+    /// The stream fails with [`Error::InvalidCursor`] if `cursor` continues another kind of
+    /// history.
+    pub fn scripthash_txs_stream_cursor(
+        &self,
+        script: &ScriptPubkey,
+        cursor: Option<crate::Cursor>,
+    ) -> impl Stream<Item = Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error>> + Send + 'static
+    {
+        self.txs_stream_cursor(CursorFamily::ScriptTxs, script.clone(), cursor)
+    }
+
+    /// Stream the confirmed transaction history of an [`Address`] as
+    /// [`AsyncClient::scripthash_txs_stream_cursor`] does for its script.
+    #[cfg(feature = "addresses")]
+    pub fn address_txs_stream_cursor(
+        &self,
+        address: &Address,
+        cursor: Option<crate::Cursor>,
+    ) -> impl Stream<Item = Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error>> + Send + 'static
+    {
+        self.txs_stream_cursor(CursorFamily::AddressTxs, address.script_pubkey(), cursor)
+    }
+
+    /// Streams the pages of the confirmed history of `script` after `cursor`, guarded against
+    /// servers looping over them.
+    fn txs_stream_cursor(
+        &self,
+        family: CursorFamily,
+        script: ScriptPubkey,
+        cursor: Option<crate::Cursor>,
+    ) -> impl Stream<Item = Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error>> + Send + 'static
+    {
+        let guard = PageGuard::new(self.max_pages);
+        let state = (self.clone(), script, guard, Some(cursor));
+        stream::unfold(
+            state,
+            move |(client, script, mut guard, cursor)| async move {
+                let cursor = cursor?;
+                let result = client
+                    .txs_page_cursor(family, &script, cursor.as_ref(), true)
+                    .await;
+                let (page, next) = match result {
+                    Ok((page, _)) if page.is_empty() => return None,
+                    Ok(found) => found,
+                    Err(err) => return Some((Err(err), (client, script, guard, None))),
+                };
+                if let Err(err) = guard.next_cursor(&page) {
+                    let err = err.with_partial_txs(page);
+                    return Some((Err(err), (client, script, guard, None)));
+                }
+                Some((Ok((page, next)), (client, script, guard, next.map(Some))))
+            },
+        )
+    }
+
+    /// Gets a page of the history of `script` after `cursor`, only of its confirmed
+    /// transactions if `confirmed`, the chain tip being recorded in the cursors when the first
+    /// page is requested.
+    async fn txs_page_cursor(
+        &self,
+        family: CursorFamily,
+        script: &ScriptPubkey,
+        cursor: Option<&crate::Cursor>,
+        confirmed: bool,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        let (last_seen, tip) = match cursor {
+            Some(cursor) => {
+                cursor.check_family(family)?;
+                (Some(cursor.last_seen), cursor.tip)
+            }
+            None => (None, self.tip_hash().await?),
+        };
+        let page = if confirmed {
+            self.scripthash_txs_confirmed(script, last_seen).await?
+        } else {
+            self.scripthash_txs(script, last_seen).await?
+        };
+        let next = next_page_cursor(&page).map(|last_seen| crate::Cursor {
+            family,
+            last_seen,
+            tip,
+        });
+        Ok((page, next))
+    }
+
+    /// Get an map where the key is the confirmation target (in number of blocks)
+    /// and the value is the estimated feerate (in sat/vB).
+    ///
+    /// Estimates from the configured [`FeeFallback`] are merged in when the server doesn't
+    /// provide some of the standard confirmation targets.
+    pub async fn fee_estimates(&self) -> Result<HashMap<String, f64>, Error> {
+        Ok(self.fee_estimates_with_source().await?.estimates)
+    }
+
+    /// Get fee estimates as [`AsyncClient::fee_estimates`] does, tagged with whether they come
+    /// from the server or were synthesized using the configured [`FeeFallback`].
+    ///
+    /// After an [`AsyncClient::warm_up`], the prefetched estimates are returned while they are
+    /// fresh.
+    pub async fn fee_estimates_with_source(&self) -> Result<FeeEstimates, Error> {
+        if let Some(estimates) = self.warm.fee_estimates.get() {
+            return Ok(estimates);
+        }
+        self.fetch_fee_estimates_with_source().await
+    }
+
+    async fn fetch_fee_estimates_with_source(&self) -> Result<FeeEstimates, Error> {
+        let primary = self.fetch_fee_estimates(&self.current_url()).await?;
+        let fallback = match &self.fee_fallback {
+            Some(fallback) if !has_all_fee_targets(&primary) => fallback,
+            _ => return Ok(FeeEstimates::primary(primary)),
+        };
+        let secondary = match fallback {
+            FeeFallback::Secondary(url) => match self.fetch_fee_estimates(url).await {
+                Ok(estimates) => estimates,
+                Err(e) => {
+                    debug!("unable to get fee estimates from {}: {}", url, e);
+                    HashMap::new()
+                }
+            },
+            fallback => fallback.local_estimates(),
+        };
+        Ok(FeeEstimates::merge(primary, secondary))
+    }
+
+    async fn fetch_fee_estimates(&self, base_url: &str) -> Result<HashMap<String, f64>, Error> {
+        let url = format!("{}/fee-estimates", base_url);
+        if let Some(cache) = &self.fee_cache {
+            let fetched = self.get_conditional(cache, &url).await?;
+            return Ok(parse_fee_estimates(fetched.into_text(&url)?.as_bytes())?);
+        }
+        let resp = self.get_checked(&url).await?;
+        Ok(parse_fee_estimates(&into_bytes(resp).await?)?)
+    }
+
+    /// Get the unspent outputs of the specified address/scripthash.
+    ///
+    /// When the client has a [`SyncCache`], the outputs are recorded in it.
+    pub async fn scripthash_utxo(&self, script: &ScriptPubkey) -> Result<Vec<Utxo>, Error> {
+        let mut hasher = Sha256::default();
+        hasher.update(script);
+        let script_hash = hasher.finalize();
+        let url = self.endpoint(format_args!("/scripthash/{:x}/utxo", script_hash));
+        let mut utxos: Vec<Utxo> = self.get_checked(&url).await?.json().await?;
+        self.fill_anchors(
+            utxos
+                .iter_mut()
+                .map(|utxo| (utxo.txid, &mut utxo.status))
+                .collect(),
+        )
+        .await?;
+        self.check_utxos(&utxos).await?;
+        if let Some(cache) = &self.sync_cache {
+            cache.record_utxos(script, &utxos);
+        }
+        Ok(utxos)
+    }
+
+    /// Get the unspent outputs of a script with at least `min_confirmations`, and the
+    /// unconfirmed ones too if `include_unconfirmed`, annotated with their
+    /// [`Utxo::confirmations`].
+    ///
+    /// Confirmations are counted from the chain tip height, reused from the tip cache while it
+    /// is fresh, as [`AsyncClient::height`] does.
+    pub async fn scripthash_utxo_filtered(
+        &self,
+        script: &ScriptPubkey,
+        min_confirmations: u32,
+        include_unconfirmed: bool,
+    ) -> Result<Vec<Utxo>, Error> {
+        let utxos = self.scripthash_utxo(script).await?;
+        let tip = self.height().await?;
+        Ok(Utxo::filter_by_confirmations(
+            utxos,
+            tip,
+            min_confirmations,
+            include_unconfirmed,
+        ))
+    }
+
+    /// Get the unspent outputs of an [`Address`] filtered by confirmations, as
+    /// [`AsyncClient::scripthash_utxo_filtered`] does.
+    #[cfg(feature = "addresses")]
+    pub async fn address_utxo_filtered(
+        &self,
+        address: &Address,
+        min_confirmations: u32,
+        include_unconfirmed: bool,
+    ) -> Result<Vec<Utxo>, Error> {
+        self.scripthash_utxo_filtered(
+            &address.script_pubkey(),
+            min_confirmations,
+            include_unconfirmed,
+        )
+        .await
+    }
+
+    /// Get a [`UtxoSnapshot`] of the unspent outputs of a script at the current chain tip.
+    pub async fn utxo_snapshot(&self, script: &ScriptPubkey) -> Result<UtxoSnapshot, Error> {
+        let tip_height = self.height_uncached().await?;
+        let tip_hash = self.block_hash(tip_height).await?;
+        Ok(UtxoSnapshot {
+            tip_height,
+            tip_hash,
+            utxos: self.scripthash_utxo(script).await?,
+        })
+    }
+
+    /// Get the changes of the unspent outputs of a script since the `old` snapshot, flagging a
+    /// reorg if its tip is no longer in the best chain, along with the snapshot of the current
+    /// outputs to diff against next time.
+    pub async fn utxo_diff_since(
+        &self,
+        script: &ScriptPubkey,
+        old: &UtxoSnapshot,
+    ) -> Result<(UtxoDiff, UtxoSnapshot), Error> {
+        let new = self.utxo_snapshot(script).await?;
+        let mut diff = diff_utxos(&old.utxos, &new.utxos);
+        diff.reorg = !self
+            .is_on_chain((old.tip_height, old.tip_hash), new.tip_height)
+            .await?;
+        Ok((diff, new))
+    }
+
+    /// Get the unspent outputs of a script worth spending at `feerate` (in sat/vB), valued net
+    /// of the fee of the input spending them.
+    ///
+    /// The input weight is derived from the type of the script, see
+    /// [`ScriptType::input_weight`], unless given in `opts`, which is required for the types
+    /// whose spending cost depends on the script. Outputs are filtered according to `opts`,
+    /// and the excluded ones are counted in the returned [`SpendableSet`].
+    pub async fn spendable_utxos(
+        &self,
+        script: &ScriptPubkey,
+        feerate: f64,
+        opts: SpendableOpts,
+    ) -> Result<SpendableSet, Error> {
+        let utxos = self.scripthash_utxo(script).await?;
+        let tip = if opts.min_confirmations > 1 {
+            Some(self.height().await?)
+        } else {
+            None
+        };
+        SpendableSet::evaluate(&ScriptType::from_script(script), utxos, feerate, &opts, tip)
+    }
+
+    /// Get the average time between the last `sample` blocks, computed from the timestamps of
+    /// the newest and the oldest of them.
+    ///
+    /// Fewer blocks are taken into account when the chain is shorter than `sample` blocks, and
+    /// a zero duration is returned when it has a single block.
+    pub async fn average_block_interval(&self, sample: usize) -> Result<Duration, Error> {
+        let summaries = self.walk_blocks(None, sample.saturating_add(1)).await?;
+        Ok(BlockSummary::average_interval(&summaries))
+    }
+
+    /// Get the summaries of the `count` blocks preceding `height`, from the newest to the
+    /// oldest, fewer if the genesis block is reached.
+    ///
+    /// Pages of [`Self::blocks`] are walked from the oldest summary of each, so that the
+    /// walk has no gap nor overlap whatever the number of summaries the backend returns per
+    /// page.
+    pub async fn blocks_before(
+        &self,
+        height: u32,
+        count: usize,
+    ) -> Result<Vec<BlockSummary>, Error> {
+        match height.checked_sub(1) {
+            Some(height) => self.walk_blocks(Some(height), count).await,
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Walks the pages of block summaries backward from `height`, or from the tip, until
+    /// `count` summaries are collected or the genesis block is reached.
+    async fn walk_blocks(
+        &self,
+        mut height: Option<u32>,
+        count: usize,
+    ) -> Result<Vec<BlockSummary>, Error> {
+        let mut summaries = Vec::with_capacity(count.min(MAX_PREALLOCATED_BLOCKS));
+        while summaries.len() < count {
+            let mut page = self.blocks(height).await?;
+            if let Some(height) = height {
+                // summaries above the requested height were already collected
+                page.retain(|summary| summary.time.height <= height);
+            }
+            let oldest = match page.last() {
+                Some(oldest) => oldest.time.height,
+                None => break,
+            };
+            summaries.extend(page);
+            if oldest == 0 {
+                break;
+            }
+            height = Some(oldest - 1);
+        }
+        summaries.truncate(count);
+        Ok(summaries)
+    }
+
+    /// Gets some recent block summaries starting at the tip or at `height` if provided.
+    ///
+    /// The maximum number of summaries returned depends on the backend itself: esplora returns `10`
+    /// while [mempool.space](https://mempool.space/docs/api) returns `15`.
+    ///
+    /// After an [`AsyncClient::warm_up`], the prefetched summaries of the latest blocks are
+    /// returned while they are fresh.
+    pub async fn blocks(&self, height: Option<u32>) -> Result<Vec<BlockSummary>, Error> {
+        if height.is_none() {
+            if let Some(blocks) = self.warm.recent_blocks.get() {
+                return Ok(blocks);
+            }
+        }
+        self.fetch_blocks(height).await
+    }
+
+    async fn fetch_blocks(&self, height: Option<u32>) -> Result<Vec<BlockSummary>, Error> {
+        let url = match height {
+            Some(height) => self.endpoint(format_args!("/blocks/{}", height)),
+            None => self.endpoint(format_args!("/blocks")),
+        };
+
+        Ok(self.get_checked(&url).await?.json().await?)
+    }
+
+    /// Concurrently fetch the resources selected with [`Builder::prefetch`], so that the first
+    /// queries for them are answered from memory during [`Builder::tip_cache_ttl`].
+    ///
+    /// Failures are only reported, and resources not fetched within
+    /// [`Builder::warm_up_budget`] are reported as skipped, their requests being cancelled.
+    pub async fn warm_up(&self) -> WarmUpReport {
+        let items = self.prefetch.items();
+        let mut pending = items.clone();
+        let mut report = WarmUpReport::default();
+        let mut fetches = items
+            .into_iter()
+            .map(|item| async move { (item, self.warm_up_item(item).await) })
+            .collect::<FuturesUnordered<_>>();
+
+        let record = async {
+            while let Some((item, result)) = fetches.next().await {
+                pending.retain(|pending| *pending != item);
+                report.record(item, result);
+            }
+        };
+        // an elapsed budget is reported through the skipped resources
+        let _ = with_timeout(&*self.sleeper, self.warm_up_budget, record).await;
+        report.skipped = pending;
+        report
+    }
+
+    async fn warm_up_item(&self, item: PrefetchItem) -> Result<(), Error> {
+        match item {
+            PrefetchItem::TipHeight => self.height_uncached().await.map(|_| ()),
+            PrefetchItem::TipHash => self
+                .fetch_tip_hash()
+                .await
+                .map(|hash| self.warm.tip_hash.set(hash)),
+            PrefetchItem::RecentBlocks => self
+                .fetch_blocks(None)
+                .await
+                .map(|blocks| self.warm.recent_blocks.set(blocks)),
+            PrefetchItem::FeeEstimates => self
+                .fetch_fee_estimates_with_source()
+                .await
+                .map(|estimates| self.warm.fee_estimates.set(estimates)),
+        }
+    }
+
+    /// Get a [`DryRunClient`] recording the requests this client would make, without sending
+    /// them.
+    pub fn dry_run(&self) -> DryRunClient {
+        DryRunClient::new(self.current_url(), self.max_pages)
+    }
+
+    /// Get the number of bytes received by the client and its clones, by endpoint category.
+    pub fn bandwidth_used(&self) -> BandwidthUsage {
+        self.bandwidth.usage()
+    }
+
+    /// Get the number of bytes received by the client and its clones, in all endpoint
+    /// categories.
+    ///
+    /// This is approximate: headers aren't counted, and bodies are counted with the size
+    /// announced in `Content-Length`, or their decompressed size when it isn't announced.
+    pub fn bytes_downloaded(&self) -> u64 {
+        self.bandwidth.usage().total()
+    }
+
+    /// Get the number of bytes sent by the client and its clones in the body of requests,
+    /// excluding headers.
+    pub fn bytes_uploaded(&self) -> u64 {
+        self.bandwidth.uploaded()
+    }
+
+    /// Start a new operation, resetting the bytes counted against the per-operation limit of
+    /// the [`BandwidthBudget`](crate::BandwidthBudget), e.g. before each sync of a wallet.
+    pub fn start_bandwidth_operation(&self) {
+        self.bandwidth.start_operation()
+    }
+
+    /// Get the number of requests served from the HTTP cache after the server confirmed the
+    /// cached response was still valid.
+    pub fn conditional_hits(&self) -> u64 {
+        self.http_cache
+            .as_ref()
+            .map(|cache| cache.hits())
+            .unwrap_or_default()
+    }
+
+    /// Get the underlying base URL, as the client was built with.
+    ///
+    /// After a [`AsyncClient::set_base_url`], requests are made to the
+    /// [`AsyncClient::current_url`] instead.
+    pub fn url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get the base URL requests are currently made to, which differs from
+    /// [`AsyncClient::url`] once the client was pointed to another server.
+    pub fn current_url(&self) -> String {
+        self.url.read().expect("poisoned base URL").clone()
+    }
+
+    /// Build the URL of the endpoint at `path` in a single allocation, without copying the base
+    /// URL first.
+    fn endpoint(&self, path: fmt::Arguments<'_>) -> String {
+        let base = self.url.read().expect("poisoned base URL");
+        let mut url = String::with_capacity(base.len() + 160);
+        url.push_str(&base);
+        fmt::Write::write_fmt(&mut url, path).expect("writing to a string can't fail");
+        url
+    }
+
+    /// Get the path of `url` relative to the base URL, or `url` itself if it was built before
+    /// the client was pointed to another server.
+    fn relative_path<'a>(&self, url: &'a str) -> &'a str {
+        let base = self.url.read().expect("poisoned base URL");
+        url.strip_prefix(base.as_str()).unwrap_or(url)
+    }
+
+    /// Point the client, and all of its clones, to another Esplora server.
+    ///
+    /// Requests already in flight complete against the previous server, while requests made
+    /// after the call use the new one. The URL is validated like the one given to the
+    /// [`Builder`], and cached information about the previous server is discarded: the backend
+    /// info, block hashes, chain tip, prefetched values and the contents of the read-through
+    /// [`Cache`].
+    pub fn set_base_url(&self, url: String) -> Result<(), Error> {
+        let url = normalize_base_url(&url)?;
+        let previous = std::mem::replace(&mut *self.url.write().expect("poisoned base URL"), url);
+        info!(
+            "switched Esplora base URL from {} to {}",
+            previous,
+            self.current_url()
+        );
+
+        *self.backend.lock().expect("poisoned backend info") = None;
+        self.anchors
+            .lock()
+            .expect("poisoned block hash cache")
+            .clear();
+        if let Some(block_hashes) = &self.block_hashes {
+            block_hashes
+                .lock()
+                .expect("poisoned block hash cache")
+                .clear();
+        }
+        self.tip_height.clear();
+        self.warm.clear();
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+        Ok(())
+    }
+
+    /// Identify the software and version of the Esplora server.
+    ///
+    /// This is a best-effort heuristic probing a few discriminating endpoints and response
+    /// headers; servers which can't be identified are reported as [`BackendFlavor::Unknown`].
+    /// The result is cached after the first call.
+    ///
+    /// [`BackendFlavor::Unknown`]: crate::BackendFlavor::Unknown
+    pub async fn backend_info(&self) -> Result<BackendInfo, Error> {
+        let cached = self.backend.lock().expect("poisoned backend info").clone();
+        if let Some(info) = cached {
+            return Ok(info);
+        }
+
+        let url = self.endpoint(format_args!("/v1/difficulty-adjustment"));
+        let difficulty_adjustment = match self.get_with_retry(&url).await {
+            Ok(resp) => resp.status().is_success(),
+            Err(_) => false,
+        };
+        let url = self.endpoint(format_args!("/mempool/recent"));
+        let (recent_mempool, software) = match self.get_with_retry(&url).await {
+            Ok(resp) => {
+                let headers = resp.headers();
+                let software = headers
+                    .get("X-Powered-By")
+                    .or_else(|| headers.get("Server"))
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_owned);
+                let recent_mempool =
+                    resp.status().is_success() && resp.json::<Vec<MempoolRecent>>().await.is_ok();
+                (recent_mempool, software)
+            }
+            Err(_) => (false, None),
+        };
+        let info = BackendInfo::identify(
+            &self.current_url(),
+            difficulty_adjustment,
+            recent_mempool,
+            software.as_deref(),
+        );
+
+        *self.backend.lock().expect("poisoned backend info") = Some(info.clone());
+        Ok(info)
+    }
+
+    /// Get a snapshot of the effective client configuration.
+    pub fn config(&self) -> ClientConfig {
+        ClientConfig {
+            base_url: self.current_url(),
+            proxy: self.proxy.clone(),
+            timeout: self.timeout,
+            max_pages: self.max_pages,
+            max_retries: self.retry.max_retries,
+            base_backoff: self.retry.base_backoff,
+            redirect_policy: self.redirect_policy,
+            tip_cache_ttl: self.tip_height.ttl,
+            max_concurrency: self.max_concurrency,
+            coalesce_requests: self.inflight.is_some(),
+            validate_responses: self.block_hashes.is_some(),
+            verify_txid: self.verify_txid,
+            fee_fallback: self.fee_fallback.clone(),
+        }
+    }
+
+    /// Get the underlying [`Client`].
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Get the [`Sleeper`] the client waits with.
+    #[cfg(feature = "chaos")]
+    pub(crate) fn sleeper(&self) -> &dyn Sleeper {
+        &*self.sleeper
+    }
+
+    /// Shut the client down, consuming it.
+    ///
+    /// The client doesn't spawn background tasks: block prefetchers stop as soon as their
+    /// stream is dropped, and warm-ups run within the task awaiting them. Shutting down forgets
+    /// the in-flight requests shared with clones, so that no new caller joins them, and drops
+    /// the connection pool, which is closed once the last clone of the client is gone. The
+    /// clones keep accepting requests, see [`AsyncClient::shutdown_gracefully`] to stop them.
+    pub fn shutdown(self) {
+        self.forget_inflight();
+    }
+
+    /// Shut the client and its clones down, waiting for their in-flight requests to complete
+    /// for at most the grace period set with [`Builder::shutdown_grace`].
+    ///
+    /// Requests made after the shutdown began, including the pages of block streams and the
+    /// refreshes of a [`MempoolTracker`](crate::MempoolTracker), fail with
+    /// [`Error::ClientClosed`], and [`AsyncClient::watch_chain`] streams end. Shutting down an
+    /// already shut down client only waits for the requests still in flight. Returns whether
+    /// all of them completed within the grace period.
+    pub async fn shutdown_gracefully(&self) -> bool {
+        self.lifecycle.close();
+        let drain = async {
+            while !self.lifecycle.is_idle() {
+                self.sleeper.sleep(SHUTDOWN_POLL_INTERVAL).await;
+            }
+        };
+        let idle = with_timeout(&*self.sleeper, self.shutdown_grace, drain)
+            .await
+            .is_some();
+        self.forget_inflight();
+        idle
+    }
+
+    /// Forgets the in-flight requests shared with clones, which are then only kept alive by
+    /// their callers.
+    fn forget_inflight(&self) {
+        if let Some(inflight) = &self.inflight {
+            inflight
+                .0
+                .lock()
+                .expect("poisoned in-flight request registry")
+                .clear();
+        }
+    }
+}
+
+/// Additive-increase/multiplicative-decrease controller of the number of concurrent requests.
+#[derive(Debug)]
+struct Aimd {
+    limit: usize,
+    max: usize,
+    successes: usize,
+}
+
+impl Aimd {
+    fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Aimd {
+            limit: (max / 2).max(1),
+            max,
+            successes: 0,
+        }
+    }
+
+    fn limit(&self) -> usize {
+        self.limit
+    }
+
+    fn on_success(&mut self) {
+        self.successes += 1;
+        if self.successes >= self.limit {
+            self.limit = (self.limit + 1).min(self.max);
+            self.successes = 0;
+        }
+    }
+
+    fn on_throttled(&mut self) {
+        self.limit = (self.limit / 2).max(1);
+        self.successes = 0;
+    }
+}
+
+fn is_throttled(error: &Error) -> bool {
+    match error {
+        Error::HttpResponse { status, .. } => *status == 429,
+        Error::Reqwest(e) => e.status() == Some(StatusCode::TOO_MANY_REQUESTS),
+        _ => false,
+    }
+}
+
+/// Parses the body of `resp`, a JSON array of transactions, chunk by chunk as they arrive.
+fn stream_txs(resp: Response) -> impl Stream<Item = Result<crate::Tx, Error>> + Send + 'static {
+    let state = (Some(resp), TxArrayParser::default(), VecDeque::new());
+    stream::unfold(state, |(mut resp, mut parser, mut parsed)| async move {
+        loop {
+            if let Some(item) = parsed.pop_front() {
+                return Some((item, (resp, parser, parsed)));
+            }
+            let chunk = resp.as_mut()?.chunk().await;
+            match chunk {
+                Ok(Some(chunk)) => {
+                    if !parser.feed(&chunk, &mut parsed) {
+                        resp = None;
+                    }
+                }
+                Ok(None) => {
+                    resp = None;
+                    parser.finish(&mut parsed);
+                }
+                Err(err) => {
+                    resp = None;
+                    parsed.push_back(Err(err.into()));
+                }
+            }
+        }
+    })
+}
+
+/// Turns error statuses into [`Error::HttpResponse`], classifying the error reported by the
+/// server in the response body.
+async fn error_for_status(mut resp: Response) -> Result<Response, Error> {
+    let status = resp.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return Ok(resp);
+    }
+    let request_id = request_id_of(&resp);
+    let mut body = vec![];
+    while body.len() < ERROR_BODY_LIMIT {
+        match resp.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    body.truncate(ERROR_BODY_LIMIT);
+    let body = String::from_utf8_lossy(&body);
+    Err(Error::http_response(status.as_u16(), &body).with_request_id(request_id))
+}
+
+/// Id of the request a response answers, attached to it so that the error status it may have
+/// can be reported along with the id.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+struct RequestId(String);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn tag_response(resp: &mut Response, request_id: Option<String>) {
+    if let Some(id) = request_id {
+        resp.extensions_mut().insert(RequestId(id));
+    }
+}
+
+// wasm responses have no extensions to carry the id
+#[cfg(target_arch = "wasm32")]
+fn tag_response(_resp: &mut Response, _request_id: Option<String>) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn request_id_of(resp: &Response) -> Option<String> {
+    resp.extensions().get::<RequestId>().map(|id| id.0.clone())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn request_id_of(_resp: &Response) -> Option<String> {
+    None
+}
+
+fn is_status_not_found(status: StatusCode) -> bool {
+    status == 404
+}
+
+/// Response to a GET request read whole, along with the id sent with the request, if any.
+#[derive(Clone, Debug)]
+struct Fetched {
+    status: StatusCode,
+    body: Vec<u8>,
+    request_id: Option<String>,
+}
+
+impl Fetched {
+    /// Error reported by the server, for a response with an error status.
+    fn error(&self) -> Error {
+        Error::http_response(self.status.as_u16(), &String::from_utf8_lossy(&self.body))
+            .with_request_id(self.request_id.clone())
+    }
+
+    /// Read the body of the response to `path` as text if the request succeeded.
+    fn into_text(self, path: &str) -> Result<String, Error> {
+        if !self.status.is_success() {
+            return Err(self.error());
+        }
+        text_body(path, self.body)
+    }
+}
+
+/// Tag `request` with `request_id`, unless ids are disabled.
+fn identify(request: RequestBuilder, request_id: Option<&str>) -> RequestBuilder {
+    match request_id {
+        Some(id) => request.header(REQUEST_ID_HEADER, id),
+        None => request,
+    }
+}
+
+/// Read the `body` of a response to `path` as text, failing with [`Error::InvalidUtf8`] if it
+/// isn't UTF-8 rather than with an error parsing it later.
+fn text_body(path: &str, body: Vec<u8>) -> Result<String, Error> {
+    String::from_utf8(body).map_err(|_| Error::InvalidUtf8 {
+        path: path.to_owned(),
+    })
+}
+
+/// Translates a [`RedirectPolicy`] into the `reqwest` one, reporting refused redirects as
+/// [`BlockedRedirect`] errors.
+///
+/// `reqwest` itself takes care of not forwarding credentials and cookies to other origins.
+#[cfg(not(target_arch = "wasm32"))]
+fn redirect_policy(policy: RedirectPolicy) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        let from = attempt
+            .previous()
+            .last()
+            .expect("a redirect follows a previous request");
+        let same_origin = from.origin() == attempt.url().origin();
+        if policy.allows(attempt.previous().len() - 1, same_origin) {
+            return attempt.follow();
+        }
+        let blocked = BlockedRedirect {
+            from: from.to_string(),
+            to: attempt.url().to_string(),
+        };
+        attempt.error(blocked)
+    })
+}
+
+/// Extracts a redirect refused by the [`RedirectPolicy`] from a `reqwest` error.
+fn blocked_redirect(error: &reqwest::Error) -> Option<Error> {
+    let blocked = std::error::Error::source(error)?.downcast_ref::<BlockedRedirect>()?;
+    Some(redirect_error(blocked.clone()))
+}
+
+fn into_error(error: reqwest::Error) -> Error {
+    blocked_redirect(&error).unwrap_or(Error::Reqwest(error))
+}
+
+/// Enforces the [`RedirectPolicy`] on the response to a request to `requested`.
+///
+/// `reqwest` enforces the policy as it follows redirects, except on wasm32, where the browser
+/// follows them before the client sees the response. There, a response from a URL the policy
+/// doesn't allow redirecting to is rejected once received, as if it took a single redirect.
+fn check_redirect(
+    policy: RedirectPolicy,
+    requested: &Url,
+    resp: &Response,
+) -> Result<(), BlockedRedirect> {
+    let to = resp.url();
+    if cfg!(not(target_arch = "wasm32")) || to == requested {
+        return Ok(());
+    }
+    if policy.allows(0, requested.origin() == to.origin()) {
+        return Ok(());
+    }
+    Err(BlockedRedirect {
+        from: requested.to_string(),
+        to: to.to_string(),
+    })
+}
+
+fn redirect_error(blocked: BlockedRedirect) -> Error {
+    Error::RedirectBlocked {
+        from: blocked.from,
+        to: blocked.to,
+    }
+}
+
+/// Failure of a fetch, which is [`Clone`] for its result to be shared between the callers of
+/// coalesced requests.
+#[derive(Clone, Debug)]
+enum FetchError {
+    Reqwest(Arc<reqwest::Error>),
+    Redirect(BlockedRedirect),
+    BandwidthExceeded { used: u64, budget: u64 },
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(error: reqwest::Error) -> Self {
+        FetchError::Reqwest(Arc::new(error))
+    }
+}
+
+impl FetchError {
+    fn into_error(self) -> Error {
+        match self {
+            FetchError::Reqwest(error) => match Arc::try_unwrap(error) {
+                Ok(error) => into_error(error),
+                Err(error) => blocked_redirect(&error).unwrap_or(Error::SharedReqwest(error)),
+            },
+            FetchError::Redirect(blocked) => redirect_error(blocked),
+            FetchError::BandwidthExceeded { used, budget } => {
+                Error::BandwidthExceeded { used, budget }
+            }
+        }
+    }
+}
+
+/// Run `fetch` again, with exponential backoff, while its response should be retried according
+/// to `retry`, returning the last response along with the [`RequestTrace`] of the retries.
+async fn fetch_with_retry<F, Fut>(
+    retry: &Retry,
+    sleeper: &dyn Sleeper,
+    fetch: F,
+) -> Result<(StatusCode, Vec<u8>, RequestTrace), FetchError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<(StatusCode, Vec<u8>), FetchError>>,
+{
+    let mut trace = RequestTrace::default();
+    loop {
+        let (status, body) = fetch().await?;
+        let context = RetryContext {
+            status: status.as_u16(),
+            attempt: trace.retries,
+            body: Some(&body),
+        };
+        if !retry.should_retry(&context) {
+            return Ok((status, body, trace));
+        }
+        debug!("retrying request after status {}", status);
+        let delay = retry.delay(trace.retries, Some(&body));
+        sleeper.sleep(delay).await;
+        trace.retries += 1;
+        trace.delay += delay;
+    }
+}
+
+fn checked_body_size(fetched: Fetched) -> Result<Fetched, Error> {
+    if fetched.body.len() > BYTES_LIMIT {
+        return Err(io::Error::new(io::ErrorKind::Other, "response too big").into());
+    }
+    Ok(fetched)
+}
+
+/// Send `request` and read its body, stopping one byte past [`BYTES_LIMIT`] so that an
+/// oversized response is never buffered whole and gets rejected by [`checked_body_size`].
+///
+/// The response is accounted to `bandwidth` as [`AsyncClient::send_traced`] does, by the
+/// length it announces, or by the length read when it announces none.
+async fn fetch_bytes(
+    request: RequestBuilder,
+    redirect_policy: RedirectPolicy,
+    bandwidth: &BandwidthMeter,
+) -> Result<(StatusCode, Vec<u8>), FetchError> {
+    if let Some((used, budget)) = bandwidth.exceeded() {
+        return Err(FetchError::BandwidthExceeded { used, budget });
+    }
+    let (client, request) = request.build_split();
+    let request = request?;
+    let requested = request.url().clone();
+    let mut resp = client.execute(request).await?;
+    check_redirect(redirect_policy, &requested, &resp).map_err(FetchError::Redirect)?;
+    let status = resp.status();
+    let category = EndpointCategory::of_url(requested.as_str());
+    let length = resp.content_length();
+    let mut body = vec![];
+    while let Some(chunk) = resp.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > BYTES_LIMIT {
+            body.truncate(BYTES_LIMIT + 1);
+            break;
+        }
+    }
+    bandwidth.debit(category, length.unwrap_or(body.len() as u64));
+    Ok((status, body))
+}
+
+/// Read the body of `resp` for the retry predicate to peek at, if there is one, failing if it
+/// is larger than [`BYTES_LIMIT`].
+#[cfg(not(target_arch = "wasm32"))]
+async fn peek_body(retry: &Retry, resp: &mut Response) -> Result<Option<Vec<u8>>, Error> {
+    if retry.predicate.is_none() {
+        return Ok(None);
+    }
+    let mut body = vec![];
+    while let Some(chunk) = resp.chunk().await.map_err(into_error)? {
+        body.extend_from_slice(&chunk);
+        if body.len() > BYTES_LIMIT {
+            return Err(io::Error::new(io::ErrorKind::Other, "response too big").into());
+        }
+    }
+    Ok(Some(body))
+}
+
+// wasm responses can't be rebuilt from parts once read, so the predicate doesn't see their body
+#[cfg(target_arch = "wasm32")]
+async fn peek_body(_retry: &Retry, _resp: &mut Response) -> Result<Option<Vec<u8>>, Error> {
+    Ok(None)
+}
+
+/// Rebuild a response whose body was peeked at, for it to be handed over as if it wasn't.
+#[cfg(not(target_arch = "wasm32"))]
+fn rebuild_response(resp: &Response, body: Vec<u8>) -> Response {
+    use reqwest::ResponseBuilderExt;
+
+    let mut builder = http::Response::builder()
+        .status(resp.status())
+        .version(resp.version())
+        .url(resp.url().clone());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = resp.headers().clone();
+    }
+    let rebuilt = builder
+        .body(body)
+        .expect("parts of a valid response are valid");
+    Response::from(rebuilt)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn rebuild_response(_resp: &Response, _body: Vec<u8>) -> Response {
+    unreachable!("bodies aren't peeked at on wasm")
+}
+
+/// Read the body of `resp`, failing if the connection breaks while reading it or if it is
+/// larger than [`BYTES_LIMIT`].
+async fn into_bytes(mut resp: Response) -> Result<Vec<u8>, Error> {
+    let mut buf: Vec<u8> = vec![];
+    while let Some(chunk) = resp.chunk().await.map_err(into_error)? {
+        buf.extend_from_slice(&chunk);
+        if buf.len() > BYTES_LIMIT {
+            return Err(
+                io::Error::new(io::ErrorKind::Other, "response too big for into_bytes").into(),
+            );
+        }
+    }
+    Ok(buf)
+}
+
+/// Get the statuses of `txs` along with their txid, to anchor them.
+fn tx_statuses(txs: &mut [crate::Tx]) -> Vec<(Txid, &mut TxStatus)> {
+    txs.iter_mut().map(|tx| (tx.txid, &mut tx.status)).collect()
+}
+
+/// Fills in the block hash of the confirmed transactions of the block with `block_hash`
+/// lacking it.
+fn anchor_to_block(txs: &mut [crate::Tx], block_hash: &BlockHash) {
+    for tx in txs {
+        if tx.status.confirmed && tx.status.block_hash.is_none() {
+            tx.status.block_hash = Some(*block_hash);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::JitterPolicy;
+
+    #[derive(Debug)]
+    struct NoSleep;
+
+    impl Sleeper for NoSleep {
+        fn sleep(&self, _duration: Duration) -> BoxFuture<'static, ()> {
+            future::ready(()).boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn fetched_retries_are_traced() {
+        let retry = Retry::new(
+            3,
+            Duration::from_millis(10),
+            None,
+            None,
+            JitterPolicy::None,
+            Some(0),
+        );
+        let attempts = AtomicUsize::new(0);
+        let fetch = |statuses: &'static [StatusCode]| {
+            attempts.store(0, Ordering::SeqCst);
+            let attempts = &attempts;
+            move || {
+                let status = statuses[attempts.fetch_add(1, Ordering::SeqCst)];
+                future::ready(Ok((status, vec![])))
+            }
+        };
+
+        let statuses = &[
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::OK,
+        ];
+        let (status, _, trace) = fetch_with_retry(&retry, &NoSleep, fetch(statuses))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            trace,
+            RequestTrace {
+                retries: 2,
+                delay: Duration::from_millis(10 + 20),
+            }
+        );
+
+        let statuses = &[StatusCode::NOT_FOUND];
+        let (status, _, trace) = fetch_with_retry(&retry, &NoSleep, fetch(statuses))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(trace, RequestTrace::default());
+
+        let statuses = &[StatusCode::SERVICE_UNAVAILABLE; 4];
+        let (status, _, trace) = fetch_with_retry(&retry, &NoSleep, fetch(statuses))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(trace.retries, 3);
+    }
+
+    #[test]
+    fn aimd_grows_and_halves() {
+        let mut aimd = Aimd::new(8);
+        assert_eq!(aimd.limit(), 4);
+        for _ in 0..4 {
+            aimd.on_success();
+        }
+        assert_eq!(aimd.limit(), 5);
+        aimd.on_throttled();
+        assert_eq!(aimd.limit(), 2);
+        aimd.on_throttled();
+        aimd.on_throttled();
+        assert_eq!(aimd.limit(), 1);
+        for _ in 0..100 {
+            aimd.on_success();
+        }
+        assert_eq!(aimd.limit(), 8);
+    }
 }
@@ -0,0 +1,585 @@
+//! Distribution of the requests of an application across replicas of an Esplora server.
+//!
+//! A [`BalancedClient`] wraps a [`BlockingClient`] or an [`AsyncClient`] per replica and
+//! mirrors their read methods, sending each read to a replica picked by its
+//! [`LoadBalancingPolicy`]. Replicas failing repeatedly are skipped for a while, and broadcasts
+//! can be sent to all the replicas.
+//!
+//! ```no_run
+//! # #[cfg(feature = "async")]
+//! # async fn sync() -> Result<(), esplora::Error> {
+//! use esplora::balancer::{BalancedClient, LoadBalancingPolicy};
+//! use esplora::Builder;
+//!
+//! let replicas = ["http://10.0.0.1:3002", "http://10.0.0.2:3002", "http://10.0.0.3:3002"]
+//!     .iter()
+//!     .map(|url| Builder::new(url).build_async())
+//!     .collect::<Result<Vec<_>, _>>()?;
+//! let client = BalancedClient::new(replicas, LoadBalancingPolicy::LowestLatency)
+//!     .fan_out_broadcast(true);
+//! let height = client.height().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use bpstd::{BlockHash, BlockHeader, ScriptPubkey, Tx, Txid};
+
+use crate::rng::SplitMix64;
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+#[cfg(feature = "blocking")]
+use crate::BlockingClient;
+use crate::{
+    BackendInfo, BlockStatus, BlockSummary, Error, ErrorKind, MerkleProof, OutputStatus, TxStatus,
+    Utxo,
+};
+
+/// Number of consecutive failed requests after which a replica is considered unhealthy.
+pub const UNHEALTHY_AFTER_FAILURES: u32 = 3;
+
+/// Time during which an unhealthy replica is skipped, after which it gets requests again.
+pub const UNHEALTHY_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Weight of the latest request in the rolling latency estimate of a replica.
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// Number of reads after which the [`LoadBalancingPolicy::LowestLatency`] policy sends one to
+/// the replica whose latency was measured the longest ago, to refresh its estimate.
+pub const LATENCY_PROBE_INTERVAL: usize = 16;
+
+/// Policy picking the replica a read is sent to, among the healthy ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadBalancingPolicy {
+    /// Each replica in turn.
+    RoundRobin,
+    /// Replicas drawn at random, each with a probability proportional to its weight, given in
+    /// the order of the replicas. Replicas without a weight are never picked unless all the
+    /// weighted ones are unhealthy.
+    WeightedRandom(Vec<u32>),
+    /// Replica with the lowest rolling latency estimate, replicas without an estimate being
+    /// tried first. Every [`LATENCY_PROBE_INTERVAL`] reads, the replica whose estimate is the
+    /// oldest is picked instead, so that a replica which got faster is noticed.
+    LowestLatency,
+}
+
+/// Requests made to a replica and its health.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplicaStats {
+    /// Requests sent to the replica.
+    pub requests: u64,
+    /// Requests which failed with a transport error, a server error or a rate limit.
+    pub failures: u64,
+    /// Rolling estimate of the latency of the replica, once a request succeeded.
+    pub latency: Option<Duration>,
+    /// Whether the replica gets requests, rather than being skipped after failing repeatedly.
+    pub healthy: bool,
+}
+
+/// Outcome of a broadcast, by replica.
+#[derive(Debug)]
+pub struct BroadcastReport {
+    /// Result of the broadcast by each replica it was sent to, along with the index of the
+    /// replica.
+    pub results: Vec<(usize, Result<(), Error>)>,
+}
+
+impl BroadcastReport {
+    /// Get the number of replicas which accepted the transaction.
+    pub fn accepted(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|(_, result)| result.is_ok())
+            .count()
+    }
+
+    /// Get the errors of the replicas which didn't accept the transaction, along with their
+    /// index.
+    pub fn errors(&self) -> impl Iterator<Item = (usize, &Error)> {
+        self.results
+            .iter()
+            .filter_map(|(index, result)| result.as_ref().err().map(|error| (*index, error)))
+    }
+
+    /// Succeeds with the report if any replica accepted the transaction, failing otherwise
+    /// with [`Error::BroadcastRejected`] carrying it.
+    fn into_result(self) -> Result<Self, Error> {
+        if self.accepted() > 0 {
+            return Ok(self);
+        }
+        Err(Error::BroadcastRejected(self))
+    }
+}
+
+impl Display for BroadcastReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (position, (index, error)) in self.errors().enumerate() {
+            if position > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "replica {}: {}", index, error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `error` is a failure of the replica, rather than a rejection of the request which
+/// any replica would have made.
+fn is_replica_failure(error: &Error) -> bool {
+    match error.kind() {
+        ErrorKind::Transport => true,
+        ErrorKind::Http => !matches!(error.status(), Some(status) if status != 429 && status < 500),
+        _ => false,
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Health {
+    requests: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    latency: Option<Duration>,
+    measured_at: Option<Instant>,
+    unhealthy_until: Option<Instant>,
+}
+
+impl Health {
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until.map_or(true, |until| now >= until)
+    }
+}
+
+/// Replica selection and health tracking, shared between the clones of a [`BalancedClient`].
+#[derive(Debug)]
+struct Balancer {
+    policy: LoadBalancingPolicy,
+    health: Mutex<Vec<Health>>,
+    rng: Mutex<SplitMix64>,
+    next: AtomicUsize,
+}
+
+impl Balancer {
+    fn new(policy: LoadBalancingPolicy, replicas: usize) -> Self {
+        Balancer {
+            policy,
+            health: Mutex::new(vec![Health::default(); replicas]),
+            rng: Mutex::new(SplitMix64::from_entropy()),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn health(&self) -> MutexGuard<'_, Vec<Health>> {
+        self.health.lock().expect("poisoned replica health")
+    }
+
+    /// Picks the replica the next read is sent to.
+    fn pick(&self) -> usize {
+        let health = self.health();
+        let now = Instant::now();
+        let mut candidates = (0..health.len())
+            .filter(|index| health[*index].is_healthy(now))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            // better trying an unhealthy replica than failing without a request
+            candidates = (0..health.len()).collect();
+        }
+
+        match &self.policy {
+            LoadBalancingPolicy::RoundRobin => {
+                let turn = self.next.fetch_add(1, Ordering::Relaxed);
+                candidates[turn % candidates.len()]
+            }
+            LoadBalancingPolicy::WeightedRandom(weights) => {
+                let weight = |index: usize| u64::from(weights.get(index).copied().unwrap_or(0));
+                let total = candidates.iter().map(|index| weight(*index)).sum::<u64>();
+                if total == 0 {
+                    let draw = self
+                        .rng
+                        .lock()
+                        .expect("poisoned balancer generator")
+                        .next_u64();
+                    return candidates[(draw % candidates.len() as u64) as usize];
+                }
+                let mut draw = self
+                    .rng
+                    .lock()
+                    .expect("poisoned balancer generator")
+                    .next_u64()
+                    % total;
+                for index in &candidates {
+                    if draw < weight(*index) {
+                        return *index;
+                    }
+                    draw -= weight(*index);
+                }
+                unreachable!("draw is below the total weight")
+            }
+            LoadBalancingPolicy::LowestLatency => {
+                let turn = self.next.fetch_add(1, Ordering::Relaxed);
+                // `None` sorts first, so that unmeasured replicas are tried before the others
+                let pick = if turn % LATENCY_PROBE_INTERVAL == LATENCY_PROBE_INTERVAL - 1 {
+                    candidates
+                        .iter()
+                        .min_by_key(|index| health[**index].measured_at)
+                } else {
+                    candidates
+                        .iter()
+                        .min_by_key(|index| health[**index].latency)
+                };
+                *pick.expect("there is at least one replica")
+            }
+        }
+    }
+
+    /// Records the outcome of a request to the replica at `index`, which took `elapsed`.
+    fn record<T>(&self, index: usize, elapsed: Duration, result: &Result<T, Error>) {
+        let mut health = self.health();
+        let replica = &mut health[index];
+        replica.requests += 1;
+        match result {
+            Err(error) if is_replica_failure(error) => {
+                replica.failures += 1;
+                replica.consecutive_failures += 1;
+                if replica.consecutive_failures >= UNHEALTHY_AFTER_FAILURES {
+                    replica.unhealthy_until = Some(Instant::now() + UNHEALTHY_COOLDOWN);
+                }
+            }
+            _ => {
+                replica.consecutive_failures = 0;
+                replica.unhealthy_until = None;
+                replica.latency = Some(match replica.latency {
+                    Some(latency) => {
+                        latency.mul_f64(1.0 - LATENCY_SMOOTHING)
+                            + elapsed.mul_f64(LATENCY_SMOOTHING)
+                    }
+                    None => elapsed,
+                });
+                replica.measured_at = Some(Instant::now());
+            }
+        }
+    }
+
+    fn stats(&self) -> Vec<ReplicaStats> {
+        let now = Instant::now();
+        self.health()
+            .iter()
+            .map(|health| ReplicaStats {
+                requests: health.requests,
+                failures: health.failures,
+                latency: health.latency,
+                healthy: health.is_healthy(now),
+            })
+            .collect()
+    }
+}
+
+/// Client spreading its requests across replicas of an Esplora server, each accessed through a
+/// [`BlockingClient`] or an [`AsyncClient`].
+///
+/// Reads are sent to a single replica picked by the [`LoadBalancingPolicy`], without being
+/// retried on another one if it fails. Clones share the health of the replicas.
+#[derive(Clone, Debug)]
+pub struct BalancedClient<C> {
+    replicas: Vec<C>,
+    fan_out_broadcast: bool,
+    balancer: Arc<Balancer>,
+}
+
+impl<C> BalancedClient<C> {
+    /// Spread requests across `replicas` according to `policy`.
+    ///
+    /// # Panics
+    ///
+    /// If `replicas` is empty.
+    pub fn new(replicas: Vec<C>, policy: LoadBalancingPolicy) -> Self {
+        assert!(
+            !replicas.is_empty(),
+            "a balanced client needs at least one replica"
+        );
+        BalancedClient {
+            balancer: Arc::new(Balancer::new(policy, replicas.len())),
+            replicas,
+            fan_out_broadcast: false,
+        }
+    }
+
+    /// Seed the generator drawing replicas with the [`LoadBalancingPolicy::WeightedRandom`]
+    /// policy, so that the draws can be reproduced; by default, each client is seeded
+    /// differently
+    pub fn seed(self, seed: u64) -> Self {
+        *self
+            .balancer
+            .rng
+            .lock()
+            .expect("poisoned balancer generator") = SplitMix64::new(seed);
+        self
+    }
+
+    /// Send broadcasts to all the replicas, rather than to the one picked by the policy
+    pub fn fan_out_broadcast(mut self, fan_out: bool) -> Self {
+        self.fan_out_broadcast = fan_out;
+        self
+    }
+
+    /// Get the clients of the replicas.
+    pub fn replicas(&self) -> &[C] {
+        &self.replicas
+    }
+
+    /// Get the number of requests sent to each replica and its health, in the order of the
+    /// replicas.
+    pub fn stats(&self) -> Vec<ReplicaStats> {
+        self.balancer.stats()
+    }
+}
+
+/// Mirrors read methods of a client, sending each call to the replica picked by the policy.
+macro_rules! balanced_methods {
+    ({ blocking } $($(#[$attr:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty
+        $(where $txid:ident => $not_found:expr)? $(=> $method:ident $request:expr)?;)+) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret, Error> {
+                let index = self.balancer.pick();
+                let start = Instant::now();
+                let result = self.replicas[index].$name($($arg),*);
+                self.balancer.record(index, start.elapsed(), &result);
+                result
+            }
+        )+
+    };
+    ({ async } $($(#[$attr:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty
+        $(where $txid:ident => $not_found:expr)? $(=> $method:ident $request:expr)?;)+) => {
+        $(
+            $(#[$attr])*
+            pub async fn $name(&self $(, $arg: $ty)*) -> Result<$ret, Error> {
+                let index = self.balancer.pick();
+                let start = Instant::now();
+                let result = self.replicas[index].$name($($arg),*).await;
+                self.balancer.record(index, start.elapsed(), &result);
+                result
+            }
+        )+
+    };
+}
+
+#[cfg(feature = "blocking")]
+impl BalancedClient<BlockingClient> {
+    client_endpoints!(balanced_methods! { { blocking } } reads composite);
+
+    /// Broadcast a [`Tx`] to the replica picked by the policy, or to all of them one after the
+    /// other when fanning out, succeeding if any of them accepted it.
+    ///
+    /// If none did, fails with [`Error::BroadcastRejected`] carrying the error of each replica.
+    pub fn broadcast(&self, tx: &Tx) -> Result<BroadcastReport, Error> {
+        let indexes = if self.fan_out_broadcast {
+            (0..self.replicas.len()).collect()
+        } else {
+            vec![self.balancer.pick()]
+        };
+        let results = indexes
+            .into_iter()
+            .map(|index| {
+                let start = Instant::now();
+                let result = self.replicas[index].broadcast(tx);
+                self.balancer.record(index, start.elapsed(), &result);
+                (index, result)
+            })
+            .collect();
+        BroadcastReport { results }.into_result()
+    }
+}
+
+#[cfg(feature = "async")]
+impl BalancedClient<AsyncClient> {
+    client_endpoints!(balanced_methods! { { async } } reads composite);
+
+    /// Broadcast a [`Tx`] to the replica picked by the policy, or to all of them concurrently
+    /// when fanning out, succeeding if any of them accepted it.
+    ///
+    /// If none did, fails with [`Error::BroadcastRejected`] carrying the error of each replica.
+    pub async fn broadcast(&self, tx: &Tx) -> Result<BroadcastReport, Error> {
+        let indexes = if self.fan_out_broadcast {
+            (0..self.replicas.len()).collect()
+        } else {
+            vec![self.balancer.pick()]
+        };
+        let results = futures::future::join_all(indexes.into_iter().map(|index| async move {
+            let start = Instant::now();
+            let result = self.replicas[index].broadcast(tx).await;
+            self.balancer.record(index, start.elapsed(), &result);
+            (index, result)
+        }))
+        .await;
+        BroadcastReport { results }.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(balancer: &Balancer, index: usize, millis: u64) {
+        balancer.record(index, Duration::from_millis(millis), &Ok::<_, Error>(()));
+    }
+
+    fn fail(balancer: &Balancer, index: usize, error: fn() -> Error) {
+        balancer.record(index, Duration::ZERO, &Err::<(), _>(error()));
+    }
+
+    #[test]
+    fn round_robin_skips_unhealthy_replicas() {
+        let balancer = Balancer::new(LoadBalancingPolicy::RoundRobin, 3);
+        let picks = (0..6).map(|_| balancer.pick()).collect::<Vec<_>>();
+        assert_eq!(picks, [0, 1, 2, 0, 1, 2]);
+
+        for _ in 0..UNHEALTHY_AFTER_FAILURES {
+            fail(&balancer, 1, || Error::DeadlineExceeded);
+        }
+        assert!((0..6).all(|_| balancer.pick() != 1));
+        assert!(!balancer.stats()[1].healthy);
+
+        // with every replica unhealthy, they are all tried anyway
+        for index in [0, 2] {
+            for _ in 0..UNHEALTHY_AFTER_FAILURES {
+                fail(&balancer, index, || Error::DeadlineExceeded);
+            }
+        }
+        let mut picks = (0..3).map(|_| balancer.pick()).collect::<Vec<_>>();
+        picks.sort_unstable();
+        assert_eq!(picks, [0, 1, 2]);
+    }
+
+    #[test]
+    fn weighted_random_follows_the_weights() {
+        let balancer = Balancer::new(LoadBalancingPolicy::WeightedRandom(vec![0, 3, 1]), 3);
+        let mut counts = [0; 3];
+        for _ in 0..4_000 {
+            counts[balancer.pick()] += 1;
+        }
+        assert_eq!(counts[0], 0);
+        assert!(counts[1] > 2 * counts[2], "{:?}", counts);
+    }
+
+    #[test]
+    fn weighted_random_draws_differ_between_balancers() {
+        let draws = |balancer: &Balancer| (0..64).map(|_| balancer.pick()).collect::<Vec<_>>();
+        let policy = LoadBalancingPolicy::WeightedRandom(vec![1, 1]);
+        let first = Balancer::new(policy.clone(), 2);
+        let second = Balancer::new(policy, 2);
+        assert_ne!(draws(&first), draws(&second));
+
+        // unless they are seeded alike
+        for balancer in [&first, &second] {
+            *balancer.rng.lock().unwrap() = SplitMix64::new(7);
+        }
+        assert_eq!(draws(&first), draws(&second));
+    }
+
+    #[test]
+    fn lowest_latency_tries_unmeasured_replicas_first() {
+        let balancer = Balancer::new(LoadBalancingPolicy::LowestLatency, 3);
+        assert_eq!(balancer.pick(), 0);
+        ok(&balancer, 0, 10);
+        assert_eq!(balancer.pick(), 1);
+        ok(&balancer, 1, 5);
+        assert_eq!(balancer.pick(), 2);
+        ok(&balancer, 2, 20);
+        assert_eq!(balancer.pick(), 1);
+    }
+
+    #[test]
+    fn lowest_latency_probes_stale_replicas() {
+        let balancer = Balancer::new(LoadBalancingPolicy::LowestLatency, 2);
+        ok(&balancer, 0, 50);
+        ok(&balancer, 1, 5);
+        let picks = (0..LATENCY_PROBE_INTERVAL)
+            .map(|_| {
+                let index = balancer.pick();
+                ok(&balancer, index, if index == 0 { 1 } else { 5 });
+                index
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(picks.iter().filter(|index| **index == 0).count(), 1);
+        assert_eq!(picks.last(), Some(&0));
+        // the probe noticed the first replica got faster
+        assert!(balancer.stats()[0].latency < Some(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn client_errors_keep_replicas_healthy() {
+        let balancer = Balancer::new(LoadBalancingPolicy::RoundRobin, 1);
+        for _ in 0..UNHEALTHY_AFTER_FAILURES {
+            fail(&balancer, 0, || {
+                Error::http_response(400, "bad-txns-inputs-missingorspent")
+            });
+            fail(&balancer, 0, || {
+                Error::http_response(404, "Transaction not found")
+            });
+            fail(&balancer, 0, || Error::InvalidServerData);
+        }
+        let stats = balancer.stats()[0];
+        assert_eq!(stats.failures, 0);
+        assert!(stats.healthy);
+
+        for status in [500, 503, 429] {
+            let balancer = Balancer::new(LoadBalancingPolicy::RoundRobin, 1);
+            for _ in 0..UNHEALTHY_AFTER_FAILURES {
+                balancer.record(
+                    0,
+                    Duration::ZERO,
+                    &Err::<(), _>(Error::http_response(status, "unavailable")),
+                );
+            }
+            let stats = balancer.stats()[0];
+            assert_eq!(stats.failures, u64::from(UNHEALTHY_AFTER_FAILURES));
+            assert!(!stats.healthy, "status {}", status);
+        }
+    }
+
+    #[test]
+    fn success_restores_health() {
+        let balancer = Balancer::new(LoadBalancingPolicy::RoundRobin, 1);
+        for _ in 0..UNHEALTHY_AFTER_FAILURES {
+            fail(&balancer, 0, || Error::DeadlineExceeded);
+        }
+        assert!(!balancer.stats()[0].healthy);
+        ok(&balancer, 0, 1);
+        let stats = balancer.stats()[0];
+        assert!(stats.healthy);
+        assert_eq!(stats.requests, u64::from(UNHEALTHY_AFTER_FAILURES) + 1);
+    }
+
+    #[test]
+    fn rejected_broadcasts_keep_the_report() {
+        let report = BroadcastReport {
+            results: vec![
+                (0, Err(Error::http_response(400, "txn-mempool-conflict"))),
+                (1, Err(Error::DeadlineExceeded)),
+            ],
+        };
+        let error = report.into_result().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Http);
+        match &error {
+            Error::BroadcastRejected(report) => {
+                assert_eq!(report.accepted(), 0);
+                assert_eq!(
+                    report.errors().map(|(index, _)| index).collect::<Vec<_>>(),
+                    [0, 1]
+                );
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(error.to_string().contains("replica 1: deadline exceeded"));
+
+        let report = BroadcastReport {
+            results: vec![(0, Err(Error::DeadlineExceeded)), (1, Ok(()))],
+        };
+        assert_eq!(report.into_result().unwrap().accepted(), 1);
+    }
+}
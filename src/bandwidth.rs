@@ -0,0 +1,203 @@
+//! Accounting of the data received by a client, and budgets capping it on metered connections.
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::time::Instant;
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use crate::Error;
+
+/// Group of Esplora endpoints data received is accounted to.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum EndpointCategory {
+    /// transactions
+    Tx,
+
+    /// script and address histories
+    History,
+
+    /// blocks
+    Blocks,
+
+    /// other endpoints
+    Other,
+}
+
+impl EndpointCategory {
+    /// Get the category of the endpoint requested with `url`.
+    pub fn of_url(url: &str) -> Self {
+        if url.contains("/scripthash/") || url.contains("/address/") {
+            EndpointCategory::History
+        } else if url.contains("/block/") || url.contains("/blocks") {
+            EndpointCategory::Blocks
+        } else if url.contains("/tx/") || url.ends_with("/tx") {
+            EndpointCategory::Tx
+        } else {
+            EndpointCategory::Other
+        }
+    }
+}
+
+/// Limits on the number of bytes received by a client. No limit is set by default.
+///
+/// Sizes are the ones announced by the server in `Content-Length`, which is the compressed size
+/// of compressed responses, whatever the way the client reads the body. For responses of
+/// unknown length, the size of the body is counted once read by the client, when it is read as
+/// bytes. Each attempt of a retried request is counted.
+///
+/// Once a budget is used up, requests fail with [`crate::Error::BandwidthExceeded`] without being
+/// made. Methods returning the result of each request, like `txs_partial`, keep the results
+/// received before. Broadcasts are never refused, so that a sync using up the budget doesn't
+/// prevent sending a payment.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct BandwidthBudget {
+    /// Maximum number of bytes received during an operation, started with the
+    /// `start_bandwidth_operation` method of the clients.
+    pub per_operation: Option<u64>,
+    /// Maximum number of bytes received during each period of the given duration, starting with
+    /// the first request.
+    pub per_window: Option<(u64, Duration)>,
+}
+
+impl BandwidthBudget {
+    /// Set the maximum number of bytes received during an operation
+    pub fn per_operation(mut self, bytes: u64) -> Self {
+        self.per_operation = Some(bytes);
+        self
+    }
+
+    /// Set the maximum number of bytes received during each `window`
+    pub fn per_window(mut self, bytes: u64, window: Duration) -> Self {
+        self.per_window = Some((bytes, window));
+        self
+    }
+}
+
+/// Number of bytes received by a client, by [`EndpointCategory`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct BandwidthUsage {
+    pub tx: u64,
+    pub history: u64,
+    pub blocks: u64,
+    pub other: u64,
+}
+
+impl BandwidthUsage {
+    /// Get the number of bytes received from the endpoints of `category`.
+    pub fn get(&self, category: EndpointCategory) -> u64 {
+        match category {
+            EndpointCategory::Tx => self.tx,
+            EndpointCategory::History => self.history,
+            EndpointCategory::Blocks => self.blocks,
+            EndpointCategory::Other => self.other,
+        }
+    }
+
+    /// Get the number of bytes received from all the endpoints.
+    pub fn total(&self) -> u64 {
+        self.tx + self.history + self.blocks + self.other
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    fn add(&mut self, category: EndpointCategory, bytes: u64) {
+        let counter = match category {
+            EndpointCategory::Tx => &mut self.tx,
+            EndpointCategory::History => &mut self.history,
+            EndpointCategory::Blocks => &mut self.blocks,
+            EndpointCategory::Other => &mut self.other,
+        };
+        *counter = counter.saturating_add(bytes);
+    }
+}
+
+/// Bytes received by a client and its clones, checked against its budget before each request.
+#[cfg(any(feature = "blocking", feature = "async"))]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BandwidthMeter(Arc<Mutex<MeterState>>);
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+#[derive(Debug, Default)]
+struct MeterState {
+    budget: BandwidthBudget,
+    usage: BandwidthUsage,
+    uploaded: u64,
+    operation: u64,
+    window: Option<(Instant, u64)>,
+}
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+impl BandwidthMeter {
+    pub fn new(budget: BandwidthBudget) -> Self {
+        BandwidthMeter(Arc::new(Mutex::new(MeterState {
+            budget,
+            ..MeterState::default()
+        })))
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, MeterState> {
+        self.0.lock().expect("poisoned bandwidth meter")
+    }
+
+    /// Fails with [`Error::BandwidthExceeded`] if a budget was used up, in which case no further
+    /// request must be made.
+    pub fn check(&self) -> Result<(), Error> {
+        match self.exceeded() {
+            Some((used, budget)) => Err(Error::BandwidthExceeded { used, budget }),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the number of bytes used and the budget of a budget used up, if any.
+    pub fn exceeded(&self) -> Option<(u64, u64)> {
+        let mut state = self.state();
+        if let Some(budget) = state.budget.per_operation {
+            if state.operation >= budget {
+                return Some((state.operation, budget));
+            }
+        }
+        if let Some((budget, window)) = state.budget.per_window {
+            match state.window {
+                Some((start, _)) if start.elapsed() >= window => state.window = None,
+                Some((_, used)) if used >= budget => return Some((used, budget)),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Accounts `bytes` received from an endpoint of `category`.
+    pub fn debit(&self, category: EndpointCategory, bytes: u64) {
+        let mut state = self.state();
+        state.usage.add(category, bytes);
+        state.operation = state.operation.saturating_add(bytes);
+        if let Some((_, window)) = state.budget.per_window {
+            let (start, used) = match state.window {
+                Some((start, used)) if start.elapsed() < window => (start, used),
+                _ => (Instant::now(), 0),
+            };
+            state.window = Some((start, used.saturating_add(bytes)));
+        }
+    }
+
+    /// Accounts `bytes` sent in the body of a request.
+    pub fn upload(&self, bytes: u64) {
+        let mut state = self.state();
+        state.uploaded = state.uploaded.saturating_add(bytes);
+    }
+
+    /// Resets the bytes counted against the budget of an operation.
+    pub fn start_operation(&self) {
+        self.state().operation = 0;
+    }
+
+    pub fn usage(&self) -> BandwidthUsage {
+        self.state().usage
+    }
+
+    pub fn uploaded(&self) -> u64 {
+        self.state().uploaded
+    }
+}
@@ -11,28 +11,115 @@
 
 //! Esplora by way of `ureq` HTTP client.
 
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::io;
 use std::io::Cursor;
+use std::mem;
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+#[cfg(feature = "aws-sigv4")]
+use std::time::SystemTime;
+use std::time::{Duration, Instant};
 
-use bpstd::{BlockHash, ConsensusDecode, ScriptPubkey, Tx, Txid};
+#[cfg(feature = "addresses")]
+use bpstd::{Address, AddressNetwork};
+use bpstd::{BlockHash, BlockHeader, ConsensusDecode, ScriptPubkey, Tx, Txid};
 
+use amplify::hex::{FromHex, ToHex};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace};
 use sha2::{Digest, Sha256};
 
-use ureq::{Agent, Proxy, Response};
+use ureq::{Agent, MiddlewareNext, Proxy, Request, Response};
 
-use crate::{BlockStatus, BlockSummary, Builder, Config, Error, OutputStatus, TxStatus, Utxo};
+use crate::api::{parse_fee_estimates, stream_parse_txs};
+use crate::bandwidth::BandwidthMeter;
+use crate::cache::{is_cacheable, Cache, HttpCache, MemoryCache};
+use crate::headers::{check_header_chain, cumulative_work, Work};
+use crate::lifecycle::{Lifecycle, SHUTDOWN_POLL_INTERVAL};
+use crate::pagination::{next_page_cursor, PageGuard, BLOCK_TXS_PAGE_SIZE};
+#[cfg(feature = "addresses")]
+use crate::parse_address;
+#[cfg(feature = "addresses")]
+use crate::query::QueryParams;
+use crate::request_id::RequestIds;
+use crate::rng::SplitMix64;
+use crate::validation;
+use crate::wallet_history::HistoryMerge;
+use crate::warm_up::{Prefetched, WarmCache};
+use crate::watch::{ChainWatch, WatchStep};
+#[cfg(feature = "aws-sigv4")]
+use crate::SigV4;
+use crate::{
+    dedup_history, diff_utxos, extract_witness_commitment, has_all_fee_targets, normalize_base_url,
+    output_index, parse_input, BackendInfo, BandwidthUsage, BlockCommitments, BlockFeeStats,
+    BlockHashCache, BlockStatus, BlockSummary, Builder, CheckpointUpdate, Checkpoints,
+    ClientConfig, Config, ConfirmationDetails, CursorFamily, DryRunClient, EndpointCategory, Error,
+    ErrorKind, FeeEstimates, FeeFallback, MempoolRecent, MempoolStats, MerkleProof, OutputStatus,
+    PaymentInfo, PrefetchItem, PrefetchSet, RedirectPolicy, RequestIdPolicy, RequestMeta,
+    RequestTrace, Retry, RetryContext, ScriptType, SpendableOpts, SpendableSet, SyncCache,
+    TipCache, TxStatus, Utxo, UtxoDiff, UtxoSnapshot, WarmUpReport, WatchFilter, WatchMatch,
+    WatchedBlock, ANCHOR_CACHE_DEPTH, BROADCAST_POLL_INTERVAL, DEFAULT_BLOCK_VSIZE,
+    DEFAULT_MAX_PAGES, DEFAULT_SHUTDOWN_GRACE, DEFAULT_WARM_UP_BUDGET, ERROR_BODY_LIMIT,
+    MAX_ANCESTOR_DEPTH, MAX_FEE_STATS_TXS, MAX_PREALLOCATED_BLOCKS, REQUEST_ID_HEADER,
+};
 
 #[derive(Debug, Clone)]
 pub struct BlockingClient {
-    url: String,
+    /// Base URL the client was built with.
+    base_url: String,
+    /// Base URL requests are made to, shared by all the clones.
+    url: Arc<RwLock<String>>,
+    proxy: Option<String>,
+    timeout: Option<u64>,
+    max_pages: usize,
+    fee_fallback: Option<FeeFallback>,
+    /// Last fee estimates, requested again conditionally.
+    fee_cache: Option<Arc<HttpCache>>,
+    backend: Arc<Mutex<Option<BackendInfo>>>,
+    block_hashes: Option<BlockHashCache>,
+    require_anchors: bool,
+    anchors: BlockHashCache,
+    http_cache: Option<Arc<HttpCache>>,
+    cache: Option<Arc<dyn Cache>>,
+    sync_cache: Option<Arc<SyncCache>>,
+    retry: Retry,
+    redirect_policy: RedirectPolicy,
+    verify_txid: bool,
+    tip_height: TipCache,
+    prefetch: PrefetchSet,
+    warm_up_budget: Duration,
+    warm: WarmCache,
+    url_buffer: UrlBuffer,
+    /// Instant past which requests fail, set on the clones fetching resources for a warm-up.
+    deadline: Option<Instant>,
+    lifecycle: Lifecycle,
+    shutdown_grace: Duration,
+    block_vsize: u64,
+    bandwidth: BandwidthMeter,
+    request_ids: RequestIds,
+    trace_retries: bool,
+    #[cfg(feature = "addresses")]
+    network: Option<AddressNetwork>,
+    #[cfg(feature = "aws-sigv4")]
+    sigv4: Option<SigV4>,
     agent: Agent,
 }
 
+/// Buffer the URLs of requests are built in, so that building one doesn't allocate once the
+/// buffer has grown. Clones of a client get their own buffer.
+#[derive(Debug, Default)]
+struct UrlBuffer(Mutex<String>);
+
+impl Clone for UrlBuffer {
+    fn clone(&self) -> Self {
+        UrlBuffer::default()
+    }
+}
+
 impl BlockingClient {
     /// build a blocking client from a [`Builder`]
     pub fn from_builder(builder: Builder) -> Result<Self, Error> {
@@ -46,7 +133,74 @@ impl BlockingClient {
             agent_builder = agent_builder.proxy(Proxy::new(proxy)?);
         }
 
-        Ok(Self::from_agent(builder.base_url, agent_builder.build()))
+        // redirects are followed by `call_redirected` according to the redirect policy
+        agent_builder = agent_builder.redirects(0);
+
+        if let Some(languages) = builder.accept_language_header()? {
+            let languages = languages.to_owned();
+            agent_builder =
+                agent_builder.middleware(move |request: Request, next: MiddlewareNext| {
+                    next.handle(request.set("Accept-Language", &languages))
+                });
+        }
+
+        let base_url = normalize_base_url(&builder.base_url)?;
+        Ok(BlockingClient {
+            base_url: base_url.clone(),
+            url: Arc::new(RwLock::new(base_url)),
+            proxy: builder.proxy,
+            timeout: builder.timeout,
+            max_pages: builder.max_pages,
+            fee_fallback: builder
+                .fee_fallback
+                .map(FeeFallback::normalized)
+                .transpose()?,
+            fee_cache: if builder.conditional_fee_cache {
+                Some(Arc::new(HttpCache::new(Arc::new(MemoryCache::default()))))
+            } else {
+                None
+            },
+            backend: Arc::new(Mutex::new(None)),
+            block_hashes: if builder.validate_responses {
+                Some(BlockHashCache::default())
+            } else {
+                None
+            },
+            http_cache: builder
+                .http_cache
+                .map(|cache| Arc::new(HttpCache::new(cache))),
+            cache: builder.cache,
+            sync_cache: builder.sync_cache,
+            retry: Retry::new(
+                builder.max_retries,
+                builder.base_backoff,
+                builder.retry_if,
+                builder.retry_budget,
+                builder.backoff_jitter,
+                builder.jitter_seed,
+            ),
+            redirect_policy: builder.redirect_policy,
+            verify_txid: builder.verify_txid,
+            tip_height: TipCache::new(builder.tip_cache_ttl),
+            prefetch: builder.prefetch,
+            warm_up_budget: builder.warm_up_budget,
+            warm: WarmCache::new(builder.tip_cache_ttl),
+            url_buffer: UrlBuffer::default(),
+            deadline: None,
+            lifecycle: Lifecycle::default(),
+            shutdown_grace: builder.shutdown_grace,
+            block_vsize: builder.block_vsize,
+            require_anchors: builder.require_anchors,
+            anchors: BlockHashCache::default(),
+            bandwidth: BandwidthMeter::new(builder.bandwidth_budget),
+            request_ids: RequestIds::new(builder.request_id),
+            trace_retries: builder.trace_retries,
+            #[cfg(feature = "addresses")]
+            network: builder.network,
+            #[cfg(feature = "aws-sigv4")]
+            sigv4: builder.sigv4,
+            agent: agent_builder.build(),
+        })
     }
 
     /// build a blocking client from a [`Config`]
@@ -56,33 +210,395 @@ impl BlockingClient {
 
     /// build a blocking client from an [`Agent`]
     pub fn from_agent(url: String, agent: Agent) -> Self {
-        BlockingClient { url, agent }
+        BlockingClient {
+            base_url: url.clone(),
+            url: Arc::new(RwLock::new(url)),
+            proxy: None,
+            timeout: None,
+            max_pages: DEFAULT_MAX_PAGES,
+            fee_fallback: None,
+            fee_cache: None,
+            backend: Arc::new(Mutex::new(None)),
+            block_hashes: None,
+            http_cache: None,
+            cache: None,
+            sync_cache: None,
+            retry: Retry::default(),
+            redirect_policy: RedirectPolicy::default(),
+            verify_txid: true,
+            tip_height: TipCache::default(),
+            prefetch: PrefetchSet::default(),
+            warm_up_budget: DEFAULT_WARM_UP_BUDGET,
+            warm: WarmCache::default(),
+            url_buffer: UrlBuffer::default(),
+            deadline: None,
+            lifecycle: Lifecycle::default(),
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            block_vsize: DEFAULT_BLOCK_VSIZE,
+            require_anchors: false,
+            anchors: BlockHashCache::default(),
+            bandwidth: BandwidthMeter::default(),
+            request_ids: RequestIds::new(RequestIdPolicy::default()),
+            trace_retries: false,
+            #[cfg(feature = "addresses")]
+            network: None,
+            #[cfg(feature = "aws-sigv4")]
+            sigv4: None,
+            agent,
+        }
+    }
+
+    /// Make a GET request to `url`, retrying and following redirects as configured.
+    fn get_with_retry(&self, url: &str) -> Result<Response, Error> {
+        self.call_with_retry(self.agent.get(url))
+    }
+
+    /// Make a GET request to the endpoint at `path` as [`BlockingClient::get_with_retry`]
+    /// does, building its URL in the buffer of the client rather than in a new string.
+    fn get_endpoint(&self, path: fmt::Arguments<'_>) -> Result<Response, Error> {
+        let request = match self.url_buffer.0.try_lock() {
+            Ok(mut url) => {
+                url.clear();
+                url.push_str(&self.url.read().expect("poisoned base URL"));
+                fmt::Write::write_fmt(&mut *url, path).expect("writing to a string can't fail");
+                self.agent.get(&url)
+            }
+            // the buffer is in use by another thread sharing this clone
+            Err(_) => self.agent.get(&self.endpoint(path)),
+        };
+        self.call_with_retry(request)
+    }
+
+    /// Make a GET request to `path`, returning the response body along with the
+    /// [`RequestTrace`] of the retries it took.
+    pub fn get_traced(&self, path: &str) -> Result<(Vec<u8>, RequestTrace), Error> {
+        let url = self.endpoint(format_args!("{}", path));
+        let (resp, trace) = self.call_traced(self.agent.get(&url))?;
+        Ok((into_bytes(resp)?, trace))
+    }
+
+    /// Make `request` as [`BlockingClient::call_traced`] does, logging the retries it took when
+    /// enabled with [`Builder::trace_retries`].
+    fn call_with_retry(&self, request: Request) -> Result<Response, Error> {
+        let url = self.trace_retries.then(|| request.url().to_owned());
+        let (resp, trace) = self.call_traced(request)?;
+        if let Some(url) = url.filter(|_| trace.retries > 0) {
+            info!(
+                "request to {} completed with status {} after {} retries, waiting {:?}",
+                url,
+                resp.status(),
+                trace.retries,
+                trace.delay
+            );
+        }
+        Ok(resp)
+    }
+
+    /// Make `request`, retrying it with exponential backoff while the server responds with a
+    /// retryable status or the retry predicate asks for it, up to the maximum number of retries.
+    ///
+    /// Error statuses are reported as [`Error::HttpResponse`].
+    fn call_traced(&self, request: Request) -> Result<(Response, RequestTrace), Error> {
+        let _request = self.lifecycle.enter()?;
+        let (request, request_id) = self.identified(request);
+        let mut trace = RequestTrace::default();
+        loop {
+            self.bandwidth.check()?;
+            let (status, resp) = self.call_redirected(request.clone(), &[])?;
+            let length = match &resp {
+                Ok(resp) | Err(resp) => content_length(resp),
+            };
+            if let Some(length) = length {
+                self.bandwidth
+                    .debit(EndpointCategory::of_url(request.url()), length);
+            }
+            let (resp, body) = match (resp, &self.retry.predicate) {
+                (resp, None) => (resp, None),
+                (Ok(resp), Some(_)) => {
+                    let (resp, body) = peek_body(resp)?;
+                    (Ok(resp), Some(body))
+                }
+                (Err(resp), Some(_)) => {
+                    let (resp, body) = peek_body(resp)?;
+                    (Err(resp), Some(body))
+                }
+            };
+            let context = RetryContext {
+                status,
+                attempt: trace.retries,
+                body: body.as_deref(),
+            };
+            if !self.retry.should_retry(&context) {
+                return match resp {
+                    Ok(resp) => Ok((resp, trace)),
+                    Err(resp) => Err(into_http_error(resp).with_request_id(request_id)),
+                };
+            }
+            debug!(
+                "retrying request to {} after status {}",
+                request.url(),
+                status
+            );
+            let body = match body {
+                Some(body) => Some(body),
+                None => resp
+                    .err()
+                    .and_then(|resp| into_bytes(resp).ok())
+                    .map(Arc::from),
+            };
+            let delay = self.retry.delay(trace.retries, body.as_deref());
+            if matches!(self.deadline, Some(deadline) if Instant::now() + delay >= deadline) {
+                return Err(Error::DeadlineExceeded);
+            }
+            thread::sleep(delay);
+            trace.retries += 1;
+            trace.delay += delay;
+        }
+    }
+
+    /// Make `request` with `body`, following the redirects allowed by the [`RedirectPolicy`],
+    /// and get the status of the final response along with it, as an error for error statuses.
+    ///
+    /// Redirects are followed here rather than by the agent, so that the policy can be enforced
+    /// on all the requests of the client. Only `307` and `308` redirects keep the method and
+    /// the body, the others being followed with a GET.
+    fn call_redirected(
+        &self,
+        mut request: Request,
+        mut body: &[u8],
+    ) -> Result<(u16, Result<Response, Response>), Error> {
+        let mut redirects = 0;
+        loop {
+            let signed = self.signed(self.bounded(request.clone())?, body)?;
+            let result = if body.is_empty() {
+                signed.call()
+            } else {
+                signed.send_bytes(body)
+            };
+            let resp = match result {
+                Ok(resp) if is_status_redirect(resp.status()) => resp,
+                Ok(resp) => return Ok((resp.status(), Ok(resp))),
+                Err(ureq::Error::Status(code, resp)) => return Ok((code, Err(resp))),
+                Err(e) => return Err(Error::Ureq(e)),
+            };
+            let status = resp.status();
+            let from = request.request_url()?;
+            let to = match resp.header("Location") {
+                Some(location) => from
+                    .as_url()
+                    .join(location)
+                    .map_err(|_| Error::InvalidServerData)?,
+                None => return Ok((status, Ok(resp))),
+            };
+            let same_origin = from.as_url().origin() == to.origin();
+            if !self.redirect_policy.allows(redirects, same_origin) {
+                return Err(Error::RedirectBlocked {
+                    from: from.as_url().to_string(),
+                    to: to.to_string(),
+                });
+            }
+
+            let method = match status {
+                307 | 308 => request.method(),
+                _ => {
+                    body = &[];
+                    "GET"
+                }
+            };
+            let mut redirected = self.agent.request(method, to.as_str());
+            for name in request.header_names() {
+                if !same_origin && is_header_sensitive(&name) {
+                    continue;
+                }
+                if let Some(value) = request.header(&name) {
+                    redirected = redirected.set(&name, value);
+                }
+            }
+            request = redirected;
+            redirects += 1;
+        }
+    }
+
+    /// Bound `request` by the time left before the deadline of the client, if any, failing
+    /// with [`Error::DeadlineExceeded`] once it is past.
+    fn bounded(&self, request: Request) -> Result<Request, Error> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return Ok(request),
+        };
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(left) if !left.is_zero() => Ok(request.timeout(left)),
+            _ => Err(Error::DeadlineExceeded),
+        }
+    }
+
+    /// Tag `request` with the id of the next request, unless ids are disabled, returning the
+    /// id along with it.
+    fn identified(&self, request: Request) -> (Request, Option<String>) {
+        match self.request_ids.next() {
+            Some(id) => (request.set(REQUEST_ID_HEADER, &id), Some(id)),
+            None => (request, None),
+        }
+    }
+
+    /// Sign `request`, whose body is `body`, with the SigV4 signer set on the builder, if any,
+    /// right before sending it.
+    #[cfg(feature = "aws-sigv4")]
+    fn signed(&self, request: Request, body: &[u8]) -> Result<Request, Error> {
+        let signer = match &self.sigv4 {
+            Some(signer) => signer,
+            None => return Ok(request),
+        };
+        let url = request.request_url()?;
+        let headers = signer.sign(request.method(), url.as_url(), body, SystemTime::now());
+        Ok(headers
+            .into_iter()
+            .fold(request, |request, (name, value)| request.set(name, &value)))
+    }
+
+    #[cfg(not(feature = "aws-sigv4"))]
+    fn signed(&self, request: Request, _body: &[u8]) -> Result<Request, Error> {
+        Ok(request)
     }
 
     /// Get a [`Transaction`] option given its [`Txid`]
     pub fn tx(&self, txid: &Txid) -> Result<Option<Tx>, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/tx/{}/raw", self.url, txid))
-            .call();
-
-        match resp {
-            Ok(resp) => {
-                let bytes = into_bytes(resp)?;
+        match self.tx_raw_bytes(txid)? {
+            Some(bytes) => {
                 let tx = Tx::consensus_decode(&mut Cursor::new(bytes))
                     .map_err(|_| Error::InvalidServerData)?;
+                if self.verify_txid && tx.txid() != *txid {
+                    return Err(Error::TxidMismatch {
+                        requested: *txid,
+                        got: tx.txid(),
+                    });
+                }
                 Ok(Some(tx))
             }
-            Err(ureq::Error::Status(code, _)) => {
-                if is_status_not_found(code) {
-                    return Ok(None);
+            None => Ok(None),
+        }
+    }
+
+    /// Get a [`Transaction`] option given its [`Txid`] as a hex string.
+    ///
+    /// Fails with [`Error::InvalidInput`] before making any request if `txid` isn't a valid
+    /// transaction id.
+    pub fn tx_by_str(&self, txid: &str) -> Result<Option<Tx>, Error> {
+        self.tx(&parse_input("txid", txid)?)
+    }
+
+    /// Get the consensus-encoded bytes of a transaction given its [`Txid`], without decoding
+    /// them.
+    pub fn tx_raw_bytes(&self, txid: &Txid) -> Result<Option<Vec<u8>>, Error> {
+        self.get_immutable(self.endpoint(format_args!("/tx/{}/raw", txid)))
+    }
+
+    /// Get the consensus encoding of a transaction as a hex string given its [`Txid`].
+    ///
+    /// The binary encoding, half the size of the hex one, is fetched and encoded locally.
+    pub fn tx_hex(&self, txid: &Txid) -> Result<Option<String>, Error> {
+        Ok(self.tx_raw_bytes(txid)?.map(|bytes| bytes[..].to_hex()))
+    }
+
+    /// Make a GET request to `url`, returning the response body or `None` if the resource
+    /// doesn't exist.
+    ///
+    /// Responses found in the read-through cache are returned without making any request.
+    /// Otherwise, when an HTTP cache is configured, the request is made conditional on the
+    /// validators of the cached response, which is reused if the server confirms it didn't
+    /// change.
+    fn get_immutable(&self, url: String) -> Result<Option<Vec<u8>>, Error> {
+        let _request = self.lifecycle.enter()?;
+        let path = self.relative_path(&url);
+        let store = self.cache.as_ref().filter(|_| is_cacheable(path));
+        if let Some(body) = store.and_then(|store| store.get(&url)) {
+            return Ok(Some(body));
+        }
+        let cache = self.http_cache.as_deref().filter(|_| is_cacheable(path));
+        let body = self.get_validated(cache, &url)?;
+        if let (Some(store), Some(body)) = (store, &body) {
+            store.put(&url, body.clone());
+        }
+        Ok(body)
+    }
+
+    /// Make a GET request to `url`, conditional on the validators of the response cached in
+    /// `cache`, if any.
+    fn get_validated(
+        &self,
+        cache: Option<&HttpCache>,
+        url: &str,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let cached = cache.and_then(|cache| cache.load(url));
+
+        let mut request = self.agent.get(url);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.set("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.set("If-Modified-Since", last_modified);
+            }
+        }
+
+        match self.call_with_retry(request) {
+            Ok(resp) if resp.status() == 304 => match (cache, cached) {
+                (Some(cache), Some(cached)) => {
+                    cache.hit();
+                    Ok(Some(cached.body))
+                }
+                _ => Err(Error::http_response(304, "")),
+            },
+            Ok(resp) => {
+                let etag = resp.header("ETag").map(str::to_owned);
+                let last_modified = resp
+                    .header("Last-Modified")
+                    .or_else(|| resp.header("Date"))
+                    .map(str::to_owned);
+                let length = content_length(&resp);
+                let body = into_bytes(resp)?;
+                if length.is_none() {
+                    self.bandwidth
+                        .debit(EndpointCategory::of_url(url), body.len() as u64);
+                }
+                if let Some(cache) = cache {
+                    cache.store(url, etag, last_modified, &body);
                 }
-                Err(Error::HttpResponse(code))
+                Ok(Some(body))
             }
-            Err(e) => Err(Error::Ureq(e)),
+            Err(Error::HttpResponse { status, .. }) if is_status_not_found(status) => Ok(None),
+            Err(e) => Err(e),
         }
     }
 
+    /// Get transactions given their [`Txid`]s, in the same order.
+    pub fn txs(&self, txids: &[Txid]) -> Result<Vec<Option<Tx>>, Error> {
+        txids.iter().map(|txid| self.tx(txid)).collect()
+    }
+
+    /// Get transactions given their [`Txid`]s, returning the result of each request in the
+    /// same order.
+    ///
+    /// Unlike [`BlockingClient::txs`], a failed request doesn't discard the others, so that
+    /// only the failed ones need to be retried.
+    pub fn txs_partial(&self, txids: &[Txid]) -> Vec<Result<Option<Tx>, Error>> {
+        txids.iter().map(|txid| self.tx(txid)).collect()
+    }
+
+    /// Get the statuses of transactions given their [`Txid`]s, in the same order.
+    pub fn tx_statuses(&self, txids: &[Txid]) -> Result<Vec<TxStatus>, Error> {
+        txids.iter().map(|txid| self.tx_status(txid)).collect()
+    }
+
+    /// Get the unspent outputs of [`Address`]es, in the same order.
+    #[cfg(feature = "addresses")]
+    pub fn addresses_utxo(&self, addresses: &[Address]) -> Result<Vec<Vec<Utxo>>, Error> {
+        addresses
+            .iter()
+            .map(|address| self.scripthash_utxo(&address.script_pubkey()))
+            .collect()
+    }
+
     /// Get a [`Transaction`] given its [`Txid`].
     pub fn tx_no_opt(&self, txid: &Txid) -> Result<Tx, Error> {
         match self.tx(txid) {
@@ -92,237 +608,1042 @@ impl BlockingClient {
         }
     }
 
+    /// Get transaction info given its [`Txid`], including its status and fee.
+    ///
+    /// A malformed response fails with [`Error::Json`] in both clients.
+    pub fn tx_info(&self, txid: &Txid) -> Result<Option<crate::Tx>, Error> {
+        let resp = self.get_endpoint(format_args!("/tx/{}", txid));
+
+        match resp {
+            Ok(resp) => {
+                let mut tx: crate::Tx = serde_json::from_reader(resp.into_reader())?;
+                self.fill_anchor(tx.txid, &mut tx.status)?;
+                Ok(Some(tx))
+            }
+            Err(Error::HttpResponse { status, .. }) if is_status_not_found(status) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get all outputs of the transaction with the given [`Txid`] paying to an [`Address`].
+    ///
+    /// Returns an empty list if the transaction exists but doesn't pay to the address, and
+    /// [`Error::TransactionNotFound`] if the transaction is unknown.
+    #[cfg(feature = "addresses")]
+    pub fn find_payment(&self, txid: &Txid, address: &Address) -> Result<Vec<PaymentInfo>, Error> {
+        self.find_script_payment(txid, &address.script_pubkey())
+    }
+
+    /// Get all outputs of the transaction with the given [`Txid`] paying to a [`ScriptPubkey`].
+    ///
+    /// Returns an empty list if the transaction exists but doesn't pay to the script, and
+    /// [`Error::TransactionNotFound`] if the transaction is unknown.
+    pub fn find_script_payment(
+        &self,
+        txid: &Txid,
+        script: &ScriptPubkey,
+    ) -> Result<Vec<PaymentInfo>, Error> {
+        match self.tx_info(txid)? {
+            Some(tx) => Ok(tx.payments_to(script)),
+            None => Err(Error::TransactionNotFound(*txid)),
+        }
+    }
+
+    /// Get the fee paid by a transaction given its [`Txid`].
+    ///
+    /// Uses the fee reported by the server when available. Otherwise the fee is computed from the
+    /// values of the spent outputs, fetching the previous transactions whenever their outputs are
+    /// not embedded into the response.
+    ///
+    /// Fails with [`Error::InvalidServerData`] if the values reported by the server overflow or
+    /// the outputs are worth more than the inputs.
+    pub fn tx_fee(&self, txid: &Txid) -> Result<u64, Error> {
+        match self.tx_info(txid)? {
+            Some(tx) => self.fee_of(&tx),
+            None => Err(Error::TransactionNotFound(*txid)),
+        }
+    }
+
+    /// Get the fee paid by a transaction, computing it from the spent outputs when the server
+    /// doesn't report it.
+    fn fee_of(&self, tx: &crate::Tx) -> Result<u64, Error> {
+        if tx.is_coinbase() {
+            return Err(Error::CoinbaseFee(tx.txid));
+        }
+        if let Some(fee) = tx.fee {
+            return Ok(fee);
+        }
+
+        let mut input_value = 0u64;
+        for vin in &tx.vin {
+            let value = match &vin.prevout {
+                Some(prevout) => prevout.value,
+                None => {
+                    let prev_tx = match self.tx_info(&vin.txid)? {
+                        Some(prev_tx) => prev_tx,
+                        None => return Err(Error::TransactionNotFound(vin.txid)),
+                    };
+                    match prev_tx.vout.get(vin.vout as usize) {
+                        Some(vout) => vout.value,
+                        None => return Err(Error::InvalidServerData),
+                    }
+                }
+            };
+            input_value = input_value
+                .checked_add(value)
+                .ok_or(Error::InvalidServerData)?;
+        }
+        let output_value = tx
+            .vout
+            .iter()
+            .try_fold(0u64, |sum, vout| sum.checked_add(vout.value))
+            .ok_or(Error::InvalidServerData)?;
+        input_value
+            .checked_sub(output_value)
+            .ok_or(Error::InvalidServerData)
+    }
+
+    /// Get the feerate (in sat/vB) of a transaction together with its unconfirmed ancestors.
+    ///
+    /// For an unconfirmed transaction, the unconfirmed ancestors are found by walking the
+    /// transactions spent by its inputs, stopping at confirmed ones. Ancestors deeper than
+    /// [`MAX_ANCESTOR_DEPTH`] are not taken into account. For a confirmed transaction this is
+    /// just its own feerate.
+    pub fn effective_feerate(&self, txid: &Txid) -> Result<f32, Error> {
+        let tx = match self.tx_info(txid)? {
+            Some(tx) => tx,
+            None => return Err(Error::TransactionNotFound(*txid)),
+        };
+        let mut total_fee = self.fee_of(&tx)?;
+        let mut total_vsize = tx.vsize();
+
+        if !tx.status.confirmed {
+            let mut visited = HashSet::new();
+            let mut level = tx.vin.iter().map(|vin| vin.txid).collect::<Vec<_>>();
+            for _ in 0..MAX_ANCESTOR_DEPTH {
+                let mut next = vec![];
+                for parent_txid in level {
+                    if !visited.insert(parent_txid) {
+                        continue;
+                    }
+                    let parent = match self.tx_info(&parent_txid)? {
+                        Some(parent) => parent,
+                        None => return Err(Error::TransactionNotFound(parent_txid)),
+                    };
+                    if parent.status.confirmed {
+                        continue;
+                    }
+                    total_fee += self.fee_of(&parent)?;
+                    total_vsize += parent.vsize();
+                    next.extend(parent.vin.iter().map(|vin| vin.txid));
+                }
+                if next.is_empty() {
+                    break;
+                }
+                level = next;
+            }
+        }
+
+        Ok(total_fee as f32 / total_vsize as f32)
+    }
+
     /// Get a [`Txid`] of a transaction given its index in a block with a given hash.
+    ///
+    /// Returns `None` if the block is unknown or the index is out of range. Backends disagree
+    /// on how to report the latter (404, 400 or an empty body), so all of them are mapped to
+    /// `None`.
     pub fn txid_at_block_index(
         &self,
         block_hash: &BlockHash,
         index: usize,
     ) -> Result<Option<Txid>, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/block/{}/txid/{}", self.url, block_hash, index))
-            .call();
+        let path = format!("/block/{}/txid/{}", block_hash, index);
+        let resp = self.get_with_retry(&self.endpoint(format_args!("{}", path)));
 
         match resp {
-            Ok(resp) => Ok(Some(Txid::from_str(&resp.into_string()?)?)),
-            Err(ureq::Error::Status(code, _)) => {
-                if is_status_not_found(code) {
+            Ok(resp) => {
+                let text = into_text(&path, resp)?;
+                if text.trim().is_empty() {
                     return Ok(None);
                 }
-                Err(Error::HttpResponse(code))
+                Ok(Some(Txid::from_str(text.trim())?))
+            }
+            Err(Error::HttpResponse { status, .. })
+                if is_status_not_found(status) || status == 400 =>
+            {
+                Ok(None)
             }
-            Err(e) => Err(Error::Ureq(e)),
+            Err(e) => Err(e),
         }
     }
 
     /// Get the status of a [`Transaction`] given its [`Txid`].
     pub fn tx_status(&self, txid: &Txid) -> Result<TxStatus, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/tx/{}/status", self.url, txid))
-            .call();
+        let resp = self.get_endpoint(format_args!("/tx/{}/status", txid));
 
-        match resp {
-            Ok(resp) => Ok(resp.into_json()?),
-            Err(ureq::Error::Status(code, _)) => Err(Error::HttpResponse(code)),
-            Err(e) => Err(Error::Ureq(e)),
+        let mut status = resp?.into_json()?;
+        self.fill_anchor(*txid, &mut status)?;
+        self.check_tx_status(&status)?;
+        Ok(status)
+    }
+
+    /// Fills in the block hash of the confirmed `status` of `txid` lacking it, if anchors are
+    /// required.
+    fn fill_anchor(&self, txid: Txid, status: &mut TxStatus) -> Result<(), Error> {
+        self.fill_anchors(vec![(txid, status)])
+    }
+
+    /// Fills in the block hash of the confirmed statuses lacking it, given with the txid they
+    /// are the status of, if anchors are required.
+    ///
+    /// The block at each height is looked up once.
+    fn fill_anchors(&self, statuses: Vec<(Txid, &mut TxStatus)>) -> Result<(), Error> {
+        if !self.require_anchors {
+            return Ok(());
+        }
+        let mut blocks = HashMap::<u32, (BlockHash, Option<Vec<Txid>>)>::new();
+        for (txid, status) in statuses {
+            let height = match status.block_height {
+                Some(height) if status.confirmed && status.block_hash.is_none() => height,
+                _ => continue,
+            };
+            let (hash, txids) = match blocks.entry(height) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.anchor_block(height)?),
+            };
+            if let Some(txids) = txids {
+                validation::check_anchor(&txid, height, *hash, txids)?;
+            }
+            status.block_hash = Some(*hash);
+        }
+        Ok(())
+    }
+
+    /// Get the hash of the block at `height` to anchor confirmed statuses or proofs, caching it
+    /// once the block is deep enough not to be reorganized.
+    ///
+    /// The txids of a block too recent to be cached are returned along with its hash, so that
+    /// the anchored transactions can be checked to be in it: after a reorg, the block now at
+    /// `height` may not be the one the server reported them confirmed in.
+    fn anchor_block(&self, height: u32) -> Result<(BlockHash, Option<Vec<Txid>>), Error> {
+        let cached = self
+            .anchors
+            .lock()
+            .expect("poisoned block hash cache")
+            .get(&height)
+            .copied();
+        if let Some(hash) = cached {
+            return Ok((hash, None));
+        }
+        let hash = self.block_hash(height)?;
+        if height.saturating_add(ANCHOR_CACHE_DEPTH) <= self.height()? {
+            self.anchors
+                .lock()
+                .expect("poisoned block hash cache")
+                .insert(height, hash);
+            return Ok((hash, None));
+        }
+        Ok((hash, Some(self.block_txids(&hash)?)))
+    }
+
+    /// Cross-checks the block hash of a confirmed transaction status against the hash of the
+    /// block at the reported height, if response validation is enabled.
+    fn check_tx_status(&self, status: &TxStatus) -> Result<(), Error> {
+        let cache = match &self.block_hashes {
+            Some(cache) => cache,
+            None => return Ok(()),
+        };
+        let (height, hash) = match validation::unverified_block(cache, status) {
+            Some(claimed) => claimed,
+            None => return Ok(()),
+        };
+        let actual = self.block_hash(height)?;
+        validation::check_block_hash(cache, height, hash, actual)
+    }
+
+    /// Checks that a merkle `proof` of `txid` leads to the merkle root of the header of the
+    /// block at its height, if response validation is enabled.
+    fn check_merkle_proof(&self, txid: &Txid, proof: &MerkleProof) -> Result<(), Error> {
+        if self.block_hashes.is_none() {
+            return Ok(());
+        }
+        let block_hash = self.block_hash(proof.block_height)?;
+        let header = self.header_by_hash(&block_hash)?;
+        validation::check_merkle_proof(txid, proof, &header)
+    }
+
+    /// Spot-checks a sample of `utxos` against the number of outputs of their funding
+    /// transactions, if response validation is enabled.
+    fn check_utxos(&self, utxos: &[Utxo]) -> Result<(), Error> {
+        if self.block_hashes.is_none() {
+            return Ok(());
         }
+        let mut rng = SplitMix64::from_entropy();
+        for utxo in validation::sample_utxos(utxos, &mut rng) {
+            let outputs = self.tx(&utxo.txid)?.map(|tx| tx.outputs.len());
+            validation::check_utxo(utxo, outputs)?;
+        }
+        Ok(())
     }
 
-    /* Uncomment once `bp-primitives` will support consensus serialziation
     /// Get a [`BlockHeader`] given a particular block hash.
     pub fn header_by_hash(&self, block_hash: &BlockHash) -> Result<BlockHeader, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/block/{}/header", self.url, block_hash))
-            .call();
+        BlockHeader::from_str(&self.header_hex(block_hash)?).map_err(|_| Error::InvalidServerData)
+    }
 
-        match resp {
-            Ok(resp) => Ok(deserialize(&Vec::from_hex(&resp.into_string()?)?)?),
-            Err(ureq::Error::Status(code, _)) => Err(Error::HttpResponse(code)),
-            Err(e) => Err(Error::Ureq(e)),
+    /// Get the consensus encoding of the header of a block as a hex string, given its
+    /// [`BlockHash`].
+    ///
+    /// Esplora serves headers only in hex: the raw block would be the only binary alternative,
+    /// which is far larger than the 160 hex characters of a header.
+    pub fn header_hex(&self, block_hash: &BlockHash) -> Result<String, Error> {
+        let bytes = self
+            .get_immutable(self.endpoint(format_args!("/block/{}/header", block_hash)))?
+            .ok_or(Error::HeaderHashNotFound(*block_hash))?;
+        let hex = String::from_utf8(bytes).map_err(|_| Error::InvalidUtf8 {
+            path: format!("/block/{}/header", block_hash),
+        })?;
+        Ok(hex.trim().to_owned())
+    }
+
+    /// Get the consensus-encoded bytes of the header of a block given its [`BlockHash`],
+    /// decoded locally from [`BlockingClient::header_hex`].
+    pub fn header_raw_bytes(&self, block_hash: &BlockHash) -> Result<Vec<u8>, Error> {
+        Vec::<u8>::from_hex(&self.header_hex(block_hash)?).map_err(|_| Error::InvalidServerData)
+    }
+
+    /// Get a [`BlockHeader`] given a block hash as a hex string.
+    ///
+    /// Fails with [`Error::InvalidInput`] before making any request if `block_hash` isn't a
+    /// valid block hash.
+    pub fn header_by_hash_str(&self, block_hash: &str) -> Result<BlockHeader, Error> {
+        self.header_by_hash(&parse_input("block hash", block_hash)?)
+    }
+
+    /// Get the merkle root and the witness commitment of a block given its [`BlockHash`].
+    ///
+    /// The coinbase is fetched with [`BlockingClient::txid_at_block_index`] and
+    /// [`BlockingClient::tx_info`]. When the server doesn't return it, the block is reported
+    /// without coinbase txid nor witness commitment.
+    pub fn block_commitments(&self, block_hash: &BlockHash) -> Result<BlockCommitments, Error> {
+        let header = self.header_by_hash(block_hash)?;
+        let coinbase = match self.txid_at_block_index(block_hash, 0)? {
+            Some(txid) => self.tx_info(&txid)?,
+            None => None,
+        };
+        Ok(BlockCommitments {
+            merkle_root: header.merkle_root,
+            coinbase_txid: coinbase.as_ref().map(|tx| tx.txid),
+            witness_commitment: coinbase.as_ref().and_then(extract_witness_commitment),
+        })
+    }
+
+    /// Get the total work proven by the headers of the blocks from `from_height` to
+    /// `to_height`, both included.
+    ///
+    /// Fails with [`Error::InconsistentBackend`] unless each header is the one of the block
+    /// at its height and builds on the previous one.
+    pub fn chain_work_between(&self, from_height: u32, to_height: u32) -> Result<Work, Error> {
+        let capacity = to_height.saturating_sub(from_height) as usize + 1;
+        let mut hashes = Vec::with_capacity(capacity);
+        let mut headers = Vec::with_capacity(capacity);
+        for height in from_height..=to_height {
+            let block_hash = self.block_hash(height)?;
+            headers.push(self.header_by_hash(&block_hash)?);
+            hashes.push(block_hash);
         }
+        check_header_chain(&hashes, &headers)?;
+        Ok(cumulative_work(&headers))
     }
-     */
 
     /// Get the [`BlockStatus`] given a particular [`BlockHash`].
     pub fn block_status(&self, block_hash: &BlockHash) -> Result<BlockStatus, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/block/{}/status", self.url, block_hash))
-            .call();
+        let resp = self.get_endpoint(format_args!("/block/{}/status", block_hash));
 
-        match resp {
-            Ok(resp) => Ok(resp.into_json()?),
-            Err(ureq::Error::Status(code, _)) => Err(Error::HttpResponse(code)),
-            Err(e) => Err(Error::Ureq(e)),
-        }
+        Ok(resp?.into_json()?)
     }
 
     /* TODO: Uncomment once `bp-primitives` will support blocks
     /// Get a [`Block`] given a particular [`BlockHash`].
     pub fn block_by_hash(&self, block_hash: &BlockHash) -> Result<Option<Block>, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/block/{}/raw", self.url, block_hash))
-            .call();
+        let resp = self.get_endpoint(format_args!("/block/{}/raw", block_hash));
 
         match resp {
             Ok(resp) => Ok(Some(deserialize(&into_bytes(resp)?)?)),
-            Err(ureq::Error::Status(code, _)) => {
-                if is_status_not_found(code) {
-                    return Ok(None);
-                }
-                Err(Error::HttpResponse(code))
-            }
-            Err(e) => Err(Error::Ureq(e)),
+            Err(Error::HttpResponse { status, .. }) if is_status_not_found(status) => Ok(None),
+            Err(e) => Err(e),
         }
     }
 
-    /// Get a merkle inclusion proof for a [`Transaction`] with the given [`Txid`].
-    pub fn merkle_proof(&self, txid: &Txid) -> Result<Option<MerkleProof>, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/tx/{}/merkle-proof", self.url, txid))
-            .call();
+    /// Get a [`MerkleBlock`] inclusion proof for a [`Transaction`] with the given [`Txid`].
+    pub fn merkle_block(&self, txid: &Txid) -> Result<Option<MerkleBlock>, Error> {
+        let resp = self.get_endpoint(format_args!("/tx/{}/merkleblock-proof", txid));
 
         match resp {
-            Ok(resp) => Ok(Some(resp.into_json()?)),
-            Err(ureq::Error::Status(code, _)) => {
-                if is_status_not_found(code) {
-                    return Ok(None);
-                }
-                Err(Error::HttpResponse(code))
-            }
-            Err(e) => Err(Error::Ureq(e)),
+            Ok(resp) => Ok(Some(deserialize(&Vec::from_hex(&resp.into_string()?)?)?)),
+            Err(Error::HttpResponse { status, .. }) if is_status_not_found(status) => Ok(None),
+            Err(e) => Err(e),
         }
     }
+     */
 
-    /// Get a [`MerkleBlock`] inclusion proof for a [`Transaction`] with the given [`Txid`].
-    pub fn merkle_block(&self, txid: &Txid) -> Result<Option<MerkleBlock>, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/tx/{}/merkleblock-proof", self.url, txid))
-            .call();
+    /// Get a merkle inclusion proof for a [`Transaction`] with the given [`Txid`].
+    pub fn merkle_proof(&self, txid: &Txid) -> Result<Option<MerkleProof>, Error> {
+        let resp = self.get_endpoint(format_args!("/tx/{}/merkle-proof", txid));
 
         match resp {
-            Ok(resp) => Ok(Some(deserialize(&Vec::from_hex(&resp.into_string()?)?)?)),
-            Err(ureq::Error::Status(code, _)) => {
-                if is_status_not_found(code) {
-                    return Ok(None);
-                }
-                Err(Error::HttpResponse(code))
+            Ok(resp) => {
+                let proof: MerkleProof = resp.into_json()?;
+                self.check_merkle_proof(txid, &proof)?;
+                Ok(Some(proof))
             }
-            Err(e) => Err(Error::Ureq(e)),
+            Err(Error::HttpResponse { status, .. }) if is_status_not_found(status) => Ok(None),
+            Err(e) => Err(e),
         }
     }
-     */
+
+    /// Get a merkle inclusion proof for a [`Transaction`] with the given [`Txid`], along with
+    /// the hash of the block it proves inclusion in, so that it can be verified against the
+    /// header of that block.
+    ///
+    /// Esplora doesn't return the hash with the proof, so it is the hash of the block at the
+    /// height of the proof, which is cached once the block is deep enough. Until then, the
+    /// txids of the block are also requested to check that the transaction is still in it.
+    pub fn merkle_proof_with_block(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<(MerkleProof, BlockHash)>, Error> {
+        let proof = match self.merkle_proof(txid)? {
+            Some(proof) => proof,
+            None => return Ok(None),
+        };
+        let (block_hash, txids) = self.anchor_block(proof.block_height)?;
+        if let Some(txids) = txids {
+            validation::check_anchor(txid, proof.block_height, block_hash, &txids)?;
+        }
+        Ok(Some((proof, block_hash)))
+    }
 
     /// Get the spending status of an output given a [`Txid`] and the output index.
-    pub fn output_status(&self, txid: &Txid, index: u64) -> Result<Option<OutputStatus>, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/tx/{}/outspend/{}", self.url, txid, index))
-            .call();
+    pub fn outspend(&self, txid: &Txid, vout: u32) -> Result<Option<OutputStatus>, Error> {
+        let resp = self.get_endpoint(format_args!("/tx/{}/outspend/{}", txid, vout));
 
         match resp {
-            Ok(resp) => Ok(Some(resp.into_json()?)),
-            Err(ureq::Error::Status(code, _)) => {
-                if is_status_not_found(code) {
-                    return Ok(None);
+            Ok(resp) => {
+                let mut output: OutputStatus = resp.into_json()?;
+                if let (Some(txid), Some(status)) = (output.txid, &mut output.status) {
+                    self.fill_anchor(txid, status)?;
                 }
-                Err(Error::HttpResponse(code))
+                Ok(Some(output))
             }
-            Err(e) => Err(Error::Ureq(e)),
+            Err(Error::HttpResponse { status, .. }) if is_status_not_found(status) => Ok(None),
+            Err(e) => Err(e),
         }
     }
 
-    /// Broadcast a [`Transaction`] to Esplora
-    pub fn broadcast(&self, tx: &Tx) -> Result<(), Error> {
-        let resp = self
-            .agent
-            .post(&format!("{}/tx", self.url))
-            .send_string(&format!("{tx:x}"));
+    /// Get the spending status of all the outputs of a transaction given its [`Txid`], in
+    /// output order.
+    pub fn tx_outspends(&self, txid: &Txid) -> Result<Vec<OutputStatus>, Error> {
+        let resp = self.get_endpoint(format_args!("/tx/{}/outspends", txid));
 
-        match resp {
-            Ok(_) => Ok(()), // We do not return the txid?
-            Err(ureq::Error::Status(code, _)) => Err(Error::HttpResponse(code)),
-            Err(e) => Err(Error::Ureq(e)),
-        }
+        let mut outputs: Vec<OutputStatus> = resp?.into_json()?;
+        self.fill_anchors(
+            outputs
+                .iter_mut()
+                .filter_map(|output| Some((output.txid?, output.status.as_mut()?)))
+                .collect(),
+        )?;
+        Ok(outputs)
     }
 
-    /// Get the height of the current blockchain tip.
-    pub fn height(&self) -> Result<u32, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/blocks/tip/height", self.url))
-            .call();
+    /// Get the spending status of the outputs of each transaction of a block, in block order,
+    /// which tells the outputs the block created and which of them were spent since.
+    ///
+    /// This is a heavy operation: it makes one request for the txids of the block, then one
+    /// request per transaction, sequentially. The async client makes them concurrently.
+    pub fn block_outspends(
+        &self,
+        block_hash: &BlockHash,
+    ) -> Result<Vec<(Txid, Vec<OutputStatus>)>, Error> {
+        let txids = self.block_txids(block_hash)?;
+        let outspends = txids
+            .iter()
+            .map(|txid| self.tx_outspends(txid))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(txids.into_iter().zip(outspends).collect())
+    }
 
-        match resp {
-            Ok(resp) => Ok(resp.into_string()?.parse()?),
-            Err(ureq::Error::Status(code, _)) => Err(Error::HttpResponse(code)),
-            Err(e) => Err(Error::Ureq(e)),
-        }
+    /// Get the spending status of an output given a [`Txid`] and an output index provided as
+    /// `u64`, which is rejected if it doesn't fit a `u32`.
+    #[deprecated(since = "0.11.0", note = "output indexes are u32, use `outspend`")]
+    pub fn output_status(&self, txid: &Txid, index: u64) -> Result<Option<OutputStatus>, Error> {
+        self.outspend(txid, output_index(index)?)
     }
 
-    /// Get the [`BlockHash`] of the current blockchain tip.
-    pub fn tip_hash(&self) -> Result<BlockHash, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/blocks/tip/hash", self.url))
-            .call();
+    /// Broadcast a [`Transaction`] to Esplora
+    pub fn broadcast(&self, tx: &Tx) -> Result<(), Error> {
+        self.broadcast_with_meta(tx).map(|_| ())
+    }
+
+    /// Broadcast a [`Transaction`] to Esplora as [`BlockingClient::broadcast`] does, returning
+    /// the id sent with the request so that it can be logged.
+    pub fn broadcast_with_meta(&self, tx: &Tx) -> Result<RequestMeta, Error> {
+        let _request = self.lifecycle.enter()?;
+        let body = format!("{tx:x}");
+        self.bandwidth.upload(body.len() as u64);
+        let (request, request_id) =
+            self.identified(self.agent.post(&self.endpoint(format_args!("/tx"))));
 
-        Self::process_block_result(resp)
+        match self.call_redirected(request, body.as_bytes())?.1 {
+            Ok(_) => Ok(RequestMeta { request_id }), // We do not return the txid?
+            Err(resp) => Err(into_http_error(resp).with_request_id(request_id)),
+        }
     }
 
-    /// Get the [`BlockHash`] of a specific block height
-    pub fn block_hash(&self, block_height: u32) -> Result<BlockHash, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/block-height/{}", self.url, block_height))
-            .call();
+    /// Broadcast transactions one after the other, returning the result of each broadcast in the
+    /// same order, so that a rejected transaction doesn't prevent the others from being
+    /// broadcast.
+    ///
+    /// Rejections carry the reason given by the node, as for [`BlockingClient::broadcast`].
+    /// Transactions are broadcast in order, so one can spend an earlier one.
+    pub fn broadcast_all(&self, txs: &[Tx]) -> Vec<Result<Txid, Error>> {
+        txs.iter()
+            .map(|tx| self.broadcast(tx).map(|()| tx.txid()))
+            .collect()
+    }
 
-        if let Err(ureq::Error::Status(code, _)) = resp {
-            if is_status_not_found(code) {
-                return Err(Error::HeaderHeightNotFound(block_height));
+    /// Broadcast a [`Tx`] to Esplora, then wait until the server returns it, which means it
+    /// was accepted into its mempool, for at most `timeout` after the broadcast.
+    ///
+    /// Fails with [`Error::DeadlineExceeded`] if the transaction isn't returned in time.
+    pub fn broadcast_and_wait(&self, tx: &Tx, timeout: Duration) -> Result<Txid, Error> {
+        let txid = tx.txid();
+        self.broadcast(tx)?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.tx_info(&txid)?.is_some() {
+                return Ok(txid);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::DeadlineExceeded);
             }
+            thread::sleep(BROADCAST_POLL_INTERVAL.min(deadline - now));
         }
-
-        Self::process_block_result(resp)
     }
 
-    fn process_block_result(response: Result<Response, ureq::Error>) -> Result<BlockHash, Error> {
-        match response {
-            Ok(resp) => Ok(BlockHash::from_str(&resp.into_string()?)?),
-            Err(ureq::Error::Status(code, _)) => Err(Error::HttpResponse(code)),
-            Err(e) => Err(Error::Ureq(e)),
+    /// Get the height of the current blockchain tip.
+    ///
+    /// The height is reused for the TTL set with [`Builder::tip_cache_ttl`], so that polling
+    /// methods don't request it again and again.
+    pub fn height(&self) -> Result<u32, Error> {
+        if let Some(height) = self.tip_height.get() {
+            return Ok(height);
         }
+        self.height_uncached()
     }
 
-    /// Get an map where the key is the confirmation target (in number of blocks)
-    /// and the value is the estimated feerate (in sat/vB).
-    pub fn fee_estimates(&self) -> Result<HashMap<String, f64>, Error> {
-        let resp = self
-            .agent
-            .get(&format!("{}/fee-estimates", self.url,))
-            .call();
+    /// Get the height of the current blockchain tip, bypassing the cached height, which is
+    /// then updated.
+    pub fn height_uncached(&self) -> Result<u32, Error> {
+        let height = self.fetch_height()?;
+        self.tip_height.set(height);
+        Ok(height)
+    }
 
-        let map = match resp {
-            Ok(resp) => {
-                let map: HashMap<String, f64> = resp.into_json()?;
-                Ok(map)
-            }
-            Err(ureq::Error::Status(code, _)) => Err(Error::HttpResponse(code)),
-            Err(e) => Err(Error::Ureq(e)),
-        }?;
+    fn fetch_height(&self) -> Result<u32, Error> {
+        let path = "/blocks/tip/height";
+        let resp = self.get_endpoint(format_args!("{}", path));
 
-        Ok(map)
+        Ok(into_text(path, resp?)?.parse()?)
     }
 
-    /// Get confirmed transaction history for the specified address/scripthash,
-    /// sorted with newest first. Returns 25 transactions per page.
-    /// More can be requested by specifying the last txid seen by the previous query.
-    pub fn scripthash_txs(
+    /// Get the number of confirmations of a transaction given its [`Txid`], zero if it is
+    /// unconfirmed.
+    pub fn tx_confirmations(&self, txid: &Txid) -> Result<u32, Error> {
+        let status = self.tx_status(txid)?;
+        let block_height = match status.block_height {
+            Some(block_height) if status.confirmed => block_height,
+            _ => return Ok(0),
+        };
+        let tip = self.height()?;
+        Ok(tip.saturating_sub(block_height) + 1)
+    }
+
+    /// Get the confirmations, block hash, height and time of a transaction given its
+    /// [`Txid`], or `None` if the server doesn't know it.
+    ///
+    /// Confirmations are computed from the cached tip height, and are zero while the
+    /// transaction is in the mempool.
+    pub fn tx_confirmation_details(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<ConfirmationDetails>, Error> {
+        let status = match self.tx_status(txid) {
+            Ok(status) => status,
+            Err(err) if err.is_not_found() => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let confirmations = match status.block_height {
+            Some(block_height) if status.confirmed => {
+                self.height()?.saturating_sub(block_height) + 1
+            }
+            _ => 0,
+        };
+        Ok(Some(ConfirmationDetails {
+            confirmations,
+            block_hash: status.block_hash.filter(|_| status.confirmed),
+            block_height: status.block_height.filter(|_| status.confirmed),
+            block_time: status.block_time.filter(|_| status.confirmed),
+        }))
+    }
+
+    /// Check `checkpoints` against the chain of the server, adding one at the tip when it is far
+    /// enough above the highest one.
+    ///
+    /// When the highest checkpoint is no longer in the chain, the highest one still in it is
+    /// looked up with a binary search, and the checkpoints above it are dropped and reported in
+    /// [`CheckpointUpdate::Reorg`]. The fork point is then found between the two checkpoints
+    /// from the ancestors of the lowest dropped one.
+    pub fn update_checkpoints(
+        &self,
+        checkpoints: &mut Checkpoints,
+    ) -> Result<CheckpointUpdate, Error> {
+        let tip = self.height_uncached()?;
+        let on_chain = match checkpoints.latest() {
+            Some(latest) => self.is_on_chain(latest, tip)?,
+            None => true,
+        };
+
+        if !on_chain {
+            // the checkpoint at `high` is known not to be in the chain
+            let (mut low, mut high) = (0, checkpoints.len() - 1);
+            while low < high {
+                let mid = (low + high) / 2;
+                if self.is_on_chain(checkpoints.get(mid), tip)? {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            let surviving = high.checked_sub(1).map(|index| checkpoints.get(index).0);
+            let dropped = checkpoints.drop_from(high);
+            let fork_height = self.fork_height(surviving, dropped[0], tip)?;
+            return Ok(CheckpointUpdate::Reorg {
+                fork_height,
+                dropped,
+            });
+        }
+
+        if !checkpoints.is_due(tip) {
+            return Ok(CheckpointUpdate::Unchanged);
+        }
+        let hash = self.block_hash(tip)?;
+        checkpoints.push(tip, hash);
+        Ok(CheckpointUpdate::Added { height: tip, hash })
+    }
+
+    /// Finds the height of the highest block of the chain whose tip is at height `tip` which is
+    /// an ancestor of the `dropped` checkpoint, at or above the `surviving` checkpoint.
+    ///
+    /// The ancestors are walked down from the dropped checkpoint, then the highest one still in
+    /// the chain is looked up with a binary search. If the server no longer serves them, the
+    /// surviving checkpoint is taken as the fork. Without one, the fork is somewhere below all
+    /// the checkpoints, and the height right below the dropped one is reported.
+    fn fork_height(
+        &self,
+        surviving: Option<u32>,
+        (height, hash): (u32, BlockHash),
+        tip: u32,
+    ) -> Result<u32, Error> {
+        let floor = match surviving {
+            Some(floor) => floor,
+            None => return Ok(height.saturating_sub(1)),
+        };
+        // hashes of the stale branch, from `height` down to `floor + 1`
+        let mut branch = vec![hash];
+        for _ in floor + 1..height {
+            let last = branch[branch.len() - 1];
+            match self.header_by_hash(&last) {
+                Ok(header) => branch.push(header.prev_block_hash),
+                Err(err) if err.kind() == ErrorKind::NotFound => return Ok(floor),
+                Err(err) => return Err(err),
+            }
+        }
+        // the blocks of the branch still in the chain are the ones up to the fork
+        let (mut low, mut high) = (floor, height);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            let hash = branch[(height - mid) as usize];
+            if self.is_on_chain((mid, hash), tip)? {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
+    }
+
+    /// Whether the block `hash` at `height` is in the chain whose tip is at height `tip`.
+    fn is_on_chain(&self, (height, hash): (u32, BlockHash), tip: u32) -> Result<bool, Error> {
+        if height > tip {
+            return Ok(false);
+        }
+        Ok(self.block_hash(height)? == hash)
+    }
+
+    /// Get the [`BlockHash`] of the current blockchain tip.
+    ///
+    /// After a [`BlockingClient::warm_up`], the prefetched hash is returned while it is fresh.
+    pub fn tip_hash(&self) -> Result<BlockHash, Error> {
+        if let Some(hash) = self.warm.tip_hash.get() {
+            return Ok(hash);
+        }
+        self.fetch_tip_hash()
+    }
+
+    fn fetch_tip_hash(&self) -> Result<BlockHash, Error> {
+        let path = "/blocks/tip/hash";
+        let resp = self.get_endpoint(format_args!("{}", path));
+
+        process_block_result(path, resp)
+    }
+
+    /// Get the [`BlockHash`] of a specific block height
+    pub fn block_hash(&self, block_height: u32) -> Result<BlockHash, Error> {
+        let path = format!("/block-height/{}", block_height);
+        let resp = self.get_endpoint(format_args!("{}", path));
+
+        if let Err(Error::HttpResponse { status, .. }) = resp {
+            if is_status_not_found(status) {
+                return Err(Error::HeaderHeightNotFound(block_height));
+            }
+        }
+
+        process_block_result(&path, resp)
+    }
+
+    /// Get a page of the transaction history of a script as [`BlockingClient::scripthash_txs`]
+    /// does, continuing after `cursor`, along with the cursor of the next page, or `None` if
+    /// this is the last page.
+    ///
+    /// Fails with [`Error::InvalidCursor`] if `cursor` continues another kind of history.
+    pub fn scripthash_txs_cursor(
+        &self,
+        script: &ScriptPubkey,
+        cursor: Option<&crate::Cursor>,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        self.txs_page_cursor(CursorFamily::ScriptTxs, script, cursor, false)
+    }
+
+    /// Get a page of the transaction history of an [`Address`] as
+    /// [`BlockingClient::scripthash_txs_cursor`] does for its script.
+    #[cfg(feature = "addresses")]
+    pub fn address_txs_cursor(
+        &self,
+        address: &Address,
+        cursor: Option<&crate::Cursor>,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        self.txs_page_cursor(
+            CursorFamily::AddressTxs,
+            &address.script_pubkey(),
+            cursor,
+            false,
+        )
+    }
+
+    /// Get a page of the confirmed transaction history of a script as
+    /// [`BlockingClient::scripthash_txs_confirmed`] does, continuing after `cursor`, along with the
+    /// cursor of the next page, or `None` if this is the last page.
+    ///
+    /// Fails with [`Error::InvalidCursor`] if `cursor` continues another kind of history.
+    pub fn scripthash_txs_confirmed_cursor(
+        &self,
+        script: &ScriptPubkey,
+        cursor: Option<&crate::Cursor>,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        self.txs_page_cursor(CursorFamily::ScriptTxs, script, cursor, true)
+    }
+
+    /// Get a page of the confirmed transaction history of an [`Address`] as
+    /// [`BlockingClient::scripthash_txs_confirmed_cursor`] does for its script.
+    #[cfg(feature = "addresses")]
+    pub fn address_txs_confirmed_cursor(
+        &self,
+        address: &Address,
+        cursor: Option<&crate::Cursor>,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        self.txs_page_cursor(
+            CursorFamily::AddressTxs,
+            &address.script_pubkey(),
+            cursor,
+            true,
+        )
+    }
+
+    /// Gets a page of the history of `script` after `cursor`, only of its confirmed
+    /// transactions if `confirmed`, the chain tip being recorded in the cursors when the first
+    /// page is requested.
+    fn txs_page_cursor(
+        &self,
+        family: CursorFamily,
+        script: &ScriptPubkey,
+        cursor: Option<&crate::Cursor>,
+        confirmed: bool,
+    ) -> Result<(Vec<crate::Tx>, Option<crate::Cursor>), Error> {
+        let (last_seen, tip) = match cursor {
+            Some(cursor) => {
+                cursor.check_family(family)?;
+                (Some(cursor.last_seen), cursor.tip)
+            }
+            None => (None, self.tip_hash()?),
+        };
+        let page = if confirmed {
+            self.scripthash_txs_confirmed(script, last_seen)?
+        } else {
+            self.scripthash_txs(script, last_seen)?
+        };
+        let next = next_page_cursor(&page).map(|last_seen| crate::Cursor {
+            family,
+            last_seen,
+            tip,
+        });
+        Ok((page, next))
+    }
+
+    /// Get an map where the key is the confirmation target (in number of blocks)
+    /// and the value is the estimated feerate (in sat/vB).
+    ///
+    /// Estimates from the configured [`FeeFallback`] are merged in when the server doesn't
+    /// provide some of the standard confirmation targets.
+    pub fn fee_estimates(&self) -> Result<HashMap<String, f64>, Error> {
+        Ok(self.fee_estimates_with_source()?.estimates)
+    }
+
+    /// Get fee estimates as [`BlockingClient::fee_estimates`] does, tagged with whether they
+    /// come from the server or were synthesized using the configured [`FeeFallback`].
+    ///
+    /// After a [`BlockingClient::warm_up`], the prefetched estimates are returned while they
+    /// are fresh.
+    pub fn fee_estimates_with_source(&self) -> Result<FeeEstimates, Error> {
+        if let Some(estimates) = self.warm.fee_estimates.get() {
+            return Ok(estimates);
+        }
+        self.fetch_fee_estimates_with_source()
+    }
+
+    fn fetch_fee_estimates_with_source(&self) -> Result<FeeEstimates, Error> {
+        let primary = self.fetch_fee_estimates(&self.current_url())?;
+        let fallback = match &self.fee_fallback {
+            Some(fallback) if !has_all_fee_targets(&primary) => fallback,
+            _ => return Ok(FeeEstimates::primary(primary)),
+        };
+        let secondary = match fallback {
+            FeeFallback::Secondary(url) => match self.fetch_fee_estimates(url) {
+                Ok(estimates) => estimates,
+                Err(e) => {
+                    debug!("unable to get fee estimates from {}: {}", url, e);
+                    HashMap::new()
+                }
+            },
+            fallback => fallback.local_estimates(),
+        };
+        Ok(FeeEstimates::merge(primary, secondary))
+    }
+
+    fn fetch_fee_estimates(&self, base_url: &str) -> Result<HashMap<String, f64>, Error> {
+        let url = format!("{}/fee-estimates", base_url);
+        if let Some(cache) = self.fee_cache.as_deref() {
+            let body = self
+                .get_validated(Some(cache), &url)?
+                .ok_or_else(|| Error::http_response(404, ""))?;
+            return Ok(parse_fee_estimates(&body)?);
+        }
+        let resp = self.get_with_retry(&url)?;
+        Ok(parse_fee_estimates(&into_bytes(resp)?)?)
+    }
+
+    /// Get the [`BlockSummary`] of a block given its [`BlockHash`].
+    pub fn block_summary(&self, block_hash: &BlockHash) -> Result<Option<BlockSummary>, Error> {
+        let resp = self.get_endpoint(format_args!("/block/{}", block_hash));
+
+        match resp {
+            Ok(resp) => Ok(Some(resp.into_json()?)),
+            Err(Error::HttpResponse { status, .. }) if is_status_not_found(status) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the [`Txid`]s of the transactions of a block given its [`BlockHash`], in block order.
+    pub fn block_txids(&self, block_hash: &BlockHash) -> Result<Vec<Txid>, Error> {
+        let resp = self.get_endpoint(format_args!("/block/{}/txids", block_hash));
+
+        Ok(resp?.into_json()?)
+    }
+
+    /// Get the [`BlockFeeStats`] of a block given its [`BlockHash`].
+    ///
+    /// This is costly, as all the transactions of the block are fetched, a page of 25 at a time,
+    /// after the summary of the block giving their count. Unless `force` is set, fails with
+    /// [`Error::BlockTooLarge`] before fetching them when the block has more than
+    /// [`MAX_FEE_STATS_TXS`] transactions.
+    pub fn block_fee_stats(
+        &self,
+        block_hash: &BlockHash,
+        force: bool,
+    ) -> Result<BlockFeeStats, Error> {
+        let tx_count = self
+            .block_summary(block_hash)?
+            .ok_or(Error::HeaderHashNotFound(*block_hash))?
+            .tx_count as usize;
+        if !force && tx_count > MAX_FEE_STATS_TXS {
+            return Err(Error::BlockTooLarge {
+                tx_count,
+                limit: MAX_FEE_STATS_TXS,
+            });
+        }
+        let txs = self.block_txs_paged(block_hash, tx_count)?;
+        BlockFeeStats::from_txs(&txs)
+    }
+
+    /// Fetches the `tx_count` transactions of a block, in block order, in pages of 25.
+    fn block_txs_paged(
+        &self,
+        block_hash: &BlockHash,
+        tx_count: usize,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let mut txs = Vec::with_capacity(tx_count);
+        for start in (0..tx_count).step_by(BLOCK_TXS_PAGE_SIZE) {
+            let url = self.endpoint(format_args!("/block/{}/txs/{}", block_hash, start));
+            let page: Vec<crate::Tx> = self.get_with_retry(&url)?.into_json()?;
+            txs.extend(page);
+        }
+        if self.require_anchors {
+            anchor_to_block(&mut txs, block_hash);
+        }
+        Ok(txs)
+    }
+
+    /// Get the transactions of a block matching `filter`, in block order, with the reason why
+    /// they match.
+    ///
+    /// The transactions are fetched in pages of 25, unless the filter is empty, and matched
+    /// locally.
+    pub fn scan_block(
+        &self,
+        block_hash: &BlockHash,
+        filter: &WatchFilter,
+    ) -> Result<Vec<WatchMatch>, Error> {
+        if filter.is_empty() {
+            return Ok(Vec::new());
+        }
+        let tx_count = self
+            .block_summary(block_hash)?
+            .ok_or(Error::HeaderHashNotFound(*block_hash))?
+            .tx_count as usize;
+        let txs = self.block_txs_paged(block_hash, tx_count)?;
+        Ok(filter.scan(&txs))
+    }
+
+    /// Iterate over the blocks mined after the current chain tip, each with its transactions
+    /// matching `filter`, as [`BlockingClient::scan_block`] finds them, blocking until the next
+    /// block is mined.
+    ///
+    /// The tip is polled every `poll`. On a reorg, the blocks of the new best chain are scanned
+    /// from the fork, but the ones scanned before are not returned again. Errors are returned
+    /// without ending the iteration, the failed block being retried after `poll`, except
+    /// [`Error::ClientClosed`]: the iteration ends once the client is shut down.
+    pub fn watch_chain(
+        &self,
+        filter: WatchFilter,
+        poll: Duration,
+    ) -> impl Iterator<Item = Result<WatchedBlock, Error>> + '_ {
+        let mut watch: Option<ChainWatch> = None;
+        let mut failed = false;
+        std::iter::from_fn(move || {
+            if failed {
+                thread::sleep(poll);
+            }
+            loop {
+                let watched = match watch.as_mut() {
+                    Some(state) => self.next_watched_block(state, &filter),
+                    None => self.height_uncached().map(|tip| {
+                        watch = Some(ChainWatch::new(tip));
+                        None
+                    }),
+                };
+                failed = watched.is_err();
+                match watched {
+                    Ok(Some(block)) => return Some(Ok(block)),
+                    Ok(None) => thread::sleep(poll),
+                    Err(Error::ClientClosed) => return None,
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+        })
+    }
+
+    /// Scans the next block of the chain for [`BlockingClient::watch_chain`], or returns `None`
+    /// if the chain doesn't reach it yet.
+    fn next_watched_block(
+        &self,
+        watch: &mut ChainWatch,
+        filter: &WatchFilter,
+    ) -> Result<Option<WatchedBlock>, Error> {
+        loop {
+            let height = watch.next_height();
+            let hash = match self.block_hash(height) {
+                Ok(hash) => hash,
+                Err(Error::HeaderHeightNotFound(_)) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            let summary = self
+                .block_summary(&hash)?
+                .ok_or(Error::HeaderHashNotFound(hash))?;
+            match watch.step(&summary) {
+                WatchStep::Skip | WatchStep::Rewind => continue,
+                WatchStep::Scan => {
+                    let matches = self.scan_block(&hash, filter)?;
+                    watch.scanned(height, hash);
+                    return Ok(Some(WatchedBlock {
+                        height,
+                        hash,
+                        matches,
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Get the [`BlockSummary`]s of the blocks at the given heights, in the same order.
+    ///
+    /// Heights above the current tip are skipped, so fewer summaries than requested heights
+    /// may be returned.
+    pub fn block_summaries_at(&self, heights: &[u32]) -> Result<Vec<BlockSummary>, Error> {
+        let mut summaries = Vec::with_capacity(heights.len());
+        for height in heights {
+            if let Some(summary) = self.block_at_height(*height)? {
+                summaries.push(summary);
+            }
+        }
+        Ok(summaries)
+    }
+
+    /// Get the [`BlockSummary`] of the block at `height` in the best chain, or `None` if the
+    /// chain doesn't reach that height.
+    pub fn block_at_height(&self, height: u32) -> Result<Option<BlockSummary>, Error> {
+        let block_hash = match self.block_hash(height) {
+            Ok(block_hash) => block_hash,
+            Err(Error::HeaderHeightNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        self.block_summary(&block_hash)
+    }
+
+    /// Get confirmed transaction history for the specified address/scripthash,
+    /// sorted with newest first. Returns 25 transactions per page.
+    /// More can be requested by specifying the last txid seen by the previous query.
+    pub fn scripthash_txs(
         &self,
         script: &ScriptPubkey,
         last_seen: Option<Txid>,
@@ -331,54 +1652,900 @@ impl BlockingClient {
         hasher.update(script);
         let script_hash = hasher.finalize();
         let url = match last_seen {
-            Some(last_seen) => format!(
-                "{}/scripthash/{:x}/txs/chain/{}",
-                self.url, script_hash, last_seen
-            ),
-            None => format!("{}/scripthash/{:x}/txs", self.url, script_hash),
+            Some(last_seen) => self.endpoint(format_args!(
+                "/scripthash/{:x}/txs/chain/{}",
+                script_hash, last_seen
+            )),
+            None => self.endpoint(format_args!("/scripthash/{:x}/txs", script_hash)),
         };
-        Ok(self.agent.get(&url).call()?.into_json()?)
+        let mut txs: Vec<crate::Tx> = self.get_with_retry(&url)?.into_json()?;
+        self.fill_anchors(tx_statuses(&mut txs))?;
+        Ok(txs)
     }
 
-    /// Get confirmed transaction history for the specified address/scripthash,
-    /// sorted with newest first. Returns 25 transactions per page.
+    /// Get the unconfirmed transactions of the specified address/scripthash.
+    pub fn scripthash_mempool_txs(&self, script: &ScriptPubkey) -> Result<Vec<crate::Tx>, Error> {
+        let mut hasher = Sha256::default();
+        hasher.update(script);
+        let script_hash = hasher.finalize();
+        let url = self.endpoint(format_args!("/scripthash/{:x}/txs/mempool", script_hash));
+        Ok(self.get_with_retry(&url)?.into_json()?)
+    }
+
+    /// Get the statistics of the outputs funded and spent by a script.
+    pub fn scripthash_stats(&self, script: &ScriptPubkey) -> Result<crate::AddressStats, Error> {
+        let mut hasher = Sha256::default();
+        hasher.update(script);
+        let script_hash = hasher.finalize();
+        let url = self.endpoint(format_args!("/scripthash/{:x}", script_hash));
+        Ok(self.get_with_retry(&url)?.into_json()?)
+    }
+
+    /// Get the backlog of the mempool, including its fee histogram.
+    pub fn mempool(&self) -> Result<MempoolStats, Error> {
+        Ok(self
+            .get_with_retry(&self.endpoint(format_args!("/mempool")))?
+            .into_json()?)
+    }
+
+    /// Get the feerate (in sat/vB) needed for a transaction to be part of the next block,
+    /// from the fee histogram of the mempool.
+    ///
+    /// Unlike the estimates by confirmation target of [`BlockingClient::fee_estimates`], this
+    /// reflects the current content of the mempool. The block is assumed to be as large as set
+    /// with [`Builder::block_vsize`], see [`MempoolStats::next_block_feerate`].
+    pub fn suggested_feerate_for_next_block(&self) -> Result<f32, Error> {
+        Ok(self.mempool()?.next_block_feerate(self.block_vsize))
+    }
+
+    /// Get the [`Txid`]s of all the transactions in the mempool.
+    pub fn mempool_txids(&self) -> Result<Vec<Txid>, Error> {
+        Ok(self
+            .get_with_retry(&self.endpoint(format_args!("/mempool/txids")))?
+            .into_json()?)
+    }
+
+    /// Get confirmed transaction history for the specified address/scripthash, sorted with
+    /// newest first, excluding mempool transactions. Returns 25 transactions per page.
     /// More can be requested by specifying the last txid seen by the previous query.
+    pub fn scripthash_txs_confirmed(
+        &self,
+        script: &ScriptPubkey,
+        last_seen: Option<Txid>,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let mut hasher = Sha256::default();
+        hasher.update(script);
+        let script_hash = hasher.finalize();
+        let url = match last_seen {
+            Some(last_seen) => self.endpoint(format_args!(
+                "/scripthash/{:x}/txs/chain/{}",
+                script_hash, last_seen
+            )),
+            None => self.endpoint(format_args!("/scripthash/{:x}/txs/chain", script_hash)),
+        };
+        let mut txs: Vec<crate::Tx> = self.get_with_retry(&url)?.into_json()?;
+        self.fill_anchors(tx_statuses(&mut txs))?;
+        Ok(txs)
+    }
+
+    /// Get confirmed transaction history for the specified address, sorted with newest first.
+    /// Returns 25 transactions per page. More can be requested by specifying the last txid seen
+    /// by the previous query.
+    ///
+    /// Unlike the `/address/:address/txs` endpoint, whose first page also lists up to 50
+    /// mempool transactions, this always queries `/address/:address/txs/chain`, so no
+    /// unconfirmed transaction is ever returned.
+    #[cfg(feature = "addresses")]
+    pub fn address_txs_confirmed(
+        &self,
+        address: &Address,
+        last_seen: Option<Txid>,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let url = match last_seen {
+            Some(last_seen) => {
+                self.endpoint(format_args!("/address/{}/txs/chain/{}", address, last_seen))
+            }
+            None => self.endpoint(format_args!("/address/{}/txs/chain", address)),
+        };
+        let mut txs: Vec<crate::Tx> = self.get_with_retry(&url)?.into_json()?;
+        self.fill_anchors(tx_statuses(&mut txs))?;
+        Ok(txs)
+    }
+
+    /// Get a page of the transaction history of an [`Address`] as
+    /// [`TxSummary`](crate::TxSummary) values, with their effect on the balance of
+    /// `my_scripts`, as listed by `/address/:address/txs` or after `last_seen` by
+    /// `/address/:address/txs/chain/:last_seen`.
+    ///
+    /// Transactions are summarized as soon as they are parsed from the response, so that
+    /// their inputs and outputs are never held in memory all together.
+    #[cfg(feature = "addresses")]
+    pub fn address_tx_summaries(
+        &self,
+        address: &Address,
+        last_seen: Option<Txid>,
+        my_scripts: &HashSet<ScriptPubkey>,
+    ) -> Result<Vec<crate::TxSummary>, Error> {
+        let url = match last_seen {
+            Some(last_seen) => {
+                self.endpoint(format_args!("/address/{}/txs/chain/{}", address, last_seen))
+            }
+            None => self.endpoint(format_args!("/address/{}/txs", address)),
+        };
+        stream_parse_txs(self.get_with_retry(&url)?.into_reader())
+            .map(|tx| tx.and_then(|tx| tx.summarize(my_scripts)))
+            .collect()
+    }
+
+    /// Get a page of the confirmed transaction history of an [`Address`], newest first,
+    /// continuing after `after_txid` and keeping at most `limit` transactions.
+    ///
+    /// Backends supporting it, as detected by [`BlockingClient::backend_info`], are queried with
+    /// the `after_txid` query parameter, and the others with the path form used by
+    /// [`BlockingClient::address_txs_confirmed`].
+    #[cfg(feature = "addresses")]
+    pub fn address_txs_after(
+        &self,
+        address: &Address,
+        after_txid: Option<Txid>,
+        limit: Option<usize>,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let mut txs: Vec<crate::Tx> = match after_txid {
+            Some(after_txid) if self.backend_info()?.supports_query_pagination() => {
+                let query = QueryParams::new().param("after_txid", after_txid);
+                let url = self.endpoint(format_args!("/address/{}/txs{}", address, query));
+                let mut txs: Vec<crate::Tx> = self.get_with_retry(&url)?.into_json()?;
+                self.fill_anchors(tx_statuses(&mut txs))?;
+                txs
+            }
+            _ => self.address_txs_confirmed(address, after_txid)?,
+        };
+        if let Some(limit) = limit {
+            txs.truncate(limit);
+        }
+        Ok(txs)
+    }
+
+    /// Get the `n` most recent transactions of an [`Address`], unconfirmed ones first, then
+    /// confirmed ones newest first.
+    ///
+    /// Pages of the history are only fetched until `n` transactions are found, so that a
+    /// single request is made when the first page, with up to 50 unconfirmed and 25 confirmed
+    /// transactions, has enough of them. Fails with [`Error::PaginationAnomaly`] as
+    /// [`BlockingClient::scripthash_txs_all`] does.
+    #[cfg(feature = "addresses")]
+    pub fn address_txs_latest(&self, address: &Address, n: usize) -> Result<Vec<crate::Tx>, Error> {
+        let script = address.script_pubkey();
+        let mut guard = PageGuard::new(self.max_pages);
+        let mut txs = vec![];
+        let mut last_seen = None;
+        while txs.len() < n {
+            let page = self.scripthash_txs(&script, last_seen)?;
+            let next = guard.next_cursor(&page);
+            txs.extend(page);
+            last_seen =
+                next.map_err(|err| err.with_partial_txs(dedup_history(mem::take(&mut txs))))?;
+            if last_seen.is_none() {
+                break;
+            }
+        }
+        let mut txs = dedup_history(txs);
+        txs.truncate(n);
+        Ok(txs)
+    }
+
+    /// Get the statistics of the outputs funded and spent by an [`Address`].
+    #[cfg(feature = "addresses")]
+    pub fn address_stats(&self, address: &Address) -> Result<crate::AddressStats, Error> {
+        let url = self.endpoint(format_args!("/address/{}", address));
+        Ok(self.get_with_retry(&url)?.into_json()?)
+    }
+
+    /// Get the statistics of several [`Address`]es, in the same order, e.g. to sum their
+    /// balances with [`total_balance`](crate::total_balance).
+    #[cfg(feature = "addresses")]
+    pub fn addresses_stats(
+        &self,
+        addresses: &[Address],
+    ) -> Result<Vec<crate::AddressStats>, Error> {
+        addresses
+            .iter()
+            .map(|address| self.address_stats(address))
+            .collect()
+    }
+
+    /// Get the balance of an [`Address`] cross-checked against the sum of its unspent
+    /// outputs, which can disagree when they are requested across a new block or from a
+    /// lagging backend.
+    ///
+    /// The check is made once more if the chain tip changed while requesting them. A
+    /// discrepancy remaining after that isn't an error, but is reported in the returned
+    /// [`VerifiedBalance`](crate::VerifiedBalance) so that it can be monitored.
+    #[cfg(feature = "addresses")]
+    pub fn verified_balance(&self, address: &Address) -> Result<crate::VerifiedBalance, Error> {
+        let script = address.script_pubkey();
+        let mut retried = false;
+        loop {
+            let tip = self.fetch_tip_hash()?;
+            let stats = self.address_stats(address)?;
+            let utxos = self.scripthash_utxo(&script)?;
+            let tip_after = self.fetch_tip_hash()?;
+            let balance = crate::VerifiedBalance::check(&stats, &utxos, tip_after)?;
+            if balance.verified || retried || tip_after == tip {
+                return Ok(balance);
+            }
+            debug!(
+                "balance of {} doesn't match its unspent outputs across a new block, checking again",
+                address
+            );
+            retried = true;
+        }
+    }
+
+    /// Get the unspent outputs of an [`Address`] along with the status of their spending
+    /// transaction, if they were already spent by an unconfirmed one, e.g. to detect double
+    /// spends or outputs not worth sweeping anymore.
+    ///
+    /// Outputs are checked one after the other.
+    #[cfg(feature = "addresses")]
+    pub fn address_utxo_spend_status(
+        &self,
+        address: &Address,
+    ) -> Result<Vec<(Utxo, Option<OutputStatus>)>, Error> {
+        self.scripthash_utxo(&address.script_pubkey())?
+            .into_iter()
+            .map(|utxo| {
+                let spend = self
+                    .outspend(&utxo.txid, utxo.vout)?
+                    .filter(|status| status.spent);
+                Ok((utxo, spend))
+            })
+            .collect()
+    }
+
+    /// Check whether an [`Address`] appears in any confirmed or unconfirmed transaction.
+    ///
+    /// This only requests the address statistics, without fetching any page of transactions,
+    /// which makes it much cheaper than [`BlockingClient::address_txs_confirmed`] to decide
+    /// whether a wallet scan reached its gap limit. The [`SyncCache`] is used as in
+    /// [`BlockingClient::script_has_history`].
+    #[cfg(feature = "addresses")]
+    pub fn address_has_history(&self, address: &Address) -> Result<bool, Error> {
+        self.script_has_history(&address.script_pubkey())
+    }
+
+    /// Check whether a script appears in any confirmed or unconfirmed transaction, from its
+    /// statistics.
+    ///
+    /// When the client has a [`SyncCache`], a state still valid at the current tip, identified
+    /// by its height and hash, is reused without requesting the statistics, and the checked
+    /// state is recorded otherwise.
+    pub fn script_has_history(&self, script: &ScriptPubkey) -> Result<bool, Error> {
+        let cache = match &self.sync_cache {
+            Some(cache) => cache,
+            None => return Ok(self.scripthash_stats(script)?.has_history()),
+        };
+        let tip = self.height()?;
+        let tip_hash = self.tip_hash()?;
+        cache.observe_tip(tip, tip_hash);
+        if let Some(state) = cache.lookup(script, tip, tip_hash) {
+            return Ok(state.has_history);
+        }
+        let stats = self.scripthash_stats(script)?;
+        cache.record_stats(script, &stats, tip, tip_hash);
+        Ok(stats.has_history())
+    }
+
+    /// Get statistics about an address given as a string.
+    ///
+    /// Fails with [`Error::InvalidInput`] before making any request if the address can't be
+    /// parsed or is for another network than the one set with [`Builder::network`].
+    #[cfg(feature = "addresses")]
+    pub fn address_stats_str(&self, address: &str) -> Result<crate::AddressStats, Error> {
+        let address = self.parse_address(address)?;
+        self.address_stats(&address)
+    }
+
+    /// Get confirmed transaction history for an address given as a string, and optionally
+    /// the last txid seen as a hex string.
+    ///
+    /// Fails with [`Error::InvalidInput`] before making any request if the address or the txid
+    /// can't be parsed, or if the address is for another network than the one set with
+    /// [`Builder::network`].
+    #[cfg(feature = "addresses")]
+    pub fn address_txs_confirmed_str(
+        &self,
+        address: &str,
+        last_seen: Option<&str>,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let address = self.parse_address(address)?;
+        let last_seen = match last_seen {
+            Some(txid) => Some(parse_input("last seen txid", txid)?),
+            None => None,
+        };
+        self.address_txs_confirmed(&address, last_seen)
+    }
+
+    /// Parse an address given as a string, checking it is for the network of the client.
+    #[cfg(feature = "addresses")]
+    pub(crate) fn parse_address(&self, address: &str) -> Result<Address, Error> {
+        parse_address(self.network, address)
+    }
+
+    /// Get the transactions of a block given its [`BlockHash`], parsing the response
+    /// incrementally.
+    ///
+    /// Meant for servers returning all the transactions in a single response, which is then
+    /// never held in memory at once.
+    pub fn block_txs_streamed(
+        &self,
+        block_hash: &BlockHash,
+    ) -> Result<impl Iterator<Item = Result<crate::Tx, Error>> + Send, Error> {
+        let resp = self.get_endpoint(format_args!("/block/{}/txs", block_hash))?;
+        Ok(stream_parse_txs(resp.into_reader()))
+    }
+
+    /// Get the transaction history for the specified address/scripthash, as returned by
+    /// `/scripthash/:hash/txs`, parsing the response incrementally.
+    ///
+    /// Meant for servers returning the whole history in a single response, which is then
+    /// never held in memory at once.
+    pub fn scripthash_txs_streamed(
+        &self,
+        script: &ScriptPubkey,
+    ) -> Result<impl Iterator<Item = Result<crate::Tx, Error>> + Send, Error> {
+        let mut hasher = Sha256::default();
+        hasher.update(script);
+        let script_hash = hasher.finalize();
+        let resp = self.get_endpoint(format_args!("/scripthash/{:x}/txs", script_hash))?;
+        Ok(stream_parse_txs(resp.into_reader()))
+    }
+
+    /// Get the full transaction history for the specified address/scripthash, sorted with
+    /// newest first, following the pagination of the confirmed history until its end.
+    ///
+    /// Transactions repeated across pages are deduplicated with the precedence of
+    /// [`merge_histories`](crate::merge_histories), keeping the order of the server.
+    ///
+    /// Fails with [`Error::PaginationAnomaly`] when the server keeps paginating past the
+    /// configured page limit or returns pages which would never end.
+    /// The transactions fetched until then can be recovered with [`Error::partial_txs`].
+    ///
+    /// When the client has a [`SyncCache`], the history is recorded in it.
+    pub fn scripthash_txs_all(&self, script: &ScriptPubkey) -> Result<Vec<crate::Tx>, Error> {
+        let mut guard = PageGuard::new(self.max_pages);
+        let mut txs = vec![];
+        let mut last_seen = None;
+        loop {
+            let page = self.scripthash_txs(script, last_seen)?;
+            let next = guard.next_cursor(&page);
+            txs.extend(page);
+            last_seen =
+                next.map_err(|err| err.with_partial_txs(dedup_history(mem::take(&mut txs))))?;
+            if last_seen.is_none() {
+                break;
+            }
+        }
+        let txs = dedup_history(txs);
+        if let Some(cache) = &self.sync_cache {
+            cache.record_history(script, &txs);
+        }
+        Ok(txs)
+    }
+
+    /// Get the transactions of several scripts, newest first across all of them, by merging
+    /// their paginated histories.
+    ///
+    /// The pages of each script are fetched lazily, as the iterator advances, only as far as
+    /// needed to tell which transaction comes next, and a transaction touching several of the
+    /// scripts is only returned once. Transactions are ordered with
+    /// [`cmp_tx_for_display`](crate::cmp_tx_for_display), the iterator ending after the first
+    /// error.
+    pub fn wallet_history_iter(
+        &self,
+        scripts: Vec<ScriptPubkey>,
+    ) -> impl Iterator<Item = Result<crate::Tx, Error>> + '_ {
+        let mut merge = Some(HistoryMerge::new(scripts, self.max_pages));
+        std::iter::from_fn(move || {
+            let state = merge.as_mut()?;
+            while let Some((index, script, cursor)) = state.pending() {
+                let pushed = self
+                    .scripthash_txs(&script, cursor)
+                    .and_then(|page| state.push_page(index, page));
+                if let Err(err) = pushed {
+                    merge = None;
+                    return Some(Err(err));
+                }
+            }
+            state.pop().map(Ok)
+        })
+    }
+
+    /// Get the unspent outputs of the specified address/scripthash.
+    ///
+    /// When the client has a [`SyncCache`], the outputs are recorded in it.
     pub fn scripthash_utxo(&self, script: &ScriptPubkey) -> Result<Vec<Utxo>, Error> {
         let mut hasher = Sha256::default();
         hasher.update(script);
         let script_hash = hasher.finalize();
-        let url = format!("{}/scripthash/{:x}/utxo", self.url, script_hash);
-        Ok(self.agent.get(&url).call()?.into_json()?)
+        let url = self.endpoint(format_args!("/scripthash/{:x}/utxo", script_hash));
+        let mut utxos: Vec<Utxo> = self.get_with_retry(&url)?.into_json()?;
+        self.fill_anchors(
+            utxos
+                .iter_mut()
+                .map(|utxo| (utxo.txid, &mut utxo.status))
+                .collect(),
+        )?;
+        self.check_utxos(&utxos)?;
+        if let Some(cache) = &self.sync_cache {
+            cache.record_utxos(script, &utxos);
+        }
+        Ok(utxos)
+    }
+
+    /// Get the unspent outputs of a script with at least `min_confirmations`, and the
+    /// unconfirmed ones too if `include_unconfirmed`, annotated with their
+    /// [`Utxo::confirmations`].
+    ///
+    /// Confirmations are counted from the chain tip height, reused from the tip cache while it
+    /// is fresh, as [`BlockingClient::height`] does.
+    pub fn scripthash_utxo_filtered(
+        &self,
+        script: &ScriptPubkey,
+        min_confirmations: u32,
+        include_unconfirmed: bool,
+    ) -> Result<Vec<Utxo>, Error> {
+        let utxos = self.scripthash_utxo(script)?;
+        let tip = self.height()?;
+        Ok(Utxo::filter_by_confirmations(
+            utxos,
+            tip,
+            min_confirmations,
+            include_unconfirmed,
+        ))
+    }
+
+    /// Get the unspent outputs of an [`Address`] filtered by confirmations, as
+    /// [`BlockingClient::scripthash_utxo_filtered`] does.
+    #[cfg(feature = "addresses")]
+    pub fn address_utxo_filtered(
+        &self,
+        address: &Address,
+        min_confirmations: u32,
+        include_unconfirmed: bool,
+    ) -> Result<Vec<Utxo>, Error> {
+        self.scripthash_utxo_filtered(
+            &address.script_pubkey(),
+            min_confirmations,
+            include_unconfirmed,
+        )
+    }
+
+    /// Get a [`UtxoSnapshot`] of the unspent outputs of a script at the current chain tip.
+    pub fn utxo_snapshot(&self, script: &ScriptPubkey) -> Result<UtxoSnapshot, Error> {
+        let tip_height = self.height_uncached()?;
+        let tip_hash = self.block_hash(tip_height)?;
+        Ok(UtxoSnapshot {
+            tip_height,
+            tip_hash,
+            utxos: self.scripthash_utxo(script)?,
+        })
+    }
+
+    /// Get the changes of the unspent outputs of a script since the `old` snapshot, flagging a
+    /// reorg if its tip is no longer in the best chain, along with the snapshot of the current
+    /// outputs to diff against next time.
+    pub fn utxo_diff_since(
+        &self,
+        script: &ScriptPubkey,
+        old: &UtxoSnapshot,
+    ) -> Result<(UtxoDiff, UtxoSnapshot), Error> {
+        let new = self.utxo_snapshot(script)?;
+        let mut diff = diff_utxos(&old.utxos, &new.utxos);
+        diff.reorg = !self.is_on_chain((old.tip_height, old.tip_hash), new.tip_height)?;
+        Ok((diff, new))
+    }
+
+    /// Get the unspent outputs of a script worth spending at `feerate` (in sat/vB), valued net
+    /// of the fee of the input spending them.
+    ///
+    /// The input weight is derived from the type of the script, see
+    /// [`ScriptType::input_weight`], unless given in `opts`, which is required for the types
+    /// whose spending cost depends on the script. Outputs are filtered according to `opts`,
+    /// and the excluded ones are counted in the returned [`SpendableSet`].
+    pub fn spendable_utxos(
+        &self,
+        script: &ScriptPubkey,
+        feerate: f64,
+        opts: SpendableOpts,
+    ) -> Result<SpendableSet, Error> {
+        let utxos = self.scripthash_utxo(script)?;
+        let tip = if opts.min_confirmations > 1 {
+            Some(self.height()?)
+        } else {
+            None
+        };
+        SpendableSet::evaluate(&ScriptType::from_script(script), utxos, feerate, &opts, tip)
+    }
+
+    /// Get the average time between the last `sample` blocks, computed from the timestamps of
+    /// the newest and the oldest of them.
+    ///
+    /// Fewer blocks are taken into account when the chain is shorter than `sample` blocks, and
+    /// a zero duration is returned when it has a single block.
+    pub fn average_block_interval(&self, sample: usize) -> Result<Duration, Error> {
+        let summaries = self.walk_blocks(None, sample.saturating_add(1))?;
+        Ok(BlockSummary::average_interval(&summaries))
+    }
+
+    /// Get the summaries of the `count` blocks preceding `height`, from the newest to the
+    /// oldest, fewer if the genesis block is reached.
+    ///
+    /// Pages of [`Self::blocks`] are walked from the oldest summary of each, so that the
+    /// walk has no gap nor overlap whatever the number of summaries the backend returns per
+    /// page.
+    pub fn blocks_before(&self, height: u32, count: usize) -> Result<Vec<BlockSummary>, Error> {
+        match height.checked_sub(1) {
+            Some(height) => self.walk_blocks(Some(height), count),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Walks the pages of block summaries backward from `height`, or from the tip, until
+    /// `count` summaries are collected or the genesis block is reached.
+    fn walk_blocks(
+        &self,
+        mut height: Option<u32>,
+        count: usize,
+    ) -> Result<Vec<BlockSummary>, Error> {
+        let mut summaries = Vec::with_capacity(count.min(MAX_PREALLOCATED_BLOCKS));
+        while summaries.len() < count {
+            let mut page = self.blocks(height)?;
+            if let Some(height) = height {
+                // summaries above the requested height were already collected
+                page.retain(|summary| summary.time.height <= height);
+            }
+            let oldest = match page.last() {
+                Some(oldest) => oldest.time.height,
+                None => break,
+            };
+            summaries.extend(page);
+            if oldest == 0 {
+                break;
+            }
+            height = Some(oldest - 1);
+        }
+        summaries.truncate(count);
+        Ok(summaries)
     }
 
     /// Gets some recent block summaries starting at the tip or at `height` if provided.
     ///
     /// The maximum number of summaries returned depends on the backend itself: esplora returns `10`
     /// while [mempool.space](https://mempool.space/docs/api) returns `15`.
+    ///
+    /// After a [`BlockingClient::warm_up`], the prefetched summaries of the latest blocks are
+    /// returned while they are fresh.
     pub fn blocks(&self, height: Option<u32>) -> Result<Vec<BlockSummary>, Error> {
+        if height.is_none() {
+            if let Some(blocks) = self.warm.recent_blocks.get() {
+                return Ok(blocks);
+            }
+        }
+        self.fetch_blocks(height)
+    }
+
+    fn fetch_blocks(&self, height: Option<u32>) -> Result<Vec<BlockSummary>, Error> {
         let url = match height {
-            Some(height) => format!("{}/blocks/{}", self.url, height),
-            None => format!("{}/blocks", self.url),
+            Some(height) => self.endpoint(format_args!("/blocks/{}", height)),
+            None => self.endpoint(format_args!("/blocks")),
         };
 
-        Ok(self.agent.get(&url).call()?.into_json()?)
+        Ok(self.get_with_retry(&url)?.into_json()?)
+    }
+
+    /// Concurrently fetch the resources selected with [`Builder::prefetch`], so that the first
+    /// queries for them are answered from memory during [`Builder::tip_cache_ttl`].
+    ///
+    /// Each resource is fetched on its own thread. Failures are only reported, and resources
+    /// not fetched within [`Builder::warm_up_budget`] are reported as skipped and left
+    /// uncached: the requests of the threads are bounded by the budget, and resources they
+    /// fetch after it ran out are dropped.
+    pub fn warm_up(&self) -> WarmUpReport {
+        let deadline = Instant::now() + self.warm_up_budget;
+        let mut pending = self.prefetch.items();
+        let (sender, receiver) = mpsc::channel();
+        for item in &pending {
+            let (mut client, sender, item) = (self.clone(), sender.clone(), *item);
+            client.deadline = Some(deadline);
+            thread::spawn(move || {
+                // the receiver is gone when the budget ran out first
+                let _ = sender.send((item, client.prefetch_item(item)));
+            });
+        }
+
+        let mut report = WarmUpReport::default();
+        while !pending.is_empty() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(timeout) {
+                Ok((item, result)) => {
+                    pending.retain(|pending| *pending != item);
+                    report.record(item, result.map(|prefetched| self.store(prefetched)));
+                }
+                Err(_) => break,
+            }
+        }
+        report.skipped = pending;
+        report
     }
 
-    /// Get the underlying base URL.
+    fn prefetch_item(&self, item: PrefetchItem) -> Result<Prefetched, Error> {
+        Ok(match item {
+            PrefetchItem::TipHeight => Prefetched::TipHeight(self.fetch_height()?),
+            PrefetchItem::TipHash => Prefetched::TipHash(self.fetch_tip_hash()?),
+            PrefetchItem::RecentBlocks => Prefetched::RecentBlocks(self.fetch_blocks(None)?),
+            PrefetchItem::FeeEstimates => {
+                Prefetched::FeeEstimates(self.fetch_fee_estimates_with_source()?)
+            }
+        })
+    }
+
+    /// Cache a resource fetched for a warm-up.
+    fn store(&self, prefetched: Prefetched) {
+        match prefetched {
+            Prefetched::TipHeight(height) => self.tip_height.set(height),
+            Prefetched::TipHash(hash) => self.warm.tip_hash.set(hash),
+            Prefetched::RecentBlocks(blocks) => self.warm.recent_blocks.set(blocks),
+            Prefetched::FeeEstimates(estimates) => self.warm.fee_estimates.set(estimates),
+        }
+    }
+
+    /// Get a [`DryRunClient`] recording the requests this client would make, without sending
+    /// them.
+    pub fn dry_run(&self) -> DryRunClient {
+        DryRunClient::new(self.current_url(), self.max_pages)
+    }
+
+    /// Get the number of bytes received by the client and its clones, by endpoint category.
+    pub fn bandwidth_used(&self) -> BandwidthUsage {
+        self.bandwidth.usage()
+    }
+
+    /// Get the number of bytes received by the client and its clones, in all endpoint
+    /// categories.
+    ///
+    /// This is approximate: headers aren't counted, and bodies are counted with the size
+    /// announced in `Content-Length`, or their decompressed size when it isn't announced.
+    pub fn bytes_downloaded(&self) -> u64 {
+        self.bandwidth.usage().total()
+    }
+
+    /// Get the number of bytes sent by the client and its clones in the body of requests,
+    /// excluding headers.
+    pub fn bytes_uploaded(&self) -> u64 {
+        self.bandwidth.uploaded()
+    }
+
+    /// Start a new operation, resetting the bytes counted against the per-operation limit of
+    /// the [`BandwidthBudget`](crate::BandwidthBudget), e.g. before each sync of a wallet.
+    pub fn start_bandwidth_operation(&self) {
+        self.bandwidth.start_operation()
+    }
+
+    /// Get the number of requests served from the HTTP cache after the server confirmed the
+    /// cached response was still valid.
+    pub fn conditional_hits(&self) -> u64 {
+        self.http_cache
+            .as_ref()
+            .map(|cache| cache.hits())
+            .unwrap_or_default()
+    }
+
+    /// Get the underlying base URL, as the client was built with.
+    ///
+    /// After a [`BlockingClient::set_base_url`], requests are made to the
+    /// [`BlockingClient::current_url`] instead.
     pub fn url(&self) -> &str {
-        &self.url
+        &self.base_url
+    }
+
+    /// Get the base URL requests are currently made to, which differs from
+    /// [`BlockingClient::url`] once the client was pointed to another server.
+    pub fn current_url(&self) -> String {
+        self.url.read().expect("poisoned base URL").clone()
+    }
+
+    /// Build the URL of the endpoint at `path` in a single allocation, without copying the base
+    /// URL first.
+    fn endpoint(&self, path: fmt::Arguments<'_>) -> String {
+        let base = self.url.read().expect("poisoned base URL");
+        let mut url = String::with_capacity(base.len() + 160);
+        url.push_str(&base);
+        fmt::Write::write_fmt(&mut url, path).expect("writing to a string can't fail");
+        url
+    }
+
+    /// Get the path of `url` relative to the base URL, or `url` itself if it was built before
+    /// the client was pointed to another server.
+    fn relative_path<'a>(&self, url: &'a str) -> &'a str {
+        let base = self.url.read().expect("poisoned base URL");
+        url.strip_prefix(base.as_str()).unwrap_or(url)
+    }
+
+    /// Point the client, and all of its clones, to another Esplora server.
+    ///
+    /// Requests already in flight complete against the previous server, while requests made
+    /// after the call use the new one. The URL is validated like the one given to the
+    /// [`Builder`], and cached information about the previous server is discarded: the backend
+    /// info, block hashes, chain tip, prefetched values and the contents of the read-through
+    /// [`Cache`].
+    pub fn set_base_url(&self, url: String) -> Result<(), Error> {
+        let url = normalize_base_url(&url)?;
+        let previous = std::mem::replace(&mut *self.url.write().expect("poisoned base URL"), url);
+        info!(
+            "switched Esplora base URL from {} to {}",
+            previous,
+            self.current_url()
+        );
+
+        *self.backend.lock().expect("poisoned backend info") = None;
+        self.anchors
+            .lock()
+            .expect("poisoned block hash cache")
+            .clear();
+        if let Some(block_hashes) = &self.block_hashes {
+            block_hashes
+                .lock()
+                .expect("poisoned block hash cache")
+                .clear();
+        }
+        self.tip_height.clear();
+        self.warm.clear();
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+        Ok(())
+    }
+
+    /// Identify the software and version of the Esplora server.
+    ///
+    /// This is a best-effort heuristic probing a few discriminating endpoints and response
+    /// headers; servers which can't be identified are reported as [`BackendFlavor::Unknown`].
+    /// The result is cached after the first call.
+    ///
+    /// [`BackendFlavor::Unknown`]: crate::BackendFlavor::Unknown
+    pub fn backend_info(&self) -> Result<BackendInfo, Error> {
+        let cached = self.backend.lock().expect("poisoned backend info").clone();
+        if let Some(info) = cached {
+            return Ok(info);
+        }
+
+        let _request = self.lifecycle.enter()?;
+        let (request, _) = self.identified(
+            self.agent
+                .get(&self.endpoint(format_args!("/v1/difficulty-adjustment"))),
+        );
+        let difficulty_adjustment = matches!(self.call_redirected(request, &[]), Ok((_, Ok(_))));
+        let (request, _) = self.identified(
+            self.agent
+                .get(&self.endpoint(format_args!("/mempool/recent"))),
+        );
+        let software_of = |resp: &Response| {
+            resp.header("X-Powered-By")
+                .or_else(|| resp.header("Server"))
+                .map(str::to_owned)
+        };
+        let (recent_mempool, software) = match self.call_redirected(request, &[]) {
+            Ok((_, Ok(resp))) => {
+                let software = software_of(&resp);
+                (resp.into_json::<Vec<MempoolRecent>>().is_ok(), software)
+            }
+            Ok((_, Err(resp))) => (false, software_of(&resp)),
+            Err(_) => (false, None),
+        };
+        let info = BackendInfo::identify(
+            &self.current_url(),
+            difficulty_adjustment,
+            recent_mempool,
+            software.as_deref(),
+        );
+
+        *self.backend.lock().expect("poisoned backend info") = Some(info.clone());
+        Ok(info)
+    }
+
+    /// Get a snapshot of the effective client configuration.
+    pub fn config(&self) -> ClientConfig {
+        ClientConfig {
+            base_url: self.current_url(),
+            proxy: self.proxy.clone(),
+            timeout: self.timeout,
+            max_pages: self.max_pages,
+            max_retries: self.retry.max_retries,
+            base_backoff: self.retry.base_backoff,
+            redirect_policy: self.redirect_policy,
+            tip_cache_ttl: self.tip_height.ttl,
+            max_concurrency: 1,
+            coalesce_requests: false,
+            validate_responses: self.block_hashes.is_some(),
+            verify_txid: self.verify_txid,
+            fee_fallback: self.fee_fallback.clone(),
+        }
     }
 
     /// Get the underlying [`Agent`].
     pub fn agent(&self) -> &Agent {
         &self.agent
     }
+
+    /// Shut the client and its clones down, blocking until their in-flight requests complete
+    /// or the grace period set with [`Builder::shutdown_grace`] elapses.
+    ///
+    /// Requests made after the shutdown began fail with [`Error::ClientClosed`], and
+    /// [`BlockingClient::watch_chain`] iterations end. Shutting down an already shut down
+    /// client only waits for the requests still in flight. Returns whether
+    /// all of them completed within the grace period.
+    pub fn shutdown_blocking(&self) -> bool {
+        self.lifecycle.close();
+        let deadline = Instant::now() + self.shutdown_grace;
+        while !self.lifecycle.is_idle() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+        true
+    }
 }
 
 fn is_status_not_found(status: u16) -> bool {
     status == 404
 }
 
+/// Reads the start of the body of an error response to classify the error reported by the
+/// server.
+fn into_http_error(resp: Response) -> Error {
+    use std::io::Read;
+
+    let status = resp.status();
+    let mut body = vec![];
+    let _ = resp
+        .into_reader()
+        .take(ERROR_BODY_LIMIT as u64)
+        .read_to_end(&mut body);
+    Error::http_response(status, &String::from_utf8_lossy(&body))
+}
+
+fn is_status_redirect(status: u16) -> bool {
+    matches!(status, 301 | 302 | 303 | 307 | 308)
+}
+
+fn is_header_sensitive(name: &str) -> bool {
+    ["authorization", "cookie", "proxy-authorization"].contains(&name.to_ascii_lowercase().as_str())
+}
+
+fn content_length(resp: &Response) -> Option<u64> {
+    resp.header("Content-Length")?.parse().ok()
+}
+
+/// Read the body of `resp` for the retry predicate to peek at, rebuilding the response around
+/// it for it to be handed over as if it wasn't read.
+///
+/// The rebuilt response keeps the status and headers, but not the URL.
+fn peek_body(resp: Response) -> Result<(Response, Arc<[u8]>), Error> {
+    let mut builder = http::Response::builder().status(resp.status());
+    for name in resp.headers_names() {
+        for value in resp.all(&name) {
+            builder = builder.header(name.as_str(), value);
+        }
+    }
+    let body = Arc::<[u8]>::from(into_bytes(resp)?);
+    let rebuilt = builder
+        .body(body.clone())
+        .map_err(|_| Error::InvalidServerData)?;
+    Ok((Response::from(rebuilt), body))
+}
+
 fn into_bytes(resp: Response) -> Result<Vec<u8>, std::io::Error> {
     use std::io::Read;
     const BYTES_LIMIT: usize = 10 * 1_024 * 1_024;
@@ -396,3 +2563,30 @@ fn into_bytes(resp: Response) -> Result<Vec<u8>, std::io::Error> {
 
     Ok(buf)
 }
+
+/// Read the body of `resp` to `path` as text, failing with [`Error::InvalidUtf8`] if it isn't
+/// UTF-8 rather than with an error parsing it later.
+fn into_text(path: &str, resp: Response) -> Result<String, Error> {
+    String::from_utf8(into_bytes(resp)?).map_err(|_| Error::InvalidUtf8 {
+        path: path.to_owned(),
+    })
+}
+
+fn process_block_result(path: &str, response: Result<Response, Error>) -> Result<BlockHash, Error> {
+    Ok(BlockHash::from_str(&into_text(path, response?)?)?)
+}
+
+/// Get the statuses of `txs` along with their txid, to anchor them.
+fn tx_statuses(txs: &mut [crate::Tx]) -> Vec<(Txid, &mut TxStatus)> {
+    txs.iter_mut().map(|tx| (tx.txid, &mut tx.status)).collect()
+}
+
+/// Fills in the block hash of the confirmed transactions of the block with `block_hash`
+/// lacking it.
+fn anchor_to_block(txs: &mut [crate::Tx], block_hash: &BlockHash) {
+    for tx in txs {
+        if tx.status.confirmed && tx.status.block_hash.is_none() {
+            tx.status.block_hash = Some(*block_hash);
+        }
+    }
+}
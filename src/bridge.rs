@@ -0,0 +1,83 @@
+//! Synchronous access to an [`AsyncClient`] from the non-async parts of an async application.
+//!
+//! A [`BlockingHandle`] runs the requests of the async client on a captured Tokio runtime, so
+//! that sync code shares its configuration, caches and connection pool instead of needing a
+//! separate [`BlockingClient`](crate::BlockingClient).
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use bpstd::{BlockHash, BlockHeader, ScriptPubkey, Tx, Txid};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use crate::{
+    AsyncClient, BackendInfo, BlockStatus, BlockSummary, Error, MerkleProof, OutputStatus,
+    TxStatus, Utxo,
+};
+
+/// Mirrors async methods of the client as blocking methods of the handle.
+macro_rules! blocking_methods {
+    ($($(#[$attr:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty
+        $(where $txid:ident => $not_found:expr)? $(=> $method:ident $request:expr)?;)+) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret, Error> {
+                self.run(|client| client.$name($($arg),*))
+            }
+        )+
+    };
+}
+
+/// Blocking access to an [`AsyncClient`] through a Tokio runtime, created with
+/// [`AsyncClient::blocking_handle`].
+///
+/// The methods of the handle block the calling thread until the request completes on the
+/// runtime. They must be called from threads outside of any runtime, like plain threads,
+/// FFI callbacks or rayon jobs: when called from within a runtime, including its blocking
+/// tasks, they return [`Error::BlockingInRuntime`] instead of deadlocking.
+///
+/// The runtime must be a multi-thread one, built with Tokio's `rt-multi-thread` feature: the
+/// IO and timers of a current-thread runtime are only driven by the thread blocking on it, so
+/// the requests would hang. The methods of a handle on such a runtime return
+/// [`Error::CurrentThreadRuntime`].
+#[derive(Clone, Debug)]
+pub struct BlockingHandle {
+    client: AsyncClient,
+    runtime: Handle,
+}
+
+impl AsyncClient {
+    /// Get a [`BlockingHandle`] making the requests of this client on `runtime`, sharing its
+    /// configuration and caches.
+    pub fn blocking_handle(&self, runtime: Handle) -> BlockingHandle {
+        BlockingHandle {
+            client: self.clone(),
+            runtime,
+        }
+    }
+}
+
+impl BlockingHandle {
+    /// Get the async client the handle makes requests with.
+    pub fn client(&self) -> &AsyncClient {
+        &self.client
+    }
+
+    /// Run any request of the async client on the runtime, blocking until it completes.
+    pub fn run<'a, T, F, Fut>(&'a self, request: F) -> Result<T, Error>
+    where
+        F: FnOnce(&'a AsyncClient) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        // blocking on a runtime from within an async context would deadlock its worker
+        if Handle::try_current().is_ok() {
+            return Err(Error::BlockingInRuntime);
+        }
+        if self.runtime.runtime_flavor() == RuntimeFlavor::CurrentThread {
+            return Err(Error::CurrentThreadRuntime);
+        }
+        self.runtime.block_on(request(&self.client))
+    }
+
+    client_endpoints!(blocking_methods! {} reads composite writes);
+}
@@ -0,0 +1,292 @@
+//! Caches of responses for immutable Esplora resources: a [`CacheBackend`] of validated
+//! responses, allowing conditional requests (`If-None-Match`/`If-Modified-Since`) across process
+//! restarts, and a read-through [`Cache`] answering requests without reaching the server.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::sync::Arc;
+use std::sync::Mutex;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+use sha2::{Digest, Sha256};
+
+/// Default number of responses kept by a [`LruCache`].
+pub const DEFAULT_LRU_CAPACITY: usize = 1024;
+
+/// Response body stored together with the validators the server sent along with it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CachedResponse {
+    /// Value of the `ETag` header.
+    pub etag: Option<String>,
+    /// Value of the `Last-Modified` header, or else of the `Date` one.
+    pub last_modified: Option<String>,
+    /// Response body.
+    pub body: Vec<u8>,
+}
+
+/// Storage for cached responses, keyed by request URL.
+pub trait CacheBackend: Debug + Send + Sync {
+    /// Get the response cached for `url`, if any.
+    fn load(&self, url: &str) -> Option<CachedResponse>;
+
+    /// Store the response received for `url`, replacing any previous one.
+    fn store(&self, url: &str, response: &CachedResponse);
+}
+
+/// In-memory [`CacheBackend`], lost when the process exits.
+#[derive(Debug, Default)]
+pub struct MemoryCache(Mutex<HashMap<String, CachedResponse>>);
+
+impl CacheBackend for MemoryCache {
+    fn load(&self, url: &str) -> Option<CachedResponse> {
+        self.0
+            .lock()
+            .expect("poisoned memory cache")
+            .get(url)
+            .cloned()
+    }
+
+    fn store(&self, url: &str, response: &CachedResponse) {
+        self.0
+            .lock()
+            .expect("poisoned memory cache")
+            .insert(url.to_owned(), response.clone());
+    }
+}
+
+/// [`CacheBackend`] keeping one file per URL in a directory.
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// Use `dir` as cache directory, creating it if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(DiskCache { dir })
+    }
+
+    fn file(&self, url: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:x}", Sha256::digest(url.as_bytes())))
+    }
+}
+
+impl CacheBackend for DiskCache {
+    fn load(&self, url: &str) -> Option<CachedResponse> {
+        let data = fs::read(self.file(url)).ok()?;
+        // validators can't contain line breaks, so they are stored as the first two lines
+        let mut parts = data.splitn(3, |byte| *byte == b'\n');
+        let etag = parts.next()?;
+        let last_modified = parts.next()?;
+        let body = parts.next()?;
+        Some(CachedResponse {
+            etag: validator(etag),
+            last_modified: validator(last_modified),
+            body: body.to_vec(),
+        })
+    }
+
+    fn store(&self, url: &str, response: &CachedResponse) {
+        let mut data = Vec::with_capacity(response.body.len() + 128);
+        data.extend(response.etag.as_deref().unwrap_or_default().as_bytes());
+        data.push(b'\n');
+        data.extend(
+            response
+                .last_modified
+                .as_deref()
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+        data.push(b'\n');
+        data.extend(&response.body);
+
+        let file = self.file(url);
+        let tmp = file.with_extension("tmp");
+        if let Err(err) = fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, &file)) {
+            debug!("unable to cache the response for {}: {}", url, err);
+        }
+    }
+}
+
+/// Read-through storage of the bodies of immutable resources, keyed by request URL.
+///
+/// Unlike a [`CacheBackend`], a response found in the cache is used without asking the server,
+/// so implementations backed by a persistent store let block and transaction data be reused
+/// across process restarts.
+pub trait Cache: Debug + Send + Sync {
+    /// Get the response body cached for `key`, if any.
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Store the response body received for `key`.
+    fn put(&self, key: &str, value: Vec<u8>);
+
+    /// Drop all the cached bodies, as done when a client is pointed to another server.
+    ///
+    /// Does nothing by default, which is fine for caches shared by clients of servers of the
+    /// same network, since their keys include the base URL.
+    fn clear(&self) {}
+}
+
+/// In-memory [`Cache`] keeping the most recently used responses.
+#[derive(Debug)]
+pub struct LruCache {
+    capacity: usize,
+    inner: Mutex<LruEntries>,
+}
+
+#[derive(Debug, Default)]
+struct LruEntries {
+    /// Last use and body by key.
+    values: HashMap<String, (u64, Vec<u8>)>,
+    /// Keys by last use.
+    uses: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl Default for LruCache {
+    fn default() -> Self {
+        LruCache::new(DEFAULT_LRU_CAPACITY)
+    }
+}
+
+impl LruCache {
+    /// Create a cache keeping at most `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity,
+            inner: Mutex::new(LruEntries::default()),
+        }
+    }
+}
+
+impl LruEntries {
+    /// Marks `key` as used, returning its body if it is present.
+    fn touch(&mut self, key: &str) -> Option<&Vec<u8>> {
+        self.clock += 1;
+        let clock = self.clock;
+        let (last_use, value) = self.values.get_mut(key)?;
+        self.uses.remove(last_use);
+        self.uses.insert(clock, key.to_owned());
+        *last_use = clock;
+        Some(value)
+    }
+}
+
+impl Cache for LruCache {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.inner
+            .lock()
+            .expect("poisoned LRU cache")
+            .touch(key)
+            .cloned()
+    }
+
+    fn put(&self, key: &str, value: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().expect("poisoned LRU cache");
+        if inner.touch(key).is_none() {
+            while inner.values.len() >= self.capacity {
+                let oldest = match inner.uses.keys().next() {
+                    Some(oldest) => *oldest,
+                    None => break,
+                };
+                if let Some(evicted) = inner.uses.remove(&oldest) {
+                    inner.values.remove(&evicted);
+                }
+            }
+            let clock = inner.clock;
+            inner.uses.insert(clock, key.to_owned());
+            inner.values.insert(key.to_owned(), (clock, value));
+        } else if let Some(entry) = inner.values.get_mut(key) {
+            entry.1 = value;
+        }
+    }
+
+    fn clear(&self) {
+        let mut inner = self.inner.lock().expect("poisoned LRU cache");
+        inner.values.clear();
+        inner.uses.clear();
+    }
+}
+
+fn validator(bytes: &[u8]) -> Option<String> {
+    if bytes.is_empty() {
+        return None;
+    }
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Whether the resource at `path` never changes once it exists, so that it can be safely
+/// served from the cache: raw transactions, block headers and block txids.
+#[cfg(any(feature = "blocking", feature = "async"))]
+pub(crate) fn is_cacheable(path: &str) -> bool {
+    let segments = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+    matches!(
+        segments.as_slice(),
+        ["tx", _, "raw"] | ["block", _, "header"] | ["block", _, "txids"]
+    )
+}
+
+/// Cache backend used by a client, along with the number of conditional requests it served.
+#[cfg(any(feature = "blocking", feature = "async"))]
+#[derive(Debug)]
+pub(crate) struct HttpCache {
+    backend: Arc<dyn CacheBackend>,
+    hits: AtomicU64,
+}
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+impl HttpCache {
+    pub fn new(backend: Arc<dyn CacheBackend>) -> Self {
+        HttpCache {
+            backend,
+            hits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn load(&self, url: &str) -> Option<CachedResponse> {
+        self.backend.load(url)
+    }
+
+    /// Caches a response, unless it came without validators.
+    pub fn store(
+        &self,
+        url: &str,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        body: &[u8],
+    ) {
+        if etag.is_none() && last_modified.is_none() {
+            return;
+        }
+        self.backend.store(
+            url,
+            &CachedResponse {
+                etag,
+                last_modified,
+                body: body.to_vec(),
+            },
+        );
+    }
+
+    /// Registers a request answered with `304 Not Modified`.
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
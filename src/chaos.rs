@@ -0,0 +1,455 @@
+//! Fault injection around the clients, for testing how applications cope with an unreliable
+//! Esplora server.
+//!
+//! A [`ChaosClient`] wraps a [`BlockingClient`] or an [`AsyncClient`] and mirrors its
+//! endpoints, randomly failing them, delaying them or corrupting the bodies returned by the
+//! server as set in a [`ChaosConfig`]. Faults are drawn from a seeded generator, so that a test
+//! run can be reproduced.
+//!
+//! ```no_run
+//! # #[cfg(feature = "async")]
+//! # async fn test() -> Result<(), esplora::Error> {
+//! use std::time::Duration;
+//!
+//! use esplora::chaos::{ChaosClient, ChaosConfig, Latency};
+//! use esplora::Builder;
+//!
+//! let client = Builder::new("http://localhost:3002").build_async()?;
+//! let config = ChaosConfig::new(42)
+//!     .failure_rate(0.2, &[500, 503])
+//!     .latency(0.5, Latency::Uniform(Duration::ZERO, Duration::from_millis(500)))
+//!     .garble_rate(0.05);
+//! let client = ChaosClient::new(client, config);
+//! // exercise the application with `client`, then check it saw some faults
+//! assert!(client.stats().failures > 0);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bpstd::{BlockHash, BlockHeader, ConsensusDecode, ScriptPubkey, Tx, Txid};
+use sha2::{Digest, Sha256};
+
+use crate::rng::SplitMix64;
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+#[cfg(feature = "blocking")]
+use crate::BlockingClient;
+use crate::{
+    BackendInfo, BlockStatus, BlockSummary, Error, MerkleProof, OutputStatus, TxStatus, Utxo,
+};
+
+/// Distribution of the latency added to a request.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Latency {
+    /// Always the same delay.
+    Fixed(Duration),
+    /// Delay uniformly distributed between two bounds, both included.
+    Uniform(Duration, Duration),
+}
+
+/// Faults injected by a [`ChaosClient`], all disabled by default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChaosConfig {
+    /// Seed of the generator drawing the faults.
+    pub seed: u64,
+    /// Probability of a request failing with an error status, without reaching the server.
+    pub failure_rate: f64,
+    /// Statuses of the failed requests, picked uniformly.
+    pub failure_statuses: Vec<u16>,
+    /// Probability of a request being delayed.
+    pub latency_rate: f64,
+    /// Delay of the delayed requests.
+    pub latency: Option<Latency>,
+    /// Probability of the body of a response being truncated or having a byte altered, which
+    /// usually makes it fail to decode. Only the responses of single GET requests are garbled.
+    pub garble_rate: f64,
+    /// Transactions reported as unknown to the server, the way Esplora reports them: as
+    /// missing, unconfirmed or with [`Error::TransactionNotFound`], depending on the method.
+    pub not_found: HashSet<Txid>,
+}
+
+impl ChaosConfig {
+    /// Create a configuration injecting no fault, drawing them from `seed` once enabled.
+    pub fn new(seed: u64) -> Self {
+        ChaosConfig {
+            seed,
+            ..ChaosConfig::default()
+        }
+    }
+
+    /// Fail requests with probability `rate`, responding with one of `statuses`
+    pub fn failure_rate(mut self, rate: f64, statuses: &[u16]) -> Self {
+        self.failure_rate = rate;
+        self.failure_statuses = statuses.to_vec();
+        self
+    }
+
+    /// Delay requests with probability `rate`, as drawn from `latency`
+    pub fn latency(mut self, rate: f64, latency: Latency) -> Self {
+        self.latency_rate = rate;
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Truncate or alter the bodies of responses with probability `rate`
+    pub fn garble_rate(mut self, rate: f64) -> Self {
+        self.garble_rate = rate;
+        self
+    }
+
+    /// Report a transaction as unknown to the server
+    pub fn not_found(mut self, txid: Txid) -> Self {
+        self.not_found.insert(txid);
+        self
+    }
+}
+
+/// Number of requests made through a [`ChaosClient`] and of the faults injected in them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ChaosStats {
+    /// Requests made through the client.
+    pub requests: u64,
+    /// Requests failed with an error status.
+    pub failures: u64,
+    /// Requests delayed.
+    pub delays: u64,
+    /// Responses truncated or garbled.
+    pub garbled: u64,
+    /// Transactions reported as unknown.
+    pub not_found: u64,
+}
+
+/// Faults drawn for a request.
+#[derive(Copy, Clone, Debug, Default)]
+struct Plan {
+    latency: Option<Duration>,
+    failure: Option<u16>,
+    garble: bool,
+}
+
+impl Plan {
+    /// Fails with the injected error status, if any.
+    fn check(&self) -> Result<(), Error> {
+        match self.failure {
+            Some(status) => Err(Error::http_response(status, "")),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    requests: AtomicU64,
+    failures: AtomicU64,
+    delays: AtomicU64,
+    garbled: AtomicU64,
+    not_found: AtomicU64,
+}
+
+/// Fault generator shared between the clones of a [`ChaosClient`].
+#[derive(Debug)]
+struct Chaos {
+    config: ChaosConfig,
+    rng: Mutex<SplitMix64>,
+    counters: Counters,
+}
+
+impl Chaos {
+    fn new(config: ChaosConfig) -> Self {
+        Chaos {
+            rng: Mutex::new(SplitMix64::new(config.seed)),
+            config,
+            counters: Counters::default(),
+        }
+    }
+
+    fn rng(&self) -> std::sync::MutexGuard<'_, SplitMix64> {
+        self.rng.lock().expect("poisoned chaos generator")
+    }
+
+    fn next_u64(&self) -> u64 {
+        self.rng().next_u64()
+    }
+
+    /// Draws `true` with probability `rate`, never drawing for a zero rate.
+    fn draw(&self, rate: f64) -> bool {
+        if rate <= 0.0 {
+            return false;
+        }
+        self.rng().next_f64() < rate
+    }
+
+    /// Draws the faults of a request, which may get its response garbled if `garbleable`.
+    fn plan(&self, garbleable: bool) -> Plan {
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        let mut plan = Plan::default();
+        if let Some(latency) = self.config.latency {
+            if self.draw(self.config.latency_rate) {
+                self.counters.delays.fetch_add(1, Ordering::Relaxed);
+                plan.latency = Some(self.delay(latency));
+            }
+        }
+        if !self.config.failure_statuses.is_empty() && self.draw(self.config.failure_rate) {
+            self.counters.failures.fetch_add(1, Ordering::Relaxed);
+            let statuses = &self.config.failure_statuses;
+            plan.failure = Some(statuses[self.next_u64() as usize % statuses.len()]);
+        } else if garbleable && self.draw(self.config.garble_rate) {
+            self.counters.garbled.fetch_add(1, Ordering::Relaxed);
+            plan.garble = true;
+        }
+        plan
+    }
+
+    fn delay(&self, latency: Latency) -> Duration {
+        match latency {
+            Latency::Fixed(delay) => delay,
+            Latency::Uniform(min, max) if max > min => {
+                let range = (max - min).as_nanos() as u64;
+                min + Duration::from_nanos(self.next_u64() % range.saturating_add(1))
+            }
+            Latency::Uniform(min, _) => min,
+        }
+    }
+
+    /// Truncates `body` or alters one of its bytes.
+    fn garble(&self, mut body: Vec<u8>) -> Vec<u8> {
+        if body.is_empty() {
+            return body;
+        }
+        let mut rng = self.rng();
+        let index = (rng.next_u64() % body.len() as u64) as usize;
+        if rng.next_u64() & 1 == 0 {
+            body.truncate(index);
+        } else {
+            body[index] ^= (rng.next_u64() % 255 + 1) as u8;
+        }
+        body
+    }
+
+    fn is_not_found(&self, txid: &Txid) -> bool {
+        let not_found = self.config.not_found.contains(txid);
+        if not_found {
+            self.counters.not_found.fetch_add(1, Ordering::Relaxed);
+        }
+        not_found
+    }
+
+    fn stats(&self) -> ChaosStats {
+        let counters = &self.counters;
+        ChaosStats {
+            requests: counters.requests.load(Ordering::Relaxed),
+            failures: counters.failures.load(Ordering::Relaxed),
+            delays: counters.delays.load(Ordering::Relaxed),
+            garbled: counters.garbled.load(Ordering::Relaxed),
+            not_found: counters.not_found.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Response of an endpoint, decoded from a possibly garbled body as the clients do.
+trait FromBody: Sized {
+    /// Decode the `body` of a response to the request to `path`.
+    fn from_body(path: &str, body: &[u8]) -> Result<Self, Error>;
+}
+
+macro_rules! json_bodies {
+    ($($ty:ty),+) => {
+        $(
+            impl FromBody for $ty {
+                fn from_body(_: &str, body: &[u8]) -> Result<Self, Error> {
+                    Ok(serde_json::from_slice(body)?)
+                }
+            }
+        )+
+    };
+}
+
+json_bodies!(
+    crate::Tx,
+    TxStatus,
+    MerkleProof,
+    OutputStatus,
+    BlockStatus,
+    BlockSummary,
+    Vec<Txid>,
+    Vec<crate::Tx>,
+    Vec<Utxo>,
+    Vec<BlockSummary>,
+    HashMap<String, f64>
+);
+
+impl<T: FromBody> FromBody for Option<T> {
+    fn from_body(path: &str, body: &[u8]) -> Result<Self, Error> {
+        T::from_body(path, body).map(Some)
+    }
+}
+
+impl FromBody for Vec<u8> {
+    fn from_body(_: &str, body: &[u8]) -> Result<Self, Error> {
+        Ok(body.to_vec())
+    }
+}
+
+impl FromBody for Tx {
+    fn from_body(_: &str, body: &[u8]) -> Result<Self, Error> {
+        Tx::consensus_decode(&mut Cursor::new(body)).map_err(|_| Error::InvalidServerData)
+    }
+}
+
+impl FromBody for BlockHeader {
+    fn from_body(path: &str, body: &[u8]) -> Result<Self, Error> {
+        BlockHeader::from_str(text(path, body)?).map_err(|_| Error::InvalidServerData)
+    }
+}
+
+impl FromBody for u32 {
+    fn from_body(path: &str, body: &[u8]) -> Result<Self, Error> {
+        Ok(text(path, body)?.parse()?)
+    }
+}
+
+impl FromBody for BlockHash {
+    fn from_body(path: &str, body: &[u8]) -> Result<Self, Error> {
+        Ok(BlockHash::from_str(text(path, body)?)?)
+    }
+}
+
+impl FromBody for Txid {
+    fn from_body(path: &str, body: &[u8]) -> Result<Self, Error> {
+        Ok(Txid::from_str(text(path, body)?)?)
+    }
+}
+
+/// Reads a text `body` of a response to the request to `path`.
+fn text<'a>(path: &str, body: &'a [u8]) -> Result<&'a str, Error> {
+    std::str::from_utf8(body)
+        .map(str::trim)
+        .map_err(|_| Error::InvalidUtf8 {
+            path: path.to_owned(),
+        })
+}
+
+fn script_hash(script: &ScriptPubkey) -> String {
+    let mut hasher = Sha256::default();
+    hasher.update(script);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Client injecting faults in the requests of a [`BlockingClient`] or an [`AsyncClient`].
+///
+/// When no fault is enabled, its methods behave exactly as those of the wrapped client. Clones
+/// share the fault generator and the counters.
+#[derive(Clone, Debug)]
+pub struct ChaosClient<C> {
+    inner: C,
+    chaos: Arc<Chaos>,
+}
+
+impl<C> ChaosClient<C> {
+    /// Wrap `inner`, injecting the faults set in `config`.
+    pub fn new(inner: C, config: ChaosConfig) -> Self {
+        ChaosClient {
+            inner,
+            chaos: Arc::new(Chaos::new(config)),
+        }
+    }
+
+    /// Get the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Get the number of requests made and of faults injected so far.
+    pub fn stats(&self) -> ChaosStats {
+        self.chaos.stats()
+    }
+}
+
+/// Whether requests made with the method of an endpoint get a garbleable response.
+macro_rules! garbleable {
+    () => {
+        false
+    };
+    (GET) => {
+        true
+    };
+    (POST) => {
+        false
+    };
+}
+
+/// Returns the garbled response to the request of a GET endpoint, when the server returns
+/// one, falling through otherwise.
+macro_rules! garble_response {
+    (GET $plan:ident $chaos:expr, $ret:ty, $request:expr, $path:ident => $get:expr) => {
+        if $plan.garble {
+            let (_, $path) = $request;
+            match $get {
+                Ok((body, _)) => return <$ret>::from_body(&$path, &$chaos.garble(body)),
+                Err(Error::HttpResponse { status: 404, .. }) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    };
+    (POST $($rest:tt)*) => {};
+}
+
+/// Mirrors methods of a client with fault injection, those taking a `txid` returning the given
+/// value for the transactions reported as unknown.
+macro_rules! chaos_methods {
+    ({ blocking } $($(#[$attr:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty
+        $(where $txid:ident => $not_found:expr)? $(=> $method:ident $request:expr)?;)+) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret, Error> {
+                let plan = self.chaos.plan(garbleable!($($method)?));
+                if let Some(latency) = plan.latency {
+                    std::thread::sleep(latency);
+                }
+                plan.check()?;
+                $(if self.chaos.is_not_found($txid) {
+                    return $not_found;
+                })?
+                $(garble_response!($method plan self.chaos, $ret, $request,
+                    path => self.inner.get_traced(&path));)?
+                self.inner.$name($($arg),*)
+            }
+        )+
+    };
+    ({ async } $($(#[$attr:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty
+        $(where $txid:ident => $not_found:expr)? $(=> $method:ident $request:expr)?;)+) => {
+        $(
+            $(#[$attr])*
+            pub async fn $name(&self $(, $arg: $ty)*) -> Result<$ret, Error> {
+                let plan = self.chaos.plan(garbleable!($($method)?));
+                if let Some(latency) = plan.latency {
+                    self.inner.sleeper().sleep(latency).await;
+                }
+                plan.check()?;
+                $(if self.chaos.is_not_found($txid) {
+                    return $not_found;
+                })?
+                $(garble_response!($method plan self.chaos, $ret, $request,
+                    path => self.inner.get_traced(&path).await);)?
+                self.inner.$name($($arg),*).await
+            }
+        )+
+    };
+}
+
+#[cfg(feature = "blocking")]
+impl ChaosClient<BlockingClient> {
+    client_endpoints!(chaos_methods! { { blocking } } reads composite writes);
+}
+
+#[cfg(feature = "async")]
+impl ChaosClient<AsyncClient> {
+    client_endpoints!(chaos_methods! { { async } } reads composite writes);
+}
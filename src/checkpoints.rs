@@ -0,0 +1,172 @@
+//! Checkpoints of the chain kept by an application, and detection of the reorganizations
+//! rolling the chain back past them.
+
+use std::collections::VecDeque;
+
+use bpstd::BlockHash;
+use serde::{Deserialize, Serialize};
+
+/// Default number of checkpoints kept by [`Checkpoints`].
+pub const DEFAULT_CHECKPOINT_CAPACITY: usize = 32;
+
+/// Ring of the last `(height, hash)` checkpoints of the chain, at least `spacing` blocks apart,
+/// which can be persisted with serde between runs.
+///
+/// Checkpoints are added and checked against the chain of the server with the
+/// `update_checkpoints` method of the clients.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoints {
+    capacity: usize,
+    spacing: u32,
+    /// Checkpoints from the lowest to the highest.
+    points: VecDeque<(u32, BlockHash)>,
+}
+
+impl Default for Checkpoints {
+    fn default() -> Self {
+        Checkpoints::new(DEFAULT_CHECKPOINT_CAPACITY, 1)
+    }
+}
+
+impl Checkpoints {
+    /// Create an empty ring keeping up to `capacity` checkpoints, at least `spacing` blocks
+    /// apart.
+    pub fn new(capacity: usize, spacing: u32) -> Self {
+        Checkpoints {
+            capacity: capacity.max(1),
+            spacing: spacing.max(1),
+            points: VecDeque::new(),
+        }
+    }
+
+    /// Get the checkpoints, from the lowest to the highest.
+    pub fn points(&self) -> impl Iterator<Item = (u32, BlockHash)> + '_ {
+        self.points.iter().copied()
+    }
+
+    /// Get the highest checkpoint, if any.
+    pub fn latest(&self) -> Option<(u32, BlockHash)> {
+        self.points.back().copied()
+    }
+
+    /// Get the number of checkpoints.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Whether there is no checkpoint.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Whether a checkpoint should be added at `height`, being far enough above the highest
+    /// one.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn is_due(&self, height: u32) -> bool {
+        self.latest().map_or(true, |(latest, _)| {
+            height >= latest.saturating_add(self.spacing)
+        })
+    }
+
+    /// Adds a checkpoint above the others, dropping the lowest one when full.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn push(&mut self, height: u32, hash: BlockHash) {
+        if self.points.len() >= self.capacity {
+            self.points.pop_front();
+        }
+        self.points.push_back((height, hash));
+    }
+
+    /// Get the checkpoint at `index`, from the lowest.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn get(&self, index: usize) -> (u32, BlockHash) {
+        self.points[index]
+    }
+
+    /// Drops the checkpoints from `index` up, returning them from the lowest.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn drop_from(&mut self, index: usize) -> Vec<(u32, BlockHash)> {
+        self.points.split_off(index).into_iter().collect()
+    }
+}
+
+/// Outcome of checking [`Checkpoints`] against the chain of the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckpointUpdate {
+    /// All the checkpoints are still in the chain, and the tip isn't far enough above the
+    /// highest one for a new checkpoint.
+    Unchanged,
+
+    /// All the checkpoints are still in the chain, and a checkpoint was added at the tip.
+    Added { height: u32, hash: BlockHash },
+
+    /// Some checkpoints are no longer in the chain and were dropped.
+    Reorg {
+        /// Height of the highest block still in the chain among the ancestors of the lowest
+        /// dropped checkpoint, found from the blocks of the stale branch.
+        ///
+        /// If the server no longer serves the stale blocks, it is the height of the highest
+        /// checkpoint still in the chain. When no checkpoint survived, the fork is below all of
+        /// them and the height right below the lowest dropped one is reported.
+        fork_height: u32,
+        /// Dropped checkpoints, from the lowest to the highest.
+        dropped: Vec<(u32, BlockHash)>,
+    },
+}
+
+#[cfg(all(test, any(feature = "blocking", feature = "async")))]
+mod tests {
+    use amplify::ByteArray;
+
+    use super::*;
+
+    fn hash(n: u8) -> BlockHash {
+        BlockHash::from_byte_array([n; 32])
+    }
+
+    #[test]
+    fn due_checkpoints() {
+        let mut checkpoints = Checkpoints::new(4, 5);
+        assert!(checkpoints.is_due(0));
+        checkpoints.push(10, hash(10));
+        assert!(!checkpoints.is_due(14));
+        assert!(checkpoints.is_due(15));
+
+        let mut checkpoints = Checkpoints::new(4, 0);
+        checkpoints.push(u32::MAX, hash(1));
+        assert!(!checkpoints.is_due(u32::MAX - 1));
+        assert!(checkpoints.is_due(u32::MAX));
+    }
+
+    #[test]
+    fn capacity() {
+        let mut checkpoints = Checkpoints::new(3, 1);
+        for height in 1..=5 {
+            checkpoints.push(height, hash(height as u8));
+        }
+        assert_eq!(checkpoints.len(), 3);
+        assert_eq!(
+            checkpoints.points().collect::<Vec<_>>(),
+            vec![(3, hash(3)), (4, hash(4)), (5, hash(5))]
+        );
+        assert_eq!(checkpoints.latest(), Some((5, hash(5))));
+
+        let mut checkpoints = Checkpoints::new(0, 1);
+        checkpoints.push(1, hash(1));
+        checkpoints.push(2, hash(2));
+        assert_eq!(checkpoints.points().collect::<Vec<_>>(), vec![(2, hash(2))]);
+    }
+
+    #[test]
+    fn dropped_checkpoints() {
+        let mut checkpoints = Checkpoints::new(4, 1);
+        for height in 1..=4 {
+            checkpoints.push(height, hash(height as u8));
+        }
+        assert_eq!(checkpoints.drop_from(2), vec![(3, hash(3)), (4, hash(4))]);
+        assert_eq!(checkpoints.latest(), Some((2, hash(2))));
+        assert!(checkpoints.drop_from(2).is_empty());
+        assert_eq!(checkpoints.drop_from(0).len(), 2);
+        assert!(checkpoints.is_empty());
+    }
+}
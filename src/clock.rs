@@ -0,0 +1,61 @@
+//! Source of the current time for time-dependent client-side bookkeeping, replaceable to make
+//! it deterministic.
+
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Source of the current time.
+pub trait Clock: Debug + Send + Sync {
+    /// Get the current instant.
+    fn now(&self) -> Instant;
+
+    /// Get the current wall-clock time, used for the timestamps persisted across restarts.
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// [`Clock`] reading the system monotonic clock.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// [`Clock`] which only moves forward when told to.
+#[derive(Debug)]
+pub struct ManualClock(Mutex<(Instant, SystemTime)>);
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+
+impl ManualClock {
+    /// Create a clock stopped at the current instant.
+    pub fn new() -> Self {
+        ManualClock(Mutex::new((Instant::now(), SystemTime::now())))
+    }
+
+    /// Move the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.0.lock().expect("poisoned manual clock");
+        now.0 += duration;
+        now.1 += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.0.lock().expect("poisoned manual clock").0
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.0.lock().expect("poisoned manual clock").1
+    }
+}
@@ -0,0 +1,49 @@
+//! Commitments of a block to its transactions: the merkle root of its header and the segwit
+//! witness commitment of its coinbase.
+
+use bpstd::{BlockMerkleRoot, Txid};
+use serde::{Deserialize, Serialize};
+
+/// Start of the output script committing to the witnesses of a block (BIP141): `OP_RETURN`,
+/// a push of 36 bytes and the commitment header `0xaa21a9ed`.
+const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Merkle root and witness commitment of a block.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BlockCommitments {
+    /// Merkle root of the transactions, from the block header.
+    pub merkle_root: BlockMerkleRoot,
+    /// Txid of the coinbase transaction, or `None` if the server didn't return it.
+    pub coinbase_txid: Option<Txid>,
+    /// Commitment to the witnesses of the transactions, found in the coinbase.
+    ///
+    /// `None` for blocks without segwit transactions, including all the blocks mined before
+    /// segwit activated, and when the coinbase isn't known.
+    pub witness_commitment: Option<[u8; 32]>,
+}
+
+impl BlockCommitments {
+    /// Whether the block commits to the witnesses of its transactions.
+    pub fn has_witness_commitment(&self) -> bool {
+        self.witness_commitment.is_some()
+    }
+}
+
+/// Extract the witness commitment from the outputs of a coinbase transaction.
+///
+/// When several outputs match, the one with the highest index is the commitment, as specified
+/// by BIP141.
+pub fn extract_witness_commitment(coinbase: &crate::Tx) -> Option<[u8; 32]> {
+    coinbase.vout.iter().rev().find_map(|vout| {
+        let script: &[u8] = vout.scriptpubkey.as_ref();
+        if script.len() < WITNESS_COMMITMENT_PREFIX.len() + 32
+            || !script.starts_with(&WITNESS_COMMITMENT_PREFIX)
+        {
+            return None;
+        }
+        let start = WITNESS_COMMITMENT_PREFIX.len();
+        let mut commitment = [0u8; 32];
+        commitment.copy_from_slice(&script[start..start + 32]);
+        Some(commitment)
+    })
+}
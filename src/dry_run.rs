@@ -0,0 +1,264 @@
+//! Dry-run client recording the requests a sync strategy would make, without sending them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::sync::Mutex;
+
+use bpstd::{BlockHash, BlockHeader, ScriptPubkey, Tx, Txid};
+use sha2::{Digest, Sha256};
+
+use crate::pagination::PageGuard;
+use crate::{BlockStatus, BlockSummary, Error, MerkleProof, OutputStatus, TxStatus, Utxo};
+
+/// Request which would have been sent to the Esplora server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedRequest {
+    /// HTTP method of the request.
+    pub method: &'static str,
+    /// Endpoint template the request belongs to, like `/tx/:txid/status`.
+    pub endpoint: &'static str,
+    /// Path of the request, relative to the base URL.
+    pub path: String,
+}
+
+/// Requests recorded by a [`DryRunClient`], in the order they would have been made.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestPlan {
+    /// Recorded requests.
+    pub requests: Vec<PlannedRequest>,
+}
+
+impl RequestPlan {
+    /// Total number of requests.
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Whether no request would have been made.
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Number of requests per endpoint template.
+    pub fn by_endpoint(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for request in &self.requests {
+            *counts.entry(request.endpoint).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Stub server responses used by composite [`DryRunClient`] methods, which need the content
+/// of a page to know which request comes next.
+pub trait DryRunDataSource {
+    /// Page of the transaction history of `script` following the `last_seen` transaction.
+    fn scripthash_txs(&self, script: &ScriptPubkey, last_seen: Option<Txid>) -> Vec<crate::Tx>;
+}
+
+/// Mirrors methods of the clients, recording the request they make.
+macro_rules! dry_run_methods {
+    ($($(#[$attr:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty
+        $(where $txid:ident => $not_found:expr)? => $method:ident $request:expr;)+) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret, Error> {
+                $(let _ = &$arg;)*
+                let (endpoint, path) = $request;
+                Err(self.record(stringify!($method), endpoint, path))
+            }
+        )+
+    };
+}
+
+/// Client exposing the endpoints of the Esplora clients, recording the request each of them
+/// would make instead of sending it.
+///
+/// Single-request methods always fail with [`Error::DryRun`] once the request is recorded.
+#[derive(Debug)]
+pub struct DryRunClient {
+    url: String,
+    max_pages: usize,
+    plan: Mutex<Vec<PlannedRequest>>,
+}
+
+impl DryRunClient {
+    /// Create a dry-run client for the server at `url`, following at most `max_pages` pages
+    /// in paginated operations.
+    pub fn new(url: String, max_pages: usize) -> Self {
+        DryRunClient {
+            url,
+            max_pages,
+            plan: Mutex::new(vec![]),
+        }
+    }
+
+    /// Get the base URL requests would have been sent to.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Get the requests recorded so far.
+    pub fn plan(&self) -> RequestPlan {
+        RequestPlan {
+            requests: self.plan.lock().expect("poisoned request plan").clone(),
+        }
+    }
+
+    /// Forget the requests recorded so far.
+    pub fn clear(&self) {
+        self.plan.lock().expect("poisoned request plan").clear();
+    }
+
+    fn record(&self, method: &'static str, endpoint: &'static str, path: String) -> Error {
+        self.plan
+            .lock()
+            .expect("poisoned request plan")
+            .push(PlannedRequest {
+                method,
+                endpoint,
+                path,
+            });
+        Error::DryRun
+    }
+
+    /// Get a batch of transactions given their [`Txid`]s.
+    pub fn txs(&self, txids: &[Txid]) -> Result<Vec<Option<Tx>>, Error> {
+        for txid in txids {
+            self.record("GET", "/tx/:txid/raw", format!("/tx/{}/raw", txid));
+        }
+        Err(Error::DryRun)
+    }
+
+    client_endpoints!(dry_run_methods! {} reads writes);
+
+    /// Get the full transaction history for the specified address/scripthash, following the
+    /// pages returned by `data`.
+    ///
+    /// Records one request per page and returns the stub transactions, so that the pagination
+    /// depth of a sync can be estimated.
+    pub fn scripthash_txs_all(
+        &self,
+        script: &ScriptPubkey,
+        data: &impl DryRunDataSource,
+    ) -> Result<Vec<crate::Tx>, Error> {
+        let mut guard = PageGuard::new(self.max_pages);
+        let mut txs = vec![];
+        let mut last_seen = None;
+        loop {
+            let _ = self.scripthash_txs(script, last_seen);
+            let page = data.scripthash_txs(script, last_seen);
+            let next = guard.next_cursor(&page);
+            txs.extend(page);
+            last_seen = next.map_err(|err| err.with_partial_txs(mem::take(&mut txs)))?;
+            if last_seen.is_none() {
+                break;
+            }
+        }
+        Ok(txs)
+    }
+}
+
+fn script_hash(script: &ScriptPubkey) -> String {
+    let mut hasher = Sha256::default();
+    hasher.update(script);
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use amplify::ByteArray;
+    use bpstd::{LockTime, TxVer};
+
+    use super::*;
+
+    struct Pages(Vec<Vec<crate::Tx>>);
+
+    impl DryRunDataSource for Pages {
+        fn scripthash_txs(&self, _: &ScriptPubkey, last_seen: Option<Txid>) -> Vec<crate::Tx> {
+            let index = match last_seen {
+                None => 0,
+                Some(last_seen) => {
+                    self.0
+                        .iter()
+                        .position(|page| page.last().map(|tx| tx.txid) == Some(last_seen))
+                        .expect("cursor of a known page")
+                        + 1
+                }
+            };
+            self.0.get(index).cloned().unwrap_or_default()
+        }
+    }
+
+    fn tx(n: u8) -> crate::Tx {
+        serde_json::from_value(serde_json::json!({
+            "txid": Txid::from_byte_array([n; 32]),
+            "version": 2,
+            "locktime": 0,
+            "vin": [],
+            "vout": [],
+            "size": 100,
+            "weight": 400,
+            "status": { "confirmed": true, "block_height": 100 - n as u32 },
+            "fee": 100,
+        }))
+        .unwrap()
+    }
+
+    fn empty_tx() -> Tx {
+        Tx {
+            version: TxVer::V2,
+            inputs: Default::default(),
+            outputs: Default::default(),
+            lock_time: LockTime::ZERO,
+        }
+    }
+
+    #[test]
+    fn records_requests() {
+        let client = DryRunClient::new("http://localhost".to_owned(), 10);
+        let txid = Txid::from_byte_array([1; 32]);
+        assert!(matches!(client.tx(&txid), Err(Error::DryRun)));
+        assert!(matches!(client.tx_status(&txid), Err(Error::DryRun)));
+        assert!(matches!(client.blocks(Some(7)), Err(Error::DryRun)));
+        assert!(matches!(client.blocks(None), Err(Error::DryRun)));
+        assert!(matches!(client.broadcast(&empty_tx()), Err(Error::DryRun)));
+
+        let plan = client.plan();
+        let paths = plan
+            .requests
+            .iter()
+            .map(|request| (request.method, request.path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                ("GET", &*format!("/tx/{}/raw", txid)),
+                ("GET", &*format!("/tx/{}/status", txid)),
+                ("GET", "/blocks/7"),
+                ("GET", "/blocks"),
+                ("POST", "/tx"),
+            ]
+        );
+        assert_eq!(plan.by_endpoint()["/tx/:txid/raw"], 1);
+
+        client.clear();
+        assert!(client.plan().is_empty());
+    }
+
+    #[test]
+    fn plans_pagination() {
+        let client = DryRunClient::new("http://localhost".to_owned(), 10);
+        let pages = Pages(vec![
+            (1..=25).map(tx).collect(),
+            (26..=30).map(tx).collect(),
+        ]);
+        let script = ScriptPubkey::new();
+        let txs = client.scripthash_txs_all(&script, &pages).unwrap();
+        assert_eq!(txs.len(), 30);
+
+        let counts = client.plan().by_endpoint();
+        assert_eq!(counts["/scripthash/:hash/txs"], 1);
+        assert_eq!(counts["/scripthash/:hash/txs/chain/:txid"], 1);
+    }
+}
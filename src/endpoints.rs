@@ -0,0 +1,147 @@
+//! Endpoint surface of the clients, listed once for the types mirroring it.
+//!
+//! The dry-run, bridge, chaos and balanced clients expose the same methods as
+//! [`BlockingClient`](crate::BlockingClient) and [`AsyncClient`](crate::AsyncClient). Rather
+//! than copying their signatures and docs, each of them generates its methods from
+//! [`client_endpoints!`], so that an endpoint added there reaches all of them.
+
+/// Invokes `$callback!` with `$prefix` followed by the endpoints of the selected groups.
+///
+/// Groups are:
+/// - `reads`, the methods making a single GET request;
+/// - `composite`, the read methods which may make several requests;
+/// - `writes`, the methods making a single POST request.
+///
+/// Each endpoint is given as
+///
+/// ```text
+/// /// docs
+/// fn name(&self, arg: Type, ...) -> Output [where txid => value] [=> METHOD (endpoint, path)];
+/// ```
+///
+/// where `Output` is the type returned inside `Result<_, Error>`, the optional `where` clause
+/// gives the value returned for a transaction unknown to the server, and the request, present
+/// for `reads` and `writes`, evaluates to the endpoint template and the path relative to the
+/// base URL. Paths refer to scripts by `script_hash(script)`, which must be in scope of the
+/// callback using them.
+macro_rules! client_endpoints {
+    ($callback:ident! { $($prefix:tt)* } $($group:ident)+) => {
+        client_endpoints!(@collect $callback { $($prefix)* } [] $($group)+);
+    };
+    (@collect $callback:ident { $($prefix:tt)* } [$($done:tt)*]) => {
+        $callback! { $($prefix)* $($done)* }
+    };
+    (@collect $callback:ident { $($prefix:tt)* } [$($done:tt)*] reads $($rest:ident)*) => {
+        client_endpoints!(@collect $callback { $($prefix)* } [$($done)*
+            /// Get a [`Tx`] given its [`Txid`].
+            fn tx(&self, txid: &Txid) -> Option<Tx> where txid => Ok(None)
+                => GET ("/tx/:txid/raw", format!("/tx/{}/raw", txid));
+            /// Get the consensus-encoded bytes of a transaction given its [`Txid`].
+            fn tx_raw_bytes(&self, txid: &Txid) -> Option<Vec<u8>> where txid => Ok(None)
+                => GET ("/tx/:txid/raw", format!("/tx/{}/raw", txid));
+            /// Get transaction info given its [`Txid`], including its status and fee.
+            fn tx_info(&self, txid: &Txid) -> Option<crate::Tx> where txid => Ok(None)
+                => GET ("/tx/:txid", format!("/tx/{}", txid));
+            /// Get the [`Txid`] of a transaction given its index in a block with a given hash.
+            fn txid_at_block_index(&self, block_hash: &BlockHash, index: usize) -> Option<Txid>
+                => GET (
+                    "/block/:hash/txid/:index",
+                    format!("/block/{}/txid/{}", block_hash, index),
+                );
+            /// Get the status of a transaction given its [`Txid`].
+            fn tx_status(&self, txid: &Txid) -> TxStatus
+                where txid => Ok(TxStatus {
+                    confirmed: false,
+                    block_height: None,
+                    block_hash: None,
+                    block_time: None,
+                })
+                => GET ("/tx/:txid/status", format!("/tx/{}/status", txid));
+            /// Get a merkle inclusion proof for a transaction given its [`Txid`].
+            fn merkle_proof(&self, txid: &Txid) -> Option<MerkleProof> where txid => Ok(None)
+                => GET ("/tx/:txid/merkle-proof", format!("/tx/{}/merkle-proof", txid));
+            /// Get the spending status of an output given a [`Txid`] and the output index.
+            fn outspend(&self, txid: &Txid, vout: u32) -> Option<OutputStatus>
+                where txid => Ok(None)
+                => GET (
+                    "/tx/:txid/outspend/:vout",
+                    format!("/tx/{}/outspend/{}", txid, vout),
+                );
+            /// Get a [`BlockHeader`] given a particular block hash.
+            fn header_by_hash(&self, block_hash: &BlockHash) -> BlockHeader
+                => GET ("/block/:hash/header", format!("/block/{}/header", block_hash));
+            /// Get the [`BlockStatus`] given a particular [`BlockHash`].
+            fn block_status(&self, block_hash: &BlockHash) -> BlockStatus
+                => GET ("/block/:hash/status", format!("/block/{}/status", block_hash));
+            /// Get the [`BlockSummary`] of a block given its [`BlockHash`].
+            fn block_summary(&self, block_hash: &BlockHash) -> Option<BlockSummary>
+                => GET ("/block/:hash", format!("/block/{}", block_hash));
+            /// Get the [`Txid`]s of the transactions of a block given its [`BlockHash`].
+            fn block_txids(&self, block_hash: &BlockHash) -> Vec<Txid>
+                => GET ("/block/:hash/txids", format!("/block/{}/txids", block_hash));
+            /// Get the height of the current blockchain tip.
+            fn height(&self) -> u32
+                => GET ("/blocks/tip/height", "/blocks/tip/height".to_owned());
+            /// Get the [`BlockHash`] of the current blockchain tip.
+            fn tip_hash(&self) -> BlockHash
+                => GET ("/blocks/tip/hash", "/blocks/tip/hash".to_owned());
+            /// Get the [`BlockHash`] of a specific block height.
+            fn block_hash(&self, block_height: u32) -> BlockHash
+                => GET ("/block-height/:height", format!("/block-height/{}", block_height));
+            /// Get a page of the confirmed transaction history of a script, after `last_seen`.
+            fn scripthash_txs(&self, script: &ScriptPubkey, last_seen: Option<Txid>)
+                -> Vec<crate::Tx>
+                => GET match last_seen {
+                    Some(last_seen) => (
+                        "/scripthash/:hash/txs/chain/:txid",
+                        format!("/scripthash/{}/txs/chain/{}", script_hash(script), last_seen),
+                    ),
+                    None => (
+                        "/scripthash/:hash/txs",
+                        format!("/scripthash/{}/txs", script_hash(script)),
+                    ),
+                };
+            /// Get the unconfirmed transactions of a script.
+            fn scripthash_mempool_txs(&self, script: &ScriptPubkey) -> Vec<crate::Tx>
+                => GET (
+                    "/scripthash/:hash/txs/mempool",
+                    format!("/scripthash/{}/txs/mempool", script_hash(script)),
+                );
+            /// Get the unspent outputs of a script.
+            fn scripthash_utxo(&self, script: &ScriptPubkey) -> Vec<Utxo>
+                => GET (
+                    "/scripthash/:hash/utxo",
+                    format!("/scripthash/{}/utxo", script_hash(script)),
+                );
+            /// Get the estimated feerates (in sat/vB) by confirmation target (in number of
+            /// blocks).
+            fn fee_estimates(&self) -> HashMap<String, f64>
+                => GET ("/fee-estimates", "/fee-estimates".to_owned());
+            /// Get some recent block summaries starting at the tip or at `height` if provided.
+            fn blocks(&self, height: Option<u32>) -> Vec<BlockSummary>
+                => GET match height {
+                    Some(height) => ("/blocks/:height", format!("/blocks/{}", height)),
+                    None => ("/blocks", "/blocks".to_owned()),
+                };
+        ] $($rest)*);
+    };
+    (@collect $callback:ident { $($prefix:tt)* } [$($done:tt)*] composite $($rest:ident)*) => {
+        client_endpoints!(@collect $callback { $($prefix)* } [$($done)*
+            /// Get the number of confirmations of a transaction given its [`Txid`].
+            fn tx_confirmations(&self, txid: &Txid) -> u32 where txid => Ok(0);
+            /// Get the fee paid by a transaction given its [`Txid`].
+            fn tx_fee(&self, txid: &Txid) -> u64
+                where txid => Err(Error::TransactionNotFound(*txid));
+            /// Get the whole transaction history of a script.
+            fn scripthash_txs_all(&self, script: &ScriptPubkey) -> Vec<crate::Tx>;
+            /// Detect the software implementing the Esplora API and its capabilities.
+            fn backend_info(&self) -> BackendInfo;
+        ] $($rest)*);
+    };
+    (@collect $callback:ident { $($prefix:tt)* } [$($done:tt)*] writes $($rest:ident)*) => {
+        client_endpoints!(@collect $callback { $($prefix)* } [$($done)*
+            /// Broadcast a [`Tx`] to Esplora.
+            fn broadcast(&self, tx: &Tx) -> () => POST ("/tx", "/tx".to_owned());
+        ] $($rest)*);
+    };
+}
@@ -0,0 +1,160 @@
+//! FFI-friendly facade over the blocking client.
+//!
+//! Only owned strings, integers and flat records and errors cross the boundary, so that the
+//! facade can be exposed through binding generators like uniffi without any unsafe code. The
+//! [`c`] module exposes it through the C ABI.
+
+pub mod c;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use amplify::hex::ToHex;
+use bpstd::{Tx, Txid};
+
+use crate::{parse_input, BlockingClient, Builder, Error};
+
+/// Error returned through the FFI, flattened to a kind and a message.
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+#[display(doc_comments)]
+pub enum FfiError {
+    /// invalid input: {message}
+    InvalidInput { message: String },
+
+    /// HTTP error {status}: {message}
+    Http { status: u16, message: String },
+
+    /// {message}
+    Other { message: String },
+}
+
+impl From<Error> for FfiError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::InvalidInput { .. } => FfiError::InvalidInput {
+                message: err.to_string(),
+            },
+            Error::HttpResponse {
+                status,
+                kind,
+                message,
+                ..
+            } => FfiError::Http {
+                status,
+                message: if message.is_empty() {
+                    kind.to_string()
+                } else {
+                    message
+                },
+            },
+            err => FfiError::Other {
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+/// Settings of an [`FfiClient`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FfiClientConfig {
+    /// Base URL of the Esplora server.
+    pub base_url: String,
+    /// URL of the proxy to use, if any.
+    pub proxy: Option<String>,
+    /// Socket timeout in seconds, if any.
+    pub timeout_secs: Option<u64>,
+}
+
+/// Confirmation status of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FfiTxStatus {
+    pub confirmed: bool,
+    pub block_height: Option<u32>,
+    /// Hash of the confirming block, as a hex string.
+    pub block_hash: Option<String>,
+    pub block_time: Option<u64>,
+}
+
+/// Opaque handle to a blocking client.
+#[derive(Debug)]
+pub struct FfiClient {
+    client: BlockingClient,
+}
+
+impl FfiClient {
+    /// Create a client for the Esplora server at `base_url`, with default settings.
+    pub fn new(base_url: String) -> Result<Arc<Self>, FfiError> {
+        FfiClient::with_config(FfiClientConfig {
+            base_url,
+            proxy: None,
+            timeout_secs: None,
+        })
+    }
+
+    /// Create a client with the given settings.
+    pub fn with_config(config: FfiClientConfig) -> Result<Arc<Self>, FfiError> {
+        let mut builder = Builder::new(&config.base_url);
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(timeout) = config.timeout_secs {
+            builder = builder.timeout(timeout);
+        }
+        Ok(Arc::new(FfiClient {
+            client: builder.build_blocking()?,
+        }))
+    }
+
+    /// Get the consensus-encoded transaction with the given txid as a hex string, if it
+    /// exists.
+    pub fn tx_hex(&self, txid: String) -> Result<Option<String>, FfiError> {
+        let txid = parse_input::<Txid>("txid", &txid)?;
+        Ok(self.client.tx_raw_bytes(&txid)?.map(|bytes| bytes.to_hex()))
+    }
+
+    /// Broadcast a consensus-encoded transaction given as a hex string, returning its txid.
+    pub fn broadcast_hex(&self, tx_hex: String) -> Result<String, FfiError> {
+        let tx = parse_input::<Tx>("transaction", &tx_hex)?;
+        self.client.broadcast(&tx)?;
+        Ok(tx.txid().to_string())
+    }
+
+    /// Get the status of the transaction with the given txid.
+    pub fn tx_status(&self, txid: String) -> Result<FfiTxStatus, FfiError> {
+        let txid = parse_input::<Txid>("txid", &txid)?;
+        let status = self.client.tx_status(&txid)?;
+        Ok(FfiTxStatus {
+            confirmed: status.confirmed,
+            block_height: status.block_height,
+            block_hash: status.block_hash.map(|hash| hash.to_string()),
+            block_time: status.block_time,
+        })
+    }
+
+    /// Get the unspent outputs of an address, as the JSON array returned by Esplora.
+    #[cfg(feature = "addresses")]
+    pub fn address_utxos_json(&self, address: String) -> Result<String, FfiError> {
+        let address = self.client.parse_address(&address)?;
+        let utxos = self.client.scripthash_utxo(&address.script_pubkey())?;
+        Ok(serde_json::to_string(&utxos).map_err(Error::from)?)
+    }
+
+    /// Get the first page of the transaction history of an address, newest first, as the
+    /// JSON array returned by Esplora.
+    #[cfg(feature = "addresses")]
+    pub fn address_history_json(&self, address: String) -> Result<String, FfiError> {
+        let address = self.client.parse_address(&address)?;
+        let txs = self.client.scripthash_txs(&address.script_pubkey(), None)?;
+        Ok(serde_json::to_string(&txs).map_err(Error::from)?)
+    }
+
+    /// Get the height of the current blockchain tip.
+    pub fn tip_height(&self) -> Result<u32, FfiError> {
+        Ok(self.client.height()?)
+    }
+
+    /// Get the estimated feerates (in sat/vB) by confirmation target (in number of blocks).
+    pub fn fee_estimates(&self) -> Result<HashMap<String, f64>, FfiError> {
+        Ok(self.client.fee_estimates()?)
+    }
+}
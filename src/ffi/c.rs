@@ -0,0 +1,331 @@
+//! `extern "C"` functions over [`FfiClient`], for embedding the client through the C ABI.
+//!
+//! Clients are opaque pointers created with [`esplora_client_new`] and released with
+//! [`esplora_client_free`]. The other functions return an [`EsploraStatus`], write their result
+//! to `out` and, when failing, the error message to `error`, unless these are null. Strings are
+//! NUL-terminated UTF-8; those returned by the functions are owned by the caller, which
+//! releases them with [`esplora_string_free`].
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Arc;
+
+use super::{FfiClient, FfiError};
+
+/// Outcome of a call.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub enum EsploraStatus {
+    /// The call succeeded and wrote its result to `out`.
+    Ok = 0,
+    /// The requested item doesn't exist, nothing was written to `out`.
+    NotFound = 1,
+    /// An argument is invalid.
+    InvalidInput = 2,
+    /// The server responded with an error status.
+    Http = 3,
+    /// Any other error.
+    Other = 4,
+}
+
+impl From<&FfiError> for EsploraStatus {
+    fn from(err: &FfiError) -> Self {
+        match err {
+            FfiError::InvalidInput { .. } => EsploraStatus::InvalidInput,
+            FfiError::Http { .. } => EsploraStatus::Http,
+            FfiError::Other { .. } => EsploraStatus::Other,
+        }
+    }
+}
+
+/// Read a string argument.
+unsafe fn input(string: *const c_char) -> Result<String, FfiError> {
+    if string.is_null() {
+        return Err(FfiError::InvalidInput {
+            message: "null string".to_owned(),
+        });
+    }
+    CStr::from_ptr(string)
+        .to_str()
+        .map(str::to_owned)
+        .map_err(|err| FfiError::InvalidInput {
+            message: err.to_string(),
+        })
+}
+
+/// Hand a string over to the caller, failing if it has an interior NUL, which the caller
+/// would take for its end.
+fn output(string: String) -> Result<*mut c_char, FfiError> {
+    CString::new(string)
+        .map(CString::into_raw)
+        .map_err(|err| FfiError::Other {
+            message: format!("result with a NUL byte at {}", err.nul_position()),
+        })
+}
+
+/// Hand the message of `err` over to the caller, escaping its NUL bytes, so that messages
+/// relayed from the server are never truncated nor lost.
+fn error_message(err: &FfiError) -> *mut c_char {
+    let message = err.to_string().replace('\0', "\\0");
+    CString::new(message)
+        .expect("NUL bytes are escaped")
+        .into_raw()
+}
+
+unsafe fn client<'a>(client: *const FfiClient) -> Result<&'a FfiClient, FfiError> {
+    client.as_ref().ok_or_else(|| FfiError::InvalidInput {
+        message: "null client".to_owned(),
+    })
+}
+
+/// Run `call`, writing its result to `out` with `write` or its error, including a panic or
+/// a failure of `write`, to `error`.
+unsafe fn run<T, O>(
+    out: *mut O,
+    error: *mut *mut c_char,
+    call: impl FnOnce() -> Result<Option<T>, FfiError>,
+    write: impl FnOnce(T) -> Result<O, FfiError>,
+) -> EsploraStatus {
+    let result = panic::catch_unwind(AssertUnwindSafe(call)).unwrap_or_else(|_| {
+        Err(FfiError::Other {
+            message: "panicked".to_owned(),
+        })
+    });
+    let result = match result {
+        Ok(Some(value)) if !out.is_null() => write(value).map(|value| *out = value),
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => return EsploraStatus::NotFound,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(()) => EsploraStatus::Ok,
+        Err(err) => {
+            if !error.is_null() {
+                *error = error_message(&err);
+            }
+            EsploraStatus::from(&err)
+        }
+    }
+}
+
+/// Create a client for the Esplora server at `base_url`, returning null on failure.
+///
+/// # Safety
+///
+/// `base_url` must be null or a NUL-terminated string, and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn esplora_client_new(
+    base_url: *const c_char,
+    error: *mut *mut c_char,
+) -> *const FfiClient {
+    let mut client = ptr::null();
+    run(
+        &mut client,
+        error,
+        || Ok(Some(FfiClient::new(input(base_url)?)?)),
+        |client| Ok(Arc::into_raw(client)),
+    );
+    client
+}
+
+/// Release a client created with [`esplora_client_new`].
+///
+/// # Safety
+///
+/// `client` must be null or a client returned by [`esplora_client_new`], which can't be used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn esplora_client_free(client: *const FfiClient) {
+    if !client.is_null() {
+        drop(Arc::from_raw(client));
+    }
+}
+
+/// Release a string returned by one of the functions.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by one of the functions, which can't be used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn esplora_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Get the consensus-encoded transaction with the given txid as a hex string.
+///
+/// # Safety
+///
+/// `client` must be null or a live client, `txid` null or a NUL-terminated string, and `out`
+/// and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn esplora_tx_hex(
+    client: *const FfiClient,
+    txid: *const c_char,
+    out: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> EsploraStatus {
+    run(
+        out,
+        error,
+        || self::client(client)?.tx_hex(input(txid)?),
+        output,
+    )
+}
+
+/// Broadcast a consensus-encoded transaction given as a hex string, writing its txid.
+///
+/// # Safety
+///
+/// `client` must be null or a live client, `tx_hex` null or a NUL-terminated string, and `out`
+/// and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn esplora_broadcast_hex(
+    client: *const FfiClient,
+    tx_hex: *const c_char,
+    out: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> EsploraStatus {
+    run(
+        out,
+        error,
+        || {
+            self::client(client)?
+                .broadcast_hex(input(tx_hex)?)
+                .map(Some)
+        },
+        output,
+    )
+}
+
+/// Get the status of the transaction with the given txid, as the JSON object returned by
+/// Esplora.
+///
+/// # Safety
+///
+/// `client` must be null or a live client, `txid` null or a NUL-terminated string, and `out`
+/// and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn esplora_tx_status_json(
+    client: *const FfiClient,
+    txid: *const c_char,
+    out: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> EsploraStatus {
+    run(
+        out,
+        error,
+        || {
+            let status = self::client(client)?.tx_status(input(txid)?)?;
+            Ok(Some(
+                serde_json::json!({
+                    "confirmed": status.confirmed,
+                    "block_height": status.block_height,
+                    "block_hash": status.block_hash,
+                    "block_time": status.block_time,
+                })
+                .to_string(),
+            ))
+        },
+        output,
+    )
+}
+
+/// Get the unspent outputs of an address, as the JSON array returned by Esplora.
+///
+/// # Safety
+///
+/// `client` must be null or a live client, `address` null or a NUL-terminated string, and
+/// `out` and `error` null or valid for writes.
+#[cfg(feature = "addresses")]
+#[no_mangle]
+pub unsafe extern "C" fn esplora_address_utxos_json(
+    client: *const FfiClient,
+    address: *const c_char,
+    out: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> EsploraStatus {
+    run(
+        out,
+        error,
+        || {
+            self::client(client)?
+                .address_utxos_json(input(address)?)
+                .map(Some)
+        },
+        output,
+    )
+}
+
+/// Get the first page of the transaction history of an address, newest first, as the JSON
+/// array returned by Esplora.
+///
+/// # Safety
+///
+/// `client` must be null or a live client, `address` null or a NUL-terminated string, and
+/// `out` and `error` null or valid for writes.
+#[cfg(feature = "addresses")]
+#[no_mangle]
+pub unsafe extern "C" fn esplora_address_history_json(
+    client: *const FfiClient,
+    address: *const c_char,
+    out: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> EsploraStatus {
+    run(
+        out,
+        error,
+        || {
+            self::client(client)?
+                .address_history_json(input(address)?)
+                .map(Some)
+        },
+        output,
+    )
+}
+
+/// Get the height of the current blockchain tip.
+///
+/// # Safety
+///
+/// `client` must be null or a live client, and `out` and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn esplora_tip_height(
+    client: *const FfiClient,
+    out: *mut u32,
+    error: *mut *mut c_char,
+) -> EsploraStatus {
+    run(
+        out,
+        error,
+        || self::client(client)?.tip_height().map(Some),
+        Ok,
+    )
+}
+
+/// Get the estimated feerates (in sat/vB) by confirmation target (in number of blocks), as
+/// the JSON object returned by Esplora.
+///
+/// # Safety
+///
+/// `client` must be null or a live client, and `out` and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn esplora_fee_estimates_json(
+    client: *const FfiClient,
+    out: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> EsploraStatus {
+    run(
+        out,
+        error,
+        || {
+            let estimates = self::client(client)?.fee_estimates()?;
+            Ok(Some(serde_json::json!(estimates).to_string()))
+        },
+        output,
+    )
+}
@@ -0,0 +1,252 @@
+//! Proof-of-work helpers for block headers: targets, validation and accumulated chain work.
+
+use std::borrow::Borrow;
+
+use amplify::num::u256;
+#[cfg(any(feature = "blocking", feature = "async"))]
+use bpstd::BlockHash;
+use bpstd::BlockHeader;
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use crate::Error;
+
+/// Amount of work, as the expected number of hashes needed to produce some headers.
+pub type Work = u256;
+
+/// Expand the compact `bits` encoding of a header into the full 256-bit target.
+///
+/// Negative and overflowing encodings can't appear in valid headers, and give a zero target.
+pub fn target_from_bits(bits: u32) -> u256 {
+    let exponent = (bits >> 24) as usize;
+    let mantissa = bits & 0x007f_ffff;
+    if mantissa == 0 || bits & 0x0080_0000 != 0 {
+        return u256::ZERO;
+    }
+    if exponent <= 3 {
+        return u256::from(mantissa >> (8 * (3 - exponent)));
+    }
+    let shift = 8 * (exponent - 3);
+    if (32 - mantissa.leading_zeros()) as usize + shift > 256 {
+        return u256::ZERO;
+    }
+    u256::from(mantissa) << shift
+}
+
+/// Check that the hash of `header` meets the target encoded in its `bits`.
+pub fn validate_pow(header: &BlockHeader) -> bool {
+    let target = target_from_bits(header.bits);
+    if target.is_zero() {
+        return false;
+    }
+    let block_hash = header.block_hash();
+    let bytes: &[u8] = block_hash.borrow();
+    // block hashes are stored in little-endian order, the reverse of their display
+    let hash = u256::from_le_slice(bytes).expect("block hash is 32 bytes");
+    hash <= target
+}
+
+/// Work proven by a header, computed from its target as `2^256 / (target + 1)`.
+pub fn header_work(header: &BlockHeader) -> Work {
+    let target = target_from_bits(header.bits);
+    if target.is_zero() {
+        return Work::ZERO;
+    }
+    // 2^256 doesn't fit into 256 bits, so compute (2^256 - target - 1) / (target + 1) + 1
+    (!target / (target + 1u64)) + 1u64
+}
+
+/// Total work proven by `headers`.
+pub fn cumulative_work(headers: &[BlockHeader]) -> Work {
+    headers
+        .iter()
+        .fold(Work::ZERO, |work, header| work + header_work(header))
+}
+
+/// Check that `headers` are those of the blocks with the given `hashes`, each of them building
+/// on the previous one, as a server may return headers of another chain or out of order.
+#[cfg(any(feature = "blocking", feature = "async"))]
+pub(crate) fn check_header_chain(
+    hashes: &[BlockHash],
+    headers: &[BlockHeader],
+) -> Result<(), Error> {
+    let mismatch = |details: String| Error::InconsistentBackend {
+        check: "header_chain",
+        details,
+    };
+    if hashes.len() != headers.len() {
+        return Err(mismatch(format!(
+            "{} headers returned for {} blocks",
+            headers.len(),
+            hashes.len()
+        )));
+    }
+    for (n, (hash, header)) in hashes.iter().zip(headers).enumerate() {
+        let actual = header.block_hash();
+        if actual != *hash {
+            return Err(mismatch(format!(
+                "header of block {} hashes to {}",
+                hash, actual
+            )));
+        }
+        if n > 0 && header.prev_block_hash != hashes[n - 1] {
+            return Err(mismatch(format!(
+                "block {} builds on {} rather than on block {}",
+                hash,
+                header.prev_block_hash,
+                hashes[n - 1]
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use amplify::hex::FromHex;
+    use amplify::ByteArray;
+    use bpstd::{BlockHash, BlockMerkleRoot};
+
+    use super::*;
+
+    fn hash(s: &str) -> BlockHash {
+        BlockHash::from_str(s).unwrap()
+    }
+
+    fn target(hex: &str) -> u256 {
+        u256::from_be_slice(&Vec::<u8>::from_hex(hex).unwrap()).unwrap()
+    }
+
+    /// Headers of the first three mainnet blocks, with their hashes.
+    fn mainnet_headers() -> (Vec<BlockHash>, Vec<BlockHeader>) {
+        let genesis = hash("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f");
+        let block1 = hash("00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048");
+        let block2 = hash("000000006a625f06636b8bb6ac7b960a8d03705d1ace08b1a19da3fdcc99ddbd");
+        let header = |prev_block_hash, merkle_root: &str, time, nonce| BlockHeader {
+            version: 1,
+            prev_block_hash,
+            merkle_root: BlockMerkleRoot::from_str(merkle_root).unwrap(),
+            time,
+            bits: 0x1d00_ffff,
+            nonce,
+        };
+        let headers = vec![
+            header(
+                BlockHash::from_byte_array([0; 32]),
+                "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+                1231006505,
+                2083236893,
+            ),
+            header(
+                genesis,
+                "0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098",
+                1231469665,
+                2573394689,
+            ),
+            header(
+                block1,
+                "9b0fc92260312ce44e74ef369f5c66bbb85848f2eddd5a7a1cde251e54ccfdd5",
+                1231469744,
+                1639830024,
+            ),
+        ];
+        (vec![genesis, block1, block2], headers)
+    }
+
+    #[test]
+    fn targets() {
+        let vectors = [
+            (
+                0x1d00_ffff,
+                "00000000ffff0000000000000000000000000000000000000000000000000000",
+            ),
+            (
+                0x1d00_d86a,
+                "00000000d86a0000000000000000000000000000000000000000000000000000",
+            ),
+            (
+                0x1b04_04cb,
+                "00000000000404cb000000000000000000000000000000000000000000000000",
+            ),
+            (
+                0x1703_4219,
+                "0000000000000000000342190000000000000000000000000000000000000000",
+            ),
+        ];
+        for (bits, expected) in vectors.iter() {
+            assert_eq!(
+                target_from_bits(*bits),
+                target(expected),
+                "bits {:08x}",
+                bits
+            );
+        }
+        assert_eq!(target_from_bits(0x0300_0080), u256::from(0x80u64));
+        assert_eq!(target_from_bits(0x0112_3456), u256::from(0x12u64));
+        assert_eq!(target_from_bits(0x0492_3456), u256::ZERO, "negative");
+        assert_eq!(target_from_bits(0xff12_3456), u256::ZERO, "overflowing");
+    }
+
+    #[test]
+    fn proof_of_work() {
+        let (_, headers) = mainnet_headers();
+        for header in &headers {
+            assert!(validate_pow(header));
+        }
+        let mut header = headers[1];
+        header.nonce += 1;
+        assert!(!validate_pow(&header));
+        header.bits = 0x0492_3456;
+        assert!(!validate_pow(&header));
+    }
+
+    #[test]
+    fn work() {
+        let (_, headers) = mainnet_headers();
+        assert_eq!(header_work(&headers[0]), Work::from(0x1_0001_0001u64));
+
+        let vectors = [
+            (0x1d00_d86a, 5_080_592_338u128),
+            (0x1b04_04cb, 70_040_908_352_512),
+            (0x1703_4219, 371_041_696_979_166_003_650_763),
+        ];
+        for (bits, expected) in vectors.iter() {
+            let header = BlockHeader {
+                bits: *bits,
+                ..headers[0]
+            };
+            assert_eq!(
+                header_work(&header),
+                Work::from(*expected),
+                "bits {:08x}",
+                bits
+            );
+        }
+
+        // chainwork of mainnet block 2, as reported by Bitcoin Core
+        assert_eq!(cumulative_work(&headers), Work::from(0x3_0003_0003u64));
+        assert_eq!(cumulative_work(&[]), Work::ZERO);
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    #[test]
+    fn header_chains() {
+        let (hashes, headers) = mainnet_headers();
+        check_header_chain(&hashes, &headers).unwrap();
+        check_header_chain(&hashes[1..], &headers[1..]).unwrap();
+
+        assert!(check_header_chain(&hashes, &headers[..2]).is_err());
+        let swapped = [headers[0], headers[2], headers[1]];
+        assert!(check_header_chain(&hashes, &swapped).is_err());
+        let gapped = [hashes[0], hashes[2]];
+        let err = check_header_chain(&gapped, &[headers[0], headers[2]]).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InconsistentBackend {
+                check: "header_chain",
+                ..
+            }
+        ));
+    }
+}
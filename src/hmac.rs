@@ -0,0 +1,53 @@
+//! HMAC-SHA256 (RFC 2104), authenticating pagination cursors and deriving the keys of signed
+//! requests.
+
+use sha2::{Digest, Sha256};
+
+/// Size of the blocks hashed by SHA-256, to which HMAC keys are padded.
+const SHA256_BLOCK_LEN: usize = 64;
+
+/// HMAC-SHA256 of `data` with `key`.
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; SHA256_BLOCK_LEN];
+    if key.len() > SHA256_BLOCK_LEN {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    inner.update(data);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+
+    let mut mac = [0u8; 32];
+    mac.copy_from_slice(&outer.finalize());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use amplify::hex::ToHex;
+
+    use super::*;
+
+    #[test]
+    fn rfc4231_vectors() {
+        // test case 2
+        assert_eq!(
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?")[..].to_hex(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // test case 6, with a key longer than a block
+        assert_eq!(
+            hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )[..]
+                .to_hex(),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Shutdown of clients: refusing new requests while the in-flight ones complete.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::Error;
+
+/// Interval at which a shutting down client checks whether its in-flight requests completed.
+pub(crate) const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Open or closed state of a client and its number of in-flight requests, shared between the
+/// clones of the client.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Lifecycle(Arc<LifecycleState>);
+
+#[derive(Debug, Default)]
+struct LifecycleState {
+    closed: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+impl Lifecycle {
+    /// Registers a new request, failing with [`Error::ClientClosed`] once the client is shut
+    /// down. The request is in flight until the returned guard is dropped.
+    pub fn enter(&self) -> Result<RequestGuard, Error> {
+        // counting the request before checking the state, so that a concurrent shutdown either
+        // waits for it or makes it fail
+        self.0.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = RequestGuard(self.clone());
+        if self.0.closed.load(Ordering::SeqCst) {
+            return Err(Error::ClientClosed);
+        }
+        Ok(guard)
+    }
+
+    /// Stops accepting new requests.
+    pub fn close(&self) {
+        self.0.closed.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether all the accepted requests completed.
+    pub fn is_idle(&self) -> bool {
+        self.0.in_flight.load(Ordering::SeqCst) == 0
+    }
+}
+
+/// Marks a request as in flight while it is alive.
+#[derive(Debug)]
+pub(crate) struct RequestGuard(Lifecycle);
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        (self.0).0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,292 @@
+//! Client-side tracking of the time mempool transactions were first seen, which Esplora
+//! doesn't report.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bpstd::{ScriptPubkey, Txid};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{AsyncClient, Clock, Error, SystemClock};
+
+/// Ages of the tracked transactions at some point in time, used to persist a
+/// [`MempoolTracker`] across restarts.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MempoolSnapshot {
+    /// UNIX timestamp, in seconds, at which the snapshot was taken.
+    pub taken_at: u64,
+    /// Number of seconds each transaction had been in the mempool when the snapshot was
+    /// taken.
+    pub ages: BTreeMap<Txid, u64>,
+}
+
+/// Records the instant unconfirmed transactions were first seen in the mempool.
+///
+/// The tracker only knows about transactions which were in the mempool during a
+/// [`MempoolTracker::refresh`] or [`MempoolTracker::refresh_script`], so the first-seen
+/// instants are as precise as the refresh interval.
+///
+/// Transactions are forgotten once they leave the mempool, as noticed by the refreshes, or when
+/// [`MempoolTracker::age_checked`] finds them confirmed.
+#[derive(Debug)]
+pub struct MempoolTracker {
+    client: AsyncClient,
+    clock: Arc<dyn Clock>,
+    seen: Mutex<HashMap<Txid, Seen>>,
+}
+
+/// Tracked transaction.
+#[derive(Clone, Debug)]
+struct Seen {
+    first_seen: Instant,
+    /// Script the transaction was seen for by [`MempoolTracker::refresh_script`], whose next
+    /// refreshes forget it once it isn't in the mempool anymore.
+    script: Option<ScriptPubkey>,
+}
+
+impl Seen {
+    fn new(first_seen: Instant) -> Self {
+        Seen {
+            first_seen,
+            script: None,
+        }
+    }
+}
+
+impl MempoolTracker {
+    /// Track the mempool of the server `client` connects to.
+    pub fn new(client: AsyncClient) -> Self {
+        MempoolTracker::with_clock(client, Arc::new(SystemClock))
+    }
+
+    /// Track the mempool of the server `client` connects to, reading time from `clock`.
+    pub fn with_clock(client: AsyncClient, clock: Arc<dyn Clock>) -> Self {
+        MempoolTracker {
+            client,
+            clock,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record the transactions currently in the mempool, and forget the tracked ones which
+    /// left it, as they were either confirmed or dropped.
+    pub async fn refresh(&self) -> Result<(), Error> {
+        let txids = self.client.mempool_txids().await?;
+        let now = self.clock.now();
+        let current = txids.into_iter().collect::<HashSet<_>>();
+
+        let mut seen = self.seen.lock().expect("poisoned mempool tracker");
+        seen.retain(|txid, _| current.contains(txid));
+        for txid in current {
+            seen.entry(txid).or_insert_with(|| Seen::new(now));
+        }
+        Ok(())
+    }
+
+    /// Record the mempool transactions of `script`, without fetching the whole mempool.
+    ///
+    /// The transactions previously seen for `script` which aren't in its mempool anymore are
+    /// forgotten. Other transactions leaving the mempool aren't noticed this way, so they must
+    /// be forgotten with [`MempoolTracker::prune`] or [`MempoolTracker::age_checked`].
+    pub async fn refresh_script(&self, script: &ScriptPubkey) -> Result<(), Error> {
+        let txs = self.client.scripthash_mempool_txs(script).await?;
+        let now = self.clock.now();
+        let current = txs.into_iter().map(|tx| tx.txid).collect::<HashSet<_>>();
+
+        let mut seen = self.seen.lock().expect("poisoned mempool tracker");
+        seen.retain(|txid, entry| entry.script.as_ref() != Some(script) || current.contains(txid));
+        for txid in current {
+            seen.entry(txid).or_insert_with(|| Seen {
+                first_seen: now,
+                script: Some(script.clone()),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the status of each tracked transaction, forgetting the confirmed and dropped
+    /// ones.
+    pub async fn prune(&self) -> Result<(), Error> {
+        for txid in self.tracked() {
+            if self.left_mempool(&txid).await? {
+                self.forget(&txid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the instant a transaction was first seen in the mempool, if it is tracked.
+    pub fn first_seen(&self, txid: &Txid) -> Option<Instant> {
+        self.seen
+            .lock()
+            .expect("poisoned mempool tracker")
+            .get(txid)
+            .map(|seen| seen.first_seen)
+    }
+
+    /// Get the time a transaction has spent in the mempool since it was first seen, if it is
+    /// tracked.
+    pub fn age(&self, txid: &Txid) -> Option<Duration> {
+        let first_seen = self.first_seen(txid)?;
+        Some(self.clock.now().saturating_duration_since(first_seen))
+    }
+
+    /// Get the time a tracked transaction has spent in the mempool, after checking with the
+    /// server that it is still unconfirmed, and forgetting it otherwise.
+    pub async fn age_checked(&self, txid: &Txid) -> Result<Option<Duration>, Error> {
+        if self.first_seen(txid).is_none() {
+            return Ok(None);
+        }
+        if self.left_mempool(txid).await? {
+            self.forget(txid);
+            return Ok(None);
+        }
+        Ok(self.age(txid))
+    }
+
+    /// Get the ids of the tracked transactions.
+    pub fn tracked(&self) -> Vec<Txid> {
+        self.seen
+            .lock()
+            .expect("poisoned mempool tracker")
+            .keys()
+            .copied()
+            .collect()
+    }
+
+    /// Stop tracking a transaction.
+    pub fn forget(&self, txid: &Txid) {
+        self.seen
+            .lock()
+            .expect("poisoned mempool tracker")
+            .remove(txid);
+    }
+
+    /// Take a snapshot of the tracked transactions, which can be persisted and later passed
+    /// to [`MempoolTracker::restore`].
+    pub fn snapshot(&self) -> MempoolSnapshot {
+        let now = self.clock.now();
+        let ages = self
+            .seen
+            .lock()
+            .expect("poisoned mempool tracker")
+            .iter()
+            .map(|(txid, seen)| {
+                let age = now.saturating_duration_since(seen.first_seen);
+                (*txid, age.as_secs())
+            })
+            .collect();
+        MempoolSnapshot {
+            taken_at: unix_time(self.clock.system_time()),
+            ages,
+        }
+    }
+
+    /// Track the transactions of a snapshot, accounting for the time elapsed since it was
+    /// taken. Transactions already tracked keep their first-seen instant.
+    pub fn restore(&self, snapshot: &MempoolSnapshot) {
+        let now = self.clock.now();
+        let elapsed = unix_time(self.clock.system_time()).saturating_sub(snapshot.taken_at);
+
+        let mut seen = self.seen.lock().expect("poisoned mempool tracker");
+        for (txid, age) in &snapshot.ages {
+            let age = Duration::from_secs(age.saturating_add(elapsed));
+            seen.entry(*txid)
+                .or_insert_with(|| Seen::new(saturating_sub(now, age)));
+        }
+    }
+
+    async fn left_mempool(&self, txid: &Txid) -> Result<bool, Error> {
+        match self.client.tx_status(txid).await {
+            Ok(status) => Ok(status.confirmed),
+            Err(err) if is_not_found(&err) => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// Get the instant `duration` before `instant`, or the earliest one which can be represented,
+/// as instants can't go before the start of the monotonic clock.
+fn saturating_sub(instant: Instant, duration: Duration) -> Instant {
+    if let Some(earlier) = instant.checked_sub(duration) {
+        return earlier;
+    }
+    // search the longest duration which can be subtracted, to the nanosecond
+    let (mut low, mut high) = (Duration::from_secs(0), duration);
+    while high - low > Duration::from_nanos(1) {
+        let mid = low + (high - low) / 2;
+        if instant.checked_sub(mid).is_some() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    instant - low
+}
+
+fn is_not_found(error: &Error) -> bool {
+    match error {
+        Error::HttpResponse { status, .. } => *status == 404,
+        Error::Reqwest(e) => e.status() == Some(StatusCode::NOT_FOUND),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amplify::ByteArray;
+
+    use super::*;
+    use crate::{Builder, ManualClock};
+
+    fn tracker(clock: &Arc<ManualClock>) -> MempoolTracker {
+        let client = Builder::new("http://127.0.0.1:1").build_async().unwrap();
+        MempoolTracker::with_clock(client, clock.clone())
+    }
+
+    #[test]
+    fn instants_saturate() {
+        let now = Instant::now();
+        let second = Duration::from_secs(1);
+        assert_eq!(saturating_sub(now, second), now - second);
+
+        let earliest = saturating_sub(now, Duration::MAX);
+        assert!(earliest < now);
+        assert_eq!(earliest.checked_sub(Duration::from_nanos(2)), None);
+    }
+
+    #[test]
+    fn snapshots_carry_ages() {
+        let clock = Arc::new(ManualClock::new());
+        let txid = Txid::from_byte_array([1u8; 32]);
+        let old = Txid::from_byte_array([2u8; 32]);
+        let snapshot = MempoolSnapshot {
+            taken_at: unix_time(clock.system_time()),
+            ages: [(txid, 60), (old, u64::MAX)].iter().copied().collect(),
+        };
+
+        clock.advance(Duration::from_secs(30));
+        let original = tracker(&clock);
+        original.restore(&snapshot);
+        assert_eq!(original.age(&txid), Some(Duration::from_secs(90)));
+        // too old for the monotonic clock, but still older than the other one
+        assert!(original.age(&old).unwrap() > Duration::from_secs(90));
+
+        clock.advance(Duration::from_secs(10));
+        let snapshot = original.snapshot();
+        assert_eq!(snapshot.taken_at, unix_time(clock.system_time()));
+        assert_eq!(snapshot.ages[&txid], 100);
+
+        let restored = tracker(&clock);
+        restored.restore(&snapshot);
+        assert_eq!(restored.first_seen(&txid), original.first_seen(&txid));
+    }
+}
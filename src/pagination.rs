@@ -0,0 +1,89 @@
+//! Safety rails for following paginated Esplora endpoints.
+
+use std::collections::HashSet;
+
+use bpstd::Txid;
+
+use crate::{Error, PaginationAnomaly};
+
+/// Number of confirmed transactions Esplora returns per history page.
+pub(crate) const CONFIRMED_PAGE_SIZE: usize = 25;
+
+/// Number of transactions Esplora returns per page of the transactions of a block.
+#[cfg(any(feature = "blocking", feature = "async"))]
+pub(crate) const BLOCK_TXS_PAGE_SIZE: usize = 25;
+
+/// Number of transactions repeated across pages tolerated before giving up. Some repetition is
+/// expected when transactions get confirmed while paging.
+const MAX_DUPLICATE_TXIDS: usize = CONFIRMED_PAGE_SIZE;
+
+/// Tracks the progress of a paginated transaction history fetch, detecting servers which would
+/// make it loop forever.
+#[derive(Debug)]
+pub(crate) struct PageGuard {
+    max_pages: usize,
+    pages_fetched: usize,
+    cursors: HashSet<Txid>,
+    txids: HashSet<Txid>,
+    duplicates: usize,
+}
+
+impl PageGuard {
+    pub fn new(max_pages: usize) -> Self {
+        PageGuard {
+            max_pages,
+            pages_fetched: 0,
+            cursors: HashSet::new(),
+            txids: HashSet::new(),
+            duplicates: 0,
+        }
+    }
+
+    /// Registers a fetched page, returning the cursor for the next page, or `None` if this was
+    /// the last one.
+    pub fn next_cursor(&mut self, page: &[crate::Tx]) -> Result<Option<Txid>, Error> {
+        self.pages_fetched += 1;
+
+        for tx in page {
+            if !self.txids.insert(tx.txid) {
+                self.duplicates += 1;
+            }
+        }
+        if self.duplicates > MAX_DUPLICATE_TXIDS {
+            return Err(self.anomaly(PaginationAnomaly::DuplicateTxids));
+        }
+
+        let cursor = match next_page_cursor(page) {
+            Some(cursor) => cursor,
+            None => return Ok(None),
+        };
+        if !self.cursors.insert(cursor) {
+            return Err(self.anomaly(PaginationAnomaly::RepeatedCursor));
+        }
+        if self.pages_fetched >= self.max_pages {
+            return Err(self.anomaly(PaginationAnomaly::PageLimit));
+        }
+        Ok(Some(cursor))
+    }
+
+    fn anomaly(&self, kind: PaginationAnomaly) -> Error {
+        Error::PaginationAnomaly {
+            kind,
+            pages_fetched: self.pages_fetched,
+            partial: vec![],
+        }
+    }
+}
+
+/// Get the txid to continue a history after `page`, or `None` if it was the last page: the
+/// last confirmed transaction of a full page of confirmed transactions.
+pub(crate) fn next_page_cursor(page: &[crate::Tx]) -> Option<Txid> {
+    let confirmed = page
+        .iter()
+        .filter(|tx| tx.status.confirmed)
+        .collect::<Vec<_>>();
+    if confirmed.len() < CONFIRMED_PAGE_SIZE {
+        return None;
+    }
+    Some(confirmed[confirmed.len() - 1].txid)
+}
@@ -0,0 +1,46 @@
+//! Query strings of endpoint URLs, for the backends paginating with query parameters.
+
+use std::fmt::{self, Display, Formatter};
+
+/// Query parameters of a request, percent-encoded in the order they were added, so that the
+/// resulting URLs are stable.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct QueryParams(Vec<(&'static str, String)>);
+
+impl QueryParams {
+    pub fn new() -> Self {
+        QueryParams::default()
+    }
+
+    /// Add a parameter.
+    pub fn param(mut self, name: &'static str, value: impl Display) -> Self {
+        self.0.push((name, value.to_string()));
+        self
+    }
+}
+
+/// Formats the query string, including the leading `?`, or nothing when there is no parameter.
+impl Display for QueryParams {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (index, (name, value)) in self.0.iter().enumerate() {
+            f.write_str(if index == 0 { "?" } else { "&" })?;
+            percent_encode(name, f)?;
+            f.write_str("=")?;
+            percent_encode(value, f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Percent-encodes all the bytes but the unreserved characters of RFC 3986.
+fn percent_encode(s: &str, f: &mut Formatter<'_>) -> fmt::Result {
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                write!(f, "{}", byte as char)?
+            }
+            _ => write!(f, "%{:02X}", byte)?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+//! Ids sent with each request in the `X-Request-Id` header, correlating the requests of a
+//! client with the logs of the gateways and servers they go through.
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::sync::Arc;
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use crate::rng::SplitMix64;
+
+/// Name of the header carrying the id of a request.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Ids sent with the requests of a client. Random UUIDs are sent by default.
+///
+/// A request keeps its id when it is retried or redirected.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RequestIdPolicy {
+    /// No id is sent.
+    Disabled,
+    /// Random UUIDs (version 4).
+    Uuid,
+    /// The given prefix followed by the number of the request, like `wallet-42`.
+    Prefixed(String),
+}
+
+impl Default for RequestIdPolicy {
+    fn default() -> Self {
+        RequestIdPolicy::Uuid
+    }
+}
+
+/// Information about the request made by a `*_with_meta` method of the clients.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RequestMeta {
+    /// Id sent in the [`REQUEST_ID_HEADER`] of the request, unless disabled.
+    pub request_id: Option<String>,
+}
+
+/// Generator of the ids of the requests of a client and its clones.
+///
+/// Ids are drawn from a counter shared between the clones, mixed with a random seed of the
+/// client for UUIDs, so that no lock is taken.
+#[cfg(any(feature = "blocking", feature = "async"))]
+#[derive(Clone, Debug)]
+pub(crate) struct RequestIds {
+    policy: RequestIdPolicy,
+    seed: u64,
+    count: Arc<AtomicU64>,
+}
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+impl RequestIds {
+    pub fn new(policy: RequestIdPolicy) -> Self {
+        RequestIds {
+            policy,
+            seed: SplitMix64::from_entropy().next_u64(),
+            count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Get the id of the next request, unless ids are disabled.
+    pub fn next(&self) -> Option<String> {
+        match &self.policy {
+            RequestIdPolicy::Disabled => None,
+            RequestIdPolicy::Prefixed(prefix) => Some(format!(
+                "{}-{}",
+                prefix,
+                self.count.fetch_add(1, Ordering::Relaxed)
+            )),
+            RequestIdPolicy::Uuid => {
+                let count = self.count.fetch_add(1, Ordering::Relaxed);
+                // each id takes two draws of the sequence of the client
+                let mut rng = SplitMix64::skipped(self.seed, count.wrapping_mul(2));
+                let high = (rng.next_u64() & !0xf000) | 0x4000;
+                let low = (rng.next_u64() & !(0b11 << 62)) | (0b10 << 62);
+                Some(format!(
+                    "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+                    high >> 32,
+                    (high >> 16) & 0xffff,
+                    high & 0xffff,
+                    low >> 48,
+                    low & 0xffff_ffff_ffff
+                ))
+            }
+        }
+    }
+}
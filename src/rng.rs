@@ -0,0 +1,99 @@
+//! Seeded pseudo-random generator, making randomized behaviors reproducible.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of generators seeded from entropy, telling apart the ones seeded with the same keys.
+static ENTROPY_SEEDED: AtomicU64 = AtomicU64::new(0);
+
+/// Increment of the state of the generator at each draw.
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// SplitMix64 generator: small and fast, and good enough for drawing faults and endpoints,
+/// though not for anything security-related.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    /// Seeds a generator from the random keys of the standard hash maps, for behaviors which
+    /// should differ between instances unless reproduced with an explicit seed.
+    ///
+    /// Unlike the system time, the keys are available on all targets. On `wasm32`, where they
+    /// are fixed, generators only differ by the order in which they are seeded.
+    pub fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(ENTROPY_SEEDED.fetch_add(1, Ordering::Relaxed));
+        let mut rng = SplitMix64(hasher.finish());
+        SplitMix64(rng.next_u64())
+    }
+
+    /// Generator seeded with `seed` after `draws` draws, reached without drawing, so that
+    /// independent draws can be made from a shared counter without locking.
+    pub fn skipped(seed: u64, draws: u64) -> Self {
+        SplitMix64(seed.wrapping_add(draws.wrapping_mul(GAMMA)))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(GAMMA);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Draws a fraction uniformly distributed in `[0, 1)`, as its numerator over `2^32`.
+    pub fn next_fraction(&mut self) -> u64 {
+        self.next_u64() >> 32
+    }
+
+    /// Draws a float uniformly distributed in `[0, 1)`.
+    #[cfg(feature = "chaos")]
+    pub fn next_f64(&mut self) -> f64 {
+        // 53 random bits give a uniform float in [0, 1)
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entropy_seeds_differ() {
+        let draws = (0..16)
+            .map(|_| SplitMix64::from_entropy().next_u64())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(draws.len(), 16);
+    }
+
+    #[test]
+    fn skipped_draws() {
+        let mut rng = SplitMix64::new(42);
+        rng.next_u64();
+        rng.next_u64();
+        assert_eq!(SplitMix64::skipped(42, 2).next_u64(), rng.next_u64());
+    }
+
+    #[test]
+    fn fractions() {
+        let mut rng = SplitMix64::new(7);
+        for _ in 0..1_000 {
+            assert!(rng.next_fraction() < 1 << 32);
+        }
+    }
+
+    #[cfg(feature = "chaos")]
+    #[test]
+    fn floats() {
+        let mut rng = SplitMix64::new(7);
+        for _ in 0..1_000 {
+            let float = rng.next_f64();
+            assert!((0.0..1.0).contains(&float));
+        }
+    }
+}
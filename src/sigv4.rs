@@ -0,0 +1,361 @@
+//! Signing of requests with AWS Signature Version 4, for Esplora servers behind gateways
+//! authenticating their clients with it, like AWS API Gateway.
+//!
+//! Only the signing algorithm is implemented: the canonical request, the string to sign and the
+//! chain of HMAC-SHA256 deriving the signing key, see
+//! <https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html>.
+
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use amplify::hex::ToHex;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::hmac::hmac_sha256;
+
+/// Identifier of the signing algorithm, starting the string to sign and the `Authorization`
+/// header.
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// AWS credentials signing requests.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Token of temporary credentials, sent in the `X-Amz-Security-Token` header.
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Instantiate long-term credentials, without a session token.
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        Credentials {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+        }
+    }
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"<redacted>")
+            .field(
+                "session_token",
+                &self.session_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+/// Source of the credentials signing each request, which may renew temporary credentials.
+pub trait CredentialsProvider: Debug + Send + Sync {
+    /// Get the credentials signing the request about to be sent.
+    fn credentials(&self) -> Credentials;
+}
+
+impl CredentialsProvider for Credentials {
+    fn credentials(&self) -> Credentials {
+        self.clone()
+    }
+}
+
+/// Signer of the requests to an AWS service in a region.
+#[derive(Clone, Debug)]
+pub struct SigV4 {
+    provider: Arc<dyn CredentialsProvider>,
+    region: String,
+    service: String,
+}
+
+impl SigV4 {
+    /// Instantiate a signer with credentials from `provider`, for `service` (`execute-api` for
+    /// API Gateway) in `region`.
+    pub fn new(
+        provider: impl CredentialsProvider + 'static,
+        region: impl Into<String>,
+        service: impl Into<String>,
+    ) -> Self {
+        SigV4 {
+            provider: Arc::new(provider),
+            region: region.into(),
+            service: service.into(),
+        }
+    }
+
+    /// Get the headers to add to a request to sign it: `X-Amz-Date`, `X-Amz-Security-Token`
+    /// with temporary credentials, and `Authorization`.
+    ///
+    /// The signature covers the method, the path and query of `url`, its host and `body`.
+    pub fn sign(
+        &self,
+        method: &str,
+        url: &Url,
+        body: &[u8],
+        time: SystemTime,
+    ) -> Vec<(&'static str, String)> {
+        let credentials = self.provider.credentials();
+        let (date, timestamp) = amz_date(time);
+
+        let mut headers = vec![("host", host(url)), ("x-amz-date", timestamp.clone())];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect::<String>();
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method,
+            canonical_uri(url),
+            canonical_query(url),
+            canonical_headers,
+            signed_headers,
+            Sha256::digest(body)[..].to_hex()
+        );
+
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "{}\n{}\n{}\n{}",
+            ALGORITHM,
+            timestamp,
+            scope,
+            Sha256::digest(canonical_request.as_bytes())[..].to_hex()
+        );
+        let key = format!("AWS4{}", credentials.secret_access_key);
+        let key = hmac_sha256(key.as_bytes(), date.as_bytes());
+        let key = hmac_sha256(&key, self.region.as_bytes());
+        let key = hmac_sha256(&key, self.service.as_bytes());
+        let key = hmac_sha256(&key, b"aws4_request");
+        let signature = hmac_sha256(&key, string_to_sign.as_bytes())[..].to_hex();
+
+        let mut signing = vec![("X-Amz-Date", timestamp)];
+        if let Some(token) = credentials.session_token {
+            signing.push(("X-Amz-Security-Token", token));
+        }
+        signing.push((
+            "Authorization",
+            format!(
+                "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+                ALGORITHM, credentials.access_key_id, scope, signed_headers, signature
+            ),
+        ));
+        signing
+    }
+}
+
+/// Value of the `Host` header of a request to `url`, with the port unless it's the default one.
+fn host(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_owned(),
+    }
+}
+
+/// Path of `url`, encoded once more as required by all the services but S3.
+fn canonical_uri(url: &Url) -> String {
+    match url.path() {
+        "" => "/".to_owned(),
+        path => uri_encode(path, false),
+    }
+}
+
+/// Query parameters of `url`, encoded and sorted by name, then value.
+fn canonical_query(url: &Url) -> String {
+    let mut params = url
+        .query_pairs()
+        .map(|(name, value)| (uri_encode(&name, true), uri_encode(&value, true)))
+        .collect::<Vec<_>>();
+    params.sort();
+    params
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Percent-encode all the bytes of `s` but the unreserved characters, and `/` unless
+/// `encode_slash`.
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Date (`YYYYMMDD`) and timestamp (`YYYYMMDDTHHMMSSZ`) of `time` in UTC.
+fn amz_date(time: SystemTime) -> (String, String) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_of_day = secs % 86_400;
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let timestamp = format!(
+        "{}T{:02}{:02}{:02}Z",
+        date,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    );
+    (date, timestamp)
+}
+
+/// Year, month and day of the proleptic Gregorian calendar of a number of days since the Unix
+/// epoch, as computed by Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Session token of the `post-sts-token` cases of the AWS test suite.
+    const SESSION_TOKEN: &str = "AQoDYXdzEPT//////////wEXAMPLEtc764bNrC9SAPBSM22wDOk4x4HIZ8j4FZTwdQWLWsKWHGBuFqwAeMicRXmxfpSPfIeoIYRqTflfKD8YUuwthAx7mSEI/qkPpKPi/kMcGdQrmGdeehM4IC1NtBmUpp2wUE8phUZampKsburEDy0KPkyQDYwT7WZ0wq5VSXDvp75YU9HFvlRd8Tx6q6fE8YQcHNVXAkiY9q6d+xo0rKwT38xVqr7ZD0u0iPPkUL64lIZbqBAz+scqKmlzm8FDrypNC9Yjc8fPOLn9FX9KSYvKTr4rvx3iSIlTJabIQwj2ICCR/oLxBA==";
+
+    /// Signer with the credentials, region and service of the AWS test suite.
+    fn signer(session_token: Option<&str>) -> SigV4 {
+        let mut credentials =
+            Credentials::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY");
+        credentials.session_token = session_token.map(str::to_owned);
+        SigV4::new(credentials, "us-east-1", "service")
+    }
+
+    /// Time of the requests of the AWS test suite, 2015-08-30T12:36:00Z.
+    fn time() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_440_938_160)
+    }
+
+    fn authorization(signed_headers: &str, signature: &str) -> String {
+        format!(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders={}, Signature={}",
+            signed_headers, signature
+        )
+    }
+
+    #[test]
+    fn test_suite() {
+        for (name, method, url, signature) in [
+            (
+                "get-vanilla",
+                "GET",
+                "https://example.amazonaws.com/",
+                "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+            ),
+            (
+                "get-vanilla-query-order-key-case",
+                "GET",
+                "https://example.amazonaws.com/?Param2=value2&Param1=value1",
+                "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500",
+            ),
+            (
+                "get-vanilla-utf8-query",
+                "GET",
+                "https://example.amazonaws.com/?ሴ=bar",
+                "2cdec8eed098649ff3a119c94853b13c643bcf08f8b0a1d91e12c9027818dd04",
+            ),
+            (
+                "post-vanilla",
+                "POST",
+                "https://example.amazonaws.com/",
+                "5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b",
+            ),
+            (
+                "post-vanilla-query",
+                "POST",
+                "https://example.amazonaws.com/?Param1=value1",
+                "28038455d6de14eafc1f9222cf5aa6f1a96197d7deb8263271d420d138af7f11",
+            ),
+        ] {
+            let headers = signer(None).sign(method, &Url::parse(url).unwrap(), b"", time());
+            assert_eq!(
+                headers,
+                vec![
+                    ("X-Amz-Date", "20150830T123600Z".to_owned()),
+                    ("Authorization", authorization("host;x-amz-date", signature)),
+                ],
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn session_tokens() {
+        // post-sts-header-before
+        let url = Url::parse("https://example.amazonaws.com/").unwrap();
+        let headers = signer(Some(SESSION_TOKEN)).sign("POST", &url, b"", time());
+        assert_eq!(
+            headers,
+            vec![
+                ("X-Amz-Date", "20150830T123600Z".to_owned()),
+                ("X-Amz-Security-Token", SESSION_TOKEN.to_owned()),
+                (
+                    "Authorization",
+                    authorization(
+                        "host;x-amz-date;x-amz-security-token",
+                        "85d96828115b5dc0cfc3bd16ad9e210dd772bbebba041836c64533a82be05ead"
+                    )
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn hosts_and_paths() {
+        let url = Url::parse("http://localhost:3000/api/tx/ab cd?x=a b").unwrap();
+        assert_eq!(host(&url), "localhost:3000");
+        assert_eq!(canonical_uri(&url), "/api/tx/ab%2520cd");
+        assert_eq!(canonical_query(&url), "x=a%20b");
+        let url = Url::parse("https://example.amazonaws.com:443/").unwrap();
+        assert_eq!(host(&url), "example.amazonaws.com");
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            amz_date(UNIX_EPOCH),
+            ("19700101".to_owned(), "19700101T000000Z".to_owned())
+        );
+        assert_eq!(
+            amz_date(time()),
+            ("20150830".to_owned(), "20150830T123600Z".to_owned())
+        );
+        assert_eq!(
+            amz_date(UNIX_EPOCH + Duration::from_secs(951_868_799)),
+            ("20000229".to_owned(), "20000229T235959Z".to_owned())
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Timers of the async runtime driving an [`AsyncClient`](crate::AsyncClient), used to wait
+//! between retries and to bound warm-ups and shutdowns.
+//!
+//! Only the timers are taken from the [`Sleeper`]: the requests themselves are made with
+//! reqwest, which needs a Tokio reactor whatever the runtime polling the client futures.
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture, Either, FutureExt};
+
+/// Source of non-blocking sleeps of an async runtime.
+///
+/// Implementations must not block the calling thread, which would stall the other tasks of
+/// the executor.
+pub trait Sleeper: Debug + Send + Sync {
+    /// Get a future completing after `duration`.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// [`Sleeper`] using the timers of the Tokio runtime, used by default.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TokioSleeper;
+
+impl Sleeper for TokioSleeper {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        tokio::time::sleep(duration).boxed()
+    }
+}
+
+/// [`Sleeper`] using the timers of the async-std runtime.
+///
+/// The client futures may then be polled by async-std, within the context of a Tokio runtime
+/// running the reactor of the requests.
+#[cfg(feature = "async-std-sleeper")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AsyncStdSleeper;
+
+#[cfg(feature = "async-std-sleeper")]
+impl Sleeper for AsyncStdSleeper {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        async_std::task::sleep(duration).boxed()
+    }
+}
+
+/// [`Sleeper`] using the timers of smol.
+///
+/// The client futures may then be polled by smol, within the context of a Tokio runtime
+/// running the reactor of the requests.
+#[cfg(feature = "smol-sleeper")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SmolSleeper;
+
+#[cfg(feature = "smol-sleeper")]
+impl Sleeper for SmolSleeper {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        smol::Timer::after(duration).map(|_| ()).boxed()
+    }
+}
+
+/// Runs `fut` for at most `duration`, returning `None` if it didn't complete in time.
+pub(crate) async fn with_timeout<T>(
+    sleeper: &dyn Sleeper,
+    duration: Duration,
+    fut: impl Future<Output = T>,
+) -> Option<T> {
+    futures::pin_mut!(fut);
+    match future::select(fut, sleeper.sleep(duration)).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
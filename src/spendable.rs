@@ -0,0 +1,273 @@
+//! Preview of the outputs of a script worth spending at a given feerate, for coin selection.
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use crate::ScriptType;
+use crate::{Error, Utxo};
+
+/// Filters applied to the outputs returned by the `spendable_utxos` methods of the clients.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SpendableOpts {
+    /// Minimum number of confirmations of the outputs, unconfirmed outputs being only
+    /// controlled by `include_unconfirmed`.
+    pub min_confirmations: u32,
+    /// Whether unconfirmed outputs, like change of pending transactions, are spendable.
+    pub include_unconfirmed: bool,
+    /// Minimum effective value (in sats) of a spendable output.
+    pub dust_floor: u64,
+    /// Weight (in weight units) of the input spending an output, overriding the one derived
+    /// from the script type. Required for the types without one, see
+    /// [`ScriptType::input_weight`].
+    #[serde(default)]
+    pub input_weight: Option<u64>,
+}
+
+impl Default for SpendableOpts {
+    fn default() -> Self {
+        SpendableOpts {
+            min_confirmations: 1,
+            include_unconfirmed: false,
+            dust_floor: 0,
+            input_weight: None,
+        }
+    }
+}
+
+/// Output worth spending, with its value net of the cost of spending it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SpendableUtxo {
+    pub utxo: Utxo,
+    /// Value (in sats) of the output minus the fee of the input spending it.
+    pub effective_value: u64,
+}
+
+/// Outputs of a script worth spending at a feerate, with a summary of the excluded ones.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpendableSet {
+    /// Spendable outputs, in the order returned by the server.
+    pub utxos: Vec<SpendableUtxo>,
+    /// Number of outputs costing more to spend than they are worth, or less than the dust
+    /// floor.
+    pub uneconomical: usize,
+    /// Total value (in sats) of the uneconomical outputs.
+    pub uneconomical_value: u64,
+    /// Number of outputs without enough confirmations.
+    pub unconfirmed: usize,
+}
+
+impl SpendableSet {
+    /// Sum of the effective values of the spendable outputs.
+    ///
+    /// Fails with [`Error::InvalidServerData`] if the sum overflows.
+    pub fn effective_value(&self) -> Result<u64, Error> {
+        self.utxos.iter().try_fold(0u64, |sum, utxo| {
+            sum.checked_add(utxo.effective_value)
+                .ok_or(Error::InvalidServerData)
+        })
+    }
+
+    /// Evaluates `utxos` of a script of type `script_type` at `feerate` (in sat/vB), `tip` being
+    /// the height of the chain tip, only needed to count confirmations beyond the first one.
+    ///
+    /// Fails with [`Error::InvalidInput`] if the input weight is neither given in `opts` nor
+    /// known for the script type.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn evaluate(
+        script_type: &ScriptType,
+        utxos: Vec<Utxo>,
+        feerate: f64,
+        opts: &SpendableOpts,
+        tip: Option<u32>,
+    ) -> Result<Self, Error> {
+        let weight = opts
+            .input_weight
+            .or_else(|| script_type.input_weight())
+            .ok_or_else(|| Error::InvalidInput {
+                field: "input_weight",
+                reason: format!(
+                    "the cost of spending {} outputs depends on their script and must be given",
+                    script_type
+                ),
+            })?;
+        let input_fee = (weight as f64 / 4.0 * feerate).ceil() as u64;
+        let mut set = SpendableSet::default();
+        for utxo in utxos {
+            let confirmations = match (utxo.status.block_height, tip) {
+                (Some(height), Some(tip)) if utxo.status.confirmed => {
+                    tip.saturating_sub(height) + 1
+                }
+                _ if utxo.status.confirmed => 1,
+                _ => 0,
+            };
+            let mature = if confirmations == 0 {
+                opts.include_unconfirmed
+            } else {
+                confirmations >= opts.min_confirmations
+            };
+            if !mature {
+                set.unconfirmed += 1;
+                continue;
+            }
+            match utxo.value.checked_sub(input_fee) {
+                Some(effective_value)
+                    if effective_value > 0 && effective_value >= opts.dust_floor =>
+                {
+                    set.utxos.push(SpendableUtxo {
+                        utxo,
+                        effective_value,
+                    })
+                }
+                _ => {
+                    set.uneconomical += 1;
+                    set.uneconomical_value = set
+                        .uneconomical_value
+                        .checked_add(utxo.value)
+                        .ok_or(Error::InvalidServerData)?;
+                }
+            }
+        }
+        Ok(set)
+    }
+}
+
+#[cfg(all(test, any(feature = "blocking", feature = "async")))]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    fn utxo(value: u64, height: Option<u32>) -> Utxo {
+        test_fixtures::utxo(1, 0, value, height)
+    }
+
+    fn input_fee(script_type: ScriptType, feerate: f64) -> u64 {
+        let set = SpendableSet::evaluate(
+            &script_type,
+            vec![utxo(10_000, Some(1))],
+            feerate,
+            &SpendableOpts::default(),
+            None,
+        )
+        .unwrap();
+        10_000 - set.utxos[0].effective_value
+    }
+
+    #[test]
+    fn input_weights() {
+        assert_eq!(input_fee(ScriptType::P2pk, 1.0), 114);
+        assert_eq!(input_fee(ScriptType::P2pkh, 1.0), 148);
+        assert_eq!(input_fee(ScriptType::V0P2wpkh, 1.0), 68);
+        assert_eq!(input_fee(ScriptType::V1P2tr, 1.0), 58);
+        assert_eq!(input_fee(ScriptType::V0P2wpkh, 10.0), 680);
+
+        for script_type in [
+            ScriptType::P2sh,
+            ScriptType::V0P2wsh,
+            ScriptType::Multisig,
+            ScriptType::Nonstandard,
+        ] {
+            let err = SpendableSet::evaluate(
+                &script_type,
+                vec![utxo(10_000, Some(1))],
+                1.0,
+                &SpendableOpts::default(),
+                None,
+            )
+            .unwrap_err();
+            assert!(matches!(
+                err,
+                Error::InvalidInput {
+                    field: "input_weight",
+                    ..
+                }
+            ));
+        }
+
+        let opts = SpendableOpts {
+            input_weight: Some(400),
+            ..SpendableOpts::default()
+        };
+        let set = SpendableSet::evaluate(
+            &ScriptType::V0P2wsh,
+            vec![utxo(10_000, Some(1))],
+            2.0,
+            &opts,
+            None,
+        )
+        .unwrap();
+        assert_eq!(set.utxos[0].effective_value, 10_000 - 200);
+    }
+
+    #[test]
+    fn confirmation_filters() {
+        let utxos = vec![
+            utxo(1_000, Some(10)),
+            utxo(2_000, Some(8)),
+            utxo(3_000, None),
+        ];
+        let evaluate = |opts: SpendableOpts| {
+            SpendableSet::evaluate(&ScriptType::V0P2wpkh, utxos.clone(), 0.0, &opts, Some(10))
+                .unwrap()
+        };
+
+        let set = evaluate(SpendableOpts::default());
+        assert_eq!(set.utxos.len(), 2);
+        assert_eq!(set.unconfirmed, 1);
+        assert_eq!(set.effective_value().unwrap(), 3_000);
+
+        let set = evaluate(SpendableOpts {
+            min_confirmations: 3,
+            ..SpendableOpts::default()
+        });
+        assert_eq!(set.utxos.len(), 1);
+        assert_eq!(set.utxos[0].utxo.value, 2_000);
+        assert_eq!(set.unconfirmed, 2);
+
+        let set = evaluate(SpendableOpts {
+            include_unconfirmed: true,
+            ..SpendableOpts::default()
+        });
+        assert_eq!(set.utxos.len(), 3);
+        assert_eq!(set.unconfirmed, 0);
+    }
+
+    #[test]
+    fn uneconomical_outputs() {
+        let utxos = vec![utxo(50, Some(1)), utxo(500, Some(1)), utxo(5_000, Some(1))];
+        let opts = SpendableOpts {
+            dust_floor: 1_000,
+            ..SpendableOpts::default()
+        };
+        let set = SpendableSet::evaluate(&ScriptType::V0P2wpkh, utxos, 1.0, &opts, None).unwrap();
+        assert_eq!(set.utxos.len(), 1);
+        assert_eq!(set.utxos[0].effective_value, 5_000 - 68);
+        assert_eq!(set.uneconomical, 2);
+        assert_eq!(set.uneconomical_value, 550);
+    }
+
+    #[test]
+    fn overflowing_values() {
+        let utxos = vec![utxo(u64::MAX, Some(1)), utxo(u64::MAX, Some(1))];
+        let set = SpendableSet::evaluate(
+            &ScriptType::V0P2wpkh,
+            utxos.clone(),
+            0.0,
+            &SpendableOpts::default(),
+            None,
+        )
+        .unwrap();
+        assert!(matches!(
+            set.effective_value(),
+            Err(Error::InvalidServerData)
+        ));
+
+        let opts = SpendableOpts {
+            dust_floor: u64::MAX,
+            input_weight: Some(4),
+            ..SpendableOpts::default()
+        };
+        let err =
+            SpendableSet::evaluate(&ScriptType::V0P2wpkh, utxos, 1.0, &opts, None).unwrap_err();
+        assert!(matches!(err, Error::InvalidServerData));
+    }
+}
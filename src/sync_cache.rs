@@ -0,0 +1,347 @@
+//! Knowledge of which scripts have a transaction history, shareable between clients so that
+//! they don't query the same scripts again and again.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use amplify::{ByteArray, Bytes32};
+use bpstd::{BlockHash, ScriptPubkey, Txid};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{AddressStats, Utxo};
+
+/// Default number of blocks below which a chain reorganization is assumed not to happen.
+pub const DEFAULT_REORG_SAFETY_DEPTH: u32 = 6;
+
+/// Last known history state of a script.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScriptState {
+    /// Whether the script appeared in any confirmed or unconfirmed transaction.
+    pub has_history: bool,
+    /// Whether the script appeared in any confirmed transaction.
+    pub has_confirmed_history: bool,
+    /// Height of the chain tip when the state was checked.
+    pub checked_tip: u32,
+    /// Hash of the chain tip when the state was checked, `None` for states recorded from a
+    /// transaction history.
+    #[serde(default)]
+    pub checked_tip_hash: Option<BlockHash>,
+    /// Newest confirmed transaction of the script in the last history fetched for it.
+    #[serde(default)]
+    pub last_confirmed_txid: Option<Txid>,
+    /// Hash of the last unspent outputs fetched for the script, telling whether they changed.
+    #[serde(default)]
+    pub utxo_hash: Option<Bytes32>,
+}
+
+/// Content of a [`SyncCache`], used to persist it across restarts.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncSnapshot {
+    /// Highest chain tip seen by the cache.
+    pub tip: u32,
+    /// Hash of the highest chain tip seen by the cache.
+    #[serde(default)]
+    pub tip_hash: Option<BlockHash>,
+    /// States by hex-encoded SHA256 hash of the script, as used by Esplora.
+    pub scripts: BTreeMap<String, ScriptState>,
+}
+
+/// Thread-safe cache of the history state of scripts, which blocking and async clients built
+/// with the same [`Builder::sync_cache`](crate::Builder::sync_cache) consult and update.
+///
+/// A script with no history is only known not to have one at the tip it was checked at, so
+/// that state is reused until a new block arrives. Confirmed history is kept across blocks,
+/// unless the chain is reorganized: a tip replaced at the same height or going back drops the
+/// states checked at or above the new tip, and a tip going back by more than the reorg safety
+/// depth drops the whole cache.
+#[derive(Debug)]
+pub struct SyncCache {
+    reorg_depth: u32,
+    inner: Mutex<SyncSnapshot>,
+}
+
+impl Default for SyncCache {
+    fn default() -> Self {
+        SyncCache::new(DEFAULT_REORG_SAFETY_DEPTH)
+    }
+}
+
+impl SyncCache {
+    /// Create an empty cache, assuming no reorganization deeper than `reorg_depth` blocks.
+    pub fn new(reorg_depth: u32) -> Self {
+        SyncCache {
+            reorg_depth,
+            inner: Mutex::new(SyncSnapshot::default()),
+        }
+    }
+
+    /// Get the state of `script` if it is still valid at the chain tip with height `tip` and
+    /// hash `tip_hash`.
+    pub fn lookup(
+        &self,
+        script: &ScriptPubkey,
+        tip: u32,
+        tip_hash: BlockHash,
+    ) -> Option<ScriptState> {
+        let state = self.state(script)?;
+        let same_tip = state.checked_tip == tip && state.checked_tip_hash == Some(tip_hash);
+        if same_tip || (state.has_confirmed_history && state.checked_tip <= tip) {
+            Some(state)
+        } else {
+            None
+        }
+    }
+
+    /// Get the last recorded state of `script`, whether or not it is still valid.
+    pub fn state(&self, script: &ScriptPubkey) -> Option<ScriptState> {
+        let inner = self.inner.lock().expect("poisoned sync cache");
+        inner.scripts.get(&script_hash(script)).copied()
+    }
+
+    /// Record the state of `script`, checked at the chain tip it contains.
+    pub fn update(&self, script: &ScriptPubkey, state: ScriptState) {
+        self.inner
+            .lock()
+            .expect("poisoned sync cache")
+            .scripts
+            .insert(script_hash(script), state);
+    }
+
+    /// Record the statistics of `script`, checked at the chain tip with height `tip` and hash
+    /// `tip_hash`, keeping the other fields of its state.
+    pub fn record_stats(
+        &self,
+        script: &ScriptPubkey,
+        stats: &AddressStats,
+        tip: u32,
+        tip_hash: BlockHash,
+    ) {
+        self.modify(script, |state| {
+            state.has_history = stats.has_history();
+            state.has_confirmed_history = stats.chain_stats.tx_count > 0;
+            state.checked_tip = tip;
+            state.checked_tip_hash = Some(tip_hash);
+        })
+    }
+
+    /// Record the transaction history of `script`, newest first as Esplora returns it.
+    pub fn record_history(&self, script: &ScriptPubkey, txs: &[crate::Tx]) {
+        let last_confirmed = txs.iter().find(|tx| tx.status.confirmed);
+        self.modify(script, |state| {
+            state.has_history |= !txs.is_empty();
+            state.has_confirmed_history |= last_confirmed.is_some();
+            state.last_confirmed_txid = last_confirmed.map(|tx| tx.txid);
+        })
+    }
+
+    /// Record the unspent outputs of `script`, returning whether they changed since the last
+    /// ones recorded.
+    ///
+    /// Outputs are compared regardless of their order, by outpoint, value and confirmation
+    /// height.
+    pub fn record_utxos(&self, script: &ScriptPubkey, utxos: &[Utxo]) -> bool {
+        let hash = utxo_hash(utxos);
+        let mut changed = false;
+        self.modify(script, |state| {
+            changed = state.utxo_hash != Some(hash);
+            state.utxo_hash = Some(hash);
+        });
+        changed
+    }
+
+    fn modify(&self, script: &ScriptPubkey, f: impl FnOnce(&mut ScriptState)) {
+        let mut inner = self.inner.lock().expect("poisoned sync cache");
+        f(inner.scripts.entry(script_hash(script)).or_default())
+    }
+
+    /// Register the current chain tip, with height `tip` and hash `tip_hash`.
+    ///
+    /// A tip going back by more than the reorg safety depth drops all the states. A tip going
+    /// back less, or replaced at the same height, drops the states checked above it and those
+    /// checked at its height on another chain.
+    pub fn observe_tip(&self, tip: u32, tip_hash: BlockHash) {
+        let mut inner = self.inner.lock().expect("poisoned sync cache");
+        let replaced = tip == inner.tip && matches!(inner.tip_hash, Some(hash) if hash != tip_hash);
+        if tip > inner.tip || (tip == inner.tip && !replaced) {
+            inner.tip = tip;
+            inner.tip_hash = Some(tip_hash);
+            return;
+        }
+        if inner.tip - tip > self.reorg_depth {
+            inner.scripts.clear();
+        } else {
+            inner.scripts.retain(|_, state| {
+                state.checked_tip < tip
+                    || (state.checked_tip == tip && state.checked_tip_hash == Some(tip_hash))
+            });
+        }
+        inner.tip = tip;
+        inner.tip_hash = Some(tip_hash);
+    }
+
+    /// Forget the state of `script`.
+    pub fn invalidate(&self, script: &ScriptPubkey) {
+        self.inner
+            .lock()
+            .expect("poisoned sync cache")
+            .scripts
+            .remove(&script_hash(script));
+    }
+
+    /// Take a snapshot of the cache, which can be persisted and later passed to
+    /// [`SyncCache::restore`].
+    pub fn snapshot(&self) -> SyncSnapshot {
+        self.inner.lock().expect("poisoned sync cache").clone()
+    }
+
+    /// Replace the content of the cache with a snapshot.
+    pub fn restore(&self, snapshot: SyncSnapshot) {
+        *self.inner.lock().expect("poisoned sync cache") = snapshot;
+    }
+}
+
+/// Hash of a set of unspent outputs, independent of their order.
+fn utxo_hash(utxos: &[Utxo]) -> Bytes32 {
+    let mut outputs = utxos
+        .iter()
+        .map(|utxo| {
+            let height = utxo.status.block_height.filter(|_| utxo.status.confirmed);
+            (utxo.txid, utxo.vout, utxo.value, height)
+        })
+        .collect::<Vec<_>>();
+    outputs.sort_unstable();
+    let mut hasher = Sha256::default();
+    for (txid, vout, value, height) in outputs {
+        hasher.update(txid.to_byte_array());
+        hasher.update(vout.to_le_bytes());
+        hasher.update(value.to_le_bytes());
+        hasher.update(height.unwrap_or(u32::MAX).to_le_bytes());
+    }
+    Bytes32::from_byte_array(<[u8; 32]>::from(hasher.finalize()))
+}
+
+fn script_hash(script: &ScriptPubkey) -> String {
+    let mut hasher = Sha256::default();
+    hasher.update(script);
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    fn script(n: u8) -> ScriptPubkey {
+        ScriptPubkey::p2wpkh([n; 20])
+    }
+
+    fn hash(n: u8) -> BlockHash {
+        BlockHash::from_byte_array([n; 32])
+    }
+
+    fn stats(chain_txs: u64, mempool_txs: u64) -> AddressStats {
+        let summary = |tx_count: u64| {
+            serde_json::json!({
+                "funded_txo_count": tx_count,
+                "funded_txo_sum": tx_count * 1_000,
+                "spent_txo_count": 0,
+                "spent_txo_sum": 0,
+                "tx_count": tx_count,
+            })
+        };
+        serde_json::from_value(serde_json::json!({
+            "chain_stats": summary(chain_txs),
+            "mempool_stats": summary(mempool_txs),
+        }))
+        .unwrap()
+    }
+
+    fn utxo(vout: u32, height: Option<u32>) -> Utxo {
+        test_fixtures::utxo(1, vout, 1000, height)
+    }
+
+    #[test]
+    fn tips_replaced_at_the_same_height() {
+        let cache = SyncCache::default();
+        cache.observe_tip(10, hash(1));
+        cache.record_stats(&script(1), &stats(0, 0), 10, hash(1));
+        cache.record_stats(&script(2), &stats(1, 0), 8, hash(3));
+        assert!(cache.lookup(&script(1), 10, hash(1)).is_some());
+        assert!(cache.lookup(&script(1), 10, hash(2)).is_none());
+
+        cache.observe_tip(10, hash(2));
+        assert_eq!(cache.state(&script(1)), None);
+        assert!(cache.lookup(&script(2), 10, hash(2)).unwrap().has_history);
+        assert_eq!(cache.snapshot().tip_hash, Some(hash(2)));
+    }
+
+    #[test]
+    fn tips_going_back() {
+        let cache = SyncCache::new(2);
+        cache.observe_tip(10, hash(1));
+        cache.record_stats(&script(1), &stats(1, 0), 8, hash(2));
+        cache.record_stats(&script(2), &stats(1, 0), 10, hash(1));
+        // no history is only valid at the tip it was checked at
+        cache.record_stats(&script(3), &stats(0, 0), 9, hash(4));
+        assert!(cache.lookup(&script(3), 10, hash(1)).is_none());
+
+        cache.observe_tip(9, hash(5));
+        assert!(cache.state(&script(1)).is_some());
+        assert_eq!(cache.state(&script(2)), None);
+        assert_eq!(cache.state(&script(3)), None);
+
+        cache.observe_tip(6, hash(6));
+        assert_eq!(cache.snapshot().scripts.len(), 0);
+    }
+
+    #[test]
+    fn recorded_histories_and_utxos() {
+        let cache = SyncCache::default();
+        let tx = |txid: &str, confirmed: bool| -> crate::Tx {
+            let mut tx = serde_json::json!({
+                "txid": txid,
+                "version": 2,
+                "locktime": 0,
+                "vin": [],
+                "vout": [],
+                "status": { "confirmed": confirmed },
+                "size": 100,
+                "weight": 400,
+            });
+            if confirmed {
+                tx["status"]["block_height"] = 7.into();
+            }
+            serde_json::from_value(tx).unwrap()
+        };
+        let unconfirmed = tx(&"11".repeat(32), false);
+        let confirmed = tx(&"22".repeat(32), true);
+        cache.record_history(&script(1), &[unconfirmed.clone(), confirmed.clone()]);
+        let state = cache.state(&script(1)).unwrap();
+        assert!(state.has_history && state.has_confirmed_history);
+        assert_eq!(state.last_confirmed_txid, Some(confirmed.txid));
+        // without a checked tip, only confirmed history is reused
+        assert!(cache.lookup(&script(1), 10, hash(1)).is_some());
+        cache.record_history(&script(2), &[unconfirmed]);
+        assert!(cache.lookup(&script(2), 10, hash(1)).is_none());
+
+        let utxos = [utxo(0, Some(7)), utxo(1, None)];
+        assert!(cache.record_utxos(&script(1), &utxos));
+        assert!(!cache.record_utxos(&script(1), &[utxos[1].clone(), utxos[0].clone()]));
+        assert!(cache.record_utxos(&script(1), &[utxo(0, Some(7)), utxo(1, Some(8))]));
+        assert!(cache.record_utxos(&script(1), &[]));
+        assert!(cache.state(&script(1)).unwrap().utxo_hash.is_some());
+    }
+
+    #[test]
+    fn snapshots() {
+        let cache = SyncCache::default();
+        cache.observe_tip(10, hash(1));
+        cache.record_stats(&script(1), &stats(1, 1), 10, hash(1));
+        cache.record_utxos(&script(1), &[utxo(0, Some(7))]);
+        let json = serde_json::to_string(&cache.snapshot()).unwrap();
+        let restored = SyncCache::default();
+        restored.restore(serde_json::from_str(&json).unwrap());
+        assert_eq!(restored.snapshot(), cache.snapshot());
+    }
+}
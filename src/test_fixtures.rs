@@ -0,0 +1,48 @@
+//! Builders of the API types shared by the unit tests of the modules working on them.
+
+use amplify::ByteArray;
+use bpstd::{BlockHash, Txid};
+
+use crate::{Tx, TxStatus, Utxo, Vin, Vout};
+
+/// Transaction id made of the `id` byte repeated.
+pub(crate) fn txid(id: u8) -> Txid {
+    Txid::from_byte_array([id; 32])
+}
+
+/// Status of a transaction confirmed at `height`, in a block whose hash is derived from the
+/// height, or of an unconfirmed one.
+pub(crate) fn status(height: Option<u32>) -> TxStatus {
+    TxStatus {
+        confirmed: height.is_some(),
+        block_height: height,
+        block_hash: height.map(|height| BlockHash::from_byte_array([height as u8; 32])),
+        block_time: None,
+    }
+}
+
+/// Unspent output `vout` of the transaction [`txid`]`(txid_byte)`.
+pub(crate) fn utxo(txid_byte: u8, vout: u32, value: u64, height: Option<u32>) -> Utxo {
+    Utxo {
+        txid: txid(txid_byte),
+        vout,
+        value,
+        status: status(height),
+        confirmations: None,
+    }
+}
+
+/// Version 2 transaction [`txid`]`(id)`, without fee.
+pub(crate) fn tx(id: u8, height: Option<u32>, vin: Vec<Vin>, vout: Vec<Vout>) -> Tx {
+    Tx {
+        txid: txid(id),
+        version: 2,
+        locktime: 0,
+        vin,
+        vout,
+        status: status(height),
+        fee: None,
+        size: 100,
+        weight: 400,
+    }
+}
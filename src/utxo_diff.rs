@@ -0,0 +1,112 @@
+//! Changes of the unspent outputs of a script between two points in time, as needed to account
+//! for what happened since a previous sync.
+
+use std::collections::HashMap;
+
+use bpstd::{BlockHash, Txid};
+use serde::{Deserialize, Serialize};
+
+use crate::Utxo;
+
+/// Unspent outputs of a script, together with the chain tip they were fetched at, so that a
+/// later [`UtxoDiff`] can tell whether the chain was reorganized since.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UtxoSnapshot {
+    /// Height of the chain tip when the snapshot was taken.
+    pub tip_height: u32,
+    /// Hash of the chain tip when the snapshot was taken.
+    pub tip_hash: BlockHash,
+    pub utxos: Vec<Utxo>,
+}
+
+/// Changes between two sets of unspent outputs, each in the order of the set it comes from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UtxoDiff {
+    /// Outputs which weren't unspent before.
+    pub added: Vec<Utxo>,
+    /// Outputs which are no longer unspent, as they were spent or, after a reorg, their
+    /// transaction was dropped.
+    pub removed: Vec<Utxo>,
+    /// Outputs still unspent whose transaction got confirmed, unconfirmed, or confirmed in
+    /// another block, with their new status.
+    pub status_changed: Vec<Utxo>,
+    /// Whether the tip of the previous snapshot is no longer in the best chain, in which case
+    /// removals may come from transactions dropped by the reorg rather than from spends.
+    pub reorg: bool,
+}
+
+impl UtxoDiff {
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.status_changed.is_empty()
+    }
+}
+
+/// Compute the changes from the `old` set of unspent outputs to the `new` one, matching them by
+/// outpoint. The returned diff doesn't flag any reorg, which can't be told from the sets alone.
+pub fn diff_utxos(old: &[Utxo], new: &[Utxo]) -> UtxoDiff {
+    let outpoint = |utxo: &Utxo| -> (Txid, u32) { (utxo.txid, utxo.vout) };
+    let old_by_outpoint = old
+        .iter()
+        .map(|utxo| (outpoint(utxo), utxo))
+        .collect::<HashMap<_, _>>();
+    let new_by_outpoint = new
+        .iter()
+        .map(|utxo| (outpoint(utxo), utxo))
+        .collect::<HashMap<_, _>>();
+
+    let mut diff = UtxoDiff::default();
+    for utxo in new {
+        match old_by_outpoint.get(&outpoint(utxo)) {
+            None => diff.added.push(utxo.clone()),
+            Some(previous)
+                if previous.status.confirmed != utxo.status.confirmed
+                    || previous.status.block_hash != utxo.status.block_hash =>
+            {
+                diff.status_changed.push(utxo.clone())
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .iter()
+        .filter(|utxo| !new_by_outpoint.contains_key(&outpoint(utxo)))
+        .cloned()
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    fn utxo(id: u8, vout: u32, height: Option<u32>) -> Utxo {
+        test_fixtures::utxo(id, vout, 10_000, height)
+    }
+
+    #[test]
+    fn additions_and_spends() {
+        let old = [utxo(1, 0, Some(1)), utxo(1, 1, Some(1)), utxo(2, 0, None)];
+        let new = [utxo(1, 1, Some(1)), utxo(3, 0, None), utxo(2, 0, None)];
+        let diff = diff_utxos(&old, &new);
+        assert_eq!(diff.added, [utxo(3, 0, None)]);
+        assert_eq!(diff.removed, [utxo(1, 0, Some(1))]);
+        assert!(diff.status_changed.is_empty());
+        assert!(!diff.reorg);
+
+        assert!(diff_utxos(&old, &old).is_empty());
+        assert_eq!(diff_utxos(&[], &old).added, old);
+        assert_eq!(diff_utxos(&old, &[]).removed, old);
+    }
+
+    #[test]
+    fn confirmation_transitions() {
+        let old = [utxo(1, 0, None), utxo(2, 0, Some(1)), utxo(3, 0, Some(1))];
+        let new = [utxo(1, 0, Some(2)), utxo(2, 0, None), utxo(3, 0, Some(2))];
+        let diff = diff_utxos(&old, &new);
+        assert_eq!(diff.status_changed, new);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert!(!diff.is_empty());
+    }
+}
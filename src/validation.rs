@@ -0,0 +1,219 @@
+//! Cross-checks of server responses, made by both clients when response validation is enabled
+//! with [`Builder::validate_responses`](crate::Builder::validate_responses).
+
+use amplify::ByteArray;
+use bpstd::{BlockHash, BlockHeader, Txid};
+
+use crate::rng::SplitMix64;
+use crate::{BlockHashCache, Error, MerkleProof, TxStatus, Utxo};
+
+/// Number of unspent outputs of a response whose funding transaction is fetched to check them.
+pub(crate) const UTXO_SAMPLE_SIZE: usize = 3;
+
+/// Get the height and block hash a confirmed `status` claims which aren't already known to be
+/// consistent from `cache`, or `None` if there is nothing to check.
+pub(crate) fn unverified_block(
+    cache: &BlockHashCache,
+    status: &TxStatus,
+) -> Option<(u32, BlockHash)> {
+    let (height, hash) = match (status.block_height, status.block_hash) {
+        (Some(height), Some(hash)) if status.confirmed => (height, hash),
+        _ => return None,
+    };
+    let cached = cache
+        .lock()
+        .expect("poisoned block hash cache")
+        .get(&height)
+        .copied();
+    if cached == Some(hash) {
+        return None;
+    }
+    Some((height, hash))
+}
+
+/// Check the `hash` of the block at `height` claimed by a transaction status against the
+/// `actual` hash of the block at that height, recording the latter in `cache`.
+///
+/// A cached hash may be stale after a reorg, so callers re-fetch the actual hash on each
+/// mismatch with the cache rather than failing right away.
+pub(crate) fn check_block_hash(
+    cache: &BlockHashCache,
+    height: u32,
+    hash: BlockHash,
+    actual: BlockHash,
+) -> Result<(), Error> {
+    cache
+        .lock()
+        .expect("poisoned block hash cache")
+        .insert(height, actual);
+    if actual != hash {
+        return Err(Error::InconsistentBackend {
+            check: "tx_status",
+            details: format!(
+                "transaction is confirmed in block {} at height {}, while the block at that \
+                 height is {}",
+                hash, height, actual
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Check that `txid`, anchored to the block with `hash` at `height`, is one of the `txids` of
+/// that block, which it isn't if the block the server confirmed it in was since reorganized.
+pub(crate) fn check_anchor(
+    txid: &Txid,
+    height: u32,
+    hash: BlockHash,
+    txids: &[Txid],
+) -> Result<(), Error> {
+    if txids.contains(txid) {
+        return Ok(());
+    }
+    Err(Error::InconsistentBackend {
+        check: "anchor",
+        details: format!(
+            "transaction {} is confirmed at height {}, while the block at that height, {}, \
+             doesn't include it",
+            txid, height, hash
+        ),
+    })
+}
+
+/// Check that `proof` proves the inclusion of `txid` in the block with the given `header`.
+pub(crate) fn check_merkle_proof(
+    txid: &Txid,
+    proof: &MerkleProof,
+    header: &BlockHeader,
+) -> Result<(), Error> {
+    let root = proof.merkle_root(txid);
+    if root != header.merkle_root.to_byte_array() {
+        return Err(Error::InconsistentBackend {
+            check: "merkle_proof",
+            details: format!(
+                "merkle proof of transaction {} doesn't lead to the merkle root of block {} at \
+                 height {}",
+                txid,
+                header.block_hash(),
+                proof.block_height
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Pick the unspent outputs to check among `utxos`, at most [`UTXO_SAMPLE_SIZE`] of them.
+pub(crate) fn sample_utxos<'a>(utxos: &'a [Utxo], rng: &mut SplitMix64) -> Vec<&'a Utxo> {
+    if utxos.len() <= UTXO_SAMPLE_SIZE {
+        return utxos.iter().collect();
+    }
+    let mut indexes = Vec::with_capacity(UTXO_SAMPLE_SIZE);
+    while indexes.len() < UTXO_SAMPLE_SIZE {
+        let index = (rng.next_u64() % utxos.len() as u64) as usize;
+        if !indexes.contains(&index) {
+            indexes.push(index);
+        }
+    }
+    indexes.into_iter().map(|index| &utxos[index]).collect()
+}
+
+/// Check that an unspent output listed by the server is one of the `outputs` of its funding
+/// transaction, which is `None` if the server doesn't know the transaction.
+pub(crate) fn check_utxo(utxo: &Utxo, outputs: Option<usize>) -> Result<(), Error> {
+    match outputs {
+        Some(outputs) if (utxo.vout as usize) < outputs => Ok(()),
+        Some(outputs) => Err(Error::InconsistentBackend {
+            check: "utxo",
+            details: format!(
+                "unspent output {}:{} is past the {} outputs of its transaction",
+                utxo.txid, utxo.vout, outputs
+            ),
+        }),
+        None => Err(Error::InconsistentBackend {
+            check: "utxo",
+            details: format!(
+                "unspent output {}:{} belongs to an unknown transaction",
+                utxo.txid, utxo.vout
+            ),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    fn utxo(vout: u32) -> Utxo {
+        test_fixtures::utxo(1, vout, 1000, None)
+    }
+
+    #[test]
+    fn block_hashes() {
+        let cache = BlockHashCache::default();
+        let hash = BlockHash::from_byte_array([1u8; 32]);
+        let other = BlockHash::from_byte_array([2u8; 32]);
+        let status = TxStatus {
+            confirmed: true,
+            block_hash: Some(hash),
+            block_height: Some(7),
+            block_time: None,
+        };
+        assert_eq!(unverified_block(&cache, &status), Some((7, hash)));
+
+        check_block_hash(&cache, 7, hash, hash).unwrap();
+        assert_eq!(unverified_block(&cache, &status), None);
+
+        let err = check_block_hash(&cache, 7, hash, other).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InconsistentBackend {
+                check: "tx_status",
+                ..
+            }
+        ));
+        assert_eq!(unverified_block(&cache, &status), Some((7, hash)));
+
+        let unconfirmed = TxStatus {
+            confirmed: false,
+            ..status
+        };
+        assert_eq!(unverified_block(&cache, &unconfirmed), None);
+    }
+
+    #[test]
+    fn anchors() {
+        let txid = Txid::from_byte_array([1u8; 32]);
+        let other = Txid::from_byte_array([2u8; 32]);
+        let hash = BlockHash::from_byte_array([3u8; 32]);
+        check_anchor(&txid, 7, hash, &[other, txid]).unwrap();
+        let err = check_anchor(&txid, 7, hash, &[other]).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InconsistentBackend {
+                check: "anchor",
+                ..
+            }
+        ));
+        assert!(check_anchor(&txid, 7, hash, &[]).is_err());
+    }
+
+    #[test]
+    fn utxo_samples() {
+        let mut rng = SplitMix64::new(7);
+        let utxos = (0..10).map(utxo).collect::<Vec<_>>();
+        let sample = sample_utxos(&utxos, &mut rng);
+        assert_eq!(sample.len(), UTXO_SAMPLE_SIZE);
+        for (n, utxo) in sample.iter().enumerate() {
+            assert!(!sample[..n].contains(utxo));
+        }
+        assert_eq!(sample_utxos(&utxos[..2], &mut rng).len(), 2);
+    }
+
+    #[test]
+    fn utxo_vouts() {
+        check_utxo(&utxo(1), Some(2)).unwrap();
+        assert!(check_utxo(&utxo(2), Some(2)).is_err());
+        assert!(check_utxo(&utxo(0), None).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Merge of the paginated histories of several scripts into a single history, in global recency
+//! order, fetching the pages of each script only as far as needed.
+
+use std::collections::{HashSet, VecDeque};
+
+use bpstd::{ScriptPubkey, Txid};
+
+use crate::pagination::PageGuard;
+use crate::{cmp_tx_for_display, Error, Tx};
+
+/// Progress through the history of one of the merged scripts.
+#[derive(Debug)]
+struct ScriptHistory {
+    script: ScriptPubkey,
+    /// Fetched transactions not yet emitted, ordered with [`cmp_tx_for_display`].
+    buffer: VecDeque<Tx>,
+    /// Cursor of the next page, if the first page was fetched.
+    cursor: Option<Txid>,
+    exhausted: bool,
+    guard: PageGuard,
+}
+
+impl ScriptHistory {
+    /// Whether all the transactions of the script in the block of its first buffered one are
+    /// known, so that the buffer head can be compared with the ones of other scripts. Pages end
+    /// at arbitrary positions within a block, so the buffer must reach an older block.
+    fn is_settled(&self) -> bool {
+        let block = |tx: &Tx| (tx.status.confirmed, tx.status.block_height);
+        self.exhausted
+            || match (self.buffer.front(), self.buffer.back()) {
+                (Some(first), Some(last)) => block(first) != block(last),
+                _ => false,
+            }
+    }
+}
+
+/// State of the merge of the histories of several scripts, driven by the clients which fetch
+/// the pages it asks for.
+#[derive(Debug)]
+pub(crate) struct HistoryMerge {
+    scripts: Vec<ScriptHistory>,
+    /// Transactions already buffered or emitted, which are skipped when they are also in the
+    /// history of another script.
+    seen: HashSet<Txid>,
+}
+
+impl HistoryMerge {
+    pub fn new(scripts: Vec<ScriptPubkey>, max_pages: usize) -> Self {
+        HistoryMerge {
+            scripts: scripts
+                .into_iter()
+                .map(|script| ScriptHistory {
+                    script,
+                    buffer: VecDeque::new(),
+                    cursor: None,
+                    exhausted: false,
+                    guard: PageGuard::new(max_pages),
+                })
+                .collect(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Get the index, script and cursor of a page to fetch before the next transaction can be
+    /// emitted, if any.
+    pub fn pending(&self) -> Option<(usize, ScriptPubkey, Option<Txid>)> {
+        self.scripts
+            .iter()
+            .enumerate()
+            .find(|(_, history)| !history.is_settled())
+            .map(|(index, history)| (index, history.script.clone(), history.cursor))
+    }
+
+    /// Adds the page fetched for the script at `index`.
+    pub fn push_page(&mut self, index: usize, page: Vec<Tx>) -> Result<(), Error> {
+        let history = &mut self.scripts[index];
+        history.cursor = match history.guard.next_cursor(&page) {
+            Ok(cursor) => cursor,
+            Err(err) => return Err(err.with_partial_txs(page)),
+        };
+        history.exhausted = history.cursor.is_none();
+        for tx in page {
+            if self.seen.insert(tx.txid) {
+                history.buffer.push_back(tx);
+            }
+        }
+        history.buffer.make_contiguous().sort_by(cmp_tx_for_display);
+        Ok(())
+    }
+
+    /// Pops the most recent transaction across all the scripts, or `None` once all the
+    /// histories are exhausted. Must only be called when no page is [`HistoryMerge::pending`].
+    pub fn pop(&mut self) -> Option<Tx> {
+        let index = self
+            .scripts
+            .iter()
+            .enumerate()
+            .filter_map(|(index, history)| history.buffer.front().map(|tx| (index, tx)))
+            .min_by(|(_, a), (_, b)| cmp_tx_for_display(a, b))?
+            .0;
+        self.scripts[index].buffer.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pagination::CONFIRMED_PAGE_SIZE;
+    use crate::{test_fixtures, PaginationAnomaly};
+
+    fn tx(id: u8, height: Option<u32>) -> Tx {
+        test_fixtures::tx(id, height, vec![], vec![])
+    }
+
+    /// Page of `history` after `cursor`, as Esplora returns it: the unconfirmed transactions
+    /// and a page of confirmed ones first, then pages of confirmed ones.
+    fn page(history: &[Tx], cursor: Option<Txid>) -> Vec<Tx> {
+        let (unconfirmed, confirmed): (Vec<_>, Vec<_>) =
+            history.iter().cloned().partition(|tx| !tx.status.confirmed);
+        let start = match cursor {
+            None => 0,
+            Some(cursor) => confirmed.iter().position(|tx| tx.txid == cursor).unwrap() + 1,
+        };
+        let page = confirmed.into_iter().skip(start).take(CONFIRMED_PAGE_SIZE);
+        match cursor {
+            None => unconfirmed.into_iter().chain(page).collect(),
+            Some(_) => page.collect(),
+        }
+    }
+
+    /// Runs `merge` over `histories`, returning the ids of the merged transactions and the
+    /// number of pages fetched.
+    fn run(mut merge: HistoryMerge, histories: &[Vec<Tx>], count: usize) -> (Vec<Txid>, usize) {
+        let mut txids = vec![];
+        let mut pages = 0;
+        while txids.len() < count {
+            while let Some((index, _, cursor)) = merge.pending() {
+                merge
+                    .push_page(index, page(&histories[index], cursor))
+                    .unwrap();
+                pages += 1;
+            }
+            match merge.pop() {
+                Some(tx) => txids.push(tx.txid),
+                None => break,
+            }
+        }
+        (txids, pages)
+    }
+
+    fn histories() -> Vec<Vec<Tx>> {
+        // 30 confirmed transactions, two per block, from height 100 down
+        let mut long = vec![tx(200, None)];
+        long.extend((0..30).map(|i| tx(i, Some(100 - u32::from(i) / 2))));
+        // the transaction at height 90 is in both histories
+        let short = vec![
+            tx(201, None),
+            tx(100, Some(95)),
+            long[21].clone(),
+            tx(101, Some(80)),
+        ];
+        vec![long, short]
+    }
+
+    #[test]
+    fn recency_order() {
+        let histories = histories();
+        let scripts = vec![ScriptPubkey::new(), ScriptPubkey::new()];
+        let (txids, pages) = run(HistoryMerge::new(scripts, 10), &histories, usize::MAX);
+
+        let mut expected = histories.concat();
+        expected.sort_by(cmp_tx_for_display);
+        expected.dedup_by_key(|tx| tx.txid);
+        assert_eq!(txids, expected.iter().map(|tx| tx.txid).collect::<Vec<_>>());
+        assert_eq!(txids.len(), 34);
+        assert_eq!(pages, 3);
+    }
+
+    #[test]
+    fn pages_fetched_as_needed() {
+        let histories = histories();
+        let scripts = vec![ScriptPubkey::new(), ScriptPubkey::new()];
+        let (txids, pages) = run(HistoryMerge::new(scripts, 10), &histories, 10);
+        assert_eq!(txids.len(), 10);
+        assert_eq!(pages, 2);
+
+        let (txids, pages) = run(HistoryMerge::new(vec![], 10), &histories, usize::MAX);
+        assert!(txids.is_empty());
+        assert_eq!(pages, 0);
+    }
+
+    #[test]
+    fn page_limit() {
+        let histories = histories();
+        let mut merge = HistoryMerge::new(vec![ScriptPubkey::new()], 1);
+        let (index, _, cursor) = merge.pending().unwrap();
+        let err = merge
+            .push_page(index, page(&histories[0], cursor))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::PaginationAnomaly {
+                kind: PaginationAnomaly::PageLimit,
+                ref partial,
+                ..
+            } if partial.len() == 1 + CONFIRMED_PAGE_SIZE
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+//! Warm-up of the client caches with the resources wallets request on startup, so that the
+//! first queries don't wait for a sequence of round trips.
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::time::Duration;
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use bpstd::BlockHash;
+
+use crate::Error;
+#[cfg(any(feature = "blocking", feature = "async"))]
+use crate::{BlockSummary, FeeEstimates, TtlCache};
+
+/// Resource fetched by a warm-up.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum PrefetchItem {
+    /// chain tip height
+    TipHeight,
+
+    /// chain tip hash
+    TipHash,
+
+    /// recent block summaries
+    RecentBlocks,
+
+    /// fee estimates
+    FeeEstimates,
+}
+
+/// Selection of the resources fetched by a warm-up. Nothing is selected by default.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct PrefetchSet {
+    /// Fetch the height of the chain tip, served by `height`.
+    pub tip_height: bool,
+    /// Fetch the hash of the chain tip, served by `tip_hash`.
+    pub tip_hash: bool,
+    /// Fetch the summaries of the latest blocks, served by `blocks(None)`.
+    pub recent_blocks: bool,
+    /// Fetch the fee estimates, served by `fee_estimates` and `fee_estimates_with_source`.
+    pub fee_estimates: bool,
+}
+
+impl PrefetchSet {
+    /// Select all the resources.
+    pub fn all() -> Self {
+        PrefetchSet {
+            tip_height: true,
+            tip_hash: true,
+            recent_blocks: true,
+            fee_estimates: true,
+        }
+    }
+
+    /// Get the selected resources.
+    pub fn items(&self) -> Vec<PrefetchItem> {
+        [
+            (self.tip_height, PrefetchItem::TipHeight),
+            (self.tip_hash, PrefetchItem::TipHash),
+            (self.recent_blocks, PrefetchItem::RecentBlocks),
+            (self.fee_estimates, PrefetchItem::FeeEstimates),
+        ]
+        .iter()
+        .filter(|(selected, _)| *selected)
+        .map(|(_, item)| *item)
+        .collect()
+    }
+}
+
+/// Outcome of a warm-up, which never fails as a whole.
+#[derive(Debug, Default)]
+pub struct WarmUpReport {
+    /// Resources fetched and cached.
+    pub succeeded: Vec<PrefetchItem>,
+    /// Resources which couldn't be fetched, with the reason.
+    pub failed: Vec<(PrefetchItem, Error)>,
+    /// Resources still being fetched when the time budget ran out.
+    pub skipped: Vec<PrefetchItem>,
+}
+
+impl WarmUpReport {
+    /// Whether all the selected resources were fetched.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.skipped.is_empty()
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn record(&mut self, item: PrefetchItem, result: Result<(), Error>) {
+        match result {
+            Ok(()) => self.succeeded.push(item),
+            Err(err) => self.failed.push((item, err)),
+        }
+    }
+}
+
+/// Resource fetched by a warm-up, before it is cached.
+#[cfg(feature = "blocking")]
+#[derive(Debug)]
+pub(crate) enum Prefetched {
+    TipHeight(u32),
+    TipHash(BlockHash),
+    RecentBlocks(Vec<BlockSummary>),
+    FeeEstimates(FeeEstimates),
+}
+
+/// Resources cached by a warm-up, shared between clones of a client. The tip height is cached
+/// by the client itself.
+#[cfg(any(feature = "blocking", feature = "async"))]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct WarmCache {
+    pub tip_hash: TtlCache<BlockHash>,
+    pub recent_blocks: TtlCache<Vec<BlockSummary>>,
+    pub fee_estimates: TtlCache<FeeEstimates>,
+}
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+impl WarmCache {
+    pub fn new(ttl: Duration) -> Self {
+        WarmCache {
+            tip_hash: TtlCache::new(ttl),
+            recent_blocks: TtlCache::new(ttl),
+            fee_estimates: TtlCache::new(ttl),
+        }
+    }
+
+    /// Drop all the prefetched values.
+    pub fn clear(&self) {
+        self.tip_hash.clear();
+        self.recent_blocks.clear();
+        self.fee_estimates.clear();
+    }
+}
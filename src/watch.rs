@@ -0,0 +1,426 @@
+//! Watch-only monitoring of the blocks of the chain for the transactions paying to some scripts,
+//! spending some outputs or carrying some `OP_RETURN` data.
+
+use std::collections::HashSet;
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::collections::VecDeque;
+
+use bpstd::{BlockHash, Outpoint, ScriptPubkey, Txid};
+use serde::{Deserialize, Serialize};
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use crate::BlockSummary;
+use crate::Tx;
+
+/// Number of the last scanned blocks remembered by the `watch_chain` methods of the clients, to
+/// detect reorgs and skip the blocks scanned before one.
+#[cfg(any(feature = "blocking", feature = "async"))]
+const WATCHED_BLOCKS: usize = 32;
+
+/// `OP_RETURN` opcode.
+const OP_RETURN: u8 = 0x6a;
+/// Opcodes pushing data whose length follows on 1, 2 and 4 bytes.
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+
+/// Transactions looked for in blocks, built by adding criteria to the empty filter, which
+/// matches nothing. A transaction matches as soon as it meets any of the criteria.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WatchFilter {
+    scripts: HashSet<ScriptPubkey>,
+    outpoints: HashSet<Outpoint>,
+    min_value: u64,
+    op_return_prefix: Option<Vec<u8>>,
+}
+
+impl WatchFilter {
+    /// Create a filter matching no transaction.
+    pub fn new() -> Self {
+        WatchFilter::default()
+    }
+
+    /// Match the transactions paying to `script`
+    pub fn script(mut self, script: ScriptPubkey) -> Self {
+        self.scripts.insert(script);
+        self
+    }
+
+    /// Match the transactions paying to any of `scripts`
+    pub fn scripts(mut self, scripts: impl IntoIterator<Item = ScriptPubkey>) -> Self {
+        self.scripts.extend(scripts);
+        self
+    }
+
+    /// Match the transactions spending `outpoint`
+    pub fn outpoint(mut self, outpoint: Outpoint) -> Self {
+        self.outpoints.insert(outpoint);
+        self
+    }
+
+    /// Match the transactions spending any of `outpoints`
+    pub fn outpoints(mut self, outpoints: impl IntoIterator<Item = Outpoint>) -> Self {
+        self.outpoints.extend(outpoints);
+        self
+    }
+
+    /// Only match the outputs paying at least `min_value` sats to the watched scripts
+    pub fn min_value(mut self, min_value: u64) -> Self {
+        self.min_value = min_value;
+        self
+    }
+
+    /// Match the transactions with an `OP_RETURN` output whose data starts with `prefix`
+    pub fn op_return_prefix(mut self, prefix: impl Into<Vec<u8>>) -> Self {
+        self.op_return_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Whether the filter matches no transaction, so that blocks don't need to be fetched.
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty() && self.outpoints.is_empty() && self.op_return_prefix.is_none()
+    }
+
+    /// Get the reason why `tx` matches, stopping at the first criterion met: inputs are checked
+    /// first, then outputs in order.
+    pub fn check(&self, tx: &Tx) -> Option<MatchReason> {
+        if !self.outpoints.is_empty() {
+            let spending = tx.vin.iter().position(|vin| {
+                !vin.is_coinbase && self.outpoints.contains(&Outpoint::new(vin.txid, vin.vout))
+            });
+            if let Some(vin) = spending {
+                return Some(MatchReason::SpendsOutpoint { vin: vin as u32 });
+            }
+        }
+        for (vout, output) in tx.vout.iter().enumerate() {
+            if output.value >= self.min_value && self.scripts.contains(&output.scriptpubkey) {
+                return Some(MatchReason::PaysScript { vout: vout as u32 });
+            }
+            if let Some(prefix) = &self.op_return_prefix {
+                match op_return_data(output.scriptpubkey.as_ref()) {
+                    Some(data) if data.starts_with(prefix) => {
+                        return Some(MatchReason::OpReturn {
+                            data: data.to_vec(),
+                        })
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+
+    /// Get the transactions of a block matching the filter, in block order.
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    pub(crate) fn scan(&self, txs: &[Tx]) -> Vec<WatchMatch> {
+        txs.iter()
+            .filter_map(|tx| {
+                self.check(tx).map(|reason| WatchMatch {
+                    txid: tx.txid,
+                    reason,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Get the data pushed right after the `OP_RETURN` starting `script`, empty if nothing is
+/// pushed, or `None` if the script doesn't start with `OP_RETURN` or the push is truncated.
+fn op_return_data(script: &[u8]) -> Option<&[u8]> {
+    let (&opcode, rest) = match script.split_first() {
+        Some((&OP_RETURN, rest)) => rest.split_first()?,
+        _ => return None,
+    };
+    let len_size = match opcode {
+        OP_PUSHDATA1 => 1,
+        OP_PUSHDATA2 => 2,
+        OP_PUSHDATA4 => 4,
+        len if len < OP_PUSHDATA1 => return rest.get(..len as usize),
+        _ => return Some(&[]),
+    };
+    let len_bytes = rest.get(..len_size)?;
+    let len = len_bytes
+        .iter()
+        .rev()
+        .fold(0usize, |len, byte| len << 8 | *byte as usize);
+    rest[len_size..].get(..len)
+}
+
+/// Reason why a transaction matches a [`WatchFilter`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum MatchReason {
+    /// The output at index `vout` pays to a watched script.
+    PaysScript { vout: u32 },
+    /// The input at index `vin` spends a watched outpoint.
+    SpendsOutpoint { vin: u32 },
+    /// An output carries `OP_RETURN` data starting with the watched prefix.
+    OpReturn { data: Vec<u8> },
+}
+
+/// Transaction matching a [`WatchFilter`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WatchMatch {
+    pub txid: Txid,
+    pub reason: MatchReason,
+}
+
+/// Block scanned by the `watch_chain` methods of the clients, with its matching transactions,
+/// if any.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WatchedBlock {
+    pub height: u32,
+    pub hash: BlockHash,
+    /// Matching transactions, in block order.
+    pub matches: Vec<WatchMatch>,
+}
+
+/// Progress of the `watch_chain` methods of the clients along the chain, driven by the clients
+/// which fetch the blocks it asks for.
+#[cfg(any(feature = "blocking", feature = "async"))]
+#[derive(Debug)]
+pub(crate) struct ChainWatch {
+    /// Height of the next block to scan.
+    next_height: u32,
+    /// Last scanned blocks, from the lowest to the highest, for the next one to be checked
+    /// against.
+    scanned: VecDeque<(u32, BlockHash)>,
+    /// Hashes of the last scanned blocks, including the ones dropped by reorgs, which are not
+    /// scanned again when they come back in the best chain.
+    seen: VecDeque<BlockHash>,
+}
+
+/// What a [`ChainWatch`] does with the block fetched at its next height.
+#[cfg(any(feature = "blocking", feature = "async"))]
+pub(crate) enum WatchStep {
+    /// The block was already scanned before a reorg; the watch moved past it.
+    Skip,
+    /// The block doesn't extend the last scanned one, which was dropped by a reorg; the watch
+    /// moved back to its height.
+    Rewind,
+    /// The block is to be scanned, then passed to [`ChainWatch::scanned`].
+    Scan,
+}
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+impl ChainWatch {
+    /// Create a watch starting after the chain tip at height `tip`.
+    pub fn new(tip: u32) -> Self {
+        ChainWatch {
+            next_height: tip.saturating_add(1),
+            scanned: VecDeque::new(),
+            seen: VecDeque::new(),
+        }
+    }
+
+    /// Get the height of the next block to scan.
+    pub fn next_height(&self) -> u32 {
+        self.next_height
+    }
+
+    /// Checks the block at the next height, whose summary is `summary`, against the last
+    /// scanned one.
+    pub fn step(&mut self, summary: &BlockSummary) -> WatchStep {
+        if let Some(&(height, hash)) = self.scanned.back() {
+            if summary.previousblockhash != Some(hash) {
+                self.scanned.pop_back();
+                self.next_height = height;
+                return WatchStep::Rewind;
+            }
+        }
+        if self.seen.contains(&summary.id) {
+            self.push(self.next_height, summary.id);
+            return WatchStep::Skip;
+        }
+        WatchStep::Scan
+    }
+
+    /// Records the block at the next height as scanned.
+    pub fn scanned(&mut self, height: u32, hash: BlockHash) {
+        self.push(height, hash);
+    }
+
+    fn push(&mut self, height: u32, hash: BlockHash) {
+        if self.scanned.len() >= WATCHED_BLOCKS {
+            self.scanned.pop_front();
+        }
+        self.scanned.push_back((height, hash));
+        if !self.seen.contains(&hash) {
+            if self.seen.len() >= 2 * WATCHED_BLOCKS {
+                self.seen.pop_front();
+            }
+            self.seen.push_back(hash);
+        }
+        self.next_height = height.saturating_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    use amplify::{ByteArray, Bytes32};
+    use bpstd::SigScript;
+
+    use super::*;
+    use crate::test_fixtures::{self, txid};
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    use crate::BlockTime;
+    use crate::{Vin, Vout};
+
+    fn vin(id: u8, vout: u32) -> Vin {
+        Vin {
+            txid: txid(id),
+            vout,
+            prevout: None,
+            scriptsig: SigScript::new(),
+            witness: vec![],
+            sequence: u32::MAX,
+            is_coinbase: false,
+        }
+    }
+
+    fn vout(value: u64, script: ScriptPubkey) -> Vout {
+        Vout {
+            value,
+            scriptpubkey: script,
+            script_type: None,
+            address: None,
+        }
+    }
+
+    fn tx(id: u8, vin: Vec<Vin>, vout: Vec<Vout>) -> Tx {
+        test_fixtures::tx(id, None, vin, vout)
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    fn summary(id: u8, prev: u8, height: u32) -> BlockSummary {
+        BlockSummary {
+            id: BlockHash::from_byte_array([id; 32]),
+            time: BlockTime {
+                timestamp: 0,
+                height,
+            },
+            previousblockhash: Some(BlockHash::from_byte_array([prev; 32])),
+            merkle_root: Bytes32::from_byte_array([0; 32]),
+            tx_count: 1,
+            size: 100,
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let watched = ScriptPubkey::p2wpkh([1; 20]);
+        let other = ScriptPubkey::p2wpkh([2; 20]);
+        let filter = WatchFilter::new()
+            .script(watched.clone())
+            .outpoint(Outpoint::new(txid(9), 1))
+            .min_value(1000)
+            .op_return_prefix(b"bp".to_vec());
+        assert!(!filter.is_empty());
+        assert!(WatchFilter::new().min_value(1000).is_empty());
+
+        let payment = tx(
+            1,
+            vec![vin(8, 0)],
+            vec![vout(5000, other.clone()), vout(1000, watched.clone())],
+        );
+        assert_eq!(
+            filter.check(&payment),
+            Some(MatchReason::PaysScript { vout: 1 })
+        );
+        let dust = tx(2, vec![vin(8, 1)], vec![vout(999, watched.clone())]);
+        assert_eq!(filter.check(&dust), None);
+
+        // inputs are checked before outputs
+        let spend = tx(3, vec![vin(8, 2), vin(9, 1)], vec![vout(1000, watched)]);
+        assert_eq!(
+            filter.check(&spend),
+            Some(MatchReason::SpendsOutpoint { vin: 1 })
+        );
+        let mut coinbase = tx(4, vec![vin(9, 1)], vec![vout(5000, other.clone())]);
+        coinbase.vin[0].is_coinbase = true;
+        assert_eq!(filter.check(&coinbase), None);
+
+        let data = tx(
+            5,
+            vec![vin(8, 3)],
+            vec![vout(0, ScriptPubkey::op_return(b"bpx")), vout(0, other)],
+        );
+        assert_eq!(
+            filter.check(&data),
+            Some(MatchReason::OpReturn {
+                data: b"bpx".to_vec()
+            })
+        );
+        let unrelated = tx(
+            6,
+            vec![vin(8, 4)],
+            vec![vout(0, ScriptPubkey::op_return(b"xbp"))],
+        );
+        assert_eq!(filter.check(&unrelated), None);
+
+        #[cfg(any(feature = "blocking", feature = "async"))]
+        {
+            let matches = filter.scan(&[payment, dust, spend, coinbase, data, unrelated]);
+            let txids = matches.iter().map(|m| m.txid).collect::<Vec<_>>();
+            assert_eq!(txids, [txid(1), txid(3), txid(5)]);
+        }
+    }
+
+    #[test]
+    fn op_return_pushes() {
+        assert_eq!(
+            op_return_data(&[OP_RETURN, 2, 0xaa, 0xbb]),
+            Some(&[0xaa, 0xbb][..])
+        );
+        assert_eq!(op_return_data(&[OP_RETURN]), None);
+        assert_eq!(op_return_data(&[OP_RETURN, 0x51]), Some(&[][..]));
+        assert_eq!(op_return_data(&[OP_RETURN, 3, 0xaa]), None);
+        assert_eq!(op_return_data(&[0x51, 1, 0xaa]), None);
+
+        let mut pushdata1 = vec![OP_RETURN, OP_PUSHDATA1, 80];
+        pushdata1.extend([7u8; 80].iter());
+        assert_eq!(op_return_data(&pushdata1), Some(&[7u8; 80][..]));
+        let pushdata2 = [OP_RETURN, OP_PUSHDATA2, 2, 0, 0xaa, 0xbb];
+        assert_eq!(op_return_data(&pushdata2), Some(&[0xaa, 0xbb][..]));
+        let pushdata4 = [OP_RETURN, OP_PUSHDATA4, 1, 0, 0, 0, 0xaa];
+        assert_eq!(op_return_data(&pushdata4), Some(&[0xaa][..]));
+        assert_eq!(op_return_data(&[OP_RETURN, OP_PUSHDATA2, 1]), None);
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    #[test]
+    fn steps() {
+        let mut watch = ChainWatch::new(10);
+        assert_eq!(watch.next_height(), 11);
+        assert!(matches!(watch.step(&summary(11, 10, 11)), WatchStep::Scan));
+        watch.scanned(11, summary(11, 10, 11).id);
+        assert!(matches!(watch.step(&summary(12, 11, 12)), WatchStep::Scan));
+        watch.scanned(12, summary(12, 11, 12).id);
+        assert_eq!(watch.next_height(), 13);
+
+        // block 12 was replaced by 22, extended by 23
+        assert!(matches!(
+            watch.step(&summary(23, 22, 13)),
+            WatchStep::Rewind
+        ));
+        assert_eq!(watch.next_height(), 12);
+        assert!(matches!(watch.step(&summary(22, 11, 12)), WatchStep::Scan));
+        watch.scanned(12, summary(22, 11, 12).id);
+        assert!(matches!(watch.step(&summary(23, 22, 13)), WatchStep::Scan));
+        watch.scanned(13, summary(23, 22, 13).id);
+
+        // and back again: block 12 was scanned before, so it is skipped
+        assert!(matches!(
+            watch.step(&summary(14, 13, 14)),
+            WatchStep::Rewind
+        ));
+        assert!(matches!(
+            watch.step(&summary(13, 12, 13)),
+            WatchStep::Rewind
+        ));
+        assert_eq!(watch.next_height(), 12);
+        assert!(matches!(watch.step(&summary(12, 11, 12)), WatchStep::Skip));
+        assert_eq!(watch.next_height(), 13);
+        assert!(matches!(watch.step(&summary(13, 12, 13)), WatchStep::Scan));
+    }
+}
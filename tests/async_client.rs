@@ -0,0 +1,1821 @@
+#![cfg(feature = "async")]
+
+mod support;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use amplify::hex::ToHex;
+use amplify::ByteArray;
+#[cfg(feature = "addresses")]
+use bpstd::AddressNetwork;
+use bpstd::{BlockHash, Outpoint, Txid};
+use esplora::balancer::{
+    BalancedClient, LoadBalancingPolicy, LATENCY_PROBE_INTERVAL, UNHEALTHY_AFTER_FAILURES,
+};
+#[cfg(feature = "blocking")]
+use esplora::SyncCache;
+use esplora::{
+    AsyncClient, BandwidthBudget, Builder, CheckpointUpdate, Checkpoints, Error, ErrorKind,
+    EsploraErrorKind, ManualClock, MempoolTracker, PaginationAnomaly, RedirectPolicy,
+    RequestIdPolicy, WatchFilter,
+};
+#[cfg(feature = "addresses")]
+use esplora::{Cursor, CursorKey};
+use futures::StreamExt;
+use support::{FakeChain, Response, Server, CHAIN_TXS_PER_PAGE, PAY_FEE};
+
+fn client(server: &Server) -> AsyncClient {
+    Builder::new(&server.url()).build_async().unwrap()
+}
+
+#[tokio::test]
+async fn tip_and_blocks() {
+    let server = Server::new();
+    server.chain().mine(12);
+    let client = client(&server);
+
+    assert_eq!(client.height_uncached().await.unwrap(), 12);
+    let hash = server.chain().block_at(5).unwrap().hash;
+    assert_eq!(client.block_hash(5).await.unwrap(), hash);
+
+    let blocks = client.blocks(None).await.unwrap();
+    assert_eq!(blocks.len(), 10);
+    assert_eq!(blocks[0].time.height, 12);
+    assert_eq!(blocks[0].previousblockhash, Some(blocks[1].id));
+}
+
+#[tokio::test]
+async fn unknown_items_are_not_found() {
+    let server = Server::new();
+    let client = client(&server);
+    let txid = Txid::from_byte_array([7u8; 32]);
+
+    assert_eq!(client.tx(&txid).await.unwrap(), None);
+    assert_eq!(client.tx_info(&txid).await.unwrap(), None);
+    assert_eq!(client.merkle_proof(&txid).await.unwrap(), None);
+
+    let err = client.block_hash(1).await.unwrap_err();
+    assert!(matches!(err, Error::HeaderHeightNotFound(1)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn mempool_to_confirmed() {
+    let server = Server::new();
+    let client = client(&server);
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    let txid = tx.txid();
+
+    assert_eq!(client.tx(&txid).await.unwrap(), Some(tx));
+    assert!(!client.tx_status(&txid).await.unwrap().confirmed);
+    assert_eq!(
+        client.tx_info(&txid).await.unwrap().unwrap().fee,
+        Some(PAY_FEE)
+    );
+
+    let hash = server.chain().mine(1)[0];
+    let status = client.tx_status(&txid).await.unwrap();
+    assert!(status.confirmed);
+    assert_eq!(status.block_hash, Some(hash));
+}
+
+#[tokio::test]
+async fn reorg_returns_txs_to_mempool() {
+    let server = Server::new();
+    let client = client(&server);
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let stale = server.chain().mine(1)[0];
+    assert!(client.tx_status(&txid).await.unwrap().confirmed);
+
+    server.chain().reorg(1);
+    assert!(!client.tx_status(&txid).await.unwrap().confirmed);
+    assert!(!client.block_status(&stale).await.unwrap().in_best_chain);
+}
+
+#[tokio::test]
+async fn history_pages() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    let client = client(&server);
+
+    let first = client.scripthash_txs(&script, None).await.unwrap();
+    assert_eq!(first.len(), CHAIN_TXS_PER_PAGE);
+    let last_seen = first.last().unwrap().txid;
+    let next = client
+        .scripthash_txs(&script, Some(last_seen))
+        .await
+        .unwrap();
+    assert_eq!(next.len(), 5);
+
+    let all = client.scripthash_txs_all(&script).await.unwrap();
+    assert_eq!(all.len(), CHAIN_TXS_PER_PAGE + 5);
+}
+
+#[cfg(feature = "addresses")]
+#[tokio::test]
+async fn histories_keep_the_server_order() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    let mempool = (0..10)
+        .map(|_| server.chain().pay(&script, 20_000).txid())
+        .collect::<Vec<_>>();
+    let expected = {
+        let chain = server.chain();
+        chain
+            .mempool_history(&script)
+            .into_iter()
+            .chain(chain.chain_history(&script))
+            .map(|tx| tx.txid())
+            .collect::<Vec<_>>()
+    };
+    let client = client(&server);
+
+    let all = client.scripthash_txs_all(&script).await.unwrap();
+    assert_eq!(all.iter().map(|tx| tx.txid).collect::<Vec<_>>(), expected);
+
+    let latest = client
+        .address_txs_latest(&FakeChain::address(1), 3)
+        .await
+        .unwrap();
+    assert_eq!(
+        latest.iter().map(|tx| tx.txid).collect::<Vec<_>>(),
+        mempool.iter().rev().take(3).copied().collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn broadcast_and_reject() {
+    let server = Server::new();
+    let client = client(&server);
+    let funding = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(1);
+
+    let spend = server.chain().spend(
+        &[Outpoint::new(funding, 0)],
+        vec![(FakeChain::script(2), 9_000)],
+    );
+    client.broadcast(&spend).await.unwrap();
+    assert_eq!(server.chain().mempool().len(), 1);
+
+    let err = client.broadcast(&spend).await.unwrap_err();
+    assert_eq!(err.esplora_kind(), Some(EsploraErrorKind::AlreadyKnown));
+}
+
+#[tokio::test]
+async fn malformed_tx_info_is_a_json_error() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.on_request(|req| {
+        if req.path.starts_with("/tx/") {
+            Some(Response::text("{\"txid\": 1"))
+        } else {
+            None
+        }
+    });
+    let client = client(&server);
+
+    let err = client.tx_info(&txid).await.unwrap_err();
+    assert!(matches!(err, Error::Json(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn pagination_anomaly_keeps_partial_results() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    let client = client(&server);
+
+    // the server answers every following page with the first one again
+    let first_page = {
+        let chain = server.chain();
+        let history = chain.chain_history(&script);
+        serde_json::Value::from(
+            history[..CHAIN_TXS_PER_PAGE]
+                .iter()
+                .map(|tx| chain.tx_json(tx))
+                .collect::<Vec<_>>(),
+        )
+    };
+    server.on_request(move |req| {
+        if req.path.contains("/txs/chain/") {
+            Some(Response::json(&first_page))
+        } else {
+            None
+        }
+    });
+
+    let mut err = client.scripthash_txs_all(&script).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::PaginationAnomaly {
+                kind: PaginationAnomaly::RepeatedCursor,
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+    assert_eq!(err.take_partial_txs().len(), CHAIN_TXS_PER_PAGE);
+    assert_eq!(err.partial_txs(), Some(&[][..]));
+}
+
+#[tokio::test]
+async fn coalesced_requests_stop_reading_oversized_bodies() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let client = Builder::new(&server.url())
+        .coalesce_requests(true)
+        .build_async()
+        .unwrap();
+    server.on_request(|req| {
+        if req.path.ends_with("/raw") {
+            Some(Response::bytes(vec![0u8; 11 * 1024 * 1024]))
+        } else {
+            None
+        }
+    });
+
+    let err = client.tx_raw_bytes(&txid).await.unwrap_err();
+    assert!(matches!(err, Error::Io(_)), "{:?}", err);
+    server.clear_hook();
+    assert!(client.tx_raw_bytes(&txid).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn anchors_fill_in_omitted_block_hashes() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    let deep = server.chain().pay(&script, 10_000).txid();
+    let deep_hash = server.chain().mine(7)[0];
+    let shallow = server.chain().pay(&script, 20_000).txid();
+    let shallow_hash = server.chain().mine(1)[0];
+    server.omit_block_hashes(true);
+    let status = client(&server).tx_status(&deep).await.unwrap();
+    assert_eq!(status.block_hash, None);
+
+    let client = Builder::new(&server.url())
+        .require_anchors(true)
+        .build_async()
+        .unwrap();
+    server.reset_log();
+    let status = client.tx_status(&deep).await.unwrap();
+    assert_eq!(status.block_hash, Some(deep_hash));
+    let tx = client.tx_info(&deep).await.unwrap().unwrap();
+    assert_eq!(tx.status.block_hash, Some(deep_hash));
+    // the hash of a deep block is cached, and trusted to include the transaction
+    assert_eq!(server.requests_to("/block-height/"), 1);
+    assert_eq!(server.requests_to("/block/"), 0);
+
+    let status = client.tx_status(&shallow).await.unwrap();
+    assert_eq!(status.block_hash, Some(shallow_hash));
+    let txids = format!("/block/{}/txids", shallow_hash);
+    assert_eq!(server.requests_to(&txids), 1);
+
+    let anchored = |txid: Txid| {
+        if txid == deep {
+            deep_hash
+        } else {
+            shallow_hash
+        }
+    };
+    let txs = client.scripthash_txs(&script, None).await.unwrap();
+    assert_eq!(txs.len(), 2);
+    for tx in txs {
+        assert_eq!(tx.status.block_hash, Some(anchored(tx.txid)));
+    }
+    let utxos = client.scripthash_utxo(&script).await.unwrap();
+    assert_eq!(utxos.len(), 2);
+    for utxo in utxos {
+        assert_eq!(utxo.status.block_hash, Some(anchored(utxo.txid)));
+    }
+    let (proof, hash) = client
+        .merkle_proof_with_block(&shallow)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(proof.block_height, 8);
+    assert_eq!(hash, shallow_hash);
+}
+
+#[tokio::test]
+async fn anchors_detect_reorganized_blocks() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(1);
+    // the transaction goes back to the mempool while a lagging server still reports it
+    // confirmed in the stale block
+    server.chain().reorg(1);
+    let stale = serde_json::json!({ "confirmed": true, "block_height": 1 });
+    server.on_request(move |req| {
+        if req.path.ends_with("/status") {
+            Some(Response::json(&stale))
+        } else {
+            None
+        }
+    });
+    let client = Builder::new(&server.url())
+        .require_anchors(true)
+        .build_async()
+        .unwrap();
+
+    let err = client.tx_status(&txid).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::InconsistentBackend {
+                check: "anchor",
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn validation_rejects_inconsistent_responses() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    let txid = server.chain().pay(&script, 10_000).txid();
+    server.chain().mine(1);
+    let client = Builder::new(&server.url())
+        .validate_responses(true)
+        .build_async()
+        .unwrap();
+    assert!(client.merkle_proof(&txid).await.unwrap().is_some());
+    assert_eq!(client.scripthash_utxo(&script).await.unwrap().len(), 1);
+
+    let mut proof = server.chain().merkle_proof_json(&txid).unwrap();
+    proof["pos"] = 0.into();
+    let mut utxos = server.chain().utxo_json(&script);
+    utxos[0]["vout"] = 2.into();
+    server.on_request(move |req| {
+        if req.path.ends_with("/merkle-proof") {
+            Some(Response::json(&proof))
+        } else if req.path.ends_with("/utxo") {
+            Some(Response::json(&utxos))
+        } else {
+            None
+        }
+    });
+    let err = client.merkle_proof(&txid).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::InconsistentBackend {
+                check: "merkle_proof",
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+    let err = client.scripthash_utxo(&script).await.unwrap_err();
+    assert!(
+        matches!(err, Error::InconsistentBackend { check: "utxo", .. }),
+        "{:?}",
+        err
+    );
+}
+
+/// Number of concurrent requests above which the server rate limits the client.
+const RATE_LIMIT: usize = 3;
+
+#[tokio::test]
+async fn batch_concurrency_stays_below_rate_limit() {
+    let server = Server::new();
+    let txids = (0..60)
+        .map(|n| server.chain().pay(&FakeChain::script(n), 10_000).txid())
+        .collect::<Vec<_>>();
+    server.set_delay(Duration::from_millis(10));
+    server.on_request(|req| {
+        if req.in_flight > RATE_LIMIT {
+            Some(Response::error(429, "Too Many Requests"))
+        } else {
+            None
+        }
+    });
+    let client = Builder::new(&server.url())
+        .adaptive_concurrency(16)
+        .max_retries(0)
+        .base_backoff(Duration::from_millis(1))
+        .build_async()
+        .unwrap();
+
+    let statuses = client.tx_statuses(&txids).await.unwrap();
+    assert_eq!(statuses.len(), txids.len());
+
+    // after the first halvings, the concurrency only probes the limit from time to time
+    let requests = server.requests();
+    let settled = &requests[requests.len() / 2..];
+    let throttled = settled
+        .iter()
+        .filter(|req| req.in_flight > RATE_LIMIT)
+        .count();
+    assert!(
+        throttled * 4 <= settled.len(),
+        "{} of {} requests throttled",
+        throttled,
+        settled.len()
+    );
+}
+
+#[tokio::test]
+async fn batch_backs_off_at_single_request() {
+    let server = Server::new();
+    let txids = (0..3)
+        .map(|n| server.chain().pay(&FakeChain::script(n), 10_000).txid())
+        .collect::<Vec<_>>();
+    let throttled = Arc::new(AtomicUsize::new(0));
+    let count = throttled.clone();
+    server.on_request(move |_| {
+        if count.fetch_add(1, Ordering::SeqCst) < 3 {
+            Some(Response::error(429, "Too Many Requests"))
+        } else {
+            None
+        }
+    });
+    let client = Builder::new(&server.url())
+        .adaptive_concurrency(1)
+        .max_retries(0)
+        .base_backoff(Duration::from_millis(1))
+        .build_async()
+        .unwrap();
+
+    assert_eq!(client.tx_statuses(&txids).await.unwrap().len(), 3);
+    assert_eq!(server.requests().len(), 6);
+}
+
+#[cfg(feature = "addresses")]
+#[tokio::test]
+async fn addresses_utxo_batch() {
+    let server = Server::new();
+    for n in 0..5 {
+        server.chain().pay(&FakeChain::script(n), 10_000);
+    }
+    server.chain().pay(&FakeChain::script(1), 20_000);
+    let client = client(&server);
+
+    let addresses = (0..6).map(FakeChain::address).collect::<Vec<_>>();
+    let utxos = client.addresses_utxo(&addresses).await.unwrap();
+    let counts = utxos.iter().map(Vec::len).collect::<Vec<_>>();
+    assert_eq!(counts, vec![1, 2, 1, 1, 1, 0]);
+}
+
+#[tokio::test]
+async fn set_base_url_switches_servers() {
+    let primary = Server::new();
+    primary.chain().mine(3);
+    let standby = Server::new();
+    standby.chain().mine(5);
+    let client = client(&primary);
+
+    assert_eq!(client.height().await.unwrap(), 3);
+    client.set_base_url(standby.url()).unwrap();
+    assert_eq!(client.url(), primary.url());
+    assert_eq!(client.current_url(), standby.url());
+    assert_eq!(client.height().await.unwrap(), 5);
+    assert_eq!(primary.requests_to("/blocks/tip/height"), 1);
+}
+
+#[tokio::test]
+async fn coalesced_requests_are_charged_like_the_others() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let busy = Arc::new(AtomicBool::new(false));
+    let hook_busy = busy.clone();
+    server.on_request(move |req| {
+        if req.path.ends_with("/raw") && hook_busy.swap(false, Ordering::SeqCst) {
+            return Some(Response::error(503, "busy"));
+        }
+        None
+    });
+
+    let mut charged = vec![];
+    for coalesce in [false, true] {
+        busy.store(true, Ordering::SeqCst);
+        let client = Builder::new(&server.url())
+            .coalesce_requests(coalesce)
+            .max_retries(1)
+            .base_backoff(Duration::from_millis(1))
+            .build_async()
+            .unwrap();
+        let bytes = client.tx_raw_bytes(&txid).await.unwrap().unwrap();
+        // both attempts are charged
+        assert_eq!(
+            client.bandwidth_used().tx,
+            (bytes.len() + "busy".len()) as u64
+        );
+        charged.push(client.bandwidth_used());
+    }
+    assert_eq!(charged[0], charged[1]);
+}
+
+#[tokio::test]
+async fn bandwidth_budget_stops_paged_sync() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..2 * CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    let unbudgeted = client(&server);
+    unbudgeted.scripthash_txs(&script, None).await.unwrap();
+    let first_page = unbudgeted.bandwidth_used().history;
+
+    for coalesce in [false, true] {
+        server.reset_log();
+        let client = Builder::new(&server.url())
+            .coalesce_requests(coalesce)
+            .bandwidth_budget(BandwidthBudget::default().per_operation(first_page + 1))
+            .build_async()
+            .unwrap();
+        let err = client.scripthash_txs_all(&script).await.unwrap_err();
+        assert!(
+            matches!(err, Error::BandwidthExceeded { used, budget }
+                if used > first_page && budget == first_page + 1),
+            "{:?}",
+            err
+        );
+        assert_eq!(server.requests_to("/scripthash"), 2);
+
+        client.start_bandwidth_operation();
+        assert!(client.scripthash_txs(&script, None).await.is_ok());
+    }
+}
+
+#[tokio::test]
+async fn retries_are_opt_in() {
+    let server = Server::new();
+    server.on_request(|req| {
+        if req.path == "/blocks/tip/height" {
+            return Some(Response::error(503, "busy"));
+        }
+        None
+    });
+
+    let err = client(&server).height_uncached().await.unwrap_err();
+    assert!(
+        matches!(err, Error::HttpResponse { status: 503, .. }),
+        "{:?}",
+        err
+    );
+    assert_eq!(server.requests_to("/blocks/tip/height"), 1);
+
+    server.reset_log();
+    let retrying = Builder::new(&server.url())
+        .max_retries(2)
+        .base_backoff(Duration::from_millis(1))
+        .build_async()
+        .unwrap();
+    assert!(retrying.height_uncached().await.is_err());
+    assert_eq!(server.requests_to("/blocks/tip/height"), 3);
+}
+
+#[tokio::test]
+async fn retry_predicate_peeks_at_the_body() {
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    server.chain().mine(1);
+    let recovered = AtomicBool::new(false);
+    server.on_request(move |req| {
+        if req.path.ends_with("/status") && !recovered.swap(true, Ordering::SeqCst) {
+            return Some(Response::text("backend unavailable"));
+        }
+        None
+    });
+    let client = Builder::new(&server.url())
+        .max_retries(2)
+        .base_backoff(Duration::from_millis(1))
+        .retry_if(Arc::new(|context| {
+            context.body == Some(&b"backend unavailable"[..])
+        }))
+        .build_async()
+        .unwrap();
+
+    let status = client.tx_status(&tx.txid()).await.unwrap();
+    assert!(status.confirmed);
+    assert_eq!(server.requests_to("/tx/"), 2);
+}
+
+#[tokio::test]
+async fn redirects_follow_the_policy() {
+    let server = Server::new();
+    let other = Server::new();
+    other.chain().mine(4);
+    let target = format!("{}/blocks/tip/height", other.url());
+    server.on_request(move |req| {
+        if req.path == "/blocks/tip/height" {
+            return Some(Response::new(302, "text/plain", "").header("Location", &target));
+        }
+        None
+    });
+
+    let err = client(&server).height_uncached().await.unwrap_err();
+    assert!(matches!(err, Error::RedirectBlocked { .. }), "{:?}", err);
+    let following = Builder::new(&server.url())
+        .redirect_policy(RedirectPolicy::Limited(1))
+        .build_async()
+        .unwrap();
+    assert_eq!(following.height_uncached().await.unwrap(), 4);
+}
+
+#[tokio::test]
+async fn broadcast_follows_redirects() {
+    let server = Server::new();
+    let funding = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(1);
+    let spend = server.chain().spend(
+        &[Outpoint::new(funding, 0)],
+        vec![(FakeChain::script(2), 9_000)],
+    );
+    let redirected = AtomicBool::new(false);
+    server.on_request(move |req| {
+        if req.method == "POST" && !redirected.swap(true, Ordering::SeqCst) {
+            return Some(Response::new(307, "text/plain", "").header("Location", "/tx"));
+        }
+        None
+    });
+
+    client(&server).broadcast(&spend).await.unwrap();
+    assert_eq!(server.requests_to("/tx"), 2);
+    assert_eq!(server.chain().mempool().len(), 1);
+}
+
+#[tokio::test]
+async fn chain_work_checks_header_linkage() {
+    let server = Server::new();
+    server.chain().mine(5);
+    let client = client(&server);
+    let headers = (1..=4)
+        .map(|height| server.chain().block_at(height).unwrap().header)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        client.chain_work_between(1, 4).await.unwrap(),
+        esplora::headers::cumulative_work(&headers)
+    );
+
+    let stale = server.chain().block_at(1).unwrap().hash;
+    server.on_request(move |req| {
+        if req.path == "/block-height/3" {
+            return Some(Response::text(stale.to_string()));
+        }
+        None
+    });
+    let err = client.chain_work_between(1, 4).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::InconsistentBackend {
+                check: "header_chain",
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[cfg(feature = "addresses")]
+#[tokio::test]
+async fn string_inputs_are_validated_before_requests() {
+    let server = Server::new();
+    let address = FakeChain::address(1);
+    server.chain().pay(&FakeChain::script(1), 10_000);
+    let client = Builder::new(&server.url())
+        .network(AddressNetwork::Regtest)
+        .build_async()
+        .unwrap();
+    let is_invalid = |err: &Error, expected: &str| matches!(err, Error::InvalidInput { field, .. } if *field == expected);
+
+    let txid = "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050";
+    for malformed in [
+        "",
+        "xyz",
+        &txid[1..],
+        &txid.replace('4', "g"),
+        &format!("{}00", txid),
+    ] {
+        let err = client.tx_by_str(malformed).await.unwrap_err();
+        assert!(is_invalid(&err, "txid"), "{}: {:?}", malformed, err);
+    }
+    let err = client.header_by_hash_str(&txid[..60]).await.unwrap_err();
+    assert!(is_invalid(&err, "block hash"), "{:?}", err);
+
+    let malformed = [
+        // mainnet and testnet addresses, of another network than the client
+        "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+        "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+        "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2",
+        // bad checksum
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt081",
+        // truncated
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7",
+        "not an address",
+    ];
+    for malformed in malformed.iter() {
+        let err = client
+            .address_stats_str(malformed)
+            .await
+            .expect_err(malformed);
+        assert!(is_invalid(&err, "address"), "{}: {:?}", malformed, err);
+    }
+    let err = client
+        .address_txs_confirmed_str(&address.to_string(), Some("00"))
+        .await
+        .unwrap_err();
+    assert!(is_invalid(&err, "last seen txid"), "{:?}", err);
+    assert!(server.requests().is_empty());
+
+    let stats = client
+        .address_stats_str(&address.to_string())
+        .await
+        .unwrap();
+    assert_eq!(stats.mempool_stats.tx_count, 1);
+    // regtest shares the base58 prefixes of testnet
+    client
+        .address_stats_str("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn mempool_tracker_ages_and_evictions() {
+    let server = Server::new();
+    let clock = Arc::new(ManualClock::new());
+    let tracker = MempoolTracker::with_clock(client(&server), clock.clone());
+    let minute = Duration::from_secs(60);
+
+    let first = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    tracker.refresh().await.unwrap();
+    clock.advance(minute);
+    let second = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let dropped = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    tracker.refresh().await.unwrap();
+    clock.advance(minute);
+    assert_eq!(tracker.age(&first), Some(2 * minute));
+    assert_eq!(tracker.age(&second), Some(minute));
+
+    server.chain().drop_tx(&dropped);
+    tracker.refresh().await.unwrap();
+    assert_eq!(tracker.first_seen(&dropped), None);
+    assert_eq!(tracker.tracked().len(), 2);
+
+    server.chain().mine(1);
+    assert_eq!(tracker.age(&first), Some(2 * minute));
+    assert_eq!(tracker.age_checked(&first).await.unwrap(), None);
+    assert_eq!(tracker.tracked(), vec![second]);
+    tracker.refresh().await.unwrap();
+    assert!(tracker.tracked().is_empty());
+
+    let script = FakeChain::script(2);
+    let scripted = server.chain().pay(&script, 10_000).txid();
+    tracker.refresh_script(&script).await.unwrap();
+    clock.advance(minute);
+    assert_eq!(tracker.age_checked(&scripted).await.unwrap(), Some(minute));
+    server.chain().mine(1);
+    tracker.refresh_script(&FakeChain::script(3)).await.unwrap();
+    assert_eq!(tracker.tracked(), vec![scripted]);
+    tracker.refresh_script(&script).await.unwrap();
+    assert!(tracker.tracked().is_empty());
+}
+
+#[tokio::test]
+async fn oversized_error_bodies_are_classified() {
+    let server = Server::new();
+    let mut body = "Block not found".to_owned();
+    body.push_str(&" ".repeat(20 * 1_024 * 1_024));
+    server.on_request(move |req| {
+        if req.path.ends_with("/status") {
+            return Some(Response::error(404, &body));
+        }
+        None
+    });
+
+    let unknown = BlockHash::from_byte_array([7u8; 32]);
+    let err = client(&server).block_status(&unknown).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::HttpResponse {
+                status: 404,
+                kind: EsploraErrorKind::BlockNotFound,
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[cfg(feature = "blocking-bridge")]
+#[test]
+fn blocking_handle_mirrors_the_endpoints() {
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    let txid = tx.txid();
+    let hash = server.chain().mine(1)[0];
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let handle = client(&server).blocking_handle(runtime.handle().clone());
+
+    assert_eq!(handle.tx(&txid).unwrap(), Some(tx));
+    assert_eq!(handle.txid_at_block_index(&hash, 1).unwrap(), Some(txid));
+    assert!(!handle.outspend(&txid, 0).unwrap().unwrap().spent);
+    let utxos = handle.scripthash_utxo(&FakeChain::script(1)).unwrap();
+    assert_eq!(utxos.len(), 1);
+    assert_eq!(handle.tx_confirmations(&txid).unwrap(), 1);
+    assert_eq!(handle.height().unwrap(), 1);
+
+    let err = runtime.block_on(async { handle.height() }).unwrap_err();
+    assert!(matches!(err, Error::BlockingInRuntime), "{:?}", err);
+}
+
+#[cfg(feature = "blocking-bridge")]
+#[test]
+fn blocking_handle_refuses_current_thread_runtimes() {
+    let server = Server::new();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let handle = client(&server).blocking_handle(runtime.handle().clone());
+
+    let err = handle.height().unwrap_err();
+    assert!(matches!(err, Error::CurrentThreadRuntime), "{:?}", err);
+    assert_eq!(err.kind(), ErrorKind::Config);
+    assert!(server.requests().is_empty());
+}
+
+#[cfg(feature = "blocking")]
+#[tokio::test]
+async fn sync_cache_is_shared_and_follows_the_tip_hash() {
+    let server = Server::new();
+    let (used, unused) = (FakeChain::script(1), FakeChain::script(2));
+    server.chain().pay(&used, 10_000);
+    server.chain().mine(1);
+    let cache = Arc::new(SyncCache::default());
+    let blocking = Builder::new(&server.url())
+        .sync_cache(cache.clone())
+        .build_blocking()
+        .unwrap();
+    assert!(blocking.script_has_history(&used).unwrap());
+    assert!(!blocking.script_has_history(&unused).unwrap());
+    assert_eq!(server.requests_to("/scripthash/"), 2);
+
+    let client = Builder::new(&server.url())
+        .sync_cache(cache.clone())
+        .build_async()
+        .unwrap();
+    assert!(client.script_has_history(&used).await.unwrap());
+    assert!(!client.script_has_history(&unused).await.unwrap());
+    assert_eq!(server.requests_to("/scripthash/"), 2);
+
+    // the tip is replaced at the same height by a block paying the unused script
+    let height = server.chain().height();
+    server.chain().disconnect(1);
+    server.chain().pay(&unused, 5_000);
+    server.chain().mine(1);
+    assert_eq!(server.chain().height(), height);
+    assert!(client.script_has_history(&unused).await.unwrap());
+    assert_eq!(server.requests_to("/scripthash/"), 3);
+}
+
+#[tokio::test]
+async fn shutdown_ends_watchers_and_refuses_requests() {
+    let server = Server::new();
+    server.chain().mine(1);
+    let client = client(&server);
+    let watcher = client.clone();
+    let script = FakeChain::script(1);
+
+    let watch = watcher
+        .watch_chain(
+            WatchFilter::new().script(script.clone()),
+            Duration::from_millis(10),
+        )
+        .collect::<Vec<_>>();
+    let shutdown = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        server.chain().pay(&script, 10_000);
+        server.chain().mine(1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let start = Instant::now();
+        let idle = client.shutdown_gracefully().await;
+        (idle, start.elapsed())
+    };
+    let (watched, (idle, elapsed)) = tokio::time::timeout(Duration::from_secs(5), async {
+        tokio::join!(watch, shutdown)
+    })
+    .await
+    .expect("the watcher ends with the shutdown");
+
+    assert!(idle);
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    assert_eq!(watched.len(), 1);
+    assert_eq!(watched[0].as_ref().unwrap().matches.len(), 1);
+    assert!(matches!(
+        client.height_uncached().await,
+        Err(Error::ClientClosed)
+    ));
+    assert!(matches!(watcher.tip_hash().await, Err(Error::ClientClosed)));
+    // shutting down again only waits for the requests in flight
+    assert!(client.shutdown_gracefully().await);
+}
+
+#[tokio::test]
+async fn shutdown_waits_for_requests_in_flight() {
+    let server = Server::new();
+    server.chain().mine(3);
+    server.set_delay(Duration::from_millis(200));
+    let shutdown = |client: AsyncClient| async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.shutdown_gracefully().await
+    };
+
+    let patient = client(&server);
+    let (height, idle) = tokio::join!(patient.height_uncached(), shutdown(patient.clone()));
+    assert_eq!(height.unwrap(), 3);
+    assert!(idle);
+
+    let hasty = Builder::new(&server.url())
+        .shutdown_grace(Duration::from_millis(20))
+        .build_async()
+        .unwrap();
+    let (height, idle) = tokio::join!(hasty.height_uncached(), shutdown(hasty.clone()));
+    assert_eq!(height.unwrap(), 3);
+    assert!(!idle);
+
+    // the consuming shutdown leaves the clones open
+    let consumed = client(&server);
+    let clone = consumed.clone();
+    consumed.shutdown();
+    assert_eq!(clone.height_uncached().await.unwrap(), 3);
+}
+
+/// Gets the tip height from `server` with a client waiting with `S` between retries, the
+/// first request being answered with an error, and the futures being polled by `block_on`
+/// within the context of a Tokio runtime without timers, which only provides the reactor of
+/// the requests.
+#[cfg(any(feature = "async-std-sleeper", feature = "smol-sleeper"))]
+fn retry_on_runtime<S: esplora::Sleeper + Default + 'static>(
+    block_on: impl FnOnce(
+        std::pin::Pin<Box<dyn std::future::Future<Output = Result<u32, Error>>>>,
+    ) -> Result<u32, Error>,
+) {
+    let server = Server::new();
+    server.chain().mine(2);
+    let failed = AtomicBool::new(false);
+    server.on_request(move |req| {
+        if req.path == "/blocks/tip/height" && !failed.swap(true, Ordering::SeqCst) {
+            return Some(Response::error(503, "busy"));
+        }
+        None
+    });
+    let reactor = tokio::runtime::Builder::new_multi_thread()
+        .enable_io()
+        .build()
+        .unwrap();
+    let _context = reactor.enter();
+    let client = Builder::new(&server.url())
+        .max_retries(1)
+        .base_backoff(Duration::from_millis(100))
+        .backoff_jitter(esplora::JitterPolicy::None)
+        .build_async_with_sleeper::<S>()
+        .unwrap();
+
+    let start = Instant::now();
+    let height = block_on(Box::pin(async move { client.height_uncached().await }));
+    assert_eq!(height.unwrap(), 2);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(server.requests_to("/blocks/tip/height"), 2);
+}
+
+#[cfg(feature = "async-std-sleeper")]
+#[test]
+fn async_std_sleeper_waits_between_retries() {
+    retry_on_runtime::<esplora::r#async::AsyncStdSleeper>(async_std::task::block_on);
+}
+
+#[cfg(feature = "smol-sleeper")]
+#[test]
+fn smol_sleeper_waits_between_retries() {
+    retry_on_runtime::<esplora::r#async::SmolSleeper>(smol::block_on);
+}
+
+#[cfg(feature = "addresses")]
+#[tokio::test]
+async fn history_stream_resumes_from_an_encoded_cursor() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..2 * CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    let client = client(&server);
+    let key = CursorKey::new([9u8; 32]);
+
+    let mut pages = Box::pin(client.scripthash_txs_stream_cursor(&script, None));
+    let (first, cursor) = pages.next().await.unwrap().unwrap();
+    assert_eq!(first.len(), CHAIN_TXS_PER_PAGE);
+    let encoded = cursor.unwrap().encode(&key);
+    drop(pages);
+
+    let resumed = Cursor::decode(&encoded, &key).unwrap();
+    let rest = client
+        .scripthash_txs_stream_cursor(&script, Some(resumed))
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(rest.len(), 2);
+    let (second, cursor) = rest[0].as_ref().unwrap();
+    assert_eq!(second.len(), CHAIN_TXS_PER_PAGE);
+    assert!(cursor.is_some());
+    let (last, cursor) = rest[1].as_ref().unwrap();
+    assert_eq!(last.len(), 5);
+    assert!(cursor.is_none());
+    let mut txids = first
+        .iter()
+        .chain(second)
+        .chain(last)
+        .map(|tx| tx.txid)
+        .collect::<Vec<_>>();
+    txids.sort();
+    txids.dedup();
+    assert_eq!(txids.len(), 2 * CHAIN_TXS_PER_PAGE + 5);
+
+    let address = FakeChain::address(1);
+    let mut pages = Box::pin(client.address_txs_stream_cursor(&address, Some(resumed)));
+    let err = pages.next().await.unwrap().unwrap_err();
+    assert!(matches!(err, Error::InvalidCursor(_)), "{:?}", err);
+    assert!(pages.next().await.is_none());
+}
+
+#[tokio::test]
+async fn broadcast_and_wait_times_only_the_wait() {
+    let server = Server::new();
+    let client = client(&server);
+    let funding = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(1);
+    server.on_request(|req| {
+        if req.method == "POST" {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        None
+    });
+
+    let spend = server.chain().spend(
+        &[Outpoint::new(funding, 0)],
+        vec![(FakeChain::script(2), 9_000)],
+    );
+    let txid = client
+        .broadcast_and_wait(&spend, Duration::from_millis(100))
+        .await
+        .unwrap();
+    assert_eq!(txid, spend.txid());
+
+    let unknown = server.chain().spend(
+        &[Outpoint::new(spend.txid(), 0)],
+        vec![(FakeChain::script(3), 8_000)],
+    );
+    server.on_request(|req| {
+        if req.method == "GET" && req.path.starts_with("/tx/") {
+            return Some(Response::error(404, "Transaction not found"));
+        }
+        None
+    });
+    let err = client
+        .broadcast_and_wait(&unknown, Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::DeadlineExceeded), "{:?}", err);
+    assert_eq!(server.chain().mempool().len(), 2);
+}
+
+#[cfg(feature = "chaos")]
+#[tokio::test]
+async fn chaos_client_forces_unknown_transactions() {
+    use esplora::chaos::{ChaosClient, ChaosConfig};
+
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    server.chain().mine(1);
+    let client = client(&server);
+    let txid = tx.txid();
+    let chaos = ChaosClient::new(client.clone(), ChaosConfig::new(7).not_found(txid));
+    let unknown = Txid::from_byte_array([7u8; 32]);
+    server.reset_log();
+
+    // the same results as the client for a transaction the server doesn't know
+    assert_eq!(chaos.tx(&txid).await.unwrap(), None);
+    assert_eq!(client.tx(&unknown).await.unwrap(), None);
+    let status = chaos.tx_status(&txid).await.unwrap();
+    assert_eq!(status, client.tx_status(&unknown).await.unwrap());
+    assert!(!status.confirmed);
+    assert_eq!(chaos.tx_confirmations(&txid).await.unwrap(), 0);
+    let err = chaos.tx_fee(&txid).await.unwrap_err();
+    assert!(
+        matches!(err, Error::TransactionNotFound(id) if id == txid),
+        "{:?}",
+        err
+    );
+    let err = client.tx_fee(&unknown).await.unwrap_err();
+    assert!(
+        matches!(err, Error::TransactionNotFound(id) if id == unknown),
+        "{:?}",
+        err
+    );
+
+    assert_eq!(chaos.stats().not_found, 4);
+    assert_eq!(server.requests_to(&format!("/tx/{}", txid)), 0);
+}
+
+#[cfg(feature = "chaos")]
+#[tokio::test]
+async fn chaos_client_garbles_real_bodies_and_passes_through() {
+    use esplora::chaos::{ChaosClient, ChaosConfig};
+
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    let hash = server.chain().mine(1)[0];
+    let client = client(&server);
+    let txid = tx.txid();
+
+    let clean = ChaosClient::new(client.clone(), ChaosConfig::new(7));
+    assert_eq!(clean.tx(&txid).await.unwrap(), Some(tx.clone()));
+    assert_eq!(
+        clean.tx_status(&txid).await.unwrap(),
+        client.tx_status(&txid).await.unwrap()
+    );
+    assert_eq!(
+        clean.header_by_hash(&hash).await.unwrap(),
+        client.header_by_hash(&hash).await.unwrap()
+    );
+    assert_eq!(clean.tx_fee(&txid).await.unwrap(), PAY_FEE);
+    assert_eq!(clean.stats().garbled, 0);
+
+    let garbling = ChaosClient::new(client.clone(), ChaosConfig::new(7).garble_rate(1.0));
+    let raw = client.tx_raw_bytes(&txid).await.unwrap().unwrap();
+    for _ in 0..10 {
+        assert_ne!(garbling.tx_raw_bytes(&txid).await.unwrap().unwrap(), raw);
+    }
+    assert_eq!(garbling.stats().garbled, 10);
+    assert_eq!(server.requests_to(&format!("/tx/{}/raw", txid)), 12);
+}
+
+#[tokio::test]
+async fn balanced_reads_skip_failing_replicas() {
+    let servers = [Server::new(), Server::new(), Server::new()];
+    servers[1].on_request(|request| {
+        (request.method == "GET").then(|| Response::error(503, "unavailable"))
+    });
+    let replicas = servers.iter().map(client).collect::<Vec<_>>();
+    let client = BalancedClient::new(replicas, LoadBalancingPolicy::RoundRobin);
+    let script = FakeChain::script(1);
+
+    let mut failures = 0;
+    for _ in 0..12 {
+        if client.scripthash_mempool_txs(&script).await.is_err() {
+            failures += 1;
+        }
+    }
+    assert_eq!(failures, 3);
+    assert_eq!(servers[1].requests_to("/scripthash"), 3);
+    assert_eq!(
+        servers[0].requests_to("/scripthash") + servers[2].requests_to("/scripthash"),
+        9
+    );
+    let stats = client.stats();
+    assert!(stats[0].healthy && !stats[1].healthy && stats[2].healthy);
+    assert_eq!(stats[1].failures, 3);
+
+    // a transaction unknown to a replica doesn't make it unhealthy
+    let unknown = Txid::from_byte_array([7u8; 32]);
+    for _ in 0..6 {
+        assert_eq!(
+            client.tx_fee(&unknown).await.unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+    assert!(client
+        .stats()
+        .iter()
+        .enumerate()
+        .all(|(index, stats)| stats.healthy == (index != 1)));
+}
+
+#[tokio::test]
+async fn balanced_reads_prefer_fast_replicas() {
+    let servers = [Server::new(), Server::new()];
+    servers[0].set_delay(Duration::from_millis(50));
+    let replicas = servers.iter().map(client).collect::<Vec<_>>();
+    let client = BalancedClient::new(replicas, LoadBalancingPolicy::LowestLatency);
+    let script = FakeChain::script(1);
+
+    for _ in 0..LATENCY_PROBE_INTERVAL {
+        client.scripthash_mempool_txs(&script).await.unwrap();
+    }
+    // each replica is measured, then only the fast one is used until the slow one is probed
+    assert_eq!(servers[0].requests_to("/scripthash"), 2);
+    assert_eq!(
+        servers[1].requests_to("/scripthash"),
+        LATENCY_PROBE_INTERVAL - 2
+    );
+    let stats = client.stats();
+    assert!(stats[0].latency > stats[1].latency);
+}
+
+#[tokio::test]
+async fn balanced_broadcast_reports_every_rejection() {
+    let servers = [Server::new(), Server::new()];
+    for server in &servers {
+        server.on_request(|request| {
+            (request.method == "POST").then(|| Response::error(400, "txn-mempool-conflict"))
+        });
+    }
+    let script = FakeChain::script(1);
+    let funding = servers[0].chain().pay(&script, 10_000).txid();
+    let spend = servers[0].chain().spend(
+        &[Outpoint::new(funding, 0)],
+        vec![(FakeChain::script(2), 9_000)],
+    );
+    let replicas = servers.iter().map(client).collect::<Vec<_>>();
+    let client =
+        BalancedClient::new(replicas, LoadBalancingPolicy::RoundRobin).fan_out_broadcast(true);
+
+    for _ in 0..UNHEALTHY_AFTER_FAILURES {
+        match client.broadcast(&spend).await.unwrap_err() {
+            Error::BroadcastRejected(report) => {
+                assert_eq!(report.accepted(), 0);
+                let errors = report.errors().collect::<Vec<_>>();
+                assert_eq!(errors.len(), 2);
+                assert!(errors.iter().all(|(_, error)| error.status() == Some(400)));
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+    assert!(client.stats().iter().all(|stats| stats.healthy));
+
+    let txid = spend.txid();
+    servers[1].on_request(move |request| (request.method == "POST").then(|| Response::text(txid)));
+    let report = client.broadcast(&spend).await.unwrap();
+    assert_eq!(report.accepted(), 1);
+    assert!(report.results[1].1.is_ok());
+}
+
+#[tokio::test]
+async fn merkle_proofs_verify_against_the_block_header() {
+    let server = Server::new();
+    let txids = (1..=3)
+        .map(|n| server.chain().pay(&FakeChain::script(n), 10_000).txid())
+        .collect::<Vec<_>>();
+    server.chain().mine(3);
+    let client = client(&server);
+
+    for txid in txids {
+        let (proof, hash) = client
+            .merkle_proof_with_block(&txid)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(hash, server.chain().block_at(1).unwrap().hash);
+        let header = client.header_by_hash(&hash).await.unwrap();
+        assert_eq!(proof.merkle_root(&txid), header.merkle_root.to_byte_array());
+    }
+    // the hash isn't part of the proof, it is the hash of the block at the proof height, not
+    // cached while the block is shallow
+    assert_eq!(server.requests_to("/block-height/1"), 3);
+}
+
+#[tokio::test]
+async fn checkpoints_report_the_exact_fork() {
+    let server = Server::new();
+    server.chain().mine(20);
+    let client = client(&server);
+    let mut checkpoints = Checkpoints::new(8, 5);
+
+    for height in [20, 25, 30].iter() {
+        let mined = server.chain().height();
+        server.chain().mine(*height - mined);
+        let update = client.update_checkpoints(&mut checkpoints).await.unwrap();
+        assert!(
+            matches!(update, CheckpointUpdate::Added { height: added, .. } if added == *height),
+            "{:?}",
+            update
+        );
+    }
+    assert_eq!(
+        client.update_checkpoints(&mut checkpoints).await.unwrap(),
+        CheckpointUpdate::Unchanged
+    );
+
+    // 1-deep rollback of the checkpoint at the tip: the fork is right below it
+    let stale = server.chain().tip().hash;
+    server.chain().reorg(1);
+    assert_eq!(
+        client.update_checkpoints(&mut checkpoints).await.unwrap(),
+        CheckpointUpdate::Reorg {
+            fork_height: 29,
+            dropped: vec![(30, stale)],
+        }
+    );
+    assert_eq!(checkpoints.latest().map(|(height, _)| height), Some(25));
+
+    // 3-deep rollback: the fork is between the checkpoints at 25 and 31
+    let update = client.update_checkpoints(&mut checkpoints).await.unwrap();
+    assert!(
+        matches!(update, CheckpointUpdate::Added { height: 31, .. }),
+        "{:?}",
+        update
+    );
+    let stale = server.chain().tip().hash;
+    server.chain().reorg(3);
+    assert_eq!(
+        client.update_checkpoints(&mut checkpoints).await.unwrap(),
+        CheckpointUpdate::Reorg {
+            fork_height: 28,
+            dropped: vec![(31, stale)],
+        }
+    );
+    let hash = server.chain().block_at(25).unwrap().hash;
+    assert_eq!(checkpoints.latest(), Some((25, hash)));
+}
+
+#[tokio::test]
+async fn block_walks_stop_at_genesis() {
+    let server = Server::new();
+    server.chain().mine(24);
+    let client = client(&server);
+
+    let summaries = client.blocks_before(20, usize::MAX).await.unwrap();
+    assert_eq!(
+        summaries
+            .iter()
+            .map(|summary| summary.time.height)
+            .collect::<Vec<_>>(),
+        (0..20).rev().collect::<Vec<_>>()
+    );
+    assert_eq!(client.blocks_before(20, 3).await.unwrap().len(), 3);
+    assert!(client
+        .blocks_before(0, usize::MAX)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(client.average_block_interval(usize::MAX).await.is_ok());
+}
+
+#[cfg(feature = "addresses")]
+#[tokio::test]
+async fn verified_balances_are_checked_again_across_blocks() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    server.chain().pay(&script, 10_000);
+    server.chain().mine(1);
+    server.chain().pay(&script, 5_000);
+    let client = client(&server);
+    let address = FakeChain::address(1);
+
+    let balance = client.verified_balance(&address).await.unwrap();
+    assert!(balance.verified);
+    assert_eq!(balance.balance, 15_000);
+    assert_eq!(balance.tip, server.chain().tip().hash);
+
+    // the first check sees a stale tip and misses the unconfirmed output
+    let tip_hashes = Arc::new(AtomicUsize::new(0));
+    let utxo_lists = Arc::new(AtomicUsize::new(0));
+    server.on_request(move |request| {
+        if request.path == "/blocks/tip/hash" && tip_hashes.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Some(Response::text(BlockHash::from_byte_array([7u8; 32])));
+        }
+        if request.path.ends_with("/utxo") && utxo_lists.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Some(Response::json(&serde_json::json!([])));
+        }
+        None
+    });
+    server.reset_log();
+    let balance = client.verified_balance(&address).await.unwrap();
+    assert!(balance.verified);
+    assert_eq!(balance.discrepancy, None);
+    assert_eq!(server.requests_to("/blocks/tip/hash"), 4);
+
+    // a discrepancy at the same tip is reported without checking again
+    server.on_request(|request| {
+        request
+            .path
+            .ends_with("/utxo")
+            .then(|| Response::json(&serde_json::json!([])))
+    });
+    server.reset_log();
+    let balance = client.verified_balance(&address).await.unwrap();
+    assert!(!balance.verified);
+    assert_eq!(balance.discrepancy, Some(-15_000));
+    assert_eq!(server.requests_to("/blocks/tip/hash"), 2);
+}
+
+#[tokio::test]
+async fn accept_language_is_sent() {
+    let server = Server::new();
+    let client = Builder::new(&server.url())
+        .accept_language("fr-CH, fr;q=0.9")
+        .unwrap()
+        .build_async()
+        .unwrap();
+    client.height_uncached().await.unwrap();
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].header("Accept-Language"),
+        Some("fr-CH, fr;q=0.9")
+    );
+
+    let mut builder = Builder::new(&server.url());
+    builder.accept_language = Some("fr_CH".to_owned());
+    assert!(matches!(
+        builder.build_async(),
+        Err(Error::InvalidHttpHeaderValue(_))
+    ));
+}
+
+/// Ids sent in the `X-Request-Id` header of the requests received by `server`.
+fn request_ids(server: &Server) -> Vec<Option<String>> {
+    server
+        .requests()
+        .iter()
+        .map(|request| request.header("X-Request-Id").map(str::to_owned))
+        .collect()
+}
+
+#[tokio::test]
+async fn request_ids_are_kept_across_retries() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let failed = Arc::new(AtomicBool::new(false));
+    let hook_failed = failed.clone();
+    server.on_request(move |request| {
+        (request.path.ends_with("/raw") && !hook_failed.swap(true, Ordering::SeqCst))
+            .then(|| Response::error(503, "busy"))
+    });
+    for coalesce in [false, true] {
+        failed.store(false, Ordering::SeqCst);
+        server.reset_log();
+        let client = Builder::new(&server.url())
+            .max_retries(2)
+            .base_backoff(Duration::from_millis(1))
+            .coalesce_requests(coalesce)
+            .build_async()
+            .unwrap();
+        client.tx_raw_bytes(&txid).await.unwrap().unwrap();
+        client.tx_raw_bytes(&txid).await.unwrap().unwrap();
+
+        let ids = request_ids(&server);
+        assert_eq!(ids.len(), 3);
+        assert!(ids[0].is_some());
+        assert_eq!(ids[0], ids[1], "coalescing: {}", coalesce);
+        assert_ne!(ids[1], ids[2]);
+    }
+}
+
+#[tokio::test]
+async fn request_ids_are_reported_with_errors() {
+    let server = Server::new();
+    server.chain().mine(1);
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.on_request(|request| {
+        (request.path != "/blocks/tip/height").then(|| Response::error(500, "boom"))
+    });
+    let client = Builder::new(&server.url())
+        .request_id(RequestIdPolicy::Prefixed("wallet".to_owned()))
+        .build_async()
+        .unwrap();
+
+    let tip = server.chain().tip().hash;
+    let errors = [
+        client.tx_raw_bytes(&txid).await.unwrap_err(),
+        client.block_hash(1).await.unwrap_err(),
+        client.header_hex(&tip).await.unwrap_err(),
+        client
+            .scripthash_utxo(&FakeChain::script(1))
+            .await
+            .unwrap_err(),
+    ];
+    let ids = request_ids(&server);
+    assert_eq!(ids.len(), errors.len());
+    for (error, id) in errors.iter().zip(&ids) {
+        assert_eq!(error.status(), Some(500));
+        assert_eq!(error.request_id(), id.as_deref());
+    }
+    assert_eq!(ids[0].as_deref(), Some("wallet-0"));
+    assert_eq!(ids[3].as_deref(), Some("wallet-3"));
+
+    server.reset_log();
+    let client = Builder::new(&server.url())
+        .request_id(RequestIdPolicy::Disabled)
+        .build_async()
+        .unwrap();
+    let error = client.block_hash(1).await.unwrap_err();
+    assert_eq!(error.request_id(), None);
+    assert_eq!(request_ids(&server), [None]);
+    client.height_uncached().await.unwrap();
+}
+
+fn request_paths(server: &Server) -> Vec<String> {
+    server
+        .requests()
+        .into_iter()
+        .map(|request| request.path)
+        .collect()
+}
+
+#[tokio::test]
+async fn encodings_are_converted_from_the_cheapest_endpoint() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(100);
+    let client = client(&server);
+
+    server.reset_log();
+    let hex = client.tx_hex(&txid).await.unwrap().unwrap();
+    assert_eq!(request_paths(&server), [format!("/tx/{}/raw", txid)]);
+    let raw = client.tx_raw_bytes(&txid).await.unwrap().unwrap();
+    assert_eq!(hex, raw.to_hex());
+
+    let hash = server.chain().block_at(1).unwrap().hash;
+    server.reset_log();
+    let raw = client.header_raw_bytes(&hash).await.unwrap();
+    assert_eq!(request_paths(&server), [format!("/block/{}/header", hash)]);
+    assert_eq!(raw.len(), 80);
+    assert_eq!(client.header_hex(&hash).await.unwrap(), raw.to_hex());
+
+    // a batch of 100 headers costs 160 bytes each, less than the raw blocks even with a
+    // single coinbase transaction
+    let client = self::client(&server);
+    let hashes = (1..=100)
+        .map(|height| server.chain().block_at(height).unwrap().hash)
+        .collect::<Vec<_>>();
+    for hash in &hashes {
+        assert_eq!(client.header_raw_bytes(hash).await.unwrap().len(), 80);
+    }
+    assert_eq!(client.bytes_downloaded(), 100 * 160);
+    let raw_blocks = hashes
+        .iter()
+        .map(|hash| server.chain().block(hash).unwrap().serialize().len() as u64)
+        .sum::<u64>();
+    assert!(raw_blocks > 100 * 160, "{}", raw_blocks);
+}
+
+#[tokio::test]
+async fn binary_bodies_of_text_endpoints_are_reported() {
+    let server = Server::new();
+    server.chain().mine(1);
+    let hash = server.chain().block_at(1).unwrap().hash;
+    server.on_request(|request| match request.path.as_str() {
+        "/blocks/tip/height" | "/block-height/1" => Some(Response::bytes(vec![0xff, 0xfe, 0])),
+        path if path.ends_with("/txid/0") => Some(Response::bytes(vec![0xff; 32])),
+        // the connection is closed before the announced body was sent
+        path if path.ends_with("/txid/1") => {
+            Some(Response::text("00").header("Content-Length", "64"))
+        }
+        _ => None,
+    });
+    let client = client(&server);
+    let invalid_utf8 = |err: Error| match err {
+        Error::InvalidUtf8 { path } => path,
+        err => panic!("unexpected error {:?}", err),
+    };
+
+    let err = client.height_uncached().await.unwrap_err();
+    assert_eq!(invalid_utf8(err), "/blocks/tip/height");
+    let err = client.block_hash(1).await.unwrap_err();
+    assert_eq!(invalid_utf8(err), "/block-height/1");
+    let err = client.txid_at_block_index(&hash, 0).await.unwrap_err();
+    assert_eq!(invalid_utf8(err), format!("/block/{}/txid/0", hash));
+
+    let err = client.txid_at_block_index(&hash, 1).await.unwrap_err();
+    assert!(!matches!(err, Error::InvalidUtf8 { .. }), "{:?}", err);
+}
+
+#[tokio::test]
+async fn error_bodies_are_parsed() {
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    let sent = Arc::new(AtomicUsize::new(0));
+    let counter = sent.clone();
+    server.on_request(move |request| {
+        if request.method != "POST" {
+            return None;
+        }
+        Some(match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => Response::new(
+                400,
+                "application/json",
+                r#"{"error": "bad-txns-inputs-missingorspent", "code": -25}"#,
+            ),
+            1 => Response::error(400, "min relay fee not met"),
+            _ => Response::error(400, ""),
+        })
+    });
+    let client = client(&server);
+    let message = |err: &Error| match err {
+        Error::HttpResponse { message, .. } => message.clone(),
+        err => panic!("unexpected error {:?}", err),
+    };
+
+    let err = client.broadcast(&tx).await.unwrap_err();
+    assert_eq!(err.esplora_kind(), Some(EsploraErrorKind::MissingInputs));
+    assert_eq!(err.api_code(), Some(-25));
+    assert_eq!(message(&err), "bad-txns-inputs-missingorspent");
+    assert_eq!(err.details().unwrap().error, message(&err));
+
+    let err = client.broadcast(&tx).await.unwrap_err();
+    assert_eq!(err.esplora_kind(), Some(EsploraErrorKind::InsufficientFee));
+    assert_eq!((err.api_code(), err.details()), (None, None));
+    assert_eq!(message(&err), "min relay fee not met");
+
+    let err = client.broadcast(&tx).await.unwrap_err();
+    assert_eq!(err.esplora_kind(), Some(EsploraErrorKind::Other));
+    assert_eq!((err.api_code(), err.details()), (None, None));
+    assert_eq!(message(&err), "");
+    assert_eq!(sent.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn block_fee_stats_count_transactions_from_the_summary() {
+    let server = Server::new();
+    server.chain().pay(&FakeChain::script(1), 10_000);
+    server.chain().pay(&FakeChain::script(2), 20_000);
+    let hash = server.chain().mine(1)[0];
+    let client = client(&server);
+
+    let stats = client.block_fee_stats(&hash, false).await.unwrap();
+    assert_eq!(stats.total_fees, 2 * PAY_FEE);
+    assert!(stats.min_feerate > 0.0);
+    assert_eq!(server.requests_to(&format!("/block/{}/txids", hash)), 0);
+
+    let unknown = BlockHash::from_byte_array([7u8; 32]);
+    let err = client.block_fee_stats(&unknown, false).await.unwrap_err();
+    assert!(
+        matches!(err, Error::HeaderHashNotFound(hash) if hash == unknown),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn scanned_blocks_count_transactions_from_the_summary() {
+    let server = Server::new();
+    let watched = FakeChain::script(1);
+    let mut paying = vec![];
+    for n in 0..30u8 {
+        let script = if n % 10 == 0 {
+            &watched
+        } else {
+            &FakeChain::script(2)
+        };
+        let txid = server.chain().pay(script, 10_000).txid();
+        if n % 10 == 0 {
+            paying.push(txid);
+        }
+    }
+    let hash = server.chain().mine(1)[0];
+    let client = client(&server);
+
+    let filter = WatchFilter::new().script(watched);
+    let matches = client.scan_block(&hash, &filter).await.unwrap();
+    let mut txids = matches.iter().map(|m| m.txid).collect::<Vec<_>>();
+    txids.sort();
+    paying.sort();
+    assert_eq!(txids, paying);
+    assert_eq!(server.requests_to(&format!("/block/{}/txids", hash)), 0);
+
+    let unknown = BlockHash::from_byte_array([7u8; 32]);
+    let err = client.scan_block(&unknown, &filter).await.unwrap_err();
+    assert!(
+        matches!(err, Error::HeaderHashNotFound(hash) if hash == unknown),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn blocks_at_the_tip_and_past_it() {
+    let server = Server::new();
+    server.chain().pay(&FakeChain::script(1), 10_000);
+    server.chain().pay(&FakeChain::script(2), 20_000);
+    let hash = server.chain().mine(1)[0];
+    let tip = server.chain().height();
+    let size = server.chain().block(&hash).unwrap().serialize().len() as u32;
+    let client = client(&server);
+
+    let summary = client.block_at_height(tip).await.unwrap().unwrap();
+    assert_eq!(summary.id, hash);
+    assert_eq!(summary.time.height, tip);
+    assert_eq!(summary.tx_count, 3);
+    assert_eq!(summary.size, size);
+    assert!(client.block_at_height(tip + 1).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn utxo_diffs_since_a_snapshot() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    let spent = server.chain().pay(&script, 10_000).txid();
+    server.chain().pay(&script, 20_000);
+    server.chain().mine(1);
+    let pending = server.chain().pay(&script, 30_000).txid();
+    let client = client(&server);
+    let txids = |utxos: &[esplora::Utxo]| {
+        let mut txids = utxos.iter().map(|utxo| utxo.txid).collect::<Vec<_>>();
+        txids.sort();
+        txids
+    };
+
+    let old = client.utxo_snapshot(&script).await.unwrap();
+    assert_eq!(old.utxos.len(), 3);
+    let spend = server.chain().spend(
+        &[Outpoint::new(spent, 0)],
+        vec![(FakeChain::script(2), 9_000)],
+    );
+    server.chain().broadcast(spend).unwrap();
+    let added = server.chain().pay(&script, 40_000).txid();
+    server.chain().mine(1);
+
+    let (diff, new) = client.utxo_diff_since(&script, &old).await.unwrap();
+    assert_eq!(txids(&diff.added), [added]);
+    assert_eq!(txids(&diff.removed), [spent]);
+    assert_eq!(txids(&diff.status_changed), [pending]);
+    assert!(!diff.reorg);
+    assert_eq!(new.tip_hash, server.chain().tip().hash);
+
+    // the block confirming them is reorganized out, leaving them in the mempool
+    server.chain().reorg(1);
+    let (diff, _) = client.utxo_diff_since(&script, &new).await.unwrap();
+    assert!(diff.added.is_empty() && diff.removed.is_empty());
+    let mut unconfirmed = vec![pending, added];
+    unconfirmed.sort();
+    assert_eq!(txids(&diff.status_changed), unconfirmed);
+    assert!(diff.reorg);
+}
+
+#[tokio::test]
+async fn named_fee_tiers_are_mapped_to_targets() {
+    let server = Server::new();
+    server.on_request(|request| {
+        (request.path == "/fee-estimates").then(|| {
+            Response::json(&serde_json::json!({
+                "fastestFee": 30,
+                "halfHourFee": 20,
+                "hourFee": 10,
+                "economyFee": 5,
+                "minimumFee": 1,
+            }))
+        })
+    });
+
+    let estimates = client(&server).fee_estimates().await.unwrap();
+    assert_eq!(estimates.len(), 5);
+    assert_eq!(estimates["1"], 30.0);
+    assert_eq!(estimates["3"], 20.0);
+    assert_eq!(estimates["1008"], 1.0);
+}
+
+#[tokio::test]
+async fn traced_requests_report_their_retries() {
+    let server = Server::new();
+    server.chain().mine(3);
+    let failures = AtomicUsize::new(2);
+    server.on_request(move |request| {
+        let failing = request.path == "/blocks/tip/height"
+            && failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                    left.checked_sub(1)
+                })
+                .is_ok();
+        failing.then(|| Response::error(503, "unavailable"))
+    });
+    let client = Builder::new(&server.url())
+        .max_retries(2)
+        .base_backoff(Duration::from_millis(1))
+        .trace_retries(true)
+        .build_async()
+        .unwrap();
+
+    let (body, trace) = client.get_traced("/blocks/tip/height").await.unwrap();
+    assert_eq!(body, b"3");
+    assert_eq!(trace.retries, 2);
+    assert!(trace.delay > Duration::from_secs(0));
+
+    let (_, trace) = client.get_traced("/blocks/tip/height").await.unwrap();
+    assert_eq!(trace.retries, 0);
+    assert_eq!(trace.delay, Duration::from_secs(0));
+    assert_eq!(server.requests_to("/blocks/tip/height"), 4);
+}
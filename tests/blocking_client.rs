@@ -0,0 +1,1841 @@
+#![cfg(feature = "blocking")]
+
+mod support;
+
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use amplify::hex::ToHex;
+use amplify::ByteArray;
+#[cfg(feature = "addresses")]
+use bpstd::AddressNetwork;
+use bpstd::{BlockHash, Outpoint, ScriptPubkey, Tx, Txid};
+use esplora::balancer::{
+    BalancedClient, LoadBalancingPolicy, LATENCY_PROBE_INTERVAL, UNHEALTHY_AFTER_FAILURES,
+};
+use esplora::{
+    BandwidthBudget, BlockingClient, Builder, Cache, CheckpointUpdate, Checkpoints, Error,
+    ErrorKind, EsploraErrorKind, FeeFallback, LruCache, PaginationAnomaly, PrefetchItem,
+    PrefetchSet, RedirectPolicy, RequestIdPolicy, SpendableOpts, WatchFilter,
+};
+#[cfg(feature = "addresses")]
+use esplora::{Cursor, CursorKey};
+use support::{FakeChain, Response, Server, CHAIN_TXS_PER_PAGE, PAY_FEE};
+
+fn client(server: &Server) -> BlockingClient {
+    Builder::new(&server.url()).build_blocking().unwrap()
+}
+
+#[test]
+fn tip_and_blocks() {
+    let server = Server::new();
+    server.chain().mine(12);
+    let client = client(&server);
+
+    assert_eq!(client.height_uncached().unwrap(), 12);
+    assert_eq!(client.tip_hash().unwrap(), server.chain().tip().hash);
+    assert_eq!(
+        client.block_hash(5).unwrap(),
+        server.chain().block_at(5).unwrap().hash
+    );
+
+    let blocks = client.blocks(None).unwrap();
+    assert_eq!(blocks.len(), 10);
+    assert_eq!(blocks[0].time.height, 12);
+    assert_eq!(blocks[9].time.height, 3);
+    assert_eq!(blocks[0].previousblockhash, Some(blocks[1].id));
+    let blocks = client.blocks(Some(2)).unwrap();
+    assert_eq!(blocks.len(), 3);
+    assert_eq!(blocks[2].previousblockhash, None);
+}
+
+#[test]
+fn unknown_items_are_not_found() {
+    let server = Server::new();
+    let client = client(&server);
+    let txid = Txid::from_byte_array([7u8; 32]);
+
+    assert_eq!(client.tx(&txid).unwrap(), None);
+    assert_eq!(client.tx_info(&txid).unwrap(), None);
+    assert_eq!(client.merkle_proof(&txid).unwrap(), None);
+    assert!(!client.tx_status(&txid).unwrap().confirmed);
+
+    let err = client.block_hash(1).unwrap_err();
+    assert!(matches!(err, Error::HeaderHeightNotFound(1)), "{:?}", err);
+    let unknown = BlockHash::from_byte_array([7u8; 32]);
+    assert_eq!(client.block_summary(&unknown).unwrap(), None);
+    let err = client.header_by_hash(&unknown).unwrap_err();
+    assert!(
+        matches!(err, Error::HeaderHashNotFound(hash) if hash == unknown),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn mempool_to_confirmed() {
+    let server = Server::new();
+    let client = client(&server);
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    let txid = tx.txid();
+
+    assert_eq!(client.tx(&txid).unwrap(), Some(tx.clone()));
+    assert!(!client.tx_status(&txid).unwrap().confirmed);
+    let info = client.tx_info(&txid).unwrap().unwrap();
+    assert_eq!(info.fee, Some(PAY_FEE));
+    assert_eq!(Tx::try_from(&info).unwrap(), tx);
+    assert_eq!(client.mempool().unwrap().count, 1);
+
+    let hash = server.chain().mine(1)[0];
+    let status = client.tx_status(&txid).unwrap();
+    assert!(status.confirmed);
+    assert_eq!(status.block_hash, Some(hash));
+    assert_eq!(status.block_height, Some(server.chain().height()));
+    assert_eq!(client.mempool().unwrap().count, 0);
+
+    let proof = client.merkle_proof(&txid).unwrap().unwrap();
+    assert_eq!(proof.block_height, server.chain().height());
+    assert_eq!(proof.pos, 1);
+}
+
+#[test]
+fn reorg_returns_txs_to_mempool() {
+    let server = Server::new();
+    let client = client(&server);
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let stale = server.chain().mine(1)[0];
+    assert!(client.tx_status(&txid).unwrap().confirmed);
+
+    let height = server.chain().height();
+    server.chain().reorg(1);
+    assert_eq!(client.height_uncached().unwrap(), height + 1);
+    assert!(!client.tx_status(&txid).unwrap().confirmed);
+    assert!(!client.block_status(&stale).unwrap().in_best_chain);
+    assert!(client.tx_info(&txid).unwrap().is_some());
+
+    server.chain().mine(1);
+    let status = client.tx_status(&txid).unwrap();
+    assert_eq!(status.block_height, Some(height + 2));
+}
+
+#[test]
+fn history_pages() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    server.chain().pay(&script, 20_000);
+    let client = client(&server);
+
+    // the first page holds the mempool transactions before the confirmed ones
+    let first = client.scripthash_txs(&script, None).unwrap();
+    assert_eq!(first.len(), CHAIN_TXS_PER_PAGE + 1);
+    assert!(!first[0].status.confirmed);
+    let last_seen = first.last().unwrap().txid;
+    let next = client.scripthash_txs(&script, Some(last_seen)).unwrap();
+    assert_eq!(next.len(), 5);
+    let after = client
+        .scripthash_txs(&script, Some(next.last().unwrap().txid))
+        .unwrap();
+    assert!(after.is_empty());
+
+    let all = client.scripthash_txs_all(&script).unwrap();
+    assert_eq!(all.len(), CHAIN_TXS_PER_PAGE + 6);
+    assert_eq!(
+        client.scripthash_utxo(&script).unwrap().len(),
+        CHAIN_TXS_PER_PAGE + 6
+    );
+}
+
+#[test]
+fn history_page_boundary() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..CHAIN_TXS_PER_PAGE {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    let client = client(&server);
+
+    let first = client.scripthash_txs(&script, None).unwrap();
+    assert_eq!(first.len(), CHAIN_TXS_PER_PAGE);
+    let next = client
+        .scripthash_txs(&script, Some(first.last().unwrap().txid))
+        .unwrap();
+    assert!(next.is_empty());
+    assert_eq!(
+        client.scripthash_txs_all(&script).unwrap().len(),
+        CHAIN_TXS_PER_PAGE
+    );
+}
+
+#[cfg(feature = "addresses")]
+#[test]
+fn histories_keep_the_server_order() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    let mempool = (0..10)
+        .map(|_| server.chain().pay(&script, 20_000).txid())
+        .collect::<Vec<_>>();
+    let expected = {
+        let chain = server.chain();
+        chain
+            .mempool_history(&script)
+            .into_iter()
+            .chain(chain.chain_history(&script))
+            .map(|tx| tx.txid())
+            .collect::<Vec<_>>()
+    };
+    let client = client(&server);
+
+    let all = client.scripthash_txs_all(&script).unwrap();
+    assert_eq!(all.iter().map(|tx| tx.txid).collect::<Vec<_>>(), expected);
+
+    let latest = client
+        .address_txs_latest(&FakeChain::address(1), 3)
+        .unwrap();
+    assert_eq!(
+        latest.iter().map(|tx| tx.txid).collect::<Vec<_>>(),
+        mempool.iter().rev().take(3).copied().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn broadcast_and_reject() {
+    let server = Server::new();
+    let client = client(&server);
+    let script = FakeChain::script(1);
+    let funding = server.chain().pay(&script, 10_000).txid();
+    server.chain().mine(1);
+
+    let outpoint = Outpoint::new(funding, 0);
+    let spend = server
+        .chain()
+        .spend(&[outpoint], vec![(FakeChain::script(2), 9_000)]);
+    client.broadcast(&spend).unwrap();
+    assert_eq!(server.chain().mempool().len(), 1);
+    let outspend = client.outspend(&funding, 0).unwrap().unwrap();
+    assert!(outspend.spent);
+    assert_eq!(outspend.txid, Some(spend.txid()));
+    #[allow(deprecated)]
+    {
+        assert_eq!(client.output_status(&funding, 0).unwrap(), Some(outspend));
+        let requests = server.requests().len();
+        let err = client
+            .output_status(&funding, u64::from(u32::MAX) + 1)
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::InvalidInput {
+                    field: "output index",
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+        assert_eq!(server.requests().len(), requests);
+    }
+
+    let err = client.broadcast(&spend).unwrap_err();
+    assert_eq!(err.esplora_kind(), Some(EsploraErrorKind::AlreadyKnown));
+    let conflict = server
+        .chain()
+        .spend(&[outpoint], vec![(FakeChain::script(3), 8_000)]);
+    let err = client.broadcast(&conflict).unwrap_err();
+    assert!(
+        matches!(err, Error::HttpResponse { status: 400, .. }),
+        "{:?}",
+        err
+    );
+    assert_eq!(err.esplora_kind(), Some(EsploraErrorKind::MempoolConflict));
+}
+
+#[test]
+fn block_txids_past_a_page() {
+    let server = Server::new();
+    for n in 0..40 {
+        server.chain().pay(&FakeChain::script(n), 10_000);
+    }
+    let hash = server.chain().mine(1)[0];
+    let client = client(&server);
+
+    let txids = client.block_txids(&hash).unwrap();
+    assert_eq!(txids.len(), 41);
+    assert_eq!(
+        client.txid_at_block_index(&hash, 40).unwrap(),
+        Some(txids[40])
+    );
+    assert_eq!(client.txid_at_block_index(&hash, 41).unwrap(), None);
+    let summary = client.block_summary(&hash).unwrap().unwrap();
+    assert_eq!(summary.id, hash);
+}
+
+#[test]
+fn malformed_tx_info_is_a_json_error() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.on_request(|req| {
+        if req.path.starts_with("/tx/") {
+            Some(Response::text("{\"txid\": 1"))
+        } else {
+            None
+        }
+    });
+    let client = client(&server);
+
+    let err = client.tx_info(&txid).unwrap_err();
+    assert!(matches!(err, Error::Json(_)), "{:?}", err);
+}
+
+#[test]
+fn tx_fee_rejects_overflowing_values() {
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    let txid = tx.txid();
+    let client = client(&server);
+
+    let mut json = server.chain().tx_json(&tx);
+    json.as_object_mut().unwrap().remove("fee");
+    server.on_request(move |req| {
+        if req.path == format!("/tx/{}", txid) {
+            Some(Response::json(&json))
+        } else {
+            None
+        }
+    });
+    assert_eq!(client.tx_fee(&txid).unwrap(), PAY_FEE);
+
+    let mut json = server.chain().tx_json(&tx);
+    json.as_object_mut().unwrap().remove("fee");
+    json["vout"][0]["value"] = u64::MAX.into();
+    server.on_request(move |req| {
+        if req.path == format!("/tx/{}", txid) {
+            Some(Response::json(&json))
+        } else {
+            None
+        }
+    });
+    let err = client.tx_fee(&txid).unwrap_err();
+    assert!(matches!(err, Error::InvalidServerData), "{:?}", err);
+}
+
+#[test]
+fn pagination_anomaly_keeps_partial_results() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    let client = client(&server);
+
+    // the server answers every following page with the first one again
+    let first_page = {
+        let chain = server.chain();
+        let history = chain.chain_history(&script);
+        serde_json::Value::from(
+            history[..CHAIN_TXS_PER_PAGE]
+                .iter()
+                .map(|tx| chain.tx_json(tx))
+                .collect::<Vec<_>>(),
+        )
+    };
+    server.on_request(move |req| {
+        if req.path.contains("/txs/chain/") {
+            Some(Response::json(&first_page))
+        } else {
+            None
+        }
+    });
+
+    let err = client.scripthash_txs_all(&script).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::PaginationAnomaly {
+                kind: PaginationAnomaly::RepeatedCursor,
+                pages_fetched: 2,
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+    assert_eq!(err.partial_txs().unwrap().len(), CHAIN_TXS_PER_PAGE);
+}
+
+fn validating_client(server: &Server) -> BlockingClient {
+    Builder::new(&server.url())
+        .validate_responses(true)
+        .build_blocking()
+        .unwrap()
+}
+
+fn is_inconsistent(err: &Error, expected: &str) -> bool {
+    matches!(err, Error::InconsistentBackend { check, .. } if *check == expected)
+}
+
+#[test]
+fn validation_passes_consistent_responses() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    let mut txids = vec![];
+    for _ in 0..6 {
+        txids.push(server.chain().pay(&script, 10_000).txid());
+    }
+    server.chain().mine(1);
+    let client = validating_client(&server);
+
+    for txid in &txids {
+        assert!(client.tx_status(txid).unwrap().confirmed);
+        assert!(client.merkle_proof(txid).unwrap().is_some());
+    }
+    assert_eq!(client.scripthash_utxo(&script).unwrap().len(), 6);
+}
+
+#[test]
+fn validation_rejects_inconsistent_tx_status() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(2);
+    let client = validating_client(&server);
+    assert!(client.tx_status(&txid).unwrap().confirmed);
+
+    let mut status = server.chain().status_json(&txid);
+    status["block_hash"] = server.chain().tip().hash.to_string().into();
+    server.on_request(move |req| {
+        if req.path.ends_with("/status") {
+            Some(Response::json(&status))
+        } else {
+            None
+        }
+    });
+    let err = client.tx_status(&txid).unwrap_err();
+    assert!(is_inconsistent(&err, "tx_status"), "{:?}", err);
+}
+
+#[test]
+fn validation_rejects_inconsistent_merkle_proof() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(1);
+    let validating = validating_client(&server);
+
+    let mut proof = server.chain().merkle_proof_json(&txid).unwrap();
+    proof["pos"] = 0.into();
+    server.on_request(move |req| {
+        if req.path.ends_with("/merkle-proof") {
+            Some(Response::json(&proof))
+        } else {
+            None
+        }
+    });
+    let err = validating.merkle_proof(&txid).unwrap_err();
+    assert!(is_inconsistent(&err, "merkle_proof"), "{:?}", err);
+    // without validation, the proof is returned as is
+    assert_eq!(client(&server).merkle_proof(&txid).unwrap().unwrap().pos, 0);
+}
+
+#[test]
+fn validation_rejects_inconsistent_utxos() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    server.chain().pay(&script, 10_000);
+    let client = validating_client(&server);
+
+    let mut utxos = server.chain().utxo_json(&script);
+    utxos[0]["vout"] = 2.into();
+    server.on_request(move |req| {
+        if req.path.ends_with("/utxo") {
+            Some(Response::json(&utxos))
+        } else {
+            None
+        }
+    });
+    let err = client.scripthash_utxo(&script).unwrap_err();
+    assert!(is_inconsistent(&err, "utxo"), "{:?}", err);
+}
+
+#[test]
+fn anchors_fill_in_omitted_block_hashes() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    let deep = server.chain().pay(&script, 10_000).txid();
+    let deep_hash = server.chain().mine(7)[0];
+    let shallow = server.chain().pay(&script, 20_000).txid();
+    let shallow_hash = server.chain().mine(1)[0];
+    server.omit_block_hashes(true);
+    let status = client(&server).tx_status(&deep).unwrap();
+    assert_eq!(status.block_hash, None);
+
+    let client = Builder::new(&server.url())
+        .require_anchors(true)
+        .build_blocking()
+        .unwrap();
+    server.reset_log();
+    let status = client.tx_status(&deep).unwrap();
+    assert_eq!(status.block_hash, Some(deep_hash));
+    let tx = client.tx_info(&deep).unwrap().unwrap();
+    assert_eq!(tx.status.block_hash, Some(deep_hash));
+    // the hash of a deep block is cached, and trusted to include the transaction
+    assert_eq!(server.requests_to("/block-height/"), 1);
+    assert_eq!(server.requests_to("/block/"), 0);
+
+    let status = client.tx_status(&shallow).unwrap();
+    assert_eq!(status.block_hash, Some(shallow_hash));
+    let txids = format!("/block/{}/txids", shallow_hash);
+    assert_eq!(server.requests_to(&txids), 1);
+
+    let anchored = |txid: Txid| {
+        if txid == deep {
+            deep_hash
+        } else {
+            shallow_hash
+        }
+    };
+    let txs = client.scripthash_txs(&script, None).unwrap();
+    assert_eq!(txs.len(), 2);
+    for tx in txs {
+        assert_eq!(tx.status.block_hash, Some(anchored(tx.txid)));
+    }
+    let utxos = client.scripthash_utxo(&script).unwrap();
+    assert_eq!(utxos.len(), 2);
+    for utxo in utxos {
+        assert_eq!(utxo.status.block_hash, Some(anchored(utxo.txid)));
+    }
+    let (proof, hash) = client.merkle_proof_with_block(&shallow).unwrap().unwrap();
+    assert_eq!(proof.block_height, 8);
+    assert_eq!(hash, shallow_hash);
+}
+
+#[test]
+fn anchors_detect_reorganized_blocks() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(1);
+    // the transaction goes back to the mempool while a lagging server still reports it
+    // confirmed in the stale block
+    server.chain().reorg(1);
+    let stale = serde_json::json!({ "confirmed": true, "block_height": 1 });
+    server.on_request(move |req| {
+        if req.path.ends_with("/status") {
+            Some(Response::json(&stale))
+        } else {
+            None
+        }
+    });
+    let client = Builder::new(&server.url())
+        .require_anchors(true)
+        .build_blocking()
+        .unwrap();
+
+    let err = client.tx_status(&txid).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::InconsistentBackend {
+                check: "anchor",
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn spendable_utxos_require_an_input_weight() {
+    let server = Server::new();
+    let script = FakeChain::script(2);
+    server.chain().pay(&script, 10_000);
+    server.chain().pay(&script, 30);
+    server.chain().mine(1);
+    server.chain().pay(&script, 5_000);
+    let client = client(&server);
+
+    let set = client
+        .spendable_utxos(&script, 1.0, SpendableOpts::default())
+        .unwrap();
+    assert_eq!(set.utxos.len(), 1);
+    assert_eq!(set.utxos[0].effective_value, 10_000 - 68);
+    assert_eq!((set.uneconomical, set.uneconomical_value), (1, 30));
+    assert_eq!(set.unconfirmed, 1);
+
+    let p2wsh = ScriptPubkey::p2wsh([3u8; 32]);
+    let err = client
+        .spendable_utxos(&p2wsh, 1.0, SpendableOpts::default())
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::InvalidInput {
+                field: "input_weight",
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+    let opts = SpendableOpts {
+        input_weight: Some(400),
+        ..SpendableOpts::default()
+    };
+    assert!(client
+        .spendable_utxos(&p2wsh, 1.0, opts)
+        .unwrap()
+        .utxos
+        .is_empty());
+}
+
+#[test]
+fn secondary_fee_server_url_is_normalized() {
+    let server = Server::new();
+    let secondary = Server::new();
+    secondary
+        .chain()
+        .set_fee_estimates(&[("1", 20.0), ("6", 5.0)]);
+
+    let client = Builder::new(&server.url())
+        .fee_fallback(FeeFallback::Secondary(format!("{}/ ", secondary.url())))
+        .build_blocking()
+        .unwrap();
+    let estimates = client.fee_estimates_with_source().unwrap();
+    assert_eq!(estimates.estimates.get("6"), Some(&5.0));
+    assert_eq!(secondary.requests_to("/fee-estimates"), 1);
+
+    let err = Builder::new(&server.url())
+        .fee_fallback(FeeFallback::Secondary("esplora.example".to_owned()))
+        .build_blocking()
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidBaseUrl(_)), "{:?}", err);
+}
+
+#[test]
+fn set_base_url_switches_servers_and_drops_caches() {
+    let primary = Server::new();
+    primary.chain().mine(3);
+    let standby = Server::new();
+    standby.chain().mine(5);
+    let cache = Arc::new(LruCache::default());
+    let client = Builder::new(&primary.url())
+        .cache(cache.clone())
+        .build_blocking()
+        .unwrap();
+    let clone = client.clone();
+
+    assert_eq!(client.height().unwrap(), 3);
+    let hash = client.block_hash(1).unwrap();
+    client.header_by_hash(&hash).unwrap();
+    let key = format!("{}/block/{}/header", primary.url(), hash);
+    assert!(cache.get(&key).is_some());
+
+    client.set_base_url(format!("{}/", standby.url())).unwrap();
+    assert_eq!(client.url(), primary.url());
+    assert_eq!(client.current_url(), standby.url());
+    assert!(cache.get(&key).is_none());
+
+    // the cached tip is dropped even though it is still fresh
+    primary.reset_log();
+    assert_eq!(clone.height().unwrap(), 5);
+    assert_eq!(
+        clone.block_hash(1).unwrap(),
+        standby.chain().block_at(1).unwrap().hash
+    );
+    assert!(primary.requests().is_empty());
+
+    let err = client.set_base_url("ftp://standby".to_owned()).unwrap_err();
+    assert!(matches!(err, Error::InvalidBaseUrl(_)), "{:?}", err);
+    assert_eq!(client.current_url(), standby.url());
+}
+
+#[test]
+fn retried_requests_are_charged_on_each_attempt() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let busy = Arc::new(AtomicBool::new(true));
+    let hook_busy = busy.clone();
+    server.on_request(move |req| {
+        if req.path.ends_with("/raw") && hook_busy.swap(false, Ordering::SeqCst) {
+            return Some(Response::error(503, "busy"));
+        }
+        None
+    });
+    let client = Builder::new(&server.url())
+        .max_retries(1)
+        .base_backoff(Duration::from_millis(1))
+        .build_blocking()
+        .unwrap();
+
+    let bytes = client.tx_raw_bytes(&txid).unwrap().unwrap();
+    assert_eq!(
+        client.bandwidth_used().tx,
+        (bytes.len() + "busy".len()) as u64
+    );
+}
+
+#[test]
+fn bandwidth_budget_stops_paged_sync() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    for _ in 0..2 * CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&script, 10_000);
+    }
+    server.chain().mine(1);
+    let unbudgeted = client(&server);
+    unbudgeted.scripthash_txs(&script, None).unwrap();
+    let first_page = unbudgeted.bandwidth_used().history;
+
+    server.reset_log();
+    let client = Builder::new(&server.url())
+        .bandwidth_budget(BandwidthBudget::default().per_operation(first_page + 1))
+        .build_blocking()
+        .unwrap();
+    let err = client.scripthash_txs_all(&script).unwrap_err();
+    assert!(
+        matches!(err, Error::BandwidthExceeded { used, budget }
+            if used > first_page && budget == first_page + 1),
+        "{:?}",
+        err
+    );
+    assert_eq!(server.requests_to("/scripthash"), 2);
+
+    client.start_bandwidth_operation();
+    assert!(client.scripthash_txs(&script, None).is_ok());
+}
+
+#[test]
+fn retries_are_opt_in() {
+    let server = Server::new();
+    server.on_request(|req| {
+        if req.path == "/blocks/tip/height" {
+            return Some(Response::error(503, "busy"));
+        }
+        None
+    });
+
+    let err = client(&server).height_uncached().unwrap_err();
+    assert!(
+        matches!(err, Error::HttpResponse { status: 503, .. }),
+        "{:?}",
+        err
+    );
+    assert_eq!(server.requests_to("/blocks/tip/height"), 1);
+
+    server.reset_log();
+    let retrying = Builder::new(&server.url())
+        .max_retries(2)
+        .base_backoff(Duration::from_millis(1))
+        .build_blocking()
+        .unwrap();
+    assert!(retrying.height_uncached().is_err());
+    assert_eq!(server.requests_to("/blocks/tip/height"), 3);
+}
+
+#[test]
+fn retry_predicate_peeks_at_the_body() {
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    server.chain().mine(1);
+    let recovered = AtomicBool::new(false);
+    server.on_request(move |req| {
+        if req.path.ends_with("/status") && !recovered.swap(true, Ordering::SeqCst) {
+            return Some(Response::text("backend unavailable"));
+        }
+        None
+    });
+    let client = Builder::new(&server.url())
+        .max_retries(2)
+        .base_backoff(Duration::from_millis(1))
+        .retry_if(Arc::new(|context| {
+            context.body == Some(&b"backend unavailable"[..])
+        }))
+        .build_blocking()
+        .unwrap();
+
+    let status = client.tx_status(&tx.txid()).unwrap();
+    assert!(status.confirmed);
+    assert_eq!(server.requests_to("/tx/"), 2);
+}
+
+#[test]
+fn redirects_follow_the_policy() {
+    let server = Server::new();
+    let other = Server::new();
+    other.chain().mine(4);
+    let target = format!("{}/blocks/tip/height", other.url());
+    server.on_request(move |req| {
+        if req.path == "/blocks/tip/height" {
+            return Some(Response::new(302, "text/plain", "").header("Location", &target));
+        }
+        None
+    });
+
+    let err = client(&server).height_uncached().unwrap_err();
+    assert!(matches!(err, Error::RedirectBlocked { .. }), "{:?}", err);
+    let following = Builder::new(&server.url())
+        .redirect_policy(RedirectPolicy::Limited(1))
+        .build_blocking()
+        .unwrap();
+    assert_eq!(following.height_uncached().unwrap(), 4);
+}
+
+#[test]
+fn broadcast_follows_redirects() {
+    let server = Server::new();
+    let funding = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(1);
+    let spend = server.chain().spend(
+        &[Outpoint::new(funding, 0)],
+        vec![(FakeChain::script(2), 9_000)],
+    );
+    let redirected = AtomicBool::new(false);
+    server.on_request(move |req| {
+        if req.method == "POST" && !redirected.swap(true, Ordering::SeqCst) {
+            return Some(Response::new(307, "text/plain", "").header("Location", "/tx"));
+        }
+        None
+    });
+
+    client(&server).broadcast(&spend).unwrap();
+    assert_eq!(server.requests_to("/tx"), 2);
+    assert_eq!(server.chain().mempool().len(), 1);
+}
+
+#[test]
+fn chain_work_checks_header_linkage() {
+    let server = Server::new();
+    server.chain().mine(5);
+    let client = client(&server);
+    let headers = (1..=4)
+        .map(|height| server.chain().block_at(height).unwrap().header)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        client.chain_work_between(1, 4).unwrap(),
+        esplora::headers::cumulative_work(&headers)
+    );
+
+    let stale = server.chain().block_at(1).unwrap().hash;
+    server.on_request(move |req| {
+        if req.path == "/block-height/3" {
+            return Some(Response::text(stale.to_string()));
+        }
+        None
+    });
+    let err = client.chain_work_between(1, 4).unwrap_err();
+    assert!(is_inconsistent(&err, "header_chain"), "{:?}", err);
+}
+
+#[cfg(feature = "addresses")]
+#[test]
+fn string_inputs_are_validated_before_requests() {
+    let server = Server::new();
+    let address = FakeChain::address(1);
+    server.chain().pay(&FakeChain::script(1), 10_000);
+    let client = Builder::new(&server.url())
+        .network(AddressNetwork::Regtest)
+        .build_blocking()
+        .unwrap();
+    let is_invalid = |err: &Error, expected: &str| matches!(err, Error::InvalidInput { field, .. } if *field == expected);
+
+    let txid = "4d2f1e7f2a8f6e8d1c0b3a29f8e7d6c5b4a3928170f6e5d4c3b2a19080706050";
+    for malformed in [
+        "",
+        "xyz",
+        &txid[1..],
+        &txid.replace('4', "g"),
+        &format!("{}00", txid),
+    ] {
+        let err = client.tx_by_str(malformed).unwrap_err();
+        assert!(is_invalid(&err, "txid"), "{}: {:?}", malformed, err);
+    }
+    let err = client.header_by_hash_str(&txid[..60]).unwrap_err();
+    assert!(is_invalid(&err, "block hash"), "{:?}", err);
+
+    let malformed = [
+        // mainnet and testnet addresses, of another network than the client
+        "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+        "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+        "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2",
+        // bad checksum
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt081",
+        // truncated
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7",
+        "not an address",
+    ];
+    for malformed in malformed.iter() {
+        let err = client.address_stats_str(malformed).expect_err(malformed);
+        assert!(is_invalid(&err, "address"), "{}: {:?}", malformed, err);
+    }
+    let err = client
+        .address_txs_confirmed_str(&address.to_string(), Some("00"))
+        .unwrap_err();
+    assert!(is_invalid(&err, "last seen txid"), "{:?}", err);
+    assert!(server.requests().is_empty());
+
+    let stats = client.address_stats_str(&address.to_string()).unwrap();
+    assert_eq!(stats.mempool_stats.tx_count, 1);
+    // regtest shares the base58 prefixes of testnet
+    client
+        .address_stats_str("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn")
+        .unwrap();
+}
+
+#[test]
+fn oversized_error_bodies_are_classified() {
+    let server = Server::new();
+    let mut body = "Block not found".to_owned();
+    body.push_str(&" ".repeat(20 * 1_024 * 1_024));
+    server.on_request(move |req| {
+        if req.path.ends_with("/status") {
+            return Some(Response::error(404, &body));
+        }
+        None
+    });
+
+    let unknown = BlockHash::from_byte_array([7u8; 32]);
+    let err = client(&server).block_status(&unknown).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::HttpResponse {
+                status: 404,
+                kind: EsploraErrorKind::BlockNotFound,
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn warm_up_skips_and_leaves_late_resources_uncached() {
+    let server = Server::new();
+    server.chain().mine(3);
+    server.chain().set_fee_estimates(&[("1", 20.0), ("6", 5.0)]);
+    let slow = Arc::new(AtomicBool::new(true));
+    let hook_slow = slow.clone();
+    server.on_request(move |req| {
+        if req.path == "/fee-estimates" && hook_slow.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(600));
+        }
+        None
+    });
+    let client = Builder::new(&server.url())
+        .prefetch(PrefetchSet {
+            tip_height: true,
+            fee_estimates: true,
+            ..PrefetchSet::default()
+        })
+        .warm_up_budget(Duration::from_millis(200))
+        .build_blocking()
+        .unwrap();
+
+    let start = Instant::now();
+    let report = client.warm_up();
+    assert!(start.elapsed() < Duration::from_millis(500));
+    assert_eq!(report.succeeded, vec![PrefetchItem::TipHeight]);
+    assert_eq!(report.skipped, vec![PrefetchItem::FeeEstimates]);
+    assert!(!report.is_complete());
+
+    // the server answers the skipped request after the budget ran out
+    std::thread::sleep(Duration::from_millis(800));
+    slow.store(false, Ordering::SeqCst);
+    assert_eq!(client.height().unwrap(), 3);
+    assert_eq!(server.requests_to("/blocks/tip/height"), 1);
+    let estimates = client.fee_estimates().unwrap();
+    assert_eq!(estimates.get("6"), Some(&5.0));
+    assert_eq!(server.requests_to("/fee-estimates"), 2);
+}
+
+#[test]
+fn shutdown_ends_watchers_and_refuses_requests() {
+    let server = Server::new();
+    server.chain().mine(1);
+    let client = client(&server);
+    let watcher = client.clone();
+    let script = FakeChain::script(1);
+
+    let filter = WatchFilter::new().script(script.clone());
+    let watch = thread::spawn(move || {
+        watcher
+            .watch_chain(filter, Duration::from_millis(10))
+            .collect::<Vec<_>>()
+    });
+    thread::sleep(Duration::from_millis(50));
+    server.chain().pay(&script, 10_000);
+    server.chain().mine(1);
+    thread::sleep(Duration::from_millis(50));
+    server.set_delay(Duration::from_millis(100));
+    let start = Instant::now();
+    assert!(client.shutdown_blocking());
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    let watched = watch.join().unwrap();
+    assert_eq!(watched.len(), 1);
+    assert_eq!(watched[0].as_ref().unwrap().matches.len(), 1);
+    assert!(matches!(client.height_uncached(), Err(Error::ClientClosed)));
+    assert!(client.shutdown_blocking());
+}
+
+#[cfg(feature = "addresses")]
+#[test]
+fn history_resumes_from_an_encoded_cursor() {
+    let server = Server::new();
+    let address = FakeChain::address(1);
+    for _ in 0..CHAIN_TXS_PER_PAGE + 5 {
+        server.chain().pay(&FakeChain::script(1), 10_000);
+    }
+    server.chain().mine(1);
+    server.chain().pay(&FakeChain::script(1), 20_000);
+    let client = client(&server);
+    let key = CursorKey::new([9u8; 32]);
+
+    let (first, cursor) = client.address_txs_cursor(&address, None).unwrap();
+    assert_eq!(first.len(), CHAIN_TXS_PER_PAGE + 1);
+    let cursor = cursor.unwrap();
+    assert_eq!(cursor.tip, server.chain().tip().hash);
+    let encoded = cursor.encode(&key);
+
+    // resuming from the string handed out, as another process of the service would
+    let resumed = Cursor::decode(&encoded, &key).unwrap();
+    let (next, cursor) = client
+        .address_txs_confirmed_cursor(&address, Some(&resumed))
+        .unwrap();
+    assert_eq!(next.len(), 5);
+    assert_eq!(cursor, None);
+    let mut txids = first
+        .iter()
+        .chain(&next)
+        .map(|tx| tx.txid)
+        .collect::<Vec<_>>();
+    txids.sort();
+    txids.dedup();
+    assert_eq!(txids.len(), CHAIN_TXS_PER_PAGE + 6);
+
+    let (confirmed, _) = client
+        .scripthash_txs_confirmed_cursor(&FakeChain::script(1), None)
+        .unwrap();
+    assert!(confirmed.iter().all(|tx| tx.status.confirmed));
+    let err = client
+        .scripthash_txs_cursor(&FakeChain::script(1), Some(&resumed))
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidCursor(_)), "{:?}", err);
+    let mut tampered = encoded.into_bytes();
+    tampered[10] = if tampered[10] == b'A' { b'B' } else { b'A' };
+    let err = Cursor::decode(&String::from_utf8(tampered).unwrap(), &key).unwrap_err();
+    assert!(matches!(err, Error::InvalidCursor(_)), "{:?}", err);
+}
+
+#[cfg(feature = "chaos")]
+#[test]
+fn chaos_client_without_faults_passes_through() {
+    use esplora::chaos::{ChaosClient, ChaosConfig};
+
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    let funding = server.chain().pay(&script, 10_000);
+    let hash = server.chain().mine(1)[0];
+    let pending = server.chain().pay(&script, 20_000).txid();
+    let client = client(&server);
+    let chaos = ChaosClient::new(client.clone(), ChaosConfig::new(7));
+    let txid = funding.txid();
+
+    assert_eq!(chaos.tx(&txid).unwrap(), client.tx(&txid).unwrap());
+    assert_eq!(
+        chaos.tx_raw_bytes(&txid).unwrap(),
+        client.tx_raw_bytes(&txid).unwrap()
+    );
+    assert_eq!(
+        chaos.tx_info(&txid).unwrap(),
+        client.tx_info(&txid).unwrap()
+    );
+    assert_eq!(
+        chaos.txid_at_block_index(&hash, 1).unwrap(),
+        client.txid_at_block_index(&hash, 1).unwrap()
+    );
+    assert_eq!(
+        chaos.tx_status(&txid).unwrap(),
+        client.tx_status(&txid).unwrap()
+    );
+    assert_eq!(
+        chaos.merkle_proof(&txid).unwrap(),
+        client.merkle_proof(&txid).unwrap()
+    );
+    assert_eq!(
+        chaos.outspend(&txid, 1).unwrap(),
+        client.outspend(&txid, 1).unwrap()
+    );
+    assert_eq!(
+        chaos.header_by_hash(&hash).unwrap(),
+        client.header_by_hash(&hash).unwrap()
+    );
+    assert_eq!(
+        chaos.block_status(&hash).unwrap(),
+        client.block_status(&hash).unwrap()
+    );
+    assert_eq!(
+        chaos.block_summary(&hash).unwrap(),
+        client.block_summary(&hash).unwrap()
+    );
+    assert_eq!(
+        chaos.block_txids(&hash).unwrap(),
+        client.block_txids(&hash).unwrap()
+    );
+    assert_eq!(chaos.height().unwrap(), client.height().unwrap());
+    assert_eq!(chaos.tip_hash().unwrap(), client.tip_hash().unwrap());
+    assert_eq!(chaos.block_hash(1).unwrap(), client.block_hash(1).unwrap());
+    assert_eq!(
+        chaos.scripthash_txs(&script, None).unwrap(),
+        client.scripthash_txs(&script, None).unwrap()
+    );
+    assert_eq!(
+        chaos.scripthash_mempool_txs(&script).unwrap(),
+        client.scripthash_mempool_txs(&script).unwrap()
+    );
+    assert_eq!(
+        chaos.scripthash_utxo(&script).unwrap(),
+        client.scripthash_utxo(&script).unwrap()
+    );
+    assert_eq!(
+        chaos.fee_estimates().unwrap(),
+        client.fee_estimates().unwrap()
+    );
+    assert_eq!(chaos.blocks(None).unwrap(), client.blocks(None).unwrap());
+    assert_eq!(
+        chaos.tx_confirmations(&txid).unwrap(),
+        client.tx_confirmations(&txid).unwrap()
+    );
+    assert_eq!(
+        chaos.tx_fee(&pending).unwrap(),
+        client.tx_fee(&pending).unwrap()
+    );
+    assert_eq!(
+        chaos.scripthash_txs_all(&script).unwrap(),
+        client.scripthash_txs_all(&script).unwrap()
+    );
+    assert_eq!(
+        chaos.backend_info().unwrap(),
+        client.backend_info().unwrap()
+    );
+    let unknown = Txid::from_byte_array([7u8; 32]);
+    assert_eq!(chaos.tx(&unknown).unwrap(), None);
+
+    let spend = server.chain().spend(
+        &[Outpoint::new(pending, 0)],
+        vec![(FakeChain::script(2), 19_000)],
+    );
+    chaos.broadcast(&spend).unwrap();
+    assert_eq!(server.chain().mempool().len(), 2);
+    let stats = chaos.stats();
+    assert_eq!((stats.failures, stats.delays, stats.garbled), (0, 0, 0));
+}
+
+#[cfg(feature = "chaos")]
+#[test]
+fn chaos_client_forces_unknown_transactions() {
+    use esplora::chaos::{ChaosClient, ChaosConfig};
+
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    server.chain().mine(1);
+    let client = client(&server);
+    let txid = tx.txid();
+    let chaos = ChaosClient::new(client.clone(), ChaosConfig::new(7).not_found(txid));
+    let unknown = Txid::from_byte_array([7u8; 32]);
+    server.reset_log();
+
+    // the same results as the client for a transaction the server doesn't know
+    assert_eq!(chaos.tx(&txid).unwrap(), None);
+    assert_eq!(client.tx(&unknown).unwrap(), None);
+    let status = chaos.tx_status(&txid).unwrap();
+    assert_eq!(status, client.tx_status(&unknown).unwrap());
+    assert!(!status.confirmed);
+    assert_eq!(chaos.tx_confirmations(&txid).unwrap(), 0);
+    let err = chaos.tx_fee(&txid).unwrap_err();
+    assert!(
+        matches!(err, Error::TransactionNotFound(id) if id == txid),
+        "{:?}",
+        err
+    );
+    let err = client.tx_fee(&unknown).unwrap_err();
+    assert!(
+        matches!(err, Error::TransactionNotFound(id) if id == unknown),
+        "{:?}",
+        err
+    );
+
+    assert_eq!(chaos.stats().not_found, 4);
+    assert_eq!(server.requests_to(&format!("/tx/{}", txid)), 0);
+}
+
+#[cfg(feature = "chaos")]
+#[test]
+fn chaos_client_garbles_response_bodies() {
+    use esplora::chaos::{ChaosClient, ChaosConfig};
+
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    server.chain().mine(1);
+    let client = client(&server);
+    let chaos = ChaosClient::new(client.clone(), ChaosConfig::new(7).garble_rate(1.0));
+    let txid = tx.txid();
+
+    let raw = client.tx_raw_bytes(&txid).unwrap().unwrap();
+    let mut failures = 0;
+    for _ in 0..20 {
+        assert_ne!(chaos.tx_raw_bytes(&txid).unwrap().unwrap(), raw);
+        if chaos.tx_status(&txid).is_err() {
+            failures += 1;
+        }
+    }
+    assert!(failures > 0);
+    assert_eq!(chaos.stats().garbled, 40);
+    // the bodies were fetched from the server rather than simulated
+    assert_eq!(server.requests_to(&format!("/tx/{}/raw", txid)), 21);
+
+    // only the responses of single GET requests are garbled
+    assert_eq!(chaos.tx_fee(&txid).unwrap(), PAY_FEE);
+    assert_eq!(chaos.stats().garbled, 40);
+}
+
+#[test]
+fn balanced_reads_skip_failing_replicas() {
+    let servers = [Server::new(), Server::new(), Server::new()];
+    servers[1].on_request(|request| {
+        (request.method == "GET").then(|| Response::error(503, "unavailable"))
+    });
+    let replicas = servers.iter().map(client).collect::<Vec<_>>();
+    let client = BalancedClient::new(replicas, LoadBalancingPolicy::RoundRobin);
+    let script = FakeChain::script(1);
+
+    let mut failures = 0;
+    for _ in 0..12 {
+        if client.scripthash_mempool_txs(&script).is_err() {
+            failures += 1;
+        }
+    }
+    assert_eq!(failures, 3);
+    assert_eq!(servers[1].requests_to("/scripthash"), 3);
+    assert_eq!(
+        servers[0].requests_to("/scripthash") + servers[2].requests_to("/scripthash"),
+        9
+    );
+    let stats = client.stats();
+    assert!(stats[0].healthy && !stats[1].healthy && stats[2].healthy);
+    assert_eq!(stats[1].failures, 3);
+
+    // a transaction unknown to a replica doesn't make it unhealthy
+    let unknown = Txid::from_byte_array([7u8; 32]);
+    for _ in 0..6 {
+        assert_eq!(
+            client.tx_fee(&unknown).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+    assert!(client
+        .stats()
+        .iter()
+        .enumerate()
+        .all(|(index, stats)| stats.healthy == (index != 1)));
+}
+
+#[test]
+fn balanced_reads_prefer_fast_replicas() {
+    let servers = [Server::new(), Server::new()];
+    servers[0].set_delay(Duration::from_millis(50));
+    let replicas = servers.iter().map(client).collect::<Vec<_>>();
+    let client = BalancedClient::new(replicas, LoadBalancingPolicy::LowestLatency);
+    let script = FakeChain::script(1);
+
+    for _ in 0..LATENCY_PROBE_INTERVAL {
+        client.scripthash_mempool_txs(&script).unwrap();
+    }
+    // each replica is measured, then only the fast one is used until the slow one is probed
+    assert_eq!(servers[0].requests_to("/scripthash"), 2);
+    assert_eq!(
+        servers[1].requests_to("/scripthash"),
+        LATENCY_PROBE_INTERVAL - 2
+    );
+    let stats = client.stats();
+    assert!(stats[0].latency > stats[1].latency);
+}
+
+#[test]
+fn balanced_broadcast_reports_every_rejection() {
+    let servers = [Server::new(), Server::new()];
+    for server in &servers {
+        server.on_request(|request| {
+            (request.method == "POST").then(|| Response::error(400, "txn-mempool-conflict"))
+        });
+    }
+    let script = FakeChain::script(1);
+    let funding = servers[0].chain().pay(&script, 10_000).txid();
+    let spend = servers[0].chain().spend(
+        &[Outpoint::new(funding, 0)],
+        vec![(FakeChain::script(2), 9_000)],
+    );
+    let replicas = servers.iter().map(client).collect::<Vec<_>>();
+    let client =
+        BalancedClient::new(replicas, LoadBalancingPolicy::RoundRobin).fan_out_broadcast(true);
+
+    for _ in 0..UNHEALTHY_AFTER_FAILURES {
+        match client.broadcast(&spend).unwrap_err() {
+            Error::BroadcastRejected(report) => {
+                assert_eq!(report.accepted(), 0);
+                let errors = report.errors().collect::<Vec<_>>();
+                assert_eq!(errors.len(), 2);
+                assert!(errors.iter().all(|(_, error)| error.status() == Some(400)));
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+    assert!(client.stats().iter().all(|stats| stats.healthy));
+
+    let txid = spend.txid();
+    servers[1].on_request(move |request| (request.method == "POST").then(|| Response::text(txid)));
+    let report = client.broadcast(&spend).unwrap();
+    assert_eq!(report.accepted(), 1);
+    assert!(report.results[1].1.is_ok());
+}
+
+#[test]
+fn merkle_proofs_verify_against_the_block_header() {
+    let server = Server::new();
+    let txids = (1..=3)
+        .map(|n| server.chain().pay(&FakeChain::script(n), 10_000).txid())
+        .collect::<Vec<_>>();
+    server.chain().mine(3);
+    let client = client(&server);
+
+    for txid in txids {
+        let (proof, hash) = client.merkle_proof_with_block(&txid).unwrap().unwrap();
+        assert_eq!(hash, server.chain().block_at(1).unwrap().hash);
+        let header = client.header_by_hash(&hash).unwrap();
+        assert_eq!(proof.merkle_root(&txid), header.merkle_root.to_byte_array());
+    }
+    // the hash isn't part of the proof, it is the hash of the block at the proof height, not
+    // cached while the block is shallow
+    assert_eq!(server.requests_to("/block-height/1"), 3);
+}
+
+#[test]
+fn checkpoints_report_the_exact_fork() {
+    let server = Server::new();
+    server.chain().mine(20);
+    let client = client(&server);
+    let mut checkpoints = Checkpoints::new(8, 5);
+
+    for height in [20, 25, 30].iter() {
+        let mined = server.chain().height();
+        server.chain().mine(*height - mined);
+        let update = client.update_checkpoints(&mut checkpoints).unwrap();
+        assert!(
+            matches!(update, CheckpointUpdate::Added { height: added, .. } if added == *height),
+            "{:?}",
+            update
+        );
+    }
+    assert_eq!(
+        client.update_checkpoints(&mut checkpoints).unwrap(),
+        CheckpointUpdate::Unchanged
+    );
+
+    // 1-deep rollback of the checkpoint at the tip: the fork is right below it
+    let stale = server.chain().tip().hash;
+    server.chain().reorg(1);
+    assert_eq!(
+        client.update_checkpoints(&mut checkpoints).unwrap(),
+        CheckpointUpdate::Reorg {
+            fork_height: 29,
+            dropped: vec![(30, stale)],
+        }
+    );
+    assert_eq!(checkpoints.latest().map(|(height, _)| height), Some(25));
+
+    // 3-deep rollback: the fork is between the checkpoints at 25 and 31
+    let update = client.update_checkpoints(&mut checkpoints).unwrap();
+    assert!(
+        matches!(update, CheckpointUpdate::Added { height: 31, .. }),
+        "{:?}",
+        update
+    );
+    let stale = server.chain().tip().hash;
+    server.chain().reorg(3);
+    assert_eq!(
+        client.update_checkpoints(&mut checkpoints).unwrap(),
+        CheckpointUpdate::Reorg {
+            fork_height: 28,
+            dropped: vec![(31, stale)],
+        }
+    );
+    let hash = server.chain().block_at(25).unwrap().hash;
+    assert_eq!(checkpoints.latest(), Some((25, hash)));
+}
+
+#[test]
+fn block_walks_stop_at_genesis() {
+    let server = Server::new();
+    server.chain().mine(24);
+    let client = client(&server);
+
+    let summaries = client.blocks_before(20, usize::MAX).unwrap();
+    assert_eq!(
+        summaries
+            .iter()
+            .map(|summary| summary.time.height)
+            .collect::<Vec<_>>(),
+        (0..20).rev().collect::<Vec<_>>()
+    );
+    assert_eq!(client.blocks_before(20, 3).unwrap().len(), 3);
+    assert!(client.blocks_before(0, usize::MAX).unwrap().is_empty());
+    assert!(client.average_block_interval(usize::MAX).is_ok());
+}
+
+#[cfg(feature = "addresses")]
+#[test]
+fn verified_balances_are_checked_again_across_blocks() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    server.chain().pay(&script, 10_000);
+    server.chain().mine(1);
+    server.chain().pay(&script, 5_000);
+    let client = client(&server);
+    let address = FakeChain::address(1);
+
+    let balance = client.verified_balance(&address).unwrap();
+    assert!(balance.verified);
+    assert_eq!(balance.balance, 15_000);
+    assert_eq!(balance.tip, server.chain().tip().hash);
+
+    // the first check sees a stale tip and misses the unconfirmed output
+    let tip_hashes = Arc::new(AtomicUsize::new(0));
+    let utxo_lists = Arc::new(AtomicUsize::new(0));
+    server.on_request(move |request| {
+        if request.path == "/blocks/tip/hash" && tip_hashes.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Some(Response::text(BlockHash::from_byte_array([7u8; 32])));
+        }
+        if request.path.ends_with("/utxo") && utxo_lists.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Some(Response::json(&serde_json::json!([])));
+        }
+        None
+    });
+    server.reset_log();
+    let balance = client.verified_balance(&address).unwrap();
+    assert!(balance.verified);
+    assert_eq!(balance.discrepancy, None);
+    assert_eq!(server.requests_to("/blocks/tip/hash"), 4);
+
+    // a discrepancy at the same tip is reported without checking again
+    server.on_request(|request| {
+        request
+            .path
+            .ends_with("/utxo")
+            .then(|| Response::json(&serde_json::json!([])))
+    });
+    server.reset_log();
+    let balance = client.verified_balance(&address).unwrap();
+    assert!(!balance.verified);
+    assert_eq!(balance.discrepancy, Some(-15_000));
+    assert_eq!(server.requests_to("/blocks/tip/hash"), 2);
+}
+
+#[test]
+fn accept_language_is_sent() {
+    let server = Server::new();
+    let client = Builder::new(&server.url())
+        .accept_language("fr-CH, fr;q=0.9")
+        .unwrap()
+        .build_blocking()
+        .unwrap();
+    client.height_uncached().unwrap();
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].header("Accept-Language"),
+        Some("fr-CH, fr;q=0.9")
+    );
+
+    let mut builder = Builder::new(&server.url());
+    builder.accept_language = Some("fr_CH".to_owned());
+    assert!(matches!(
+        builder.build_blocking(),
+        Err(Error::InvalidHttpHeaderValue(_))
+    ));
+}
+
+/// Ids sent in the `X-Request-Id` header of the requests received by `server`.
+fn request_ids(server: &Server) -> Vec<Option<String>> {
+    server
+        .requests()
+        .iter()
+        .map(|request| request.header("X-Request-Id").map(str::to_owned))
+        .collect()
+}
+
+#[test]
+fn request_ids_are_kept_across_retries() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let failed = Arc::new(AtomicBool::new(false));
+    let hook_failed = failed.clone();
+    server.on_request(move |request| {
+        (request.path.ends_with("/raw") && !hook_failed.swap(true, Ordering::SeqCst))
+            .then(|| Response::error(503, "busy"))
+    });
+    let client = Builder::new(&server.url())
+        .max_retries(2)
+        .base_backoff(Duration::from_millis(1))
+        .build_blocking()
+        .unwrap();
+    client.tx_raw_bytes(&txid).unwrap().unwrap();
+    client.tx_raw_bytes(&txid).unwrap().unwrap();
+
+    let ids = request_ids(&server);
+    assert_eq!(ids.len(), 3);
+    assert!(ids[0].is_some());
+    assert_eq!(ids[0], ids[1]);
+    assert_ne!(ids[1], ids[2]);
+}
+
+#[test]
+fn request_ids_are_reported_with_errors() {
+    let server = Server::new();
+    server.chain().mine(1);
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.on_request(|request| {
+        (request.path != "/blocks/tip/height").then(|| Response::error(500, "boom"))
+    });
+    let client = Builder::new(&server.url())
+        .request_id(RequestIdPolicy::Prefixed("wallet".to_owned()))
+        .build_blocking()
+        .unwrap();
+
+    let tip = server.chain().tip().hash;
+    let errors = [
+        client.tx_raw_bytes(&txid).unwrap_err(),
+        client.block_hash(1).unwrap_err(),
+        client.header_hex(&tip).unwrap_err(),
+        client.scripthash_utxo(&FakeChain::script(1)).unwrap_err(),
+    ];
+    let ids = request_ids(&server);
+    assert_eq!(ids.len(), errors.len());
+    for (error, id) in errors.iter().zip(&ids) {
+        assert_eq!(error.status(), Some(500));
+        assert_eq!(error.request_id(), id.as_deref());
+    }
+    assert_eq!(ids[0].as_deref(), Some("wallet-0"));
+    assert_eq!(ids[3].as_deref(), Some("wallet-3"));
+
+    server.reset_log();
+    let client = Builder::new(&server.url())
+        .request_id(RequestIdPolicy::Disabled)
+        .build_blocking()
+        .unwrap();
+    let error = client.block_hash(1).unwrap_err();
+    assert_eq!(error.request_id(), None);
+    assert_eq!(request_ids(&server), [None]);
+    client.height_uncached().unwrap();
+}
+
+fn request_paths(server: &Server) -> Vec<String> {
+    server
+        .requests()
+        .into_iter()
+        .map(|request| request.path)
+        .collect()
+}
+
+#[test]
+fn encodings_are_converted_from_the_cheapest_endpoint() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    server.chain().mine(100);
+    let client = client(&server);
+
+    server.reset_log();
+    let hex = client.tx_hex(&txid).unwrap().unwrap();
+    assert_eq!(request_paths(&server), [format!("/tx/{}/raw", txid)]);
+    let raw = client.tx_raw_bytes(&txid).unwrap().unwrap();
+    assert_eq!(hex, raw.to_hex());
+
+    let hash = server.chain().block_at(1).unwrap().hash;
+    server.reset_log();
+    let raw = client.header_raw_bytes(&hash).unwrap();
+    assert_eq!(request_paths(&server), [format!("/block/{}/header", hash)]);
+    assert_eq!(raw.len(), 80);
+    assert_eq!(client.header_hex(&hash).unwrap(), raw.to_hex());
+
+    // a batch of 100 headers costs 160 bytes each, less than the raw blocks even with a
+    // single coinbase transaction
+    let client = self::client(&server);
+    let hashes = (1..=100)
+        .map(|height| server.chain().block_at(height).unwrap().hash)
+        .collect::<Vec<_>>();
+    for hash in &hashes {
+        assert_eq!(client.header_raw_bytes(hash).unwrap().len(), 80);
+    }
+    assert_eq!(client.bytes_downloaded(), 100 * 160);
+    let raw_blocks = hashes
+        .iter()
+        .map(|hash| server.chain().block(hash).unwrap().serialize().len() as u64)
+        .sum::<u64>();
+    assert!(raw_blocks > 100 * 160, "{}", raw_blocks);
+}
+
+#[test]
+fn binary_bodies_of_text_endpoints_are_reported() {
+    let server = Server::new();
+    server.chain().mine(1);
+    let hash = server.chain().block_at(1).unwrap().hash;
+    server.on_request(|request| match request.path.as_str() {
+        "/blocks/tip/height" | "/block-height/1" => Some(Response::bytes(vec![0xff, 0xfe, 0])),
+        path if path.ends_with("/txid/0") => Some(Response::bytes(vec![0xff; 32])),
+        // the connection is closed before the announced body was sent
+        path if path.ends_with("/txid/1") => {
+            Some(Response::text("00").header("Content-Length", "64"))
+        }
+        _ => None,
+    });
+    let client = client(&server);
+    let invalid_utf8 = |err: Error| match err {
+        Error::InvalidUtf8 { path } => path,
+        err => panic!("unexpected error {:?}", err),
+    };
+
+    let err = client.height_uncached().unwrap_err();
+    assert_eq!(invalid_utf8(err), "/blocks/tip/height");
+    let err = client.block_hash(1).unwrap_err();
+    assert_eq!(invalid_utf8(err), "/block-height/1");
+    let err = client.txid_at_block_index(&hash, 0).unwrap_err();
+    assert_eq!(invalid_utf8(err), format!("/block/{}/txid/0", hash));
+
+    let err = client.txid_at_block_index(&hash, 1).unwrap_err();
+    assert!(!matches!(err, Error::InvalidUtf8 { .. }), "{:?}", err);
+}
+
+#[test]
+fn error_bodies_are_parsed() {
+    let server = Server::new();
+    let tx = server.chain().pay(&FakeChain::script(1), 10_000);
+    let sent = Arc::new(AtomicUsize::new(0));
+    let counter = sent.clone();
+    server.on_request(move |request| {
+        if request.method != "POST" {
+            return None;
+        }
+        Some(match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => Response::new(
+                400,
+                "application/json",
+                r#"{"error": "bad-txns-inputs-missingorspent", "code": -25}"#,
+            ),
+            1 => Response::error(400, "min relay fee not met"),
+            _ => Response::error(400, ""),
+        })
+    });
+    let client = client(&server);
+    let message = |err: &Error| match err {
+        Error::HttpResponse { message, .. } => message.clone(),
+        err => panic!("unexpected error {:?}", err),
+    };
+
+    let err = client.broadcast(&tx).unwrap_err();
+    assert_eq!(err.esplora_kind(), Some(EsploraErrorKind::MissingInputs));
+    assert_eq!(err.api_code(), Some(-25));
+    assert_eq!(message(&err), "bad-txns-inputs-missingorspent");
+    assert_eq!(err.details().unwrap().error, message(&err));
+
+    let err = client.broadcast(&tx).unwrap_err();
+    assert_eq!(err.esplora_kind(), Some(EsploraErrorKind::InsufficientFee));
+    assert_eq!((err.api_code(), err.details()), (None, None));
+    assert_eq!(message(&err), "min relay fee not met");
+
+    let err = client.broadcast(&tx).unwrap_err();
+    assert_eq!(err.esplora_kind(), Some(EsploraErrorKind::Other));
+    assert_eq!((err.api_code(), err.details()), (None, None));
+    assert_eq!(message(&err), "");
+    assert_eq!(sent.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn block_fee_stats_count_transactions_from_the_summary() {
+    let server = Server::new();
+    server.chain().pay(&FakeChain::script(1), 10_000);
+    server.chain().pay(&FakeChain::script(2), 20_000);
+    let hash = server.chain().mine(1)[0];
+    let client = client(&server);
+
+    let stats = client.block_fee_stats(&hash, false).unwrap();
+    assert_eq!(stats.total_fees, 2 * PAY_FEE);
+    assert!(stats.min_feerate > 0.0);
+    assert_eq!(server.requests_to(&format!("/block/{}/txids", hash)), 0);
+
+    let unknown = BlockHash::from_byte_array([7u8; 32]);
+    let err = client.block_fee_stats(&unknown, false).unwrap_err();
+    assert!(
+        matches!(err, Error::HeaderHashNotFound(hash) if hash == unknown),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn scanned_blocks_count_transactions_from_the_summary() {
+    let server = Server::new();
+    let watched = FakeChain::script(1);
+    let mut paying = vec![];
+    for n in 0..30u8 {
+        let script = if n % 10 == 0 {
+            &watched
+        } else {
+            &FakeChain::script(2)
+        };
+        let txid = server.chain().pay(script, 10_000).txid();
+        if n % 10 == 0 {
+            paying.push(txid);
+        }
+    }
+    let hash = server.chain().mine(1)[0];
+    let client = client(&server);
+
+    let filter = WatchFilter::new().script(watched);
+    let matches = client.scan_block(&hash, &filter).unwrap();
+    let mut txids = matches.iter().map(|m| m.txid).collect::<Vec<_>>();
+    txids.sort();
+    paying.sort();
+    assert_eq!(txids, paying);
+    assert_eq!(server.requests_to(&format!("/block/{}/txids", hash)), 0);
+
+    let unknown = BlockHash::from_byte_array([7u8; 32]);
+    let err = client.scan_block(&unknown, &filter).unwrap_err();
+    assert!(
+        matches!(err, Error::HeaderHashNotFound(hash) if hash == unknown),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn blocks_at_the_tip_and_past_it() {
+    let server = Server::new();
+    server.chain().pay(&FakeChain::script(1), 10_000);
+    server.chain().pay(&FakeChain::script(2), 20_000);
+    let hash = server.chain().mine(1)[0];
+    let tip = server.chain().height();
+    let size = server.chain().block(&hash).unwrap().serialize().len() as u32;
+    let client = client(&server);
+
+    let summary = client.block_at_height(tip).unwrap().unwrap();
+    assert_eq!(summary.id, hash);
+    assert_eq!(summary.time.height, tip);
+    assert_eq!(summary.tx_count, 3);
+    assert_eq!(summary.size, size);
+    assert!(client.block_at_height(tip + 1).unwrap().is_none());
+}
+
+#[test]
+fn utxo_diffs_since_a_snapshot() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    let spent = server.chain().pay(&script, 10_000).txid();
+    server.chain().pay(&script, 20_000);
+    server.chain().mine(1);
+    let pending = server.chain().pay(&script, 30_000).txid();
+    let client = client(&server);
+    let txids = |utxos: &[esplora::Utxo]| {
+        let mut txids = utxos.iter().map(|utxo| utxo.txid).collect::<Vec<_>>();
+        txids.sort();
+        txids
+    };
+
+    let old = client.utxo_snapshot(&script).unwrap();
+    assert_eq!(old.utxos.len(), 3);
+    let spend = server.chain().spend(
+        &[Outpoint::new(spent, 0)],
+        vec![(FakeChain::script(2), 9_000)],
+    );
+    server.chain().broadcast(spend).unwrap();
+    let added = server.chain().pay(&script, 40_000).txid();
+    server.chain().mine(1);
+
+    let (diff, new) = client.utxo_diff_since(&script, &old).unwrap();
+    assert_eq!(txids(&diff.added), [added]);
+    assert_eq!(txids(&diff.removed), [spent]);
+    assert_eq!(txids(&diff.status_changed), [pending]);
+    assert!(!diff.reorg);
+    assert_eq!(new.tip_hash, server.chain().tip().hash);
+
+    // the block confirming them is reorganized out, leaving them in the mempool
+    server.chain().reorg(1);
+    let (diff, _) = client.utxo_diff_since(&script, &new).unwrap();
+    assert!(diff.added.is_empty() && diff.removed.is_empty());
+    let mut unconfirmed = vec![pending, added];
+    unconfirmed.sort();
+    assert_eq!(txids(&diff.status_changed), unconfirmed);
+    assert!(diff.reorg);
+}
+
+#[test]
+fn named_fee_tiers_are_mapped_to_targets() {
+    let server = Server::new();
+    server.on_request(|request| {
+        (request.path == "/fee-estimates").then(|| {
+            Response::json(&serde_json::json!({
+                "fastestFee": 30,
+                "halfHourFee": 20,
+                "hourFee": 10,
+                "economyFee": 5,
+                "minimumFee": 1,
+            }))
+        })
+    });
+
+    let estimates = client(&server).fee_estimates().unwrap();
+    assert_eq!(estimates.len(), 5);
+    assert_eq!(estimates["1"], 30.0);
+    assert_eq!(estimates["3"], 20.0);
+    assert_eq!(estimates["1008"], 1.0);
+}
+
+#[test]
+fn traced_requests_report_their_retries() {
+    let server = Server::new();
+    server.chain().mine(3);
+    let failures = AtomicUsize::new(2);
+    server.on_request(move |request| {
+        let failing = request.path == "/blocks/tip/height"
+            && failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                    left.checked_sub(1)
+                })
+                .is_ok();
+        failing.then(|| Response::error(503, "unavailable"))
+    });
+    let client = Builder::new(&server.url())
+        .max_retries(2)
+        .base_backoff(Duration::from_millis(1))
+        .trace_retries(true)
+        .build_blocking()
+        .unwrap();
+
+    let (body, trace) = client.get_traced("/blocks/tip/height").unwrap();
+    assert_eq!(body, b"3");
+    assert_eq!(trace.retries, 2);
+    assert!(trace.delay > Duration::from_secs(0));
+
+    let (_, trace) = client.get_traced("/blocks/tip/height").unwrap();
+    assert_eq!(trace.retries, 0);
+    assert_eq!(trace.delay, Duration::from_secs(0));
+    assert_eq!(server.requests_to("/blocks/tip/height"), 4);
+}
@@ -0,0 +1,107 @@
+//! Smoke test of the C ABI of the client, calling the `extern "C"` functions as a C program
+//! would.
+
+#![cfg(feature = "ffi")]
+
+mod support;
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+use amplify::hex::ToHex;
+use bpstd::{ConsensusEncode, Outpoint, Txid};
+use esplora::ffi::c::*;
+use support::{FakeChain, Response, Server};
+
+/// Take a string returned by the functions, releasing it.
+fn take(string: *mut c_char) -> String {
+    assert!(!string.is_null());
+    let owned = unsafe { CStr::from_ptr(string) }
+        .to_str()
+        .unwrap()
+        .to_owned();
+    unsafe { esplora_string_free(string) };
+    owned
+}
+
+#[test]
+fn c_abi() {
+    let server = Server::new();
+    let script = FakeChain::script(1);
+    let funding = server.chain().pay(&script, 10_000).txid();
+    server.chain().mine(1);
+
+    let url = CString::new(server.url()).unwrap();
+    let mut error = ptr::null_mut();
+    let client = unsafe { esplora_client_new(url.as_ptr(), &mut error) };
+    assert!(!client.is_null());
+    assert!(error.is_null());
+
+    let mut height = 0;
+    let status = unsafe { esplora_tip_height(client, &mut height, &mut error) };
+    assert_eq!(status, EsploraStatus::Ok);
+    assert_eq!(height, server.chain().height());
+
+    let txid = CString::new(funding.to_string()).unwrap();
+    let mut out = ptr::null_mut();
+    let status = unsafe { esplora_tx_hex(client, txid.as_ptr(), &mut out, &mut error) };
+    assert_eq!(status, EsploraStatus::Ok);
+    let tx = server.chain().tx(&funding).unwrap().clone();
+    assert_eq!(take(out), tx.consensus_serialize().to_hex());
+
+    let status = unsafe { esplora_tx_status_json(client, txid.as_ptr(), &mut out, &mut error) };
+    assert_eq!(status, EsploraStatus::Ok);
+    let json: serde_json::Value = serde_json::from_str(&take(out)).unwrap();
+    assert_eq!(json["confirmed"], true);
+
+    let unknown = CString::new(Txid::from([7u8; 32]).to_string()).unwrap();
+    let status = unsafe { esplora_tx_hex(client, unknown.as_ptr(), &mut out, &mut error) };
+    assert_eq!(status, EsploraStatus::NotFound);
+
+    #[cfg(feature = "addresses")]
+    {
+        let address = CString::new(FakeChain::address(1).to_string()).unwrap();
+        let status =
+            unsafe { esplora_address_utxos_json(client, address.as_ptr(), &mut out, &mut error) };
+        assert_eq!(status, EsploraStatus::Ok);
+        let utxos: Vec<serde_json::Value> = serde_json::from_str(&take(out)).unwrap();
+        assert_eq!(utxos.len(), 1);
+    }
+
+    let status = unsafe { esplora_fee_estimates_json(client, &mut out, &mut error) };
+    assert_eq!(status, EsploraStatus::Ok);
+    assert!(take(out).contains("\"144\""));
+
+    let spend = server.chain().spend(
+        &[Outpoint::new(funding, 0)],
+        vec![(FakeChain::script(2), 9_000)],
+    );
+    let spend_hex = CString::new(spend.consensus_serialize().to_hex()).unwrap();
+    let status = unsafe { esplora_broadcast_hex(client, spend_hex.as_ptr(), &mut out, &mut error) };
+    assert_eq!(status, EsploraStatus::Ok);
+    assert_eq!(take(out), spend.txid().to_string());
+
+    // errors keep the message of the server
+    let status = unsafe { esplora_broadcast_hex(client, spend_hex.as_ptr(), &mut out, &mut error) };
+    assert_eq!(status, EsploraStatus::Http);
+    assert!(take(error).contains("txn-already-in-mempool"));
+
+    // NUL bytes of the messages are escaped rather than ending them
+    server
+        .on_request(|request| (request.method == "POST").then(|| Response::error(400, "bad\0tx")));
+    let status = unsafe { esplora_broadcast_hex(client, spend_hex.as_ptr(), &mut out, &mut error) };
+    assert_eq!(status, EsploraStatus::Http);
+    assert!(take(error).contains("bad\\0tx"));
+    server.clear_hook();
+
+    let malformed = CString::new("not a txid").unwrap();
+    let status = unsafe { esplora_tx_hex(client, malformed.as_ptr(), &mut out, &mut error) };
+    assert_eq!(status, EsploraStatus::InvalidInput);
+    assert!(take(error).contains("txid"));
+    let status = unsafe { esplora_tx_hex(ptr::null(), txid.as_ptr(), &mut out, &mut error) };
+    assert_eq!(status, EsploraStatus::InvalidInput);
+    take(error);
+
+    unsafe { esplora_client_free(client) };
+}
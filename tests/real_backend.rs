@@ -0,0 +1,39 @@
+//! Tests against a real Esplora backend, only run when `ESPLORA_TEST_URL` is set to its URL.
+
+#![cfg(all(feature = "blocking", feature = "async"))]
+
+mod support;
+
+use esplora::Builder;
+use support::real_backend;
+
+#[test]
+fn blocking_reads_the_tip() {
+    let url = match real_backend() {
+        Some(url) => url,
+        None => return,
+    };
+    let client = Builder::new(&url).build_blocking().unwrap();
+
+    let height = client.height_uncached().unwrap();
+    let tip = client.block_hash(height).unwrap();
+    let summary = client.block_summary(&tip).unwrap().unwrap();
+    assert_eq!(summary.time.height, height);
+    assert!(!client.block_txids(&tip).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn clients_agree() {
+    let url = match real_backend() {
+        Some(url) => url,
+        None => return,
+    };
+    let blocking = Builder::new(&url).build_blocking().unwrap();
+    let client = Builder::new(&url).build_async().unwrap();
+
+    let height = client.height_uncached().await.unwrap().saturating_sub(6);
+    let hash = client.block_hash(height).await.unwrap();
+    assert_eq!(blocking.block_hash(height).unwrap(), hash);
+    let txid = client.block_txids(&hash).await.unwrap()[0];
+    assert_eq!(client.tx(&txid).await.unwrap(), blocking.tx(&txid).unwrap());
+}
@@ -0,0 +1,82 @@
+//! Allocations made by the blocking client per request, counted by an allocator for the thread
+//! making the requests, which is why this test has its own binary.
+#![cfg(feature = "blocking")]
+
+mod support;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use esplora::Builder;
+use support::{FakeChain, Server};
+
+/// Allocator counting the allocations of the threads which enabled it.
+struct CountingAllocator;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+fn count() {
+    // the thread locals are gone while a thread is torn down
+    let _ = COUNTING.try_with(|counting| {
+        if counting.get() {
+            ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        }
+    });
+}
+
+/// Count the allocations made by `f` on the current thread.
+fn allocations(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|allocations| allocations.set(0));
+    COUNTING.with(|counting| counting.set(true));
+    f();
+    COUNTING.with(|counting| counting.set(false));
+    ALLOCATIONS.with(|allocations| allocations.get())
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Allocations a status request may take, most of them by the HTTP client and by the parsing of
+/// the response. The URL is built in the buffer of the client, without allocating.
+const MAX_ALLOCATIONS_PER_REQUEST: usize = 64;
+
+#[test]
+fn status_requests() {
+    let server = Server::new();
+    let txid = server.chain().pay(&FakeChain::script(1), 10_000).txid();
+    let client = Builder::new(&server.url()).build_blocking().unwrap();
+    // let the connection pool and the URL buffer settle
+    for _ in 0..10 {
+        client.tx_status(&txid).unwrap();
+    }
+
+    let total = allocations(|| {
+        for _ in 0..1_000 {
+            client.tx_status(&txid).unwrap();
+        }
+    });
+    assert!(
+        total <= 1_000 * MAX_ALLOCATIONS_PER_REQUEST,
+        "{} allocations for 1000 requests",
+        total
+    );
+}
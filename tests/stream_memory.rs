@@ -0,0 +1,158 @@
+//! Memory usage of [`stream_parse_txs`], measured with an allocator counting the bytes in use,
+//! which is why this test has its own binary.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use esplora::stream_parse_txs;
+
+/// Allocator keeping track of the bytes in use and of their peak.
+struct CountingAllocator;
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let in_use = IN_USE.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(in_use, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        IN_USE.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            IN_USE.fetch_sub(layout.size(), Ordering::SeqCst);
+            let in_use = IN_USE.fetch_add(new_size, Ordering::SeqCst) + new_size;
+            PEAK.fetch_max(in_use, Ordering::SeqCst);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const TX_COUNT: usize = 50_000;
+
+fn txid(n: usize) -> String {
+    format!("{:064x}", n)
+}
+
+/// JSON of a transaction as returned by Esplora, with the given txid.
+fn tx_json(n: usize) -> String {
+    format!(
+        r#"{{
+        "txid": "{}",
+        "version": 2,
+        "locktime": 0,
+        "vin": [{{
+            "txid": "1f2e3d4c5b6a79880f1e2d3c4b5a69780f1e2d3c4b5a69788796a5b4c3d2e1f0",
+            "vout": 1,
+            "prevout": {{
+                "scriptpubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+                "scriptpubkey_asm": "OP_0 OP_PUSHBYTES_20 751e76e8199196d454941c45d1b3a323f1433bd6",
+                "scriptpubkey_type": "v0_p2wpkh",
+                "scriptpubkey_address": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                "value": 100000
+            }},
+            "scriptsig": "",
+            "scriptsig_asm": "",
+            "witness": ["3044022000010203040506070809000102030405060708090001020304050607080902200001020304050607080900010203040506070809000102030405060708090001", "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"],
+            "is_coinbase": false,
+            "sequence": 4294967293
+        }}],
+        "vout": [{{
+            "scriptpubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            "scriptpubkey_asm": "OP_0 OP_PUSHBYTES_20 751e76e8199196d454941c45d1b3a323f1433bd6",
+            "scriptpubkey_type": "v0_p2wpkh",
+            "scriptpubkey_address": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "value": 90000
+        }}],
+        "size": 191,
+        "weight": 437,
+        "fee": 10000,
+        "status": {{ "confirmed": true, "block_height": {}, "block_hash": "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054", "block_time": 1690168629 }}
+    }}"#,
+        txid(n),
+        n
+    )
+}
+
+/// Reader of a JSON array of [`TX_COUNT`] transactions, generated as it is read so that the
+/// array is never held in memory either.
+struct SyntheticTxs {
+    next: usize,
+    pending: Vec<u8>,
+    read: usize,
+    total: usize,
+}
+
+impl SyntheticTxs {
+    fn new() -> Self {
+        SyntheticTxs {
+            next: 0,
+            pending: b"[".to_vec(),
+            read: 0,
+            total: 0,
+        }
+    }
+}
+
+impl Read for SyntheticTxs {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.read == self.pending.len() {
+            self.pending.clear();
+            self.read = 0;
+            match self.next {
+                n if n < TX_COUNT => {
+                    if n > 0 {
+                        self.pending.push(b',');
+                    }
+                    self.pending.extend_from_slice(tx_json(n).as_bytes());
+                }
+                n if n == TX_COUNT => self.pending.push(b']'),
+                _ => return Ok(0),
+            }
+            self.next += 1;
+        }
+        let len = buf.len().min(self.pending.len() - self.read);
+        buf[..len].copy_from_slice(&self.pending[self.read..self.read + len]);
+        self.read += len;
+        self.total += len;
+        Ok(len)
+    }
+}
+
+#[test]
+fn peak_memory_is_bounded_by_a_transaction() {
+    let array_size = {
+        let mut reader = SyntheticTxs::new();
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        reader.total
+    };
+    assert!(array_size > 40 * 1_024 * 1_024, "{} bytes", array_size);
+
+    let baseline = IN_USE.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+    let mut count = 0;
+    for (n, tx) in stream_parse_txs(SyntheticTxs::new()).enumerate() {
+        let tx = tx.unwrap();
+        assert_eq!(tx.txid.to_string(), txid(n));
+        count += 1;
+    }
+    let peak = PEAK.load(Ordering::SeqCst) - baseline;
+
+    assert_eq!(count, TX_COUNT);
+    // a few transactions and read chunks, far from the whole array
+    assert!(peak < 256 * 1_024, "peak of {} bytes", peak);
+}
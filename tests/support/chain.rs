@@ -0,0 +1,755 @@
+//! Deterministic chain served by the test [`Server`](super::Server), mined, broadcast to and
+//! reorganized by the tests.
+
+use std::collections::{BTreeMap, HashSet};
+
+use amplify::hex::ToHex;
+use amplify::ByteArray;
+use bpstd::{
+    Address, AddressNetwork, BlockHash, BlockHeader, BlockMerkleRoot, ConsensusEncode, LockTime,
+    Outpoint, Sats, ScriptPubkey, SeqNo, SigScript, Tx, TxIn, TxOut, TxVer, Txid, VarInt,
+    VarIntArray, Weight, Witness,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// Timestamp of the genesis block; each block mined afterwards is ten minutes later.
+const GENESIS_TIME: u32 = 1_600_000_000;
+/// Compact target of regtest, met by about every other header.
+const REGTEST_BITS: u32 = 0x207f_ffff;
+/// Value of the coinbase outputs, paid to [`FakeChain::miner`].
+pub const SUBSIDY: u64 = 50 * 100_000_000;
+/// Fee paid by the transactions built by [`FakeChain::pay`].
+pub const PAY_FEE: u64 = 1_000;
+/// Number of confirmed transactions per page of the history endpoints.
+pub const CHAIN_TXS_PER_PAGE: usize = 25;
+/// Maximum number of unconfirmed transactions returned by the history endpoints.
+pub const MEMPOOL_TXS_PER_PAGE: usize = 50;
+/// Number of transactions per page of `/block/:hash/txs`.
+pub const BLOCK_TXS_PER_PAGE: usize = 25;
+/// Number of block summaries returned by `/blocks`.
+pub const BLOCKS_PER_PAGE: u32 = 10;
+
+/// Block of a [`FakeChain`].
+#[derive(Clone, Debug)]
+pub struct FakeBlock {
+    pub header: BlockHeader,
+    pub hash: BlockHash,
+    pub height: u32,
+    /// Transactions of the block, starting with its coinbase.
+    pub txs: Vec<Tx>,
+}
+
+impl FakeBlock {
+    fn txids(&self) -> Vec<Txid> {
+        self.txs.iter().map(Tx::txid).collect()
+    }
+
+    /// Consensus encoding of the block.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.header.consensus_serialize();
+        bytes.extend(VarInt::new(self.txs.len() as u64).consensus_serialize());
+        for tx in &self.txs {
+            bytes.extend(tx.consensus_serialize());
+        }
+        bytes
+    }
+}
+
+/// Chain with a mempool, whose blocks and transactions are deterministic: two chains driven
+/// through the same operations have the same block hashes and txids.
+///
+/// Transactions aren't signed: inputs carry a dummy witness and are only checked to spend
+/// existing unspent outputs.
+#[derive(Clone, Debug)]
+pub struct FakeChain {
+    /// Blocks of the best chain, by height.
+    blocks: Vec<FakeBlock>,
+    /// Blocks dropped from the best chain by reorgs, still served by hash.
+    stale: Vec<FakeBlock>,
+    /// Unconfirmed transactions, in the order they were broadcast.
+    mempool: Vec<Tx>,
+    /// Number of blocks mined so far, including the stale ones.
+    mined: u32,
+    /// Estimates served by `/fee-estimates`.
+    fee_estimates: BTreeMap<String, f64>,
+}
+
+impl Default for FakeChain {
+    fn default() -> Self {
+        FakeChain::new()
+    }
+}
+
+impl FakeChain {
+    /// Create a chain made of its genesis block, with an empty mempool.
+    pub fn new() -> Self {
+        let fee_estimates = [
+            ("1", 20.0),
+            ("2", 15.0),
+            ("3", 10.0),
+            ("6", 5.0),
+            ("144", 1.0),
+        ]
+        .iter()
+        .map(|(target, feerate)| (target.to_string(), *feerate))
+        .collect();
+        let mut chain = FakeChain {
+            blocks: vec![],
+            stale: vec![],
+            mempool: vec![],
+            mined: 0,
+            fee_estimates,
+        };
+        chain.mine_block(vec![]);
+        chain
+    }
+
+    /// Script the coinbases and the change of [`FakeChain::pay`] are paid to.
+    pub fn miner() -> ScriptPubkey {
+        ScriptPubkey::p2wpkh([0xee; 20])
+    }
+
+    /// P2WPKH script distinct for each `n`, to be paid by the tests.
+    pub fn script(n: u8) -> ScriptPubkey {
+        ScriptPubkey::p2wpkh([n; 20])
+    }
+
+    /// Regtest address of [`FakeChain::script`].
+    pub fn address(n: u8) -> Address {
+        Address::with(&FakeChain::script(n), AddressNetwork::Regtest)
+            .expect("P2WPKH scripts have an address")
+    }
+
+    pub fn height(&self) -> u32 {
+        self.blocks.len() as u32 - 1
+    }
+
+    pub fn tip(&self) -> &FakeBlock {
+        self.blocks.last().expect("the chain has a genesis block")
+    }
+
+    /// Get the block of the best chain at `height`.
+    pub fn block_at(&self, height: u32) -> Option<&FakeBlock> {
+        self.blocks.get(height as usize)
+    }
+
+    /// Get a block of the best chain or dropped by a reorg.
+    pub fn block(&self, hash: &BlockHash) -> Option<&FakeBlock> {
+        self.blocks
+            .iter()
+            .chain(&self.stale)
+            .find(|block| block.hash == *hash)
+    }
+
+    /// Whether `hash` is a block of the best chain.
+    pub fn in_best_chain(&self, hash: &BlockHash) -> bool {
+        self.blocks.iter().any(|block| block.hash == *hash)
+    }
+
+    pub fn mempool(&self) -> &[Tx] {
+        &self.mempool
+    }
+
+    /// Replace the estimates served by `/fee-estimates`.
+    pub fn set_fee_estimates(&mut self, estimates: &[(&str, f64)]) {
+        self.fee_estimates = estimates
+            .iter()
+            .map(|(target, feerate)| (target.to_string(), *feerate))
+            .collect();
+    }
+
+    pub fn fee_estimates(&self) -> &BTreeMap<String, f64> {
+        &self.fee_estimates
+    }
+
+    /// Mine `count` blocks, the first one confirming the whole mempool.
+    pub fn mine(&mut self, count: u32) -> Vec<BlockHash> {
+        (0..count)
+            .map(|_| {
+                let txs = self.mempool.clone();
+                self.mine_block(txs)
+            })
+            .collect()
+    }
+
+    /// Mine a block confirming `txs` after its coinbase, removing them from the mempool.
+    ///
+    /// Transactions which were never broadcast can be confirmed this way, without being
+    /// checked.
+    pub fn mine_block(&mut self, txs: Vec<Tx>) -> BlockHash {
+        let height = self.blocks.len() as u32;
+        let fees = txs
+            .iter()
+            .map(|tx| self.fee(tx).unwrap_or_default())
+            .sum::<u64>();
+        let confirmed = txs.iter().map(Tx::txid).collect::<HashSet<_>>();
+        self.mempool.retain(|tx| !confirmed.contains(&tx.txid()));
+
+        // the count of mined blocks makes the coinbases of competing blocks distinct
+        let mut sig_script = vec![4];
+        sig_script.extend(height.to_le_bytes());
+        sig_script.push(4);
+        sig_script.extend(self.mined.to_le_bytes());
+        let coinbase = tx(
+            vec![TxIn {
+                prev_output: Outpoint::new(Txid::from_byte_array([0; 32]), u32::MAX),
+                sig_script: SigScript::from_unsafe(sig_script),
+                sequence: SeqNo::from_consensus_u32(u32::MAX),
+                witness: Witness::new(),
+            }],
+            vec![(FakeChain::miner(), SUBSIDY + fees)],
+        );
+        let mut block_txs = vec![coinbase];
+        block_txs.extend(txs);
+        let txids = block_txs.iter().map(Tx::txid).collect::<Vec<_>>();
+
+        let mut header = BlockHeader {
+            version: 0x2000_0000,
+            prev_block_hash: self
+                .blocks
+                .last()
+                .map(|block| block.hash)
+                .unwrap_or_else(|| BlockHash::from_byte_array([0; 32])),
+            merkle_root: BlockMerkleRoot::from_byte_array(merkle_root(&txids)),
+            time: GENESIS_TIME + self.mined * 600,
+            bits: REGTEST_BITS,
+            nonce: 0,
+        };
+        while header.block_hash().to_byte_array()[31] >= 0x7f {
+            header.nonce += 1;
+        }
+        let hash = header.block_hash();
+        self.mined += 1;
+        self.blocks.push(FakeBlock {
+            header,
+            hash,
+            height,
+            txs: block_txs,
+        });
+        hash
+    }
+
+    /// Drop the last `depth` blocks, whose transactions go back to the mempool, and mine
+    /// `depth + 1` empty blocks in their place, returning the hashes of the new blocks.
+    pub fn reorg(&mut self, depth: u32) -> Vec<BlockHash> {
+        self.disconnect(depth);
+        (0..=depth).map(|_| self.mine_block(vec![])).collect()
+    }
+
+    /// Drop the last `depth` blocks, whose transactions go back to the mempool.
+    pub fn disconnect(&mut self, depth: u32) {
+        assert!(depth <= self.height(), "can't disconnect the genesis block");
+        let mut unconfirmed = vec![];
+        for _ in 0..depth {
+            let block = self.blocks.pop().expect("depth is below the height");
+            unconfirmed.splice(0..0, block.txs.iter().skip(1).cloned());
+            self.stale.push(block);
+        }
+        unconfirmed.append(&mut self.mempool);
+        self.mempool = unconfirmed;
+    }
+
+    /// Remove a transaction from the mempool, as when it gets evicted or replaced.
+    pub fn drop_tx(&mut self, txid: &Txid) {
+        self.mempool.retain(|tx| tx.txid() != *txid);
+    }
+
+    /// Add `tx` to the mempool if its inputs are unspent outputs of the chain or of the
+    /// mempool, failing with the message bitcoind would return otherwise.
+    pub fn broadcast(&mut self, tx: Tx) -> Result<Txid, String> {
+        let txid = tx.txid();
+        if self.confirmation(&txid).is_some() {
+            return Err(rpc_error(-27, "Transaction already in block chain"));
+        }
+        if self.mempool.iter().any(|known| known.txid() == txid) {
+            return Err(rpc_error(-27, "txn-already-in-mempool"));
+        }
+        for input in &tx.inputs {
+            if self.output(&input.prev_output).is_none() {
+                return Err(rpc_error(-25, "bad-txns-inputs-missingorspent"));
+            }
+            match self.spender(&input.prev_output) {
+                Some((_, _, None)) => return Err(rpc_error(-26, "txn-mempool-conflict")),
+                Some(_) => return Err(rpc_error(-25, "bad-txns-inputs-missingorspent")),
+                None => {}
+            }
+        }
+        self.mempool.push(tx);
+        Ok(txid)
+    }
+
+    /// Broadcast a transaction paying `value` to `script`, spending an output of the miner and
+    /// paying [`PAY_FEE`]; a block is mined first if the miner has nothing to spend.
+    pub fn pay(&mut self, script: &ScriptPubkey, value: u64) -> Tx {
+        let needed = value + PAY_FEE;
+        let funding = match self.find_unspent(&FakeChain::miner(), needed) {
+            Some(funding) => funding,
+            None => {
+                self.mine(1);
+                self.find_unspent(&FakeChain::miner(), needed)
+                    .expect("the new coinbase pays the subsidy")
+            }
+        };
+        let (outpoint, available) = funding;
+        let mut outputs = vec![(script.clone(), value)];
+        if available > needed {
+            outputs.push((FakeChain::miner(), available - needed));
+        }
+        let tx = self.spend(&[outpoint], outputs);
+        self.broadcast(tx.clone())
+            .expect("the miner output is unspent");
+        tx
+    }
+
+    /// Build a transaction spending `outpoints` to `outputs`, without broadcasting it.
+    pub fn spend(&self, outpoints: &[Outpoint], outputs: Vec<(ScriptPubkey, u64)>) -> Tx {
+        let inputs = outpoints
+            .iter()
+            .map(|outpoint| TxIn {
+                prev_output: *outpoint,
+                sig_script: SigScript::new(),
+                sequence: SeqNo::from_consensus_u32(0xffff_fffd),
+                witness: Witness::from_consensus_stack(vec![vec![0x30; 72], vec![0x02; 33]]),
+            })
+            .collect();
+        tx(inputs, outputs)
+    }
+
+    fn find_unspent(&self, script: &ScriptPubkey, min_value: u64) -> Option<(Outpoint, u64)> {
+        self.unspent(script)
+            .into_iter()
+            .map(|(outpoint, output)| (outpoint, output.value.sats()))
+            .find(|(_, value)| *value >= min_value)
+    }
+
+    /// Get the transactions of the best chain, from the oldest, with their block, and then the
+    /// ones of the mempool.
+    fn all_txs(&self) -> impl Iterator<Item = (&Tx, Option<&FakeBlock>)> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.txs.iter().map(move |tx| (tx, Some(block))))
+            .chain(self.mempool.iter().map(|tx| (tx, None)))
+    }
+
+    /// Get a transaction of the best chain or of the mempool.
+    pub fn tx(&self, txid: &Txid) -> Option<&Tx> {
+        self.all_txs()
+            .map(|(tx, _)| tx)
+            .find(|tx| tx.txid() == *txid)
+    }
+
+    /// Get the block of the best chain confirming a transaction, with its position in it.
+    pub fn confirmation(&self, txid: &Txid) -> Option<(&FakeBlock, usize)> {
+        self.blocks.iter().find_map(|block| {
+            block
+                .txs
+                .iter()
+                .position(|tx| tx.txid() == *txid)
+                .map(|pos| (block, pos))
+        })
+    }
+
+    /// Get an output of a transaction of the best chain or of the mempool.
+    pub fn output(&self, outpoint: &Outpoint) -> Option<&TxOut> {
+        self.tx(&outpoint.txid)?
+            .outputs
+            .get(outpoint.vout.into_u32() as usize)
+    }
+
+    /// Get the transaction spending an output, with the index of the input and the block
+    /// confirming it, if any.
+    pub fn spender(&self, outpoint: &Outpoint) -> Option<(&Tx, u32, Option<&FakeBlock>)> {
+        self.all_txs().find_map(|(tx, block)| {
+            tx.inputs
+                .iter()
+                .position(|input| input.prev_output == *outpoint)
+                .map(|vin| (tx, vin as u32, block))
+        })
+    }
+
+    /// Get the fee of a transaction, or `None` if one of its previous outputs is unknown.
+    pub fn fee(&self, tx: &Tx) -> Option<u64> {
+        if is_coinbase(tx) {
+            return Some(0);
+        }
+        let inputs = tx
+            .inputs
+            .iter()
+            .map(|input| self.output(&input.prev_output).map(|out| out.value.sats()))
+            .sum::<Option<u64>>()?;
+        let outputs = tx.outputs.iter().map(|out| out.value.sats()).sum::<u64>();
+        Some(inputs - outputs)
+    }
+
+    /// Get the unspent outputs paying to `script`, confirmed first.
+    pub fn unspent(&self, script: &ScriptPubkey) -> Vec<(Outpoint, TxOut)> {
+        self.all_txs()
+            .flat_map(|(tx, _)| {
+                let txid = tx.txid();
+                tx.outputs
+                    .iter()
+                    .enumerate()
+                    .map(move |(vout, out)| (Outpoint::new(txid, vout as u32), out.clone()))
+            })
+            .filter(|(outpoint, out)| {
+                out.script_pubkey == *script && self.spender(outpoint).is_none()
+            })
+            .collect()
+    }
+
+    /// Whether `tx` pays to or spends from `script`.
+    fn touches(&self, tx: &Tx, script: &ScriptPubkey) -> bool {
+        tx.outputs.iter().any(|out| out.script_pubkey == *script)
+            || tx.inputs.iter().any(|input| {
+                self.output(&input.prev_output)
+                    .map_or(false, |out| out.script_pubkey == *script)
+            })
+    }
+
+    /// Get the confirmed transactions of `script`, from the newest.
+    pub fn chain_history(&self, script: &ScriptPubkey) -> Vec<&Tx> {
+        self.blocks
+            .iter()
+            .rev()
+            .flat_map(|block| block.txs.iter().rev())
+            .filter(|tx| self.touches(tx, script))
+            .collect()
+    }
+
+    /// Get the unconfirmed transactions of `script`, from the newest.
+    pub fn mempool_history(&self, script: &ScriptPubkey) -> Vec<&Tx> {
+        self.mempool
+            .iter()
+            .rev()
+            .filter(|tx| self.touches(tx, script))
+            .collect()
+    }
+
+    /// Find the script of an output of the chain or of the mempool from its Esplora hash.
+    pub fn script_by_hash(&self, scripthash: &str) -> Option<ScriptPubkey> {
+        self.all_txs()
+            .flat_map(|(tx, _)| tx.outputs.iter())
+            .map(|out| &out.script_pubkey)
+            .find(|script| script_hash(script) == scripthash)
+            .cloned()
+    }
+
+    /// Esplora JSON of a transaction of the best chain or of the mempool.
+    pub fn tx_json(&self, tx: &Tx) -> Value {
+        let vin = tx
+            .inputs
+            .iter()
+            .map(|input| {
+                let mut json = json!({
+                    "txid": input.prev_output.txid.to_string(),
+                    "vout": input.prev_output.vout.into_u32(),
+                    "prevout": self.output(&input.prev_output).map(output_json),
+                    "scriptsig": input.sig_script.as_slice().to_hex(),
+                    "scriptsig_asm": "",
+                    "is_coinbase": is_coinbase(tx),
+                    "sequence": input.sequence.to_consensus_u32(),
+                });
+                let witness = input
+                    .witness
+                    .elements()
+                    .map(|item| item.to_hex())
+                    .collect::<Vec<_>>();
+                if !witness.is_empty() {
+                    json["witness"] = json!(witness);
+                }
+                json
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "txid": tx.txid().to_string(),
+            "version": tx.version.to_consensus_i32(),
+            "locktime": tx.lock_time.to_consensus_u32(),
+            "vin": vin,
+            "vout": tx.outputs.iter().map(output_json).collect::<Vec<_>>(),
+            "size": tx.consensus_serialize().len(),
+            "weight": tx.weight_units().to_u32(),
+            "fee": self.fee(tx),
+            "status": self.status_json(&tx.txid()),
+        })
+    }
+
+    /// Esplora JSON of the status of a transaction, unconfirmed if it is unknown.
+    pub fn status_json(&self, txid: &Txid) -> Value {
+        match self.confirmation(txid) {
+            Some((block, _)) => json!({
+                "confirmed": true,
+                "block_height": block.height,
+                "block_hash": block.hash.to_string(),
+                "block_time": block.header.time,
+            }),
+            None => json!({ "confirmed": false }),
+        }
+    }
+
+    /// Esplora JSON of the summary of a block.
+    pub fn block_json(&self, block: &FakeBlock) -> Value {
+        let size = block.serialize().len();
+        let weight = block
+            .txs
+            .iter()
+            .map(|tx| tx.weight_units().to_u32() as usize)
+            .sum::<usize>()
+            + (size
+                - block
+                    .txs
+                    .iter()
+                    .map(|tx| tx.consensus_serialize().len())
+                    .sum::<usize>())
+                * 4;
+        let mut json = json!({
+            "id": block.hash.to_string(),
+            "height": block.height,
+            "version": block.header.version,
+            "timestamp": block.header.time,
+            "tx_count": block.txs.len(),
+            "size": size,
+            "weight": weight,
+            "merkle_root": block.header.merkle_root.to_string(),
+            "mediantime": block.header.time,
+            "nonce": block.header.nonce,
+            "bits": block.header.bits,
+            "difficulty": 0,
+        });
+        if block.height > 0 {
+            json["previousblockhash"] = json!(block.header.prev_block_hash.to_string());
+        }
+        json
+    }
+
+    /// Esplora JSON of the status of a block, out of the best chain if it is unknown.
+    pub fn block_status_json(&self, hash: &BlockHash) -> Value {
+        match self.blocks.iter().find(|block| block.hash == *hash) {
+            Some(block) => json!({
+                "in_best_chain": true,
+                "height": block.height,
+                "next_best": self.block_at(block.height + 1).map(|next| next.hash.to_string()),
+            }),
+            None => json!({ "in_best_chain": false }),
+        }
+    }
+
+    /// Esplora JSON of the merkle proof of a confirmed transaction.
+    pub fn merkle_proof_json(&self, txid: &Txid) -> Option<Value> {
+        let (block, pos) = self.confirmation(txid)?;
+        let branch = merkle_branch(&block.txids(), pos)
+            .into_iter()
+            .map(|hash| Txid::from_byte_array(hash).to_string())
+            .collect::<Vec<_>>();
+        Some(json!({ "block_height": block.height, "merkle": branch, "pos": pos }))
+    }
+
+    /// Esplora JSON of the spending status of an output, unspent if it is unknown.
+    pub fn outspend_json(&self, outpoint: &Outpoint) -> Value {
+        match self.spender(outpoint) {
+            Some((tx, vin, _)) => json!({
+                "spent": true,
+                "txid": tx.txid().to_string(),
+                "vin": vin,
+                "status": self.status_json(&tx.txid()),
+            }),
+            None => json!({ "spent": false }),
+        }
+    }
+
+    /// Esplora JSON of the statistics of a script.
+    pub fn stats_json(&self, script: &ScriptPubkey) -> Value {
+        let stats = |txs: Vec<&Tx>| {
+            let mut funded = (0u64, 0u64);
+            let mut spent = (0u64, 0u64);
+            for tx in &txs {
+                for out in tx.outputs.iter().filter(|out| out.script_pubkey == *script) {
+                    funded = (funded.0 + 1, funded.1 + out.value.sats());
+                }
+                for input in &tx.inputs {
+                    match self.output(&input.prev_output) {
+                        Some(out) if out.script_pubkey == *script => {
+                            spent = (spent.0 + 1, spent.1 + out.value.sats())
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            json!({
+                "funded_txo_count": funded.0,
+                "funded_txo_sum": funded.1,
+                "spent_txo_count": spent.0,
+                "spent_txo_sum": spent.1,
+                "tx_count": txs.len(),
+            })
+        };
+        json!({
+            "scripthash": script_hash(script),
+            "chain_stats": stats(self.chain_history(script)),
+            "mempool_stats": stats(self.mempool_history(script)),
+        })
+    }
+
+    /// Esplora JSON of the unspent outputs of a script.
+    pub fn utxo_json(&self, script: &ScriptPubkey) -> Value {
+        let utxos = self
+            .unspent(script)
+            .into_iter()
+            .map(|(outpoint, out)| {
+                json!({
+                    "txid": outpoint.txid.to_string(),
+                    "vout": outpoint.vout.into_u32(),
+                    "value": out.value.sats(),
+                    "status": self.status_json(&outpoint.txid),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!(utxos)
+    }
+
+    /// Esplora JSON of the statistics of the mempool.
+    pub fn mempool_json(&self) -> Value {
+        let mut histogram = BTreeMap::<u64, u64>::new();
+        let mut vsize = 0;
+        let mut total_fee = 0;
+        for tx in &self.mempool {
+            let tx_vsize = vsize_of(tx);
+            let fee = self.fee(tx).unwrap_or_default();
+            *histogram.entry(fee / tx_vsize).or_default() += tx_vsize;
+            vsize += tx_vsize;
+            total_fee += fee;
+        }
+        let histogram = histogram
+            .into_iter()
+            .rev()
+            .map(|(feerate, vsize)| json!([feerate as f32, vsize]))
+            .collect::<Vec<_>>();
+        json!({
+            "count": self.mempool.len(),
+            "vsize": vsize,
+            "total_fee": total_fee,
+            "fee_histogram": histogram,
+        })
+    }
+
+    /// Esplora JSON of the last transactions entered into the mempool, from the newest.
+    pub fn mempool_recent_json(&self) -> Value {
+        let recent = self
+            .mempool
+            .iter()
+            .rev()
+            .take(10)
+            .map(|tx| {
+                json!({
+                    "txid": tx.txid().to_string(),
+                    "fee": self.fee(tx).unwrap_or_default(),
+                    "vsize": vsize_of(tx),
+                    "value": tx.outputs.iter().map(|out| out.value.sats()).sum::<u64>(),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!(recent)
+    }
+}
+
+/// Build a version 2 transaction without lock time.
+fn tx(inputs: Vec<TxIn>, outputs: Vec<(ScriptPubkey, u64)>) -> Tx {
+    let outputs = outputs
+        .into_iter()
+        .map(|(script_pubkey, value)| TxOut {
+            value: Sats::from_sats(value),
+            script_pubkey,
+        })
+        .collect::<Vec<_>>();
+    Tx {
+        version: TxVer::V2,
+        inputs: VarIntArray::try_from_iter(inputs).expect("few inputs"),
+        outputs: VarIntArray::try_from_iter(outputs).expect("few outputs"),
+        lock_time: LockTime::ZERO,
+    }
+}
+
+fn is_coinbase(tx: &Tx) -> bool {
+    tx.inputs.len() == 1 && tx.inputs[0].prev_output.txid == Txid::from_byte_array([0; 32])
+}
+
+fn vsize_of(tx: &Tx) -> u64 {
+    (tx.weight_units().to_u32() as u64 + 3) / 4
+}
+
+/// Message of the errors of bitcoind relayed by Esplora on broadcast.
+fn rpc_error(code: i32, message: &str) -> String {
+    format!(
+        "sendrawtransaction RPC error: {}",
+        json!({ "code": code, "message": message })
+    )
+}
+
+/// Hash under which Esplora serves the history of a script.
+pub fn script_hash(script: &ScriptPubkey) -> String {
+    let bytes: &[u8] = script.as_ref();
+    Sha256::digest(bytes).to_hex()
+}
+
+fn output_json(out: &TxOut) -> Value {
+    let script = &out.script_pubkey;
+    let bytes: &[u8] = script.as_ref();
+    let script_type = match bytes {
+        [0x00, 0x14, ..] if bytes.len() == 22 => "v0_p2wpkh",
+        [0x00, 0x20, ..] if bytes.len() == 34 => "v0_p2wsh",
+        [0x51, 0x20, ..] if bytes.len() == 34 => "v1_p2tr",
+        [0x6a, ..] => "op_return",
+        _ if script.is_p2pkh() => "p2pkh",
+        _ if script.is_p2sh() => "p2sh",
+        _ => "unknown",
+    };
+    let mut json = json!({
+        "scriptpubkey": bytes.to_hex(),
+        "scriptpubkey_asm": "",
+        "scriptpubkey_type": script_type,
+        "value": out.value.sats(),
+    });
+    if let Ok(address) = Address::with(script, AddressNetwork::Regtest) {
+        json["scriptpubkey_address"] = json!(address.to_string());
+    }
+    json
+}
+
+fn sha256d(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&Sha256::digest(Sha256::digest(data)));
+    hash
+}
+
+/// Hash the pairs of nodes of a level of a merkle tree, the last one being paired with itself
+/// on odd levels.
+fn merkle_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| {
+            let mut data = [0u8; 64];
+            data[..32].copy_from_slice(&pair[0]);
+            data[32..].copy_from_slice(pair.get(1).unwrap_or(&pair[0]));
+            sha256d(&data)
+        })
+        .collect()
+}
+
+fn merkle_root(txids: &[Txid]) -> [u8; 32] {
+    let mut level = txids.iter().map(Txid::to_byte_array).collect::<Vec<_>>();
+    while level.len() > 1 {
+        level = merkle_level(&level);
+    }
+    level[0]
+}
+
+fn merkle_branch(txids: &[Txid], mut pos: usize) -> Vec<[u8; 32]> {
+    let mut level = txids.iter().map(Txid::to_byte_array).collect::<Vec<_>>();
+    let mut branch = vec![];
+    while level.len() > 1 {
+        branch.push(*level.get(pos ^ 1).unwrap_or(&level[pos]));
+        level = merkle_level(&level);
+        pos /= 2;
+    }
+    branch
+}
@@ -0,0 +1,27 @@
+//! In-process Esplora server over a deterministic fake chain, shared by the integration tests
+//! of the blocking and async clients.
+//!
+//! The tests against a real backend only run when [`REAL_BACKEND_VAR`] is set to its URL.
+
+#![allow(dead_code, unused_imports)]
+
+mod chain;
+mod server;
+
+pub use chain::{
+    script_hash, FakeBlock, FakeChain, BLOCKS_PER_PAGE, BLOCK_TXS_PER_PAGE, CHAIN_TXS_PER_PAGE,
+    MEMPOOL_TXS_PER_PAGE, PAY_FEE, SUBSIDY,
+};
+pub use server::{Request, Response, Server};
+
+/// Environment variable holding the URL of a real Esplora backend to run the tests marked as
+/// needing one against.
+pub const REAL_BACKEND_VAR: &str = "ESPLORA_TEST_URL";
+
+/// Get the URL of the real backend to test against, or `None` if the tests needing one are to
+/// be skipped.
+pub fn real_backend() -> Option<String> {
+    std::env::var(REAL_BACKEND_VAR)
+        .ok()
+        .filter(|url| !url.is_empty())
+}
@@ -0,0 +1,559 @@
+//! Minimal HTTP/1.1 server answering Esplora requests from a [`FakeChain`].
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use amplify::hex::{FromHex, ToHex};
+use bpstd::{Address, BlockHash, ConsensusEncode, Outpoint, ScriptPubkey, Tx, Txid};
+use serde_json::{json, Value};
+
+use super::chain::{
+    FakeChain, BLOCKS_PER_PAGE, BLOCK_TXS_PER_PAGE, CHAIN_TXS_PER_PAGE, MEMPOOL_TXS_PER_PAGE,
+};
+
+/// Request received by a [`Server`].
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    /// Path of the request, without its query.
+    pub path: String,
+    pub query: Option<String>,
+    /// Headers, by lowercase name.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+    /// Number of requests being served when the request arrived, including itself.
+    pub in_flight: usize,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+}
+
+/// Response sent by a [`Server`].
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status,
+            headers: vec![("Content-Type".to_owned(), content_type.to_owned())],
+            body: body.into(),
+        }
+    }
+
+    pub fn text(body: impl ToString) -> Self {
+        Response::new(200, "text/plain", body.to_string())
+    }
+
+    pub fn json(value: &Value) -> Self {
+        Response::new(200, "application/json", value.to_string())
+    }
+
+    pub fn bytes(body: Vec<u8>) -> Self {
+        Response::new(200, "application/octet-stream", body)
+    }
+
+    /// Plain-text error, as returned by Esplora.
+    pub fn error(status: u16, message: &str) -> Self {
+        Response::new(status, "text/plain", message)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            301 => "Moved Permanently",
+            302 => "Found",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            404 => "Not Found",
+            429 => "Too Many Requests",
+            500 => "Internal Server Error",
+            503 => "Service Unavailable",
+            _ => "Unknown",
+        }
+    }
+}
+
+type Hook = dyn Fn(&Request) -> Option<Response> + Send + Sync;
+
+struct Shared {
+    chain: Mutex<FakeChain>,
+    hook: Mutex<Option<Arc<Hook>>>,
+    omit_block_hashes: AtomicBool,
+    delay: Mutex<Duration>,
+    log: Mutex<Vec<Request>>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+    stop: AtomicBool,
+}
+
+/// Esplora server running in a background thread on a local port, serving a [`FakeChain`]
+/// the tests mine, broadcast to and reorganize through [`Server::chain`].
+///
+/// Each connection is served by its own thread and closed after one response, so that
+/// concurrent requests of the clients are served concurrently.
+pub struct Server {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server::new()
+    }
+}
+
+impl Server {
+    /// Start a server over a chain made of its genesis block.
+    pub fn new() -> Self {
+        Server::start(FakeChain::new())
+    }
+
+    /// Start a server over `chain`.
+    pub fn start(chain: FakeChain) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("local port available");
+        let addr = listener.local_addr().expect("bound listener");
+        let shared = Arc::new(Shared {
+            chain: Mutex::new(chain),
+            hook: Mutex::new(None),
+            omit_block_hashes: AtomicBool::new(false),
+            delay: Mutex::new(Duration::from_secs(0)),
+            log: Mutex::new(vec![]),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+            stop: AtomicBool::new(false),
+        });
+        let server = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if server.stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let server = Arc::clone(&server);
+                    thread::spawn(move || serve(&server, stream));
+                }
+            }
+        });
+        Server { addr, shared }
+    }
+
+    /// Base URL of the server, to build the clients with.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Lock the chain, to drive it from the test.
+    pub fn chain(&self) -> MutexGuard<'_, FakeChain> {
+        self.shared.chain.lock().expect("poisoned chain")
+    }
+
+    /// Answer the requests for which `hook` returns a response with it, instead of serving
+    /// them from the chain.
+    pub fn on_request(&self, hook: impl Fn(&Request) -> Option<Response> + Send + Sync + 'static) {
+        *self.shared.hook.lock().expect("poisoned hook") = Some(Arc::new(hook));
+    }
+
+    /// Serve all the requests from the chain again.
+    pub fn clear_hook(&self) {
+        *self.shared.hook.lock().expect("poisoned hook") = None;
+    }
+
+    /// Serve confirmed statuses without their block hash, as some backends do.
+    pub fn omit_block_hashes(&self, omit: bool) {
+        self.shared.omit_block_hashes.store(omit, Ordering::SeqCst);
+    }
+
+    /// Wait for `delay` before answering each request, to make concurrent requests overlap.
+    pub fn set_delay(&self, delay: Duration) {
+        *self.shared.delay.lock().expect("poisoned delay") = delay;
+    }
+
+    /// Get the requests received so far, in the order they arrived.
+    pub fn requests(&self) -> Vec<Request> {
+        self.shared.log.lock().expect("poisoned log").clone()
+    }
+
+    /// Get the number of requests received so far to paths starting with `prefix`.
+    pub fn requests_to(&self, prefix: &str) -> usize {
+        self.shared
+            .log
+            .lock()
+            .expect("poisoned log")
+            .iter()
+            .filter(|request| request.path.starts_with(prefix))
+            .count()
+    }
+
+    /// Get the highest number of requests served at once so far.
+    pub fn max_in_flight(&self) -> usize {
+        self.shared.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Forget the requests received so far.
+    pub fn reset_log(&self) {
+        self.shared.log.lock().expect("poisoned log").clear();
+        self.shared.max_in_flight.store(0, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        // wakes the listener thread up, for it to see the stop flag
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn serve(shared: &Shared, stream: TcpStream) {
+    let mut reader = BufReader::new(match stream.try_clone() {
+        Ok(stream) => stream,
+        Err(_) => return,
+    });
+    let mut request = match read_request(&mut reader) {
+        Some(request) => request,
+        None => return,
+    };
+    let in_flight = shared.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+    shared.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+    request.in_flight = in_flight;
+    shared
+        .log
+        .lock()
+        .expect("poisoned log")
+        .push(request.clone());
+
+    let delay = *shared.delay.lock().expect("poisoned delay");
+    if delay > Duration::from_secs(0) {
+        thread::sleep(delay);
+    }
+    let hook = shared.hook.lock().expect("poisoned hook").clone();
+    let mut response = hook
+        .and_then(|hook| hook(&request))
+        .unwrap_or_else(|| route(&mut shared.chain.lock().expect("poisoned chain"), &request));
+    if shared.omit_block_hashes.load(Ordering::SeqCst) {
+        strip_block_hashes(&mut response);
+    }
+    shared.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+    let _ = write_response(stream, &response);
+}
+
+/// Remove the block hashes from the statuses of a JSON `response`.
+fn strip_block_hashes(response: &mut Response) {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                fields.remove("block_hash");
+                fields.values_mut().for_each(strip);
+            }
+            Value::Array(items) => items.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    let is_json = response
+        .headers
+        .iter()
+        .any(|(name, value)| name == "Content-Type" && value == "application/json");
+    if let (true, Ok(mut value)) = (is_json, serde_json::from_slice(&response.body)) {
+        strip(&mut value);
+        response.body = serde_json::to_vec(&value).expect("JSON values serialize");
+    }
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let target = parts.next()?.to_owned();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+    }
+    let len = headers
+        .get("content-length")
+        .and_then(|len| len.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).ok()?;
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_owned(), Some(query.to_owned())),
+        None => (target, None),
+    };
+    Some(Request {
+        method,
+        path,
+        query,
+        headers,
+        body,
+        in_flight: 0,
+    })
+}
+
+fn write_response(mut stream: TcpStream, response: &Response) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, response.reason());
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    // a hook can announce another length, to cut the body short
+    if !response
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+    {
+        head.push_str(&format!("Content-Length: {}\r\n", response.body.len()));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+/// Serve a request like Esplora would from the state of `chain`.
+fn route(chain: &mut FakeChain, request: &Request) -> Response {
+    let segments = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+    if request.method == "POST" {
+        return match segments[..] {
+            ["tx"] => broadcast(chain, &request.body),
+            _ => not_found(),
+        };
+    }
+    if request.method != "GET" {
+        return not_found();
+    }
+    match segments[..] {
+        ["blocks", "tip", "height"] => Response::text(chain.height()),
+        ["blocks", "tip", "hash"] => Response::text(chain.tip().hash),
+        ["blocks"] => blocks(chain, chain.height()),
+        ["blocks", height] => match height.parse() {
+            Ok(height) => blocks(chain, height),
+            Err(_) => Response::error(400, "Invalid height"),
+        },
+        ["block-height", height] => {
+            match height
+                .parse()
+                .ok()
+                .and_then(|height| chain.block_at(height))
+            {
+                Some(block) => Response::text(block.hash),
+                None => Response::error(404, "Block not found"),
+            }
+        }
+        ["block", hash, ref rest @ ..] => block(chain, hash, rest),
+        ["tx", txid, ref rest @ ..] => tx(chain, txid, rest),
+        ["scripthash", hash, ref rest @ ..] => {
+            if hash.len() != 64 || Vec::<u8>::from_hex(hash).is_err() {
+                return Response::error(400, "Invalid hex string");
+            }
+            let script = chain.script_by_hash(hash);
+            history(chain, script, rest)
+        }
+        ["address", address, ref rest @ ..] => match Address::from_str(address) {
+            Ok(address) => history(chain, Some(address.script_pubkey()), rest),
+            Err(_) => Response::error(400, "Invalid Bitcoin address"),
+        },
+        ["fee-estimates"] => Response::json(&json!(chain.fee_estimates())),
+        ["mempool"] => Response::json(&chain.mempool_json()),
+        ["mempool", "txids"] => Response::json(&json!(chain
+            .mempool()
+            .iter()
+            .map(|tx| tx.txid().to_string())
+            .collect::<Vec<_>>())),
+        ["mempool", "recent"] => Response::json(&chain.mempool_recent_json()),
+        _ => not_found(),
+    }
+}
+
+fn not_found() -> Response {
+    Response::error(404, "Not Found")
+}
+
+fn broadcast(chain: &mut FakeChain, body: &[u8]) -> Response {
+    let tx = match std::str::from_utf8(body)
+        .ok()
+        .and_then(|hex| Tx::from_str(hex.trim()).ok())
+    {
+        Some(tx) => tx,
+        None => {
+            return Response::error(
+                400,
+                r#"sendrawtransaction RPC error: {"code":-22,"message":"TX decode failed"}"#,
+            )
+        }
+    };
+    match chain.broadcast(tx) {
+        Ok(txid) => Response::text(txid),
+        Err(message) => Response::error(400, &message),
+    }
+}
+
+fn blocks(chain: &FakeChain, start: u32) -> Response {
+    let summaries = (0..=start.min(chain.height()))
+        .rev()
+        .take(BLOCKS_PER_PAGE as usize)
+        .filter_map(|height| chain.block_at(height))
+        .map(|block| chain.block_json(block))
+        .collect::<Vec<_>>();
+    Response::json(&json!(summaries))
+}
+
+fn block(chain: &FakeChain, hash: &str, rest: &[&str]) -> Response {
+    let hash = match BlockHash::from_str(hash) {
+        Ok(hash) => hash,
+        Err(_) => return Response::error(400, "Invalid hex string"),
+    };
+    if rest == ["status"] {
+        return Response::json(&chain.block_status_json(&hash));
+    }
+    let block = match chain.block(&hash) {
+        Some(block) => block,
+        None => return Response::error(404, "Block not found"),
+    };
+    let txids = || block.txs.iter().map(Tx::txid).collect::<Vec<_>>();
+    match rest {
+        [] => Response::json(&chain.block_json(block)),
+        ["header"] => Response::text(block.header.consensus_serialize().to_hex()),
+        ["raw"] => Response::bytes(block.serialize()),
+        ["txids"] => Response::json(&json!(txids()
+            .iter()
+            .map(Txid::to_string)
+            .collect::<Vec<_>>())),
+        ["txid", index] => match index
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| txids().get(i).copied())
+        {
+            Some(txid) => Response::text(txid),
+            None => Response::error(404, "Transaction not found"),
+        },
+        ["txs"] => block_txs(chain, &block.txs, "0"),
+        ["txs", start] => block_txs(chain, &block.txs, start),
+        _ => not_found(),
+    }
+}
+
+fn block_txs(chain: &FakeChain, txs: &[Tx], start: &str) -> Response {
+    let start = match start.parse::<usize>() {
+        Ok(start) if start % BLOCK_TXS_PER_PAGE == 0 => start,
+        _ => return Response::error(400, "start index must be a multiple of 25"),
+    };
+    if start >= txs.len() {
+        return Response::error(404, "start index out of range");
+    }
+    let page = txs[start..]
+        .iter()
+        .take(BLOCK_TXS_PER_PAGE)
+        .map(|tx| chain.tx_json(tx))
+        .collect::<Vec<_>>();
+    Response::json(&json!(page))
+}
+
+fn tx(chain: &FakeChain, txid: &str, rest: &[&str]) -> Response {
+    let txid = match Txid::from_str(txid) {
+        Ok(txid) => txid,
+        Err(_) => return Response::error(400, "Invalid hex string"),
+    };
+    match rest {
+        ["status"] => return Response::json(&chain.status_json(&txid)),
+        ["outspend", vout] => {
+            return match vout.parse::<u32>() {
+                Ok(vout) => Response::json(&chain.outspend_json(&Outpoint::new(txid, vout))),
+                Err(_) => Response::error(400, "Invalid vout"),
+            }
+        }
+        ["merkle-proof"] => {
+            return match chain.merkle_proof_json(&txid) {
+                Some(proof) => Response::json(&proof),
+                None => Response::error(404, "Transaction not found or is unconfirmed"),
+            }
+        }
+        _ => {}
+    }
+    let tx = match chain.tx(&txid) {
+        Some(tx) => tx,
+        None => return Response::error(404, "Transaction not found"),
+    };
+    match rest {
+        [] => Response::json(&chain.tx_json(tx)),
+        ["raw"] => Response::bytes(tx.consensus_serialize()),
+        ["hex"] => Response::text(tx.consensus_serialize().to_hex()),
+        ["outspends"] => Response::json(&json!((0..tx.outputs.len() as u32)
+            .map(|vout| chain.outspend_json(&Outpoint::new(txid, vout)))
+            .collect::<Vec<_>>())),
+        _ => not_found(),
+    }
+}
+
+/// Serve the history endpoints of a script, which is `None` if it never appeared in the chain.
+fn history(chain: &FakeChain, script: Option<ScriptPubkey>, rest: &[&str]) -> Response {
+    let script = script.unwrap_or_default();
+    let page = |txs: Vec<&Tx>| {
+        Response::json(&json!(txs
+            .into_iter()
+            .map(|tx| chain.tx_json(tx))
+            .collect::<Vec<_>>()))
+    };
+    let mempool = || {
+        chain
+            .mempool_history(&script)
+            .into_iter()
+            .take(MEMPOOL_TXS_PER_PAGE)
+    };
+    let confirmed_after = |last_seen: Option<&str>| -> Result<Vec<&Tx>, Response> {
+        let mut txs = chain.chain_history(&script);
+        if let Some(last_seen) = last_seen {
+            let last_seen = Txid::from_str(last_seen)
+                .map_err(|_| Response::error(400, "Invalid hex string"))?;
+            let pos = txs.iter().position(|tx| tx.txid() == last_seen);
+            txs.drain(..pos.map_or(txs.len(), |pos| pos + 1));
+        }
+        txs.truncate(CHAIN_TXS_PER_PAGE);
+        Ok(txs)
+    };
+    match rest {
+        [] => Response::json(&chain.stats_json(&script)),
+        ["txs"] => match confirmed_after(None) {
+            Ok(confirmed) => page(mempool().chain(confirmed).collect()),
+            Err(err) => err,
+        },
+        ["txs", "mempool"] => page(mempool().collect()),
+        ["txs", "chain"] => confirmed_after(None).map_or_else(|err| err, page),
+        ["txs", "chain", last_seen] => {
+            confirmed_after(Some(last_seen)).map_or_else(|err| err, page)
+        }
+        ["utxo"] => Response::json(&chain.utxo_json(&script)),
+        _ => not_found(),
+    }
+}